@@ -0,0 +1,408 @@
+use base64::{engine::general_purpose::{STANDARD, STANDARD_NO_PAD}, Engine};
+use bech32::{FromBase32, ToBase32, Variant};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::file_encryption::{Identity, Recipient};
+use crate::key_exchange::{self, KeyExchangeError};
+use crate::q_core::{aead_open, aead_seal, AeadAlgorithm, QCoreError, NONCE_LEN};
+
+const VERSION_LINE: &str = "age-encryption.org/v1";
+const X25519_LABEL: &[u8] = b"age-encryption.org/v1/X25519";
+const SCRYPT_LABEL: &[u8] = b"age-encryption.org/v1/scrypt";
+const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const ARMOR_END: &str = "-----END AGE ENCRYPTED FILE-----";
+const RECIPIENT_HRP: &str = "age";
+const IDENTITY_HRP: &str = "age-secret-key-";
+const FILE_KEY_LEN: usize = 16;
+const TAG_LEN: usize = 16;
+const WRAPPED_KEY_LEN: usize = FILE_KEY_LEN + TAG_LEN;
+const PAYLOAD_NONCE_LEN: usize = 16;
+const SCRYPT_SALT_LEN: usize = 16;
+const CHUNK_SIZE: usize = 64 * 1024;
+// Stanza bodies and armor are both wrapped at 64 base64 columns.
+const COLUMNS: usize = 64;
+pub const DEFAULT_SCRYPT_LOG_N: u8 = 18;
+// Same ceiling as the reference implementation; higher factors take minutes to check.
+const MAX_SCRYPT_LOG_N: u8 = 22;
+
+#[derive(thiserror::Error, Debug)]
+pub enum AgeError {
+    #[error("Invalid age recipient {0:?}")]
+    InvalidRecipient(String),
+    #[error("Invalid age identity")]
+    InvalidIdentity,
+    #[error("age has no recipient type for hybrid post-quantum keys")]
+    UnsupportedRecipient,
+    #[error("At least one recipient is required")]
+    NoRecipients,
+    #[error("A passphrase must be the only recipient")]
+    PassphraseNotAlone,
+    #[error("Invalid age armor")]
+    Armor,
+    #[error("Invalid age header: {0}")]
+    Header(&'static str),
+    #[error("Header MAC mismatch")]
+    HeaderMac,
+    #[error("No identity matched any recipient stanza")]
+    NoMatch,
+    #[error("Payload is corrupt or truncated")]
+    Payload,
+    #[error("Key exchange error: {0}")]
+    KeyExchange(#[from] KeyExchangeError),
+    #[error("Encryption error: {0}")]
+    Encryption(#[from] QCoreError),
+}
+
+impl From<AgeError> for PyErr {
+    fn from(err: AgeError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// The `age1...` recipient string for an X25519 public key.
+pub fn encode_recipient(public_key: &[u8; 32]) -> String {
+    bech32::encode(RECIPIENT_HRP, public_key.to_base32(), Variant::Bech32).expect("a 32-byte key fits in bech32")
+}
+
+pub fn decode_recipient(recipient: &str) -> Result<[u8; 32], AgeError> {
+    decode_bech32(recipient, RECIPIENT_HRP).ok_or_else(|| AgeError::InvalidRecipient(recipient.to_string()))
+}
+
+/// The `AGE-SECRET-KEY-1...` identity string for an X25519 secret key.
+pub fn encode_identity(secret_key: &[u8; 32]) -> String {
+    bech32::encode(IDENTITY_HRP, secret_key.to_base32(), Variant::Bech32)
+        .expect("a 32-byte key fits in bech32")
+        .to_uppercase()
+}
+
+/// Reads one identity or an `age-keygen` identity file (one key per line,
+/// `#` comments and blank lines ignored).
+pub fn decode_identities(text: &str) -> Result<Vec<[u8; 32]>, AgeError> {
+    let keys = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| decode_bech32(line, IDENTITY_HRP).ok_or(AgeError::InvalidIdentity))
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err(AgeError::InvalidIdentity);
+    }
+    Ok(keys)
+}
+
+/// Encrypts `plaintext` to the recipients in the age v1 format, optionally
+/// ASCII-armored. Passphrases become scrypt stanzas, which age only accepts
+/// on their own.
+pub fn encrypt(plaintext: &[u8], recipients: &[Recipient], armor: bool) -> Result<Vec<u8>, AgeError> {
+    encrypt_with_work_factor(plaintext, recipients, armor, DEFAULT_SCRYPT_LOG_N)
+}
+
+pub(crate) fn encrypt_with_work_factor(
+    plaintext: &[u8],
+    recipients: &[Recipient],
+    armor: bool,
+    log_n: u8,
+) -> Result<Vec<u8>, AgeError> {
+    if recipients.is_empty() {
+        return Err(AgeError::NoRecipients);
+    }
+    if recipients.len() > 1 && recipients.iter().any(|r| matches!(r, Recipient::Passphrase(_))) {
+        return Err(AgeError::PassphraseNotAlone);
+    }
+    let mut file_key = [0u8; FILE_KEY_LEN];
+    rand::thread_rng().fill_bytes(&mut file_key);
+
+    let mut header = format!("{VERSION_LINE}\n");
+    for recipient in recipients {
+        let (args, body) = wrap_file_key(&file_key, recipient, log_n)?;
+        header.push_str(&format!("-> {}\n", args.join(" ")));
+        let encoded = STANDARD_NO_PAD.encode(body);
+        // The body always ends with a line shorter than 64 columns, even if empty.
+        for line in encoded.as_bytes().chunks(COLUMNS) {
+            header.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+            header.push('\n');
+        }
+        if encoded.len() % COLUMNS == 0 {
+            header.push('\n');
+        }
+    }
+    header.push_str("---");
+    let mac = header_mac(&file_key, header.as_bytes()).finalize().into_bytes();
+    header.push_str(&format!(" {}\n", STANDARD_NO_PAD.encode(mac)));
+
+    let mut nonce = [0u8; PAYLOAD_NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let mut out = header.into_bytes();
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&stream_seal(&hkdf(&nonce, &file_key, b"payload"), plaintext)?);
+    Ok(if armor { armor_encode(&out).into_bytes() } else { out })
+}
+
+/// Decrypts a binary or armored age file with the first identity that
+/// unlocks one of its stanzas.
+pub fn decrypt(data: &[u8], identities: &[Identity]) -> Result<Vec<u8>, AgeError> {
+    // Anything that doesn't start like a binary header is treated as armor.
+    let dearmored;
+    let data = if data.starts_with(b"age-encryption.org/") {
+        data
+    } else {
+        dearmored = armor_decode(data)?;
+        &dearmored[..]
+    };
+    let header = parse_header(data)?;
+    let file_key = unwrap_file_key(&header.stanzas, identities)?;
+    header_mac(&file_key, &data[..header.mac_input_len])
+        .verify_slice(&header.mac)
+        .map_err(|_| AgeError::HeaderMac)?;
+
+    let payload = &data[header.payload_offset..];
+    if payload.len() < PAYLOAD_NONCE_LEN {
+        return Err(AgeError::Header("missing payload nonce"));
+    }
+    let (nonce, ciphertext) = payload.split_at(PAYLOAD_NONCE_LEN);
+    stream_open(&hkdf(nonce, &file_key, b"payload"), ciphertext)
+}
+
+struct Stanza {
+    args: Vec<String>,
+    body: Vec<u8>,
+}
+
+struct Header {
+    stanzas: Vec<Stanza>,
+    mac: Vec<u8>,
+    // The MAC covers everything up to and including the "---".
+    mac_input_len: usize,
+    payload_offset: usize,
+}
+
+fn parse_header(data: &[u8]) -> Result<Header, AgeError> {
+    let mut pos = 0;
+    if next_line(data, &mut pos)? != VERSION_LINE {
+        return Err(AgeError::Header("unsupported version"));
+    }
+    let mut stanzas = Vec::new();
+    loop {
+        let line_start = pos;
+        let line = next_line(data, &mut pos)?;
+        if let Some(args) = line.strip_prefix("-> ") {
+            let args: Vec<String> = args.split(' ').map(String::from).collect();
+            if args.iter().any(String::is_empty) {
+                return Err(AgeError::Header("empty stanza argument"));
+            }
+            let mut encoded = String::new();
+            loop {
+                let line = next_line(data, &mut pos)?;
+                if line.len() > COLUMNS {
+                    return Err(AgeError::Header("stanza body line too long"));
+                }
+                encoded.push_str(line);
+                if line.len() < COLUMNS {
+                    break;
+                }
+            }
+            let body = STANDARD_NO_PAD.decode(&encoded).map_err(|_| AgeError::Header("invalid stanza body"))?;
+            stanzas.push(Stanza { args, body });
+        } else if let Some(mac) = line.strip_prefix("--- ") {
+            let mac = STANDARD_NO_PAD.decode(mac).map_err(|_| AgeError::Header("invalid MAC encoding"))?;
+            if mac.len() != 32 {
+                return Err(AgeError::Header("invalid MAC length"));
+            }
+            return Ok(Header { stanzas, mac, mac_input_len: line_start + 3, payload_offset: pos });
+        } else {
+            return Err(AgeError::Header("unexpected line"));
+        }
+    }
+}
+
+/// Header lines are printable ASCII terminated by a bare LF.
+fn next_line<'a>(data: &'a [u8], pos: &mut usize) -> Result<&'a str, AgeError> {
+    let rest = &data[*pos..];
+    let end = rest.iter().position(|&b| b == b'\n').ok_or(AgeError::Header("truncated header"))?;
+    let line = &rest[..end];
+    if !line.iter().all(|b| (0x20..=0x7e).contains(b)) {
+        return Err(AgeError::Header("invalid character"));
+    }
+    *pos += end + 1;
+    Ok(std::str::from_utf8(line).expect("checked ASCII"))
+}
+
+fn wrap_file_key(file_key: &[u8], recipient: &Recipient, log_n: u8) -> Result<(Vec<String>, Vec<u8>), AgeError> {
+    match recipient {
+        Recipient::X25519(public_key) => {
+            let (share, ephemeral_secret) = key_exchange::generate_keypair();
+            let shared = key_exchange::diffie_hellman(&ephemeral_secret, public_key)?;
+            let key = hkdf(&[share.as_slice(), public_key].concat(), &shared, X25519_LABEL);
+            let body = aead_seal(AeadAlgorithm::ChaCha20Poly1305, &key, &[0u8; NONCE_LEN], b"", file_key)?;
+            Ok((vec!["X25519".into(), STANDARD_NO_PAD.encode(share)], body))
+        }
+        Recipient::Passphrase(passphrase) => {
+            let mut salt = [0u8; SCRYPT_SALT_LEN];
+            rand::thread_rng().fill_bytes(&mut salt);
+            let key = scrypt_key(passphrase, &salt, log_n);
+            let body = aead_seal(AeadAlgorithm::ChaCha20Poly1305, &key, &[0u8; NONCE_LEN], b"", file_key)?;
+            Ok((vec!["scrypt".into(), STANDARD_NO_PAD.encode(salt), log_n.to_string()], body))
+        }
+        Recipient::Hybrid(_) => Err(AgeError::UnsupportedRecipient),
+    }
+}
+
+/// Tries every identity against every stanza of its type. Unknown stanza types
+/// are skipped, but a malformed stanza of a known type fails the whole file.
+fn unwrap_file_key(stanzas: &[Stanza], identities: &[Identity]) -> Result<Vec<u8>, AgeError> {
+    if stanzas.len() > 1 && stanzas.iter().any(|stanza| stanza.args[0] == "scrypt") {
+        return Err(AgeError::Header("scrypt stanza must be alone"));
+    }
+    for identity in identities {
+        for stanza in stanzas {
+            let key = match (identity, stanza.args[0].as_str()) {
+                (Identity::X25519(secret_key), "X25519") => x25519_stanza_key(stanza, secret_key)?,
+                (Identity::Passphrase(passphrase), "scrypt") => scrypt_stanza_key(stanza, passphrase)?,
+                _ => continue,
+            };
+            if stanza.body.len() != WRAPPED_KEY_LEN {
+                return Err(AgeError::Header("wrapped file key must be 16 bytes"));
+            }
+            if let Ok(file_key) = aead_open(AeadAlgorithm::ChaCha20Poly1305, &key, &[0u8; NONCE_LEN], b"", &stanza.body) {
+                return Ok(file_key);
+            }
+        }
+    }
+    Err(AgeError::NoMatch)
+}
+
+fn x25519_stanza_key(stanza: &Stanza, secret_key: &[u8; 32]) -> Result<[u8; 32], AgeError> {
+    let share = match &stanza.args[..] {
+        [_, share] => STANDARD_NO_PAD.decode(share).map_err(|_| AgeError::Header("invalid X25519 share"))?,
+        _ => return Err(AgeError::Header("X25519 stanza takes one argument")),
+    };
+    if share.len() != 32 {
+        return Err(AgeError::Header("invalid X25519 share"));
+    }
+    let shared = match key_exchange::diffie_hellman(secret_key, &share) {
+        Err(KeyExchangeError::LowOrderPoint) => return Err(AgeError::Header("X25519 share is a low-order point")),
+        result => result?,
+    };
+    let salt = [share.as_slice(), &key_exchange::public_key(secret_key)?].concat();
+    Ok(hkdf(&salt, &shared, X25519_LABEL))
+}
+
+fn scrypt_stanza_key(stanza: &Stanza, passphrase: &str) -> Result<[u8; 32], AgeError> {
+    let (salt, log_n) = match &stanza.args[..] {
+        [_, salt, log_n] => (salt, log_n),
+        _ => return Err(AgeError::Header("scrypt stanza takes two arguments")),
+    };
+    let salt = STANDARD_NO_PAD.decode(salt).map_err(|_| AgeError::Header("invalid scrypt salt"))?;
+    if salt.len() != SCRYPT_SALT_LEN {
+        return Err(AgeError::Header("invalid scrypt salt"));
+    }
+    // Plain decimal only: no sign, no leading zeros.
+    let log_n = match log_n.as_bytes() {
+        [b'1'..=b'9', rest @ ..] if rest.iter().all(u8::is_ascii_digit) => log_n.parse::<u8>().ok(),
+        _ => None,
+    }
+    .ok_or(AgeError::Header("invalid scrypt work factor"))?;
+    if log_n > MAX_SCRYPT_LOG_N {
+        return Err(AgeError::Header("scrypt work factor too high"));
+    }
+    Ok(scrypt_key(passphrase, &salt, log_n))
+}
+
+fn scrypt_key(passphrase: &str, salt: &[u8], log_n: u8) -> [u8; 32] {
+    let params = scrypt::Params::new(log_n, 8, 1, 32).expect("work factor is range-checked");
+    let mut key = [0u8; 32];
+    scrypt::scrypt(passphrase.as_bytes(), &[SCRYPT_LABEL, salt].concat(), &params, &mut key)
+        .expect("32 bytes is a valid scrypt output length");
+    key
+}
+
+fn header_mac(file_key: &[u8], header: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&hkdf(&[], file_key, b"header")).expect("HMAC takes any key length");
+    mac.update(header);
+    mac
+}
+
+/// STREAM: 64 KiB chunks, nonce = 11-byte big-endian counter | last-chunk flag.
+fn stream_seal(key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AgeError> {
+    let chunks: Vec<&[u8]> = if plaintext.is_empty() { vec![&[]] } else { plaintext.chunks(CHUNK_SIZE).collect() };
+    let mut out = Vec::with_capacity(plaintext.len() + chunks.len() * TAG_LEN);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let nonce = stream_nonce(i as u64, i + 1 == chunks.len());
+        out.extend_from_slice(&aead_seal(AeadAlgorithm::ChaCha20Poly1305, key, &nonce, b"", chunk)?);
+    }
+    Ok(out)
+}
+
+fn stream_open(key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AgeError> {
+    if ciphertext.is_empty() {
+        return Err(AgeError::Payload);
+    }
+    let mut out = Vec::with_capacity(ciphertext.len());
+    for (i, chunk) in ciphertext.chunks(CHUNK_SIZE + TAG_LEN).enumerate() {
+        let last = (i + 1) * (CHUNK_SIZE + TAG_LEN) >= ciphertext.len();
+        let plaintext = aead_open(AeadAlgorithm::ChaCha20Poly1305, key, &stream_nonce(i as u64, last), b"", chunk)
+            .map_err(|_| AgeError::Payload)?;
+        // Only an entirely empty payload may end with an empty chunk.
+        if last && plaintext.is_empty() && i > 0 {
+            return Err(AgeError::Payload);
+        }
+        out.extend_from_slice(&plaintext);
+    }
+    Ok(out)
+}
+
+fn stream_nonce(counter: u64, last: bool) -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[3..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+fn armor_encode(data: &[u8]) -> String {
+    let encoded = STANDARD.encode(data);
+    let mut out = format!("{ARMOR_BEGIN}\n");
+    for line in encoded.as_bytes().chunks(COLUMNS) {
+        out.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        out.push('\n');
+    }
+    out.push_str(ARMOR_END);
+    out.push('\n');
+    out
+}
+
+/// Strict armor: whitespace is only allowed around the block, every line but
+/// the last is exactly 64 columns, and the base64 must be padded and canonical.
+fn armor_decode(data: &[u8]) -> Result<Vec<u8>, AgeError> {
+    let text = std::str::from_utf8(data).map_err(|_| AgeError::Armor)?.trim_matches([' ', '\t', '\r', '\n']);
+    let lines: Vec<&str> = text.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line)).collect();
+    let [ARMOR_BEGIN, body @ .., ARMOR_END] = &lines[..] else {
+        return Err(AgeError::Armor);
+    };
+    let Some((last, full)) = body.split_last() else {
+        return Err(AgeError::Armor);
+    };
+    if full.iter().any(|line| line.len() != COLUMNS) || last.is_empty() || last.len() > COLUMNS {
+        return Err(AgeError::Armor);
+    }
+    STANDARD.decode(body.concat()).map_err(|_| AgeError::Armor)
+}
+
+fn decode_bech32(encoded: &str, hrp: &str) -> Option<[u8; 32]> {
+    let (decoded_hrp, data, variant) = bech32::decode(encoded).ok()?;
+    if decoded_hrp != hrp || variant != Variant::Bech32 {
+        return None;
+    }
+    Vec::<u8>::from_base32(&data).ok()?.try_into().ok()
+}
+
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
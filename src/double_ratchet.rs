@@ -0,0 +1,459 @@
+use std::collections::VecDeque;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::key_exchange::{self, KeyExchangeError};
+use crate::key_store::{KeyStore, KeyStoreError};
+use crate::q_core::{self, aead_open, aead_seal, AeadAlgorithm, QCoreError, NONCE_LEN};
+use crate::utils::take;
+use crate::x3dh::{self, IdentityKeyPair, InitialMessage, PqPreKey, PreKey, PreKeyBundle, SharedSecret, X3dhError};
+
+const KEY_LEN: usize = 32;
+const STATE_VERSION: u8 = 1;
+const INIT_INFO: &[u8] = b"qimem-ratchet-init";
+const ROOT_INFO: &[u8] = b"qimem-ratchet-root";
+const MESSAGE_INFO: &[u8] = b"qimem-ratchet-message";
+// ratchet public key | previous chain length u32 | message number u32
+const HEADER_LEN: usize = KEY_LEN + 4 + 4;
+/// Encrypted header: nonce | header | tag. Every message starts with one.
+pub const ENCRYPTED_HEADER_LEN: usize = NONCE_LEN + HEADER_LEN + 16;
+/// Most message keys skipped in one chain, so a forged message number can't
+/// make the receiver derive an unbounded number of keys.
+pub const MAX_SKIP: u32 = 1000;
+/// Most skipped keys kept overall; the oldest are dropped first.
+pub const MAX_SKIPPED_KEYS: usize = 2000;
+
+type Key = [u8; KEY_LEN];
+
+#[derive(thiserror::Error, Debug)]
+pub enum RatchetError {
+    #[error("Message is too short")]
+    Malformed,
+    #[error("Could not decrypt message header")]
+    InvalidHeader,
+    #[error("Could not decrypt message")]
+    DecryptionFailed,
+    #[error("Too many skipped messages")]
+    TooManySkipped,
+    #[error("Message is a replay or its key was discarded")]
+    Replayed,
+    #[error("Cannot send before receiving the first message")]
+    NotInitialized,
+    #[error("Invalid session state")]
+    InvalidState,
+    #[error("No session stored under {0}")]
+    SessionNotFound(String),
+    #[error("Key exchange error: {0}")]
+    KeyExchange(#[from] KeyExchangeError),
+    #[error("X3DH error: {0}")]
+    X3dh(#[from] X3dhError),
+    #[error("Encryption error: {0}")]
+    Encryption(#[from] QCoreError),
+    #[error("Key store error: {0}")]
+    KeyStore(#[from] KeyStoreError),
+}
+
+impl From<RatchetError> for PyErr {
+    fn from(err: RatchetError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SkippedKey {
+    header_key: Key,
+    message_number: u32,
+    message_key: Key,
+}
+
+/// One side of a Double Ratchet conversation with encrypted headers, following
+/// the Signal specification. Every message gets a fresh key from a symmetric
+/// chain, and each reply turns the DH ratchet so a compromised state can't
+/// read earlier messages or, after the next round trip, later ones.
+///
+/// Decrypting works on a copy of the state that is only kept on success, so
+/// a forged or corrupted message leaves the session unchanged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Session {
+    dh_self: Key,
+    dh_remote: Option<Key>,
+    root_key: Key,
+    send_chain: Option<Key>,
+    receive_chain: Option<Key>,
+    send_count: u32,
+    receive_count: u32,
+    previous_send_count: u32,
+    send_header_key: Option<Key>,
+    receive_header_key: Option<Key>,
+    next_send_header_key: Key,
+    next_receive_header_key: Key,
+    associated_data: Vec<u8>,
+    skipped: VecDeque<SkippedKey>,
+}
+
+struct Header {
+    dh: Key,
+    previous_count: u32,
+    number: u32,
+}
+
+impl Header {
+    fn to_bytes(&self) -> Vec<u8> {
+        [&self.dh[..], &self.previous_count.to_be_bytes(), &self.number.to_be_bytes()].concat()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Header> {
+        (bytes.len() == HEADER_LEN).then(|| Header {
+            dh: bytes[..32].try_into().unwrap(),
+            previous_count: u32::from_be_bytes(bytes[32..36].try_into().unwrap()),
+            number: u32::from_be_bytes(bytes[36..40].try_into().unwrap()),
+        })
+    }
+}
+
+impl Session {
+    /// Runs X3DH against `bundle` and starts a session whose ratchet begins
+    /// at the bundle's signed prekey. Send the returned [`InitialMessage`]
+    /// with the first message.
+    pub fn initiate(identity: &IdentityKeyPair, bundle: &PreKeyBundle) -> Result<(Session, InitialMessage), RatchetError> {
+        let (secret, message) = x3dh::initiate(identity, bundle)?;
+        Ok((Session::new_initiator(&secret, &bundle.signed_prekey)?, message))
+    }
+
+    /// The responder's counterpart to [`Session::initiate`].
+    pub fn respond(
+        identity: &IdentityKeyPair,
+        signed_prekey: &PreKey,
+        one_time_prekey: Option<&PreKey>,
+        pq_prekey: Option<&PqPreKey>,
+        message: &InitialMessage,
+    ) -> Result<Session, RatchetError> {
+        let secret = x3dh::respond(identity, signed_prekey, one_time_prekey, pq_prekey, message)?;
+        Ok(Session::new_responder(&secret, signed_prekey.secret_key))
+    }
+
+    /// Starts the initiator's ratchet from an agreed secret and the responder's ratchet public key.
+    pub fn new_initiator(secret: &SharedSecret, remote_ratchet_key: &[u8]) -> Result<Session, RatchetError> {
+        let (root_key, shared_header_key, next_header_key) = init_keys(&secret.key);
+        let remote: Key = remote_ratchet_key.try_into().map_err(|_| KeyExchangeError::InvalidKeyLength)?;
+        let (_, dh_self) = key_exchange::generate_keypair();
+        let (root_key, send_chain, next_send_header_key) = kdf_root(&root_key, &key_exchange::diffie_hellman(&dh_self, &remote)?);
+        Ok(Session {
+            dh_self,
+            dh_remote: Some(remote),
+            root_key,
+            send_chain: Some(send_chain),
+            receive_chain: None,
+            send_count: 0,
+            receive_count: 0,
+            previous_send_count: 0,
+            send_header_key: Some(shared_header_key),
+            receive_header_key: None,
+            next_send_header_key,
+            next_receive_header_key: next_header_key,
+            associated_data: secret.associated_data.clone(),
+            skipped: VecDeque::new(),
+        })
+    }
+
+    /// Starts the responder's ratchet. It can only send once the first message has arrived.
+    pub fn new_responder(secret: &SharedSecret, ratchet_secret_key: Key) -> Session {
+        let (root_key, shared_header_key, next_header_key) = init_keys(&secret.key);
+        Session {
+            dh_self: ratchet_secret_key,
+            dh_remote: None,
+            root_key,
+            send_chain: None,
+            receive_chain: None,
+            send_count: 0,
+            receive_count: 0,
+            previous_send_count: 0,
+            send_header_key: None,
+            receive_header_key: None,
+            next_send_header_key: next_header_key,
+            next_receive_header_key: shared_header_key,
+            associated_data: secret.associated_data.clone(),
+            skipped: VecDeque::new(),
+        }
+    }
+
+    /// Returns `encrypted header | ciphertext`.
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, RatchetError> {
+        let (Some(chain), Some(header_key)) = (self.send_chain, self.send_header_key) else {
+            return Err(RatchetError::NotInitialized);
+        };
+        let (next_chain, message_key) = kdf_chain(&chain);
+        let header = Header { dh: key_exchange::public_key(&self.dh_self)?, previous_count: self.previous_send_count, number: self.send_count };
+        let mut message = q_core::encrypt(&header.to_bytes(), &header_key)?;
+        let ciphertext = seal_message(&message_key, &[&self.associated_data, &message[..]].concat(), plaintext)?;
+        message.extend_from_slice(&ciphertext);
+        self.send_chain = Some(next_chain);
+        self.send_count += 1;
+        Ok(message)
+    }
+
+    pub fn decrypt(&mut self, message: &[u8]) -> Result<Vec<u8>, RatchetError> {
+        if message.len() < ENCRYPTED_HEADER_LEN {
+            return Err(RatchetError::Malformed);
+        }
+        let mut next = self.clone();
+        let plaintext = next.decrypt_in_place(message)?;
+        *self = next;
+        Ok(plaintext)
+    }
+
+    fn decrypt_in_place(&mut self, message: &[u8]) -> Result<Vec<u8>, RatchetError> {
+        let (encrypted_header, ciphertext) = message.split_at(ENCRYPTED_HEADER_LEN);
+        let aad = [&self.associated_data, encrypted_header].concat();
+        if let Some(plaintext) = self.try_skipped_keys(encrypted_header, ciphertext, &aad)? {
+            return Ok(plaintext);
+        }
+
+        let (header, turn_ratchet) = self.decrypt_header(encrypted_header)?;
+        if turn_ratchet {
+            self.skip_message_keys(header.previous_count)?;
+            self.turn_ratchet(&header)?;
+        } else if header.number < self.receive_count {
+            return Err(RatchetError::Replayed);
+        }
+        self.skip_message_keys(header.number)?;
+        let (next_chain, message_key) = kdf_chain(&self.receive_chain.ok_or(RatchetError::InvalidHeader)?);
+        self.receive_chain = Some(next_chain);
+        self.receive_count += 1;
+        open_message(&message_key, &aad, ciphertext)
+    }
+
+    fn try_skipped_keys(&mut self, encrypted_header: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Option<Vec<u8>>, RatchetError> {
+        for i in 0..self.skipped.len() {
+            let skipped = &self.skipped[i];
+            let Some(header) = decrypt_header_with(&skipped.header_key, encrypted_header) else {
+                continue;
+            };
+            if header.number == skipped.message_number {
+                let plaintext = open_message(&skipped.message_key, aad, ciphertext)?;
+                self.skipped.remove(i);
+                return Ok(Some(plaintext));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Tries the current receiving header key, then the next one; the latter
+    /// means the sender has turned their DH ratchet.
+    fn decrypt_header(&self, encrypted_header: &[u8]) -> Result<(Header, bool), RatchetError> {
+        if let Some(header) = self.receive_header_key.and_then(|key| decrypt_header_with(&key, encrypted_header)) {
+            return Ok((header, false));
+        }
+        if let Some(header) = decrypt_header_with(&self.next_receive_header_key, encrypted_header) {
+            return Ok((header, true));
+        }
+        Err(RatchetError::InvalidHeader)
+    }
+
+    fn skip_message_keys(&mut self, until: u32) -> Result<(), RatchetError> {
+        if until > self.receive_count.saturating_add(MAX_SKIP) {
+            return Err(RatchetError::TooManySkipped);
+        }
+        if let (Some(mut chain), Some(header_key)) = (self.receive_chain, self.receive_header_key) {
+            while self.receive_count < until {
+                let (next_chain, message_key) = kdf_chain(&chain);
+                self.skipped.push_back(SkippedKey { header_key, message_number: self.receive_count, message_key });
+                if self.skipped.len() > MAX_SKIPPED_KEYS {
+                    self.skipped.pop_front();
+                }
+                chain = next_chain;
+                self.receive_count += 1;
+            }
+            self.receive_chain = Some(chain);
+        }
+        Ok(())
+    }
+
+    fn turn_ratchet(&mut self, header: &Header) -> Result<(), RatchetError> {
+        self.previous_send_count = self.send_count;
+        self.send_count = 0;
+        self.receive_count = 0;
+        self.send_header_key = Some(self.next_send_header_key);
+        self.receive_header_key = Some(self.next_receive_header_key);
+        self.dh_remote = Some(header.dh);
+        let (root_key, receive_chain, next_receive_header_key) =
+            kdf_root(&self.root_key, &key_exchange::diffie_hellman(&self.dh_self, &header.dh)?);
+        self.dh_self = key_exchange::generate_keypair().1;
+        let (root_key, send_chain, next_send_header_key) = kdf_root(&root_key, &key_exchange::diffie_hellman(&self.dh_self, &header.dh)?);
+        self.root_key = root_key;
+        self.receive_chain = Some(receive_chain);
+        self.send_chain = Some(send_chain);
+        self.next_receive_header_key = next_receive_header_key;
+        self.next_send_header_key = next_send_header_key;
+        Ok(())
+    }
+
+    /// The whole session state, including skipped message keys. It is secret:
+    /// store it encrypted, e.g. with [`Session::save`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![STATE_VERSION];
+        out.extend_from_slice(&self.dh_self);
+        push_optional(&mut out, &self.dh_remote);
+        out.extend_from_slice(&self.root_key);
+        push_optional(&mut out, &self.send_chain);
+        push_optional(&mut out, &self.receive_chain);
+        for count in [self.send_count, self.receive_count, self.previous_send_count] {
+            out.extend_from_slice(&count.to_be_bytes());
+        }
+        push_optional(&mut out, &self.send_header_key);
+        push_optional(&mut out, &self.receive_header_key);
+        out.extend_from_slice(&self.next_send_header_key);
+        out.extend_from_slice(&self.next_receive_header_key);
+        out.extend_from_slice(&(self.associated_data.len() as u16).to_be_bytes());
+        out.extend_from_slice(&self.associated_data);
+        out.extend_from_slice(&(self.skipped.len() as u32).to_be_bytes());
+        for skipped in &self.skipped {
+            out.extend_from_slice(&skipped.header_key);
+            out.extend_from_slice(&skipped.message_number.to_be_bytes());
+            out.extend_from_slice(&skipped.message_key);
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Session, RatchetError> {
+        let mut reader = StateReader(bytes);
+        if reader.bytes(1)? != [STATE_VERSION] {
+            return Err(RatchetError::InvalidState);
+        }
+        let dh_self = reader.key()?;
+        let dh_remote = reader.optional_key()?;
+        let root_key = reader.key()?;
+        let send_chain = reader.optional_key()?;
+        let receive_chain = reader.optional_key()?;
+        let (send_count, receive_count, previous_send_count) = (reader.u32()?, reader.u32()?, reader.u32()?);
+        let send_header_key = reader.optional_key()?;
+        let receive_header_key = reader.optional_key()?;
+        let next_send_header_key = reader.key()?;
+        let next_receive_header_key = reader.key()?;
+        let ad_len = u16::from_be_bytes(reader.bytes(2)?.try_into().unwrap()) as usize;
+        let associated_data = reader.bytes(ad_len)?.to_vec();
+        let skipped_count = reader.u32()? as usize;
+        if skipped_count > MAX_SKIPPED_KEYS {
+            return Err(RatchetError::InvalidState);
+        }
+        let mut skipped = VecDeque::with_capacity(skipped_count);
+        for _ in 0..skipped_count {
+            skipped.push_back(SkippedKey { header_key: reader.key()?, message_number: reader.u32()?, message_key: reader.key()? });
+        }
+        if !reader.0.is_empty() {
+            return Err(RatchetError::InvalidState);
+        }
+        Ok(Session {
+            dh_self,
+            dh_remote,
+            root_key,
+            send_chain,
+            receive_chain,
+            send_count,
+            receive_count,
+            previous_send_count,
+            send_header_key,
+            receive_header_key,
+            next_send_header_key,
+            next_receive_header_key,
+            associated_data,
+            skipped,
+        })
+    }
+
+    /// Stores the session state as a record in `store` under `id`.
+    pub fn save(&self, store: &mut KeyStore, id: &str) -> Result<(), RatchetError> {
+        Ok(store.store_record(id, &self.to_bytes())?)
+    }
+
+    pub fn load(store: &KeyStore, id: &str) -> Result<Session, RatchetError> {
+        let bytes = store.retrieve_record(id).ok_or_else(|| RatchetError::SessionNotFound(id.to_string()))?;
+        Session::from_bytes(&bytes)
+    }
+}
+
+/// Splits the X3DH secret into the first root key and the two initial header
+/// keys (the initiator's sending key and the responder's next sending key).
+fn init_keys(secret: &Key) -> (Key, Key, Key) {
+    let mut okm = [0u8; KEY_LEN * 3];
+    Hkdf::<Sha256>::new(None, secret).expand(INIT_INFO, &mut okm).unwrap();
+    split_three(&okm)
+}
+
+/// KDF_RK_HE: new root key, chain key and next header key from a DH output.
+fn kdf_root(root_key: &Key, dh_output: &Key) -> (Key, Key, Key) {
+    let mut okm = [0u8; KEY_LEN * 3];
+    Hkdf::<Sha256>::new(Some(root_key), dh_output).expand(ROOT_INFO, &mut okm).unwrap();
+    split_three(&okm)
+}
+
+/// KDF_CK: the next chain key and this step's message key.
+fn kdf_chain(chain_key: &Key) -> (Key, Key) {
+    let step = |constant: u8| -> Key {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(chain_key).unwrap();
+        mac.update(&[constant]);
+        mac.finalize().into_bytes().into()
+    };
+    (step(0x02), step(0x01))
+}
+
+fn split_three(okm: &[u8; KEY_LEN * 3]) -> (Key, Key, Key) {
+    (okm[..32].try_into().unwrap(), okm[32..64].try_into().unwrap(), okm[64..].try_into().unwrap())
+}
+
+/// Each message key is used once, so the key and nonce are both derived from it.
+fn message_cipher(message_key: &Key) -> (Key, [u8; NONCE_LEN]) {
+    let mut okm = [0u8; KEY_LEN + NONCE_LEN];
+    Hkdf::<Sha256>::new(None, message_key).expand(MESSAGE_INFO, &mut okm).unwrap();
+    (okm[..KEY_LEN].try_into().unwrap(), okm[KEY_LEN..].try_into().unwrap())
+}
+
+fn seal_message(message_key: &Key, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, RatchetError> {
+    let (key, nonce) = message_cipher(message_key);
+    Ok(aead_seal(AeadAlgorithm::ChaCha20Poly1305, &key, &nonce, aad, plaintext)?)
+}
+
+fn open_message(message_key: &Key, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, RatchetError> {
+    let (key, nonce) = message_cipher(message_key);
+    aead_open(AeadAlgorithm::ChaCha20Poly1305, &key, &nonce, aad, ciphertext).map_err(|_| RatchetError::DecryptionFailed)
+}
+
+fn decrypt_header_with(header_key: &Key, encrypted_header: &[u8]) -> Option<Header> {
+    Header::from_bytes(&q_core::decrypt(encrypted_header, header_key).ok()?)
+}
+
+fn push_optional(out: &mut Vec<u8>, key: &Option<Key>) {
+    match key {
+        Some(key) => {
+            out.push(1);
+            out.extend_from_slice(key);
+        }
+        None => out.push(0),
+    }
+}
+
+struct StateReader<'a>(&'a [u8]);
+
+impl StateReader<'_> {
+    fn bytes(&mut self, len: usize) -> Result<&[u8], RatchetError> {
+        take(&mut self.0, len).ok_or(RatchetError::InvalidState)
+    }
+
+    fn key(&mut self) -> Result<Key, RatchetError> {
+        Ok(self.bytes(KEY_LEN)?.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> Result<u32, RatchetError> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn optional_key(&mut self) -> Result<Option<Key>, RatchetError> {
+        match self.bytes(1)? {
+            [0] => Ok(None),
+            [1] => Ok(Some(self.key()?)),
+            _ => Err(RatchetError::InvalidState),
+        }
+    }
+}
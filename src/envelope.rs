@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+use rand::RngCore;
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::fingerprint::Fingerprint;
+use crate::key_exchange::{self, KeyExchangeError};
+use crate::signing::{sign_message, verify_signature, SigningError};
+
+const VERSION: u8 = 1;
+const SIGNATURE_CONTEXT: &[u8] = b"qimem-envelope-v1";
+pub const MESSAGE_ID_LEN: usize = 16;
+const SIGNATURE_LEN: usize = 64;
+// version | message id | sent_at (i64 BE) | sender public key | signature
+const PREFIX_LEN: usize = 1 + MESSAGE_ID_LEN + 8 + 32 + SIGNATURE_LEN;
+pub const DEFAULT_MAX_AGE_SECS: i64 = 300;
+pub const DEFAULT_LEEWAY_SECS: i64 = 60;
+/// Upper bound for both the age window and the leeway: 30 days.
+pub const MAX_WINDOW_SECS: i64 = 30 * 24 * 60 * 60;
+
+#[derive(thiserror::Error, Debug)]
+pub enum EnvelopeError {
+    #[error("Malformed envelope")]
+    Malformed,
+    #[error("Unsupported envelope version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Envelope was sent by an untrusted key ({0})")]
+    UnexpectedSender(String),
+    #[error("Invalid envelope signature")]
+    InvalidSignature,
+    #[error("Envelope is too old (sent at {sent_at}, now {now})")]
+    Expired { sent_at: i64, now: i64 },
+    #[error("Envelope was sent in the future (sent at {sent_at}, now {now})")]
+    SentInFuture { sent_at: i64, now: i64 },
+    #[error("Envelope has already been opened (replay)")]
+    Replayed,
+    #[error("Age window and leeway must be 0 to {MAX_WINDOW_SECS} seconds, not {0}")]
+    InvalidWindow(i64),
+    #[error("Key exchange error: {0}")]
+    KeyExchange(#[from] KeyExchangeError),
+    #[error("Signing error: {0}")]
+    Signing(#[from] SigningError),
+}
+
+impl From<EnvelopeError> for PyErr {
+    fn from(err: EnvelopeError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// A verified envelope returned by [`open`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpenedEnvelope {
+    /// [`Fingerprint::key_id`] of the sender's public key.
+    pub sender_key_id: String,
+    pub message_id: [u8; MESSAGE_ID_LEN],
+    pub sent_at: i64,
+    pub payload: Vec<u8>,
+}
+
+/// Remembers the ids of opened envelopes for as long as they would still be
+/// accepted, so each one can only be opened once. Envelopes older than
+/// `max_age_secs` are refused outright, which keeps the set bounded.
+pub struct ReplayGuard {
+    max_age_secs: i64,
+    leeway_secs: i64,
+    seen: HashMap<[u8; MESSAGE_ID_LEN], i64>,
+}
+
+impl ReplayGuard {
+    pub fn new(max_age_secs: i64) -> Self {
+        ReplayGuard { max_age_secs, leeway_secs: DEFAULT_LEEWAY_SECS, seen: HashMap::new() }
+    }
+
+    pub fn with_leeway(mut self, leeway_secs: i64) -> Self {
+        self.leeway_secs = leeway_secs;
+        self
+    }
+
+    /// Like [`ReplayGuard::new`] with [`ReplayGuard::with_leeway`], but refuses
+    /// negative values and anything over [`MAX_WINDOW_SECS`], for settings
+    /// that come from configuration or callers outside Rust.
+    pub fn with_window(max_age_secs: i64, leeway_secs: i64) -> Result<Self, EnvelopeError> {
+        for secs in [max_age_secs, leeway_secs] {
+            if !(0..=MAX_WINDOW_SECS).contains(&secs) {
+                return Err(EnvelopeError::InvalidWindow(secs));
+            }
+        }
+        Ok(ReplayGuard::new(max_age_secs).with_leeway(leeway_secs))
+    }
+
+    fn check(&mut self, message_id: [u8; MESSAGE_ID_LEN], sent_at: i64, now: i64) -> Result<(), EnvelopeError> {
+        if sent_at > now.saturating_add(self.leeway_secs) {
+            return Err(EnvelopeError::SentInFuture { sent_at, now });
+        }
+        // Near i64::MIN nothing is old enough to refuse.
+        let oldest = now
+            .checked_sub(self.max_age_secs)
+            .and_then(|oldest| oldest.checked_sub(self.leeway_secs))
+            .unwrap_or(i64::MIN);
+        if sent_at < oldest {
+            return Err(EnvelopeError::Expired { sent_at, now });
+        }
+        self.seen.retain(|_, seen_at| *seen_at >= oldest);
+        if self.seen.insert(message_id, sent_at).is_some() {
+            return Err(EnvelopeError::Replayed);
+        }
+        Ok(())
+    }
+}
+
+impl Default for ReplayGuard {
+    fn default() -> Self {
+        ReplayGuard::new(DEFAULT_MAX_AGE_SECS)
+    }
+}
+
+/// Signs `payload` with the sender's Ed25519 key, then seals it to the
+/// recipient's X25519 key with [`key_exchange::seal`]. The signature covers
+/// both parties' public keys, a random message id and the send time, so the
+/// envelope can't be re-addressed to someone else or opened twice.
+pub fn seal(sender_signing_key: &[u8], recipient_public_key: &[u8], payload: &[u8]) -> Result<Vec<u8>, EnvelopeError> {
+    let mut message_id = [0u8; MESSAGE_ID_LEN];
+    rand::thread_rng().fill_bytes(&mut message_id);
+    seal_at(sender_signing_key, recipient_public_key, payload, message_id, Utc::now().timestamp())
+}
+
+pub(crate) fn seal_at(
+    sender_signing_key: &[u8],
+    recipient_public_key: &[u8],
+    payload: &[u8],
+    message_id: [u8; MESSAGE_ID_LEN],
+    sent_at: i64,
+) -> Result<Vec<u8>, EnvelopeError> {
+    let secret: [u8; 32] = sender_signing_key.try_into().map_err(|_| SigningError::InvalidSecretKey)?;
+    let sender_public_key = SigningKey::from_bytes(&secret).verifying_key().to_bytes();
+    let signed = signed_data(&sender_public_key, recipient_public_key, &message_id, sent_at, payload);
+    let signature = sign_message(sender_signing_key, &signed)?;
+
+    let mut inner = Vec::with_capacity(PREFIX_LEN + payload.len());
+    inner.push(VERSION);
+    inner.extend_from_slice(&message_id);
+    inner.extend_from_slice(&sent_at.to_be_bytes());
+    inner.extend_from_slice(&sender_public_key);
+    inner.extend_from_slice(&signature);
+    inner.extend_from_slice(payload);
+    Ok(key_exchange::seal(recipient_public_key, &inner)?)
+}
+
+/// Opens an envelope from [`seal`], checking that it was signed by
+/// `sender_public_key` for this recipient, is within the guard's age window
+/// and has not been opened before.
+pub fn open(
+    recipient_secret_key: &[u8],
+    sender_public_key: &[u8],
+    sealed: &[u8],
+    guard: &mut ReplayGuard,
+) -> Result<OpenedEnvelope, EnvelopeError> {
+    open_at(recipient_secret_key, sender_public_key, sealed, guard, Utc::now().timestamp())
+}
+
+/// Like [`open`] with an explicit current time in seconds.
+pub fn open_at(
+    recipient_secret_key: &[u8],
+    sender_public_key: &[u8],
+    sealed: &[u8],
+    guard: &mut ReplayGuard,
+    now: i64,
+) -> Result<OpenedEnvelope, EnvelopeError> {
+    open_from_any_at(recipient_secret_key, &[sender_public_key], sealed, guard, now)
+}
+
+/// Like [`open`], accepting an envelope from any of `trusted_senders`; the
+/// result's `sender_key_id` says which one sent it.
+pub fn open_from_any(
+    recipient_secret_key: &[u8],
+    trusted_senders: &[&[u8]],
+    sealed: &[u8],
+    guard: &mut ReplayGuard,
+) -> Result<OpenedEnvelope, EnvelopeError> {
+    open_from_any_at(recipient_secret_key, trusted_senders, sealed, guard, Utc::now().timestamp())
+}
+
+/// Like [`open_from_any`] with an explicit current time in seconds. The
+/// signature is checked before the message id is recorded.
+pub fn open_from_any_at(
+    recipient_secret_key: &[u8],
+    trusted_senders: &[&[u8]],
+    sealed: &[u8],
+    guard: &mut ReplayGuard,
+    now: i64,
+) -> Result<OpenedEnvelope, EnvelopeError> {
+    let inner = key_exchange::open(recipient_secret_key, sealed)?;
+    if inner.len() < PREFIX_LEN {
+        return Err(EnvelopeError::Malformed);
+    }
+    if inner[0] != VERSION {
+        return Err(EnvelopeError::UnsupportedVersion(inner[0]));
+    }
+    let message_id: [u8; MESSAGE_ID_LEN] = inner[1..1 + MESSAGE_ID_LEN].try_into().unwrap();
+    let rest = &inner[1 + MESSAGE_ID_LEN..];
+    let sent_at = i64::from_be_bytes(rest[..8].try_into().unwrap());
+    let (claimed_sender, rest) = rest[8..].split_at(32);
+    let (signature, payload) = rest.split_at(SIGNATURE_LEN);
+    let sender_key_id = Fingerprint::of(claimed_sender).key_id();
+    if !trusted_senders.contains(&claimed_sender) {
+        return Err(EnvelopeError::UnexpectedSender(sender_key_id));
+    }
+
+    let recipient_public_key = key_exchange::public_key(recipient_secret_key)?;
+    let signed = signed_data(claimed_sender, &recipient_public_key, &message_id, sent_at, payload);
+    if !verify_signature(claimed_sender, &signed, signature)? {
+        return Err(EnvelopeError::InvalidSignature);
+    }
+    guard.check(message_id, sent_at, now)?;
+    Ok(OpenedEnvelope { sender_key_id, message_id, sent_at, payload: payload.to_vec() })
+}
+
+fn signed_data(sender: &[u8], recipient: &[u8], message_id: &[u8], sent_at: i64, payload: &[u8]) -> Vec<u8> {
+    [SIGNATURE_CONTEXT, sender, recipient, message_id, &sent_at.to_be_bytes(), payload].concat()
+}
@@ -1,9 +1,28 @@
 use std::fs::{self, File};
 use std::io::Write;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use crate::age_format::{self, AgeError};
+use crate::hybrid_kem::{self, HybridKemError};
+use crate::key_exchange::{self, KeyExchangeError};
 use crate::q_core::{encrypt, decrypt, QCoreError};
-use pyo3::prelude::*;
-use pyo3::types::PyBytesMethods;
-use pyo3::exceptions::PyValueError;
+use crate::q_keygen::{derive_key_from_params, derive_key_with_context, require_strength, KdfParams, KeyGenError, SALT_LEN};
+
+const FILE_CONTEXT: &[u8] = b"qimem-file";
+const RECIPIENT_CONTEXT: &[u8] = b"qimem-file-recipient";
+const MULTI_MAGIC: &[u8; 8] = b"QIMEMMR1";
+const FILE_KEY_LEN: usize = 32;
+const STANZA_X25519: u8 = 1;
+const STANZA_PASSPHRASE: u8 = 2;
+const STANZA_HYBRID: u8 = 3;
+const HEADER_MAC_LEN: usize = 32;
+// Passphrase stanzas are derived before anything authenticates them, so they
+// get tighter limits than q_keygen's: 256 MiB, 16 passes, 4 lanes.
+const MAX_STANZA_M_COST: u32 = 1 << 18;
+const MAX_STANZA_T_COST: u32 = 16;
+const MAX_STANZA_P_COST: u32 = 4;
 
 #[derive(thiserror::Error, Debug)]
 pub enum FileEncryptionError {
@@ -11,38 +30,305 @@ pub enum FileEncryptionError {
     Io(#[from] std::io::Error),
     #[error("Encryption error: {0}")]
     Encryption(#[from] QCoreError),
+    #[error("Key derivation error: {0}")]
+    KeyGen(#[from] KeyGenError),
+    #[error("Salt must be 16 bytes")]
+    InvalidSalt,
+    #[error("Invalid encrypted file")]
+    InvalidFormat,
+    #[error("Key exchange error: {0}")]
+    KeyExchange(#[from] KeyExchangeError),
+    #[error("Hybrid KEM error: {0}")]
+    HybridKem(#[from] HybridKemError),
+    #[error("At least one recipient is required")]
+    NoRecipients,
+    #[error("No recipient stanza could be unlocked with this identity")]
+    NoMatchingRecipient,
+    #[error("At most one passphrase recipient is allowed")]
+    MultiplePassphrases,
+    #[error("File header failed authentication")]
+    HeaderMac,
+    #[error("age error: {0}")]
+    Age(#[from] AgeError),
+}
+
+pub fn encrypt_file(
+    input_path: &str,
+    output_path: &str,
+    key: &[u8],
+    salt: &[u8]
+) -> Result<(), FileEncryptionError> {
+    if salt.len() != SALT_LEN {
+        return Err(FileEncryptionError::InvalidSalt);
+    }
+    let data = fs::read(input_path)?;
+    let encrypted_data = encrypt(&data, key)?;
+    fs::write(output_path, encrypted_data)?;
+    Ok(())
+}
+
+pub fn decrypt_file(
+    input_path: &str,
+    output_path: &str,
+    key: &[u8]
+) -> Result<(), FileEncryptionError> {
+    let encrypted_data = fs::read(input_path)?;
+    let decrypted_data = decrypt(&encrypted_data, key)?;
+    let mut output_file = File::create(output_path)?;
+    output_file.write_all(&decrypted_data)?;
+    Ok(())
+}
+
+/// Encrypts a file under a key derived from `password`, refusing passwords that
+/// score below `min_score` when one is given. The output is
+/// `params_len (u16 BE) | KdfParams blob | ciphertext`.
+pub fn encrypt_file_with_password(
+    input_path: &str,
+    output_path: &str,
+    password: &str,
+    min_score: Option<u8>
+) -> Result<(), FileEncryptionError> {
+    if let Some(min_score) = min_score {
+        require_strength(password, min_score)?;
+    }
+    let (key, params) = derive_key_with_context(password, FILE_CONTEXT)?;
+    let data = fs::read(input_path)?;
+    let encrypted_data = encrypt(&data, &key)?;
+    let mut out = Vec::with_capacity(2 + params.len() + encrypted_data.len());
+    out.extend_from_slice(&(params.len() as u16).to_be_bytes());
+    out.extend_from_slice(&params);
+    out.extend_from_slice(&encrypted_data);
+    fs::write(output_path, out)?;
+    Ok(())
+}
+
+pub fn decrypt_file_with_password(
+    input_path: &str,
+    output_path: &str,
+    password: &str
+) -> Result<(), FileEncryptionError> {
+    let data = fs::read(input_path)?;
+    if data.len() < 2 {
+        return Err(FileEncryptionError::InvalidFormat);
+    }
+    let params_len = u16::from_be_bytes([data[0], data[1]]) as usize;
+    if data.len() < 2 + params_len {
+        return Err(FileEncryptionError::InvalidFormat);
+    }
+    let key = derive_key_from_params(password, &data[2..2 + params_len])?;
+    let decrypted_data = decrypt(&data[2 + params_len..], &key)?;
+    fs::write(output_path, decrypted_data)?;
+    Ok(())
 }
 
-#[pyfunction]
-pub fn encrypt_file<'py>(
-    py: Python<'py>,
+/// Someone a multi-recipient file is encrypted to.
+pub enum Recipient {
+    /// An X25519 public key from [`crate::key_exchange::generate_keypair`].
+    X25519([u8; 32]),
+    /// An X25519 + ML-KEM-768 public key from [`crate::hybrid_kem::generate_keypair`].
+    Hybrid(Vec<u8>),
+    Passphrase(String),
+}
+
+/// What a recipient decrypts with: the X25519 or hybrid secret key, or the passphrase.
+pub enum Identity {
+    X25519([u8; 32]),
+    Hybrid([u8; 32]),
+    Passphrase(String),
+}
+
+/// Encrypts a file once under a random file key and wraps that key for each
+/// recipient, so any one of them can decrypt. The output is
+/// `"QIMEMMR1" | stanza_count (u16 BE) | stanzas | mac | ciphertext`, each stanza
+/// being `kind (u8) | len (u16 BE) | data`. X25519 stanzas hold a
+/// [`key_exchange::seal`]ed file key, hybrid stanzas a [`hybrid_kem::seal`]ed
+/// one; passphrase stanzas hold the file key
+/// encrypted in the same params envelope as [`encrypt_file_with_password`].
+/// As in age, `mac` is HMAC-SHA256 over everything before it, keyed with
+/// HKDF-SHA256(file key, info "header"), and at most one stanza is a passphrase.
+pub fn encrypt_file_for_recipients(
     input_path: &str,
     output_path: &str,
-    key: Vec<u8>,
-    salt: Vec<u8>
-) -> PyResult<()> {
-    let key_array: [u8; 32] = key.try_into()
-        .map_err(|_| PyValueError::new_err("Key must be 32 bytes"))?;
-    let _salt_array: [u8; 16] = salt.try_into()
-        .map_err(|_| PyValueError::new_err("Salt must be 16 bytes"))?;
-    let data = fs::read(input_path).map_err(|e| FileEncryptionError::Io(e))?;
-    let encrypted_data = encrypt(py, &data, &key_array)?;
-    fs::write(output_path, encrypted_data.as_bytes()).map_err(|e| FileEncryptionError::Io(e))?;
+    recipients: &[Recipient]
+) -> Result<(), FileEncryptionError> {
+    if recipients.is_empty() {
+        return Err(FileEncryptionError::NoRecipients);
+    }
+    let mut file_key = [0u8; FILE_KEY_LEN];
+    rand::thread_rng().fill_bytes(&mut file_key);
+    let stanzas = recipients
+        .iter()
+        .map(|recipient| wrap_file_key(&file_key, recipient))
+        .collect::<Result<Vec<_>, _>>()?;
+    let data = fs::read(input_path)?;
+    let body = encrypt(&data, &file_key)?;
+    fs::write(output_path, encode_multi_recipient(&stanzas, &file_key, &body)?)?;
     Ok(())
 }
 
-#[pyfunction]
-pub fn decrypt_file<'py>(
-    py: Python<'py>,
+pub fn decrypt_file_for_identity(
     input_path: &str,
     output_path: &str,
-    key: Vec<u8>
-) -> PyResult<()> {
-    let key_array: [u8; 32] = key.try_into()
-        .map_err(|_| PyValueError::new_err("Key must be 32 bytes"))?;
-    let encrypted_data = fs::read(input_path).map_err(|e| FileEncryptionError::Io(e))?;
-    let decrypted_data = decrypt(py, &encrypted_data, &key_array)?;
-    let mut output_file = File::create(output_path).map_err(|e| FileEncryptionError::Io(e))?;
-    output_file.write_all(decrypted_data.as_bytes()).map_err(|e| FileEncryptionError::Io(e))?;
+    identity: &Identity
+) -> Result<(), FileEncryptionError> {
+    let data = fs::read(input_path)?;
+    let (stanzas, body) = decode_multi_recipient(&data)?;
+    let file_key = unwrap_file_key(&stanzas, identity, &data)?;
+    fs::write(output_path, decrypt(body, &file_key)?)?;
+    Ok(())
+}
+
+/// Adds recipients to an existing multi-recipient file in place. `identity`
+/// must unlock one of the current stanzas; the body is copied unchanged.
+pub fn add_file_recipients(
+    path: &str,
+    identity: &Identity,
+    recipients: &[Recipient]
+) -> Result<(), FileEncryptionError> {
+    let data = fs::read(path)?;
+    let (mut stanzas, body) = decode_multi_recipient(&data)?;
+    let file_key = unwrap_file_key(&stanzas, identity, &data)?;
+    for recipient in recipients {
+        stanzas.push(wrap_file_key(&file_key, recipient)?);
+    }
+    fs::write(path, encode_multi_recipient(&stanzas, &file_key, body)?)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Encrypts a file in the age v1 format, so `age -d` can open it.
+pub fn encrypt_file_age(
+    input_path: &str,
+    output_path: &str,
+    recipients: &[Recipient],
+    armor: bool
+) -> Result<(), FileEncryptionError> {
+    let data = fs::read(input_path)?;
+    fs::write(output_path, age_format::encrypt(&data, recipients, armor)?)?;
+    Ok(())
+}
+
+/// Decrypts a binary or armored age file with the first identity that matches.
+pub fn decrypt_file_age(
+    input_path: &str,
+    output_path: &str,
+    identities: &[Identity]
+) -> Result<(), FileEncryptionError> {
+    let data = fs::read(input_path)?;
+    fs::write(output_path, age_format::decrypt(&data, identities)?)?;
+    Ok(())
+}
+
+/// One wrapped copy of the file key.
+struct Stanza {
+    kind: u8,
+    data: Vec<u8>,
+}
+
+fn wrap_file_key(file_key: &[u8], recipient: &Recipient) -> Result<Stanza, FileEncryptionError> {
+    match recipient {
+        Recipient::X25519(public_key) => Ok(Stanza { kind: STANZA_X25519, data: key_exchange::seal(public_key, file_key)? }),
+        Recipient::Hybrid(public_key) => Ok(Stanza { kind: STANZA_HYBRID, data: hybrid_kem::seal(public_key, file_key)? }),
+        Recipient::Passphrase(passphrase) => {
+            let (key, params) = derive_key_with_context(passphrase, RECIPIENT_CONTEXT)?;
+            let mut data = (params.len() as u16).to_be_bytes().to_vec();
+            data.extend_from_slice(&params);
+            data.extend_from_slice(&encrypt(file_key, &key)?);
+            Ok(Stanza { kind: STANZA_PASSPHRASE, data })
+        }
+    }
+}
+
+/// Tries every stanza of the identity's kind; a stanza for someone else just
+/// fails to open. The first file key found must also authenticate the header
+/// of `data`.
+fn unwrap_file_key(stanzas: &[Stanza], identity: &Identity, data: &[u8]) -> Result<Vec<u8>, FileEncryptionError> {
+    for stanza in stanzas {
+        let file_key = match (identity, stanza.kind) {
+            (Identity::X25519(secret_key), STANZA_X25519) => key_exchange::open(secret_key, &stanza.data).ok(),
+            (Identity::Hybrid(secret_key), STANZA_HYBRID) => hybrid_kem::open(secret_key, &stanza.data).ok(),
+            (Identity::Passphrase(passphrase), STANZA_PASSPHRASE) => unwrap_passphrase_stanza(&stanza.data, passphrase),
+            _ => None,
+        };
+        if let Some(file_key) = file_key {
+            let header_len = header_len(stanzas);
+            header_mac(&file_key, &data[..header_len])
+                .verify_slice(&data[header_len..header_len + HEADER_MAC_LEN])
+                .map_err(|_| FileEncryptionError::HeaderMac)?;
+            return Ok(file_key);
+        }
+    }
+    Err(FileEncryptionError::NoMatchingRecipient)
+}
+
+/// `None` unless this passphrase opens the stanza. A malformed stanza, or one
+/// asking for more than the stanza cost limits, is treated as someone else's.
+fn unwrap_passphrase_stanza(data: &[u8], passphrase: &str) -> Option<Vec<u8>> {
+    let params_len = u16::from_be_bytes([*data.first()?, *data.get(1)?]) as usize;
+    let params = KdfParams::from_bytes(data.get(2..2 + params_len)?).ok()?;
+    if params.m_cost > MAX_STANZA_M_COST || params.t_cost > MAX_STANZA_T_COST || params.p_cost > MAX_STANZA_P_COST {
+        return None;
+    }
+    let key = params.derive_key(passphrase).ok()?;
+    decrypt(&data[2 + params_len..], &key).ok()
+}
+
+fn header_mac(file_key: &[u8], header: &[u8]) -> Hmac<Sha256> {
+    let mut mac_key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, file_key)
+        .expand(b"header", &mut mac_key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    let mut mac = Hmac::<Sha256>::new_from_slice(&mac_key).expect("HMAC takes any key length");
+    mac.update(header);
+    mac
+}
+
+/// Bytes from the magic through the last stanza, i.e. what the header MAC covers.
+fn header_len(stanzas: &[Stanza]) -> usize {
+    MULTI_MAGIC.len() + 2 + stanzas.iter().map(|stanza| 3 + stanza.data.len()).sum::<usize>()
+}
+
+fn encode_multi_recipient(stanzas: &[Stanza], file_key: &[u8], body: &[u8]) -> Result<Vec<u8>, FileEncryptionError> {
+    if stanzas.iter().filter(|stanza| stanza.kind == STANZA_PASSPHRASE).count() > 1 {
+        return Err(FileEncryptionError::MultiplePassphrases);
+    }
+    let count = u16::try_from(stanzas.len()).map_err(|_| FileEncryptionError::InvalidFormat)?;
+    let mut out = MULTI_MAGIC.to_vec();
+    out.extend_from_slice(&count.to_be_bytes());
+    for stanza in stanzas {
+        let len = u16::try_from(stanza.data.len()).map_err(|_| FileEncryptionError::InvalidFormat)?;
+        out.push(stanza.kind);
+        out.extend_from_slice(&len.to_be_bytes());
+        out.extend_from_slice(&stanza.data);
+    }
+    let mac = header_mac(file_key, &out).finalize().into_bytes();
+    out.extend_from_slice(&mac);
+    out.extend_from_slice(body);
+    Ok(out)
+}
+
+/// Splits a file into its stanzas and body; the header MAC between them is
+/// checked by [`unwrap_file_key`] once a file key is known.
+fn decode_multi_recipient(data: &[u8]) -> Result<(Vec<Stanza>, &[u8]), FileEncryptionError> {
+    let rest = data.strip_prefix(MULTI_MAGIC.as_slice()).ok_or(FileEncryptionError::InvalidFormat)?;
+    if rest.len() < 2 {
+        return Err(FileEncryptionError::InvalidFormat);
+    }
+    let count = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+    let mut rest = &rest[2..];
+    let mut stanzas = Vec::with_capacity(count);
+    for _ in 0..count {
+        if rest.len() < 3 {
+            return Err(FileEncryptionError::InvalidFormat);
+        }
+        let len = u16::from_be_bytes([rest[1], rest[2]]) as usize;
+        if rest.len() < 3 + len {
+            return Err(FileEncryptionError::InvalidFormat);
+        }
+        stanzas.push(Stanza { kind: rest[0], data: rest[3..3 + len].to_vec() });
+        rest = &rest[3 + len..];
+    }
+    if rest.len() < HEADER_MAC_LEN || stanzas.iter().filter(|stanza| stanza.kind == STANZA_PASSPHRASE).count() > 1 {
+        return Err(FileEncryptionError::InvalidFormat);
+    }
+    Ok((stanzas, &rest[HEADER_MAC_LEN..]))
+}
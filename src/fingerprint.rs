@@ -0,0 +1,97 @@
+use std::fmt;
+use sha2::{Digest, Sha256};
+use crate::utils::to_hex;
+use crate::wordlist;
+
+pub const FINGERPRINT_LEN: usize = 32;
+/// Words shown by default: 66 bits, plenty for a person-to-person comparison.
+pub const DEFAULT_WORDS: usize = 6;
+pub const DEFAULT_EMOJI: usize = 11;
+const KEY_ID_LEN: usize = 8;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const SECRET_KEY_CONTEXT: &[u8] = b"qimem-secret-key-fingerprint";
+const SAFETY_NUMBER_CONTEXT: &[u8] = b"qimem-safety-number";
+
+/// 64 emoji that are easy to tell apart and name aloud, one per 6 bits.
+const EMOJI: [char; 64] = [
+    '🐶', '🐱', '🐭', '🐹', '🐰', '🦊', '🐻', '🐼', '🐨', '🐯', '🦁', '🐮', '🐷', '🐸', '🐵', '🐔',
+    '🐧', '🐦', '🐤', '🦆', '🦅', '🦉', '🦇', '🐺', '🐗', '🐴', '🦄', '🐝', '🐛', '🦋', '🐌', '🐞',
+    '🐢', '🐍', '🦎', '🐙', '🦑', '🦀', '🐡', '🐠', '🐬', '🐳', '🦈', '🐊', '🐘', '🦒', '🦓', '🦔',
+    '🍎', '🍋', '🍌', '🍉', '🍇', '🍓', '🍒', '🍑', '🍍', '🥝', '🥕', '🌽', '🍄', '🌵', '🌻', '🌙',
+];
+
+/// SHA-256 fingerprint of a key, with renderings for people to compare.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Fingerprint(pub [u8; FINGERPRINT_LEN]);
+
+impl Fingerprint {
+    /// Fingerprint of a public key: plain SHA-256 of its raw bytes.
+    pub fn of(public_key: &[u8]) -> Self {
+        Fingerprint(Sha256::digest(public_key).into())
+    }
+
+    /// Fingerprint of a secret (e.g. a KeyStore entry), domain-separated so it
+    /// never equals a plain hash of the key that might be published elsewhere.
+    pub fn of_secret(secret_key: &[u8]) -> Self {
+        Fingerprint(Sha256::new().chain_update(SECRET_KEY_CONTEXT).chain_update(secret_key).finalize().into())
+    }
+
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.0)
+    }
+
+    /// RFC 4648 base32 without padding (52 characters).
+    pub fn to_base32(&self) -> String {
+        (0..(FINGERPRINT_LEN * 8).div_ceil(5))
+            .map(|i| BASE32_ALPHABET[bits(&self.0, i * 5, 5)] as char)
+            .collect()
+    }
+
+    /// Short id (the first 8 bytes in hex) for naming keys in logs and envelopes.
+    pub fn key_id(&self) -> String {
+        to_hex(&self.0[..KEY_ID_LEN])
+    }
+
+    /// The first `count` 11-bit groups as words from the BIP39 English list (at most 23).
+    pub fn to_words(&self, count: usize) -> Vec<&'static str> {
+        let count = count.min(FINGERPRINT_LEN * 8 / 11);
+        (0..count).map(|i| wordlist::words()[bits(&self.0, i * 11, 11)]).collect()
+    }
+
+    /// The first `count` 6-bit groups as emoji (at most 42).
+    pub fn to_emoji(&self, count: usize) -> String {
+        let count = count.min(FINGERPRINT_LEN * 8 / 6);
+        (0..count).map(|i| EMOJI[bits(&self.0, i * 6, 6)]).collect()
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    /// `SHA256:` followed by the hex digest in groups of four.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = self.to_hex();
+        let groups: Vec<&str> = (0..hex.len()).step_by(4).map(|i| &hex[i..i + 4]).collect();
+        write!(f, "SHA256:{}", groups.join(" "))
+    }
+}
+
+/// A fingerprint of two public keys that both owners compute identically
+/// (the keys are sorted first), so they can compare one value out loud
+/// instead of reading each other's fingerprints.
+pub fn safety_number(public_key: &[u8], peer_public_key: &[u8]) -> Fingerprint {
+    let (first, second) = if public_key <= peer_public_key { (public_key, peer_public_key) } else { (peer_public_key, public_key) };
+    let mut hasher = Sha256::new();
+    hasher.update(SAFETY_NUMBER_CONTEXT);
+    for key in [first, second] {
+        hasher.update((key.len() as u32).to_be_bytes());
+        hasher.update(key);
+    }
+    Fingerprint(hasher.finalize().into())
+}
+
+/// `width` bits starting at bit `offset` (MSB first), zero-padded past the end.
+fn bits(bytes: &[u8], offset: usize, width: usize) -> usize {
+    (offset..offset + width).fold(0, |acc, bit| {
+        let value = bytes.get(bit / 8).map_or(0, |byte| (byte >> (7 - bit % 8)) & 1);
+        (acc << 1) | value as usize
+    })
+}
@@ -0,0 +1,274 @@
+use hkdf::Hkdf;
+use sha2::Sha256;
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::key_exchange::{self, KeyExchangeError};
+use crate::q_core::{aead_open, aead_seal, AeadAlgorithm, QCoreError, NONCE_LEN};
+
+// DHKEM(X25519, HKDF-SHA256) with HKDF-SHA256 (RFC 9180 section 7).
+const KEM_ID: u16 = 0x0020;
+const KDF_ID: u16 = 0x0001;
+const N_SECRET: usize = 32;
+const N_H: usize = 32;
+const MODE_BASE: u8 = 0x00;
+const MODE_AUTH: u8 = 0x02;
+
+#[derive(thiserror::Error, Debug)]
+pub enum HpkeError {
+    #[error("Unknown AEAD {0:?} (expected chacha20poly1305, aes128gcm or aes256gcm)")]
+    UnknownAead(String),
+    #[error("Key exchange error: {0}")]
+    KeyExchange(#[from] KeyExchangeError),
+    #[error("AEAD error: {0}")]
+    Aead(#[from] QCoreError),
+    #[error("Message limit reached for this context")]
+    MessageLimitReached,
+    #[error("Export length too large")]
+    ExportTooLong,
+}
+
+impl From<HpkeError> for PyErr {
+    fn from(err: HpkeError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// Parses the AEAD names used by the Python API.
+pub fn parse_aead(name: &str) -> Result<AeadAlgorithm, HpkeError> {
+    match name.to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+        "chacha20poly1305" => Ok(AeadAlgorithm::ChaCha20Poly1305),
+        "aes128gcm" => Ok(AeadAlgorithm::Aes128Gcm),
+        "aes256gcm" => Ok(AeadAlgorithm::Aes256Gcm),
+        _ => Err(HpkeError::UnknownAead(name.to_string())),
+    }
+}
+
+fn aead_id(aead: AeadAlgorithm) -> u16 {
+    match aead {
+        AeadAlgorithm::Aes128Gcm => 0x0001,
+        AeadAlgorithm::Aes256Gcm => 0x0002,
+        AeadAlgorithm::ChaCha20Poly1305 => 0x0003,
+    }
+}
+
+/// Encryption context shared by both roles: the key schedule output and the sequence number.
+struct Context {
+    aead: AeadAlgorithm,
+    key: Vec<u8>,
+    base_nonce: [u8; NONCE_LEN],
+    exporter_secret: Vec<u8>,
+    seq: u64,
+    suite_id: Vec<u8>,
+}
+
+impl Context {
+    fn next_nonce(&mut self) -> Result<[u8; NONCE_LEN], HpkeError> {
+        let seq = self.seq;
+        self.seq = seq.checked_add(1).ok_or(HpkeError::MessageLimitReached)?;
+        let mut nonce = self.base_nonce;
+        for (n, s) in nonce[NONCE_LEN - 8..].iter_mut().zip(seq.to_be_bytes()) {
+            *n ^= s;
+        }
+        Ok(nonce)
+    }
+
+    fn export(&self, exporter_context: &[u8], length: usize) -> Result<Vec<u8>, HpkeError> {
+        if length > 255 * N_H {
+            return Err(HpkeError::ExportTooLong);
+        }
+        Ok(labeled_expand(&self.suite_id, &self.exporter_secret, b"sec", exporter_context, length))
+    }
+}
+
+/// Sender side of an HPKE context; each [`SenderContext::seal`] uses the next nonce.
+pub struct SenderContext(Context);
+
+impl SenderContext {
+    pub fn seal(&mut self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, HpkeError> {
+        let nonce = self.0.next_nonce()?;
+        Ok(aead_seal(self.0.aead, &self.0.key, &nonce, aad, plaintext)?)
+    }
+
+    /// Secret export (RFC 9180 section 5.3), identical on both sides.
+    pub fn export(&self, exporter_context: &[u8], length: usize) -> Result<Vec<u8>, HpkeError> {
+        self.0.export(exporter_context, length)
+    }
+}
+
+/// Recipient side of an HPKE context. Messages must be opened in the order they were sealed.
+pub struct RecipientContext(Context);
+
+impl RecipientContext {
+    pub fn open(&mut self, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, HpkeError> {
+        let nonce = self.0.next_nonce()?;
+        let plaintext = aead_open(self.0.aead, &self.0.key, &nonce, aad, ciphertext);
+        if plaintext.is_err() {
+            // A failed open must not consume a sequence number.
+            self.0.seq -= 1;
+        }
+        Ok(plaintext?)
+    }
+
+    pub fn export(&self, exporter_context: &[u8], length: usize) -> Result<Vec<u8>, HpkeError> {
+        self.0.export(exporter_context, length)
+    }
+}
+
+/// Base mode: anyone can encrypt to `recipient_public_key`. Returns the
+/// encapsulated key `enc`, which must be sent along with the ciphertexts.
+pub fn setup_base_sender(aead: AeadAlgorithm, recipient_public_key: &[u8], info: &[u8]) -> Result<(Vec<u8>, SenderContext), HpkeError> {
+    let (_, ephemeral_secret) = key_exchange::generate_keypair();
+    setup_sender_with_ephemeral(aead, recipient_public_key, info, None, &ephemeral_secret)
+}
+
+/// Auth mode: as base mode, but the recipient also learns that the sender holds `sender_secret_key`.
+pub fn setup_auth_sender(
+    aead: AeadAlgorithm,
+    recipient_public_key: &[u8],
+    info: &[u8],
+    sender_secret_key: &[u8],
+) -> Result<(Vec<u8>, SenderContext), HpkeError> {
+    let (_, ephemeral_secret) = key_exchange::generate_keypair();
+    setup_sender_with_ephemeral(aead, recipient_public_key, info, Some(sender_secret_key), &ephemeral_secret)
+}
+
+pub fn setup_base_recipient(aead: AeadAlgorithm, enc: &[u8], recipient_secret_key: &[u8], info: &[u8]) -> Result<RecipientContext, HpkeError> {
+    setup_recipient(aead, enc, recipient_secret_key, info, None)
+}
+
+pub fn setup_auth_recipient(
+    aead: AeadAlgorithm,
+    enc: &[u8],
+    recipient_secret_key: &[u8],
+    info: &[u8],
+    sender_public_key: &[u8],
+) -> Result<RecipientContext, HpkeError> {
+    setup_recipient(aead, enc, recipient_secret_key, info, Some(sender_public_key))
+}
+
+/// Single-shot encryption of one message, returning `(enc, ciphertext)`.
+/// Passing `sender_secret_key` selects auth mode.
+pub fn seal(
+    aead: AeadAlgorithm,
+    recipient_public_key: &[u8],
+    info: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+    sender_secret_key: Option<&[u8]>,
+) -> Result<(Vec<u8>, Vec<u8>), HpkeError> {
+    let (enc, mut context) = match sender_secret_key {
+        Some(sender_secret_key) => setup_auth_sender(aead, recipient_public_key, info, sender_secret_key)?,
+        None => setup_base_sender(aead, recipient_public_key, info)?,
+    };
+    Ok((enc, context.seal(aad, plaintext)?))
+}
+
+/// Inverse of [`seal`]; pass `sender_public_key` for auth mode.
+pub fn open(
+    aead: AeadAlgorithm,
+    enc: &[u8],
+    recipient_secret_key: &[u8],
+    info: &[u8],
+    aad: &[u8],
+    ciphertext: &[u8],
+    sender_public_key: Option<&[u8]>,
+) -> Result<Vec<u8>, HpkeError> {
+    setup_recipient(aead, enc, recipient_secret_key, info, sender_public_key)?.open(aad, ciphertext)
+}
+
+/// DeriveKeyPair for DHKEM(X25519): `(public_key, secret_key)` from input keying material.
+pub fn derive_keypair(ikm: &[u8]) -> Result<([u8; 32], [u8; 32]), HpkeError> {
+    let suite_id = kem_suite_id();
+    let prk = labeled_extract(&suite_id, b"", b"dkp_prk", ikm);
+    let secret_key: [u8; 32] = labeled_expand(&suite_id, &prk, b"sk", b"", 32).try_into().unwrap();
+    Ok((key_exchange::public_key(&secret_key)?, secret_key))
+}
+
+pub(crate) fn setup_sender_with_ephemeral(
+    aead: AeadAlgorithm,
+    recipient_public_key: &[u8],
+    info: &[u8],
+    sender_secret_key: Option<&[u8]>,
+    ephemeral_secret: &[u8],
+) -> Result<(Vec<u8>, SenderContext), HpkeError> {
+    let enc = key_exchange::public_key(ephemeral_secret)?;
+    let mut dh = key_exchange::diffie_hellman(ephemeral_secret, recipient_public_key)?.to_vec();
+    let mut kem_context = enc.to_vec();
+    kem_context.extend_from_slice(recipient_public_key);
+    if let Some(sender_secret_key) = sender_secret_key {
+        dh.extend_from_slice(&key_exchange::diffie_hellman(sender_secret_key, recipient_public_key)?);
+        kem_context.extend_from_slice(&key_exchange::public_key(sender_secret_key)?);
+    }
+    let shared_secret = extract_and_expand(&dh, &kem_context);
+    let mode = if sender_secret_key.is_some() { MODE_AUTH } else { MODE_BASE };
+    Ok((enc.to_vec(), SenderContext(key_schedule(aead, mode, &shared_secret, info))))
+}
+
+fn setup_recipient(
+    aead: AeadAlgorithm,
+    enc: &[u8],
+    recipient_secret_key: &[u8],
+    info: &[u8],
+    sender_public_key: Option<&[u8]>,
+) -> Result<RecipientContext, HpkeError> {
+    let mut dh = key_exchange::diffie_hellman(recipient_secret_key, enc)?.to_vec();
+    let mut kem_context = enc.to_vec();
+    kem_context.extend_from_slice(&key_exchange::public_key(recipient_secret_key)?);
+    if let Some(sender_public_key) = sender_public_key {
+        dh.extend_from_slice(&key_exchange::diffie_hellman(recipient_secret_key, sender_public_key)?);
+        kem_context.extend_from_slice(sender_public_key);
+    }
+    let shared_secret = extract_and_expand(&dh, &kem_context);
+    let mode = if sender_public_key.is_some() { MODE_AUTH } else { MODE_BASE };
+    Ok(RecipientContext(key_schedule(aead, mode, &shared_secret, info)))
+}
+
+fn extract_and_expand(dh: &[u8], kem_context: &[u8]) -> Vec<u8> {
+    let suite_id = kem_suite_id();
+    let prk = labeled_extract(&suite_id, b"", b"eae_prk", dh);
+    labeled_expand(&suite_id, &prk, b"shared_secret", kem_context, N_SECRET)
+}
+
+/// KeySchedule for the modes without a PSK (psk and psk_id are empty).
+fn key_schedule(aead: AeadAlgorithm, mode: u8, shared_secret: &[u8], info: &[u8]) -> Context {
+    let mut suite_id = b"HPKE".to_vec();
+    suite_id.extend_from_slice(&KEM_ID.to_be_bytes());
+    suite_id.extend_from_slice(&KDF_ID.to_be_bytes());
+    suite_id.extend_from_slice(&aead_id(aead).to_be_bytes());
+
+    let mut context = vec![mode];
+    context.extend_from_slice(&labeled_extract(&suite_id, b"", b"psk_id_hash", b""));
+    context.extend_from_slice(&labeled_extract(&suite_id, b"", b"info_hash", info));
+    let secret = labeled_extract(&suite_id, shared_secret, b"secret", b"");
+
+    Context {
+        aead,
+        key: labeled_expand(&suite_id, &secret, b"key", &context, aead.key_len()),
+        base_nonce: labeled_expand(&suite_id, &secret, b"base_nonce", &context, NONCE_LEN).try_into().unwrap(),
+        exporter_secret: labeled_expand(&suite_id, &secret, b"exp", &context, N_H),
+        seq: 0,
+        suite_id,
+    }
+}
+
+fn kem_suite_id() -> Vec<u8> {
+    let mut suite_id = b"KEM".to_vec();
+    suite_id.extend_from_slice(&KEM_ID.to_be_bytes());
+    suite_id
+}
+
+fn labeled_extract(suite_id: &[u8], salt: &[u8], label: &[u8], ikm: &[u8]) -> Vec<u8> {
+    let labeled_ikm = [b"HPKE-v1".as_slice(), suite_id, label, ikm].concat();
+    let (prk, _) = Hkdf::<Sha256>::extract(Some(salt), &labeled_ikm);
+    prk.to_vec()
+}
+
+fn labeled_expand(suite_id: &[u8], prk: &[u8], label: &[u8], info: &[u8], length: usize) -> Vec<u8> {
+    let labeled_info = [&(length as u16).to_be_bytes(), b"HPKE-v1".as_slice(), suite_id, label, info].concat();
+    let mut out = vec![0u8; length];
+    Hkdf::<Sha256>::from_prk(prk)
+        .expect("PRK is a full SHA-256 output")
+        .expand(&labeled_info, &mut out)
+        .expect("length is checked against 255 * Nh");
+    out
+}
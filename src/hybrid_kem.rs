@@ -0,0 +1,127 @@
+use rand::RngCore;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::{Digest, Sha3_256, Shake256};
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::key_exchange::{self, KeyExchangeError};
+use crate::ml_kem::{self, MlKemError};
+use crate::q_core::{decrypt, encrypt, QCoreError};
+
+pub const SECRET_KEY_LEN: usize = 32;
+pub const PUBLIC_KEY_LEN: usize = ml_kem::PUBLIC_KEY_LEN + key_exchange::KEY_LEN;
+pub const CIPHERTEXT_LEN: usize = ml_kem::CIPHERTEXT_LEN + key_exchange::KEY_LEN;
+pub const SHARED_SECRET_LEN: usize = 32;
+/// The X-Wing combiner label, `\.//^\`.
+const COMBINER_LABEL: &[u8] = b"\\.//^\\";
+
+#[derive(thiserror::Error, Debug)]
+pub enum HybridKemError {
+    #[error("Hybrid public key must be {PUBLIC_KEY_LEN} bytes")]
+    InvalidPublicKey,
+    #[error("Hybrid secret key must be {SECRET_KEY_LEN} bytes")]
+    InvalidSecretKey,
+    #[error("Hybrid ciphertext must be {CIPHERTEXT_LEN} bytes")]
+    InvalidCiphertext,
+    #[error("ML-KEM error: {0}")]
+    MlKem(#[from] MlKemError),
+    #[error("Key exchange error: {0}")]
+    KeyExchange(#[from] KeyExchangeError),
+    #[error("Encryption error: {0}")]
+    Encryption(#[from] QCoreError),
+}
+
+impl From<HybridKemError> for PyErr {
+    fn from(err: HybridKemError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// The component keys expanded from a 32-byte hybrid secret key:
+/// SHAKE256(sk, 96) gives the ML-KEM seed `d || z` and the X25519 secret.
+struct ExpandedKey {
+    ml_kem_secret: Vec<u8>,
+    ml_kem_public: Vec<u8>,
+    x25519_secret: [u8; key_exchange::KEY_LEN],
+    x25519_public: [u8; key_exchange::KEY_LEN],
+}
+
+fn expand(secret_key: &[u8]) -> Result<ExpandedKey, HybridKemError> {
+    if secret_key.len() != SECRET_KEY_LEN {
+        return Err(HybridKemError::InvalidSecretKey);
+    }
+    let mut expanded = [0u8; 96];
+    Shake256::default().chain(secret_key).finalize_xof().read(&mut expanded);
+    let (ml_kem_public, ml_kem_secret) = ml_kem::keypair_from_seed(expanded[..64].try_into().unwrap());
+    let x25519_secret: [u8; 32] = expanded[64..].try_into().unwrap();
+    let x25519_public = key_exchange::public_key(&x25519_secret)?;
+    Ok(ExpandedKey { ml_kem_secret, ml_kem_public, x25519_secret, x25519_public })
+}
+
+/// Returns `(public_key, secret_key)`. The secret key is a 32-byte seed; the
+/// public key is `ML-KEM-768 encapsulation key | X25519 public key`.
+pub fn generate_keypair() -> (Vec<u8>, [u8; SECRET_KEY_LEN]) {
+    let mut secret_key = [0u8; SECRET_KEY_LEN];
+    rand::thread_rng().fill_bytes(&mut secret_key);
+    (public_key(&secret_key).unwrap(), secret_key)
+}
+
+pub fn public_key(secret_key: &[u8]) -> Result<Vec<u8>, HybridKemError> {
+    let key = expand(secret_key)?;
+    Ok([key.ml_kem_public.as_slice(), &key.x25519_public].concat())
+}
+
+/// Returns `(ciphertext, shared_secret)`, the ciphertext being
+/// `ML-KEM ciphertext | ephemeral X25519 public key`. The secret stays safe
+/// as long as either ML-KEM-768 or X25519 does.
+pub fn encapsulate(public_key: &[u8]) -> Result<(Vec<u8>, [u8; SHARED_SECRET_LEN]), HybridKemError> {
+    if public_key.len() != PUBLIC_KEY_LEN {
+        return Err(HybridKemError::InvalidPublicKey);
+    }
+    let (ml_kem_public, x25519_public) = public_key.split_at(ml_kem::PUBLIC_KEY_LEN);
+    let (ml_kem_ciphertext, ml_kem_secret) = ml_kem::encapsulate(ml_kem_public)?;
+    let (ephemeral_public, ephemeral_secret) = key_exchange::generate_keypair();
+    let x25519_secret = key_exchange::diffie_hellman(&ephemeral_secret, x25519_public)?;
+    let shared_secret = combine(&ml_kem_secret, &x25519_secret, &ephemeral_public, x25519_public);
+    Ok(([ml_kem_ciphertext.as_slice(), &ephemeral_public].concat(), shared_secret))
+}
+
+pub fn decapsulate(secret_key: &[u8], ciphertext: &[u8]) -> Result<[u8; SHARED_SECRET_LEN], HybridKemError> {
+    if ciphertext.len() != CIPHERTEXT_LEN {
+        return Err(HybridKemError::InvalidCiphertext);
+    }
+    let key = expand(secret_key)?;
+    let (ml_kem_ciphertext, ephemeral_public) = ciphertext.split_at(ml_kem::CIPHERTEXT_LEN);
+    let ml_kem_secret = ml_kem::decapsulate(&key.ml_kem_secret, ml_kem_ciphertext)?;
+    let x25519_secret = key_exchange::diffie_hellman(&key.x25519_secret, ephemeral_public)?;
+    Ok(combine(&ml_kem_secret, &x25519_secret, ephemeral_public, &key.x25519_public))
+}
+
+/// Encrypts `plaintext` to a hybrid public key: `ciphertext | q_core ciphertext`,
+/// keyed directly with the encapsulated secret.
+pub fn seal(public_key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, HybridKemError> {
+    let (mut out, key) = encapsulate(public_key)?;
+    out.extend_from_slice(&encrypt(plaintext, &key)?);
+    Ok(out)
+}
+
+/// Opens a box produced by [`seal`].
+pub fn open(secret_key: &[u8], sealed: &[u8]) -> Result<Vec<u8>, HybridKemError> {
+    if sealed.len() < CIPHERTEXT_LEN {
+        return Err(HybridKemError::InvalidCiphertext);
+    }
+    let (ciphertext, body) = sealed.split_at(CIPHERTEXT_LEN);
+    Ok(decrypt(body, &decapsulate(secret_key, ciphertext)?)?)
+}
+
+/// SHA3-256(ss_M | ss_X | ct_X | pk_X | label), the X-Wing combiner. The
+/// ML-KEM ciphertext needn't be hashed since ML-KEM is itself CCA-secure.
+fn combine(ml_kem_secret: &[u8], x25519_secret: &[u8], ephemeral_public: &[u8], x25519_public: &[u8]) -> [u8; SHARED_SECRET_LEN] {
+    Sha3_256::new()
+        .chain_update(ml_kem_secret)
+        .chain_update(x25519_secret)
+        .chain_update(ephemeral_public)
+        .chain_update(x25519_public)
+        .chain_update(COMBINER_LABEL)
+        .finalize()
+        .into()
+}
@@ -0,0 +1,187 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+use serde_json::{json, Map, Value};
+use sha2::{Sha256, Digest};
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::signing::{sign_message, verify_signature, SigningError};
+
+pub const DEFAULT_LEEWAY_SECS: i64 = 60;
+
+#[derive(thiserror::Error, Debug)]
+pub enum JwtError {
+    #[error("Malformed JWT")]
+    Malformed,
+    #[error("Unsupported algorithm {0:?}, expected EdDSA")]
+    UnsupportedAlgorithm(String),
+    #[error("Claims must be a JSON object")]
+    InvalidClaims,
+    #[error("Claim \"{0}\" has the wrong type")]
+    InvalidClaim(&'static str),
+    #[error("Missing required claim \"{0}\"")]
+    MissingClaim(&'static str),
+    #[error("Signature does not match the public key")]
+    InvalidSignature,
+    #[error("Token expired at {expired_at} (now {now})")]
+    Expired { expired_at: i64, now: i64 },
+    #[error("Token not valid before {not_before} (now {now})")]
+    NotYetValid { not_before: i64, now: i64 },
+    #[error("Token issued in the future ({issued_at}, now {now})")]
+    IssuedInFuture { issued_at: i64, now: i64 },
+    #[error("Issuer does not match")]
+    InvalidIssuer,
+    #[error("Audience does not match")]
+    InvalidAudience,
+    #[error("Signing error: {0}")]
+    Signing(#[from] SigningError),
+}
+
+impl From<JwtError> for PyErr {
+    fn from(err: JwtError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// What [`verify_jwt`] checks besides the signature. `exp` is required unless
+/// `require_exp` is off; `nbf` and `iat` are checked when present.
+#[derive(Clone, Debug)]
+pub struct JwtValidation {
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    pub leeway: i64,
+    pub require_exp: bool,
+}
+
+impl Default for JwtValidation {
+    fn default() -> Self {
+        JwtValidation { issuer: None, audience: None, leeway: DEFAULT_LEEWAY_SECS, require_exp: true }
+    }
+}
+
+/// Issues a compact JWS with `alg: EdDSA`, signed with a 32-byte Ed25519 secret key.
+/// The header's `kid` is the RFC 7638 thumbprint of the matching public key, and
+/// `iat` is set to the current time unless the claims already carry one.
+pub fn issue_jwt(secret_key: &[u8], claims: &Value) -> Result<String, JwtError> {
+    let mut claims = claims.as_object().ok_or(JwtError::InvalidClaims)?.clone();
+    claims.entry("iat").or_insert_with(|| json!(Utc::now().timestamp()));
+    let secret: [u8; 32] = secret_key.try_into().map_err(|_| SigningError::InvalidSecretKey)?;
+    let public_key = SigningKey::from_bytes(&secret).verifying_key().to_bytes();
+    let header = json!({ "alg": "EdDSA", "typ": "JWT", "kid": jwk_thumbprint(&public_key) });
+    sign_compact(secret_key, &header, Value::Object(claims).to_string().as_bytes())
+}
+
+/// `base64url(header) . base64url(payload) . base64url(signature)`.
+pub(crate) fn sign_compact(secret_key: &[u8], header: &Value, payload: &[u8]) -> Result<String, JwtError> {
+    let signing_input = format!("{}.{}", BASE64_URL.encode(header.to_string()), BASE64_URL.encode(payload));
+    let signature = sign_message(secret_key, signing_input.as_bytes())?;
+    Ok(format!("{}.{}", signing_input, BASE64_URL.encode(signature)))
+}
+
+/// Verifies `token` against `public_key` at the current time and returns its claims.
+pub fn verify_jwt(token: &str, public_key: &[u8], validation: &JwtValidation) -> Result<Map<String, Value>, JwtError> {
+    verify_jwt_at(token, public_key, validation, Utc::now().timestamp())
+}
+
+/// Like [`verify_jwt`] with an explicit current time in Unix seconds.
+pub fn verify_jwt_at(
+    token: &str,
+    public_key: &[u8],
+    validation: &JwtValidation,
+    now: i64,
+) -> Result<Map<String, Value>, JwtError> {
+    let (signing_input, signature) = token.trim().rsplit_once('.').ok_or(JwtError::Malformed)?;
+    let (header, payload) = signing_input.split_once('.').ok_or(JwtError::Malformed)?;
+    let header: Value = decode_json(header)?;
+    // Pin the algorithm so a token cannot downgrade itself to "none" or an HMAC.
+    match header.get("alg").and_then(Value::as_str) {
+        Some("EdDSA") => {}
+        alg => return Err(JwtError::UnsupportedAlgorithm(alg.unwrap_or_default().to_string())),
+    }
+    let signature = BASE64_URL.decode(signature).map_err(|_| JwtError::Malformed)?;
+    if !verify_signature(public_key, signing_input.as_bytes(), &signature)? {
+        return Err(JwtError::InvalidSignature);
+    }
+    let Value::Object(claims) = decode_json(payload)? else {
+        return Err(JwtError::InvalidClaims);
+    };
+    validate_claims(&claims, validation, now)?;
+    Ok(claims)
+}
+
+fn validate_claims(claims: &Map<String, Value>, validation: &JwtValidation, now: i64) -> Result<(), JwtError> {
+    let time = |name: &'static str| -> Result<Option<i64>, JwtError> {
+        match claims.get(name) {
+            None => Ok(None),
+            Some(value) => value.as_i64().map(Some).ok_or(JwtError::InvalidClaim(name)),
+        }
+    };
+    match time("exp")? {
+        Some(expired_at) if expired_at + validation.leeway <= now => return Err(JwtError::Expired { expired_at, now }),
+        None if validation.require_exp => return Err(JwtError::MissingClaim("exp")),
+        _ => {}
+    }
+    if let Some(not_before) = time("nbf")? {
+        if not_before > now + validation.leeway {
+            return Err(JwtError::NotYetValid { not_before, now });
+        }
+    }
+    if let Some(issued_at) = time("iat")? {
+        if issued_at > now + validation.leeway {
+            return Err(JwtError::IssuedInFuture { issued_at, now });
+        }
+    }
+    if let Some(issuer) = &validation.issuer {
+        match claims.get("iss") {
+            Some(Value::String(iss)) if iss == issuer => {}
+            Some(Value::String(_)) => return Err(JwtError::InvalidIssuer),
+            Some(_) => return Err(JwtError::InvalidClaim("iss")),
+            None => return Err(JwtError::MissingClaim("iss")),
+        }
+    }
+    // RFC 7519 4.1.3: a token that names audiences must name ours.
+    let audiences: Vec<&str> = match claims.get("aud") {
+        None => Vec::new(),
+        Some(Value::String(aud)) => vec![aud.as_str()],
+        Some(Value::Array(auds)) => auds
+            .iter()
+            .map(|aud| aud.as_str().ok_or(JwtError::InvalidClaim("aud")))
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(JwtError::InvalidClaim("aud")),
+    };
+    match &validation.audience {
+        Some(_) if audiences.is_empty() => Err(JwtError::MissingClaim("aud")),
+        Some(audience) if !audiences.contains(&audience.as_str()) => Err(JwtError::InvalidAudience),
+        None if !audiences.is_empty() => Err(JwtError::InvalidAudience),
+        _ => Ok(()),
+    }
+}
+
+fn decode_json(part: &str) -> Result<Value, JwtError> {
+    let bytes = BASE64_URL.decode(part).map_err(|_| JwtError::Malformed)?;
+    serde_json::from_slice(&bytes).map_err(|_| JwtError::Malformed)
+}
+
+/// The public JWK (RFC 8037 `OKP`/`Ed25519`) for a key from [`crate::signing::generate_keypair`],
+/// with the RFC 7638 thumbprint as its `kid`.
+pub fn public_jwk(public_key: &[u8; 32]) -> Value {
+    json!({
+        "kty": "OKP",
+        "crv": "Ed25519",
+        "x": BASE64_URL.encode(public_key),
+        "use": "sig",
+        "alg": "EdDSA",
+        "kid": jwk_thumbprint(public_key),
+    })
+}
+
+/// A JWK Set (`{"keys": [...]}`) publishing the given public keys.
+pub fn jwks(public_keys: &[[u8; 32]]) -> Value {
+    json!({ "keys": public_keys.iter().map(public_jwk).collect::<Vec<_>>() })
+}
+
+/// RFC 7638 thumbprint: base64url SHA-256 of the required members in lexical order.
+pub fn jwk_thumbprint(public_key: &[u8; 32]) -> String {
+    let canonical = format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, BASE64_URL.encode(public_key));
+    BASE64_URL.encode(Sha256::digest(canonical.as_bytes()))
+}
@@ -0,0 +1,114 @@
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::q_core::{encrypt, decrypt, QCoreError};
+
+pub const KEY_LEN: usize = 32;
+const SHARED_KEY_INFO: &[u8] = b"qimem-x25519";
+const SEALED_BOX_INFO: &[u8] = b"qimem-sealed-box";
+
+#[derive(thiserror::Error, Debug)]
+pub enum KeyExchangeError {
+    #[error("X25519 keys must be 32 bytes")]
+    InvalidKeyLength,
+    #[error("Public key is a low-order point")]
+    LowOrderPoint,
+    #[error("Sealed box is too short")]
+    InvalidData,
+    #[error("Encryption error: {0}")]
+    Encryption(#[from] QCoreError),
+}
+
+impl From<KeyExchangeError> for PyErr {
+    fn from(err: KeyExchangeError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// Returns `(public_key, secret_key)` for X25519 key agreement. These are
+/// separate from the Ed25519 signing keys in [`crate::signing`].
+pub fn generate_keypair() -> ([u8; KEY_LEN], [u8; KEY_LEN]) {
+    let secret = StaticSecret::random_from_rng(OsRng);
+    (PublicKey::from(&secret).to_bytes(), secret.to_bytes())
+}
+
+pub fn public_key(secret_key: &[u8]) -> Result<[u8; KEY_LEN], KeyExchangeError> {
+    Ok(PublicKey::from(&static_secret(secret_key)?).to_bytes())
+}
+
+/// Raw X25519. Fails if the result is all zeros, which happens exactly when the
+/// peer's public key is a low-order point that would let it force the output.
+pub fn diffie_hellman(secret_key: &[u8], peer_public_key: &[u8]) -> Result<[u8; KEY_LEN], KeyExchangeError> {
+    let shared = static_secret(secret_key)?.diffie_hellman(&public(peer_public_key)?);
+    if !shared.was_contributory() {
+        return Err(KeyExchangeError::LowOrderPoint);
+    }
+    Ok(shared.to_bytes())
+}
+
+/// A 32-byte symmetric key shared between the owners of two X25519 keypairs:
+/// HKDF-SHA256 over the DH output, bound to both public keys and to `info`.
+/// Both sides get the same key whichever one calls it.
+pub fn derive_shared_key(secret_key: &[u8], peer_public_key: &[u8], info: &[u8]) -> Result<[u8; KEY_LEN], KeyExchangeError> {
+    let shared = diffie_hellman(secret_key, peer_public_key)?;
+    let own_public = public_key(secret_key)?;
+    let peer_public = public(peer_public_key)?.to_bytes();
+    let (first, second) = if own_public <= peer_public { (own_public, peer_public) } else { (peer_public, own_public) };
+    let mut context = SHARED_KEY_INFO.to_vec();
+    context.extend_from_slice(&first);
+    context.extend_from_slice(&second);
+    context.extend_from_slice(info);
+    Ok(hkdf(&[], &shared, &context))
+}
+
+/// Encrypts `plaintext` so that only the holder of `recipient_public_key`'s
+/// secret can read it, without revealing who sent it. The output is
+/// `ephemeral public key | q_core ciphertext`, the key coming from HKDF over
+/// the ephemeral DH output salted with both public keys.
+pub fn seal(recipient_public_key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, KeyExchangeError> {
+    let recipient = public(recipient_public_key)?;
+    let (ephemeral_public, ephemeral_secret) = generate_keypair();
+    let shared = diffie_hellman(&ephemeral_secret, recipient.as_bytes())?;
+    let key = sealed_box_key(&shared, &ephemeral_public, recipient.as_bytes());
+    let mut out = ephemeral_public.to_vec();
+    out.extend_from_slice(&encrypt(plaintext, &key)?);
+    Ok(out)
+}
+
+/// Opens a box produced by [`seal`] with the recipient's secret key.
+pub fn open(recipient_secret_key: &[u8], sealed: &[u8]) -> Result<Vec<u8>, KeyExchangeError> {
+    if sealed.len() < KEY_LEN {
+        return Err(KeyExchangeError::InvalidData);
+    }
+    let (ephemeral_public, ciphertext) = sealed.split_at(KEY_LEN);
+    let shared = diffie_hellman(recipient_secret_key, ephemeral_public)?;
+    let key = sealed_box_key(&shared, ephemeral_public, &public_key(recipient_secret_key)?);
+    Ok(decrypt(ciphertext, &key)?)
+}
+
+fn sealed_box_key(shared: &[u8], ephemeral_public: &[u8], recipient_public: &[u8]) -> [u8; KEY_LEN] {
+    let mut salt = ephemeral_public.to_vec();
+    salt.extend_from_slice(recipient_public);
+    hkdf(&salt, shared, SEALED_BOX_INFO)
+}
+
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[u8]) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+fn static_secret(secret_key: &[u8]) -> Result<StaticSecret, KeyExchangeError> {
+    let bytes: [u8; KEY_LEN] = secret_key.try_into().map_err(|_| KeyExchangeError::InvalidKeyLength)?;
+    Ok(StaticSecret::from(bytes))
+}
+
+fn public(public_key: &[u8]) -> Result<PublicKey, KeyExchangeError> {
+    let bytes: [u8; KEY_LEN] = public_key.try_into().map_err(|_| KeyExchangeError::InvalidKeyLength)?;
+    Ok(PublicKey::from(bytes))
+}
@@ -1,14 +1,17 @@
-use pyo3::prelude::*;
-use pyo3::types::PyBytesMethods;
-use crate::q_keygen::derive_key;
+use crate::q_keygen::{require_strength, KdfParams, KeyGenError};
 use crate::q_core::{encrypt, decrypt, QCoreError};
+use crate::shamir::{combine_shares, split_secret, Share, ShamirError};
+use crate::mnemonic::{self, MnemonicError};
+use crate::key_wrap::{self, KeyWrapError};
+use crate::fingerprint::Fingerprint;
 use std::collections::HashMap;
-use std::fs::{self, File};
-use std::io::Read;
+use std::fs;
 use std::path::Path;
 use bincode;
 use chrono::Utc;
-use pyo3::exceptions::{PyValueError, PyIOError};
+
+const KEYSTORE_CONTEXT: &[u8] = b"qimem-keystore";
+const TIMESTAMP_LEN: usize = 16;
 
 #[derive(thiserror::Error, Debug)]
 pub enum KeyStoreError {
@@ -18,55 +21,213 @@ pub enum KeyStoreError {
     Serialization(#[from] bincode::Error),
     #[error("Encryption error: {0}")]
     Encryption(#[from] QCoreError),
+    #[error("Key derivation error: {0}")]
+    KeyGen(#[from] KeyGenError),
+    #[error("Secret sharing error: {0}")]
+    Shamir(#[from] ShamirError),
+    #[error("Mnemonic error: {0}")]
+    Mnemonic(#[from] MnemonicError),
+    #[error("Key wrap error: {0}")]
+    KeyWrap(#[from] KeyWrapError),
+    #[error("No key with id {0}")]
+    KeyNotFound(String),
     #[error("Invalid key")]
     InvalidKey,
+    #[error("Invalid keystore file")]
+    InvalidFormat,
 }
 
-#[pyclass]
+type KeyMap = HashMap<String, [u8; 32]>;
+type RecordMap = HashMap<String, Vec<u8>>;
+
+/// Password-protected key storage.
+///
+/// On disk the file is `params_len (u16 BE) | KdfParams blob | encrypted contents`,
+/// so the master key can be re-derived with the salt it was created with. The
+/// contents are the key map, followed by the record map once any records exist.
 pub struct KeyStore {
-    keys: HashMap<String, [u8; 32]>,
+    keys: KeyMap,
+    records: RecordMap,
     path: String,
+    params: KdfParams,
     master_key: [u8; 32],
 }
 
-#[pymethods]
 impl KeyStore {
-    #[new]
-    pub fn new(py: Python<'_>, path: &str, master_password: &str) -> PyResult<Self> {
-        let (master_key_bound, _) = derive_key(py, master_password, None)?;
-        let master_key: [u8; 32] = master_key_bound.as_bytes()
-            .try_into()
-            .map_err(|_| PyValueError::new_err("Master key must be 32 bytes"))?;
-        let keys = if Path::new(path).exists() {
-            let mut encrypted_file = File::open(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
-            let mut encrypted_data = Vec::new();
-            encrypted_file.read_to_end(&mut encrypted_data).map_err(|e| PyIOError::new_err(e.to_string()))?;
-            let decrypted_bound = decrypt(py, &encrypted_data, &master_key)?;
-            let decrypted_data = decrypted_bound.as_bytes();
-            bincode::deserialize(decrypted_data).map_err(|e| PyValueError::new_err(e.to_string()))?
+    pub fn new(path: &str, master_password: &str) -> Result<Self, KeyStoreError> {
+        Self::open(path, master_password, None)
+    }
+
+    /// Like [`KeyStore::new`], but refuses to create a new store whose master
+    /// password scores below `min_score` (0-4, see [`crate::q_keygen::estimate_strength`]).
+    /// Existing stores open regardless, so tightening the policy never locks anyone out.
+    pub fn new_with_min_score(path: &str, master_password: &str, min_score: u8) -> Result<Self, KeyStoreError> {
+        Self::open(path, master_password, Some(min_score))
+    }
+
+    /// Opens an existing store with `threshold` shares of its master key, as
+    /// produced by [`KeyStore::split_master_key`], instead of the password.
+    pub fn open_with_shares(path: &str, shares: &[Share]) -> Result<Self, KeyStoreError> {
+        Self::open_with_master_key(path, &combine_shares(shares)?)
+    }
+
+    /// Opens an existing store from the word-list backup of its master key
+    /// produced by [`KeyStore::master_key_mnemonic`].
+    pub fn open_with_mnemonic(path: &str, phrase: &str) -> Result<Self, KeyStoreError> {
+        Self::open_with_master_key(path, &mnemonic::decode(phrase)?)
+    }
+
+    fn open_with_master_key(path: &str, master_key: &[u8]) -> Result<Self, KeyStoreError> {
+        let (params, encrypted_data) = read_store(path)?;
+        let master_key: [u8; 32] = master_key.try_into().map_err(|_| KeyStoreError::InvalidKey)?;
+        let (keys, records) = decode_contents(&decrypt(&encrypted_data, &master_key)?)?;
+        Ok(KeyStore {
+            keys,
+            records,
+            path: path.to_string(),
+            params,
+            master_key,
+        })
+    }
+
+    fn open(path: &str, master_password: &str, min_score: Option<u8>) -> Result<Self, KeyStoreError> {
+        let ((keys, records), params, master_key) = if Path::new(path).exists() {
+            let (params, encrypted_data) = read_store(path)?;
+            let master_key = params.derive_key(master_password)?;
+            let decrypted_data = decrypt(&encrypted_data, &master_key)?;
+            (decode_contents(&decrypted_data)?, params, master_key)
         } else {
-            HashMap::new()
+            if let Some(min_score) = min_score {
+                require_strength(master_password, min_score)?;
+            }
+            let params = KdfParams::generate().with_context(KEYSTORE_CONTEXT);
+            let master_key = params.derive_key(master_password)?;
+            ((HashMap::new(), HashMap::new()), params, master_key)
         };
         Ok(KeyStore {
             keys,
+            records,
             path: path.to_string(),
+            params,
             master_key,
         })
     }
 
-    pub fn store_key(&mut self, py: Python<'_>, id: &str, key: &[u8]) -> PyResult<()> {
-        let key_array: [u8; 32] = key.try_into().map_err(|_| PyValueError::new_err("Key must be 32 bytes"))?;
+    /// Splits the master key into `share_count` recovery shares, any `threshold`
+    /// of which can open the store through [`KeyStore::open_with_shares`].
+    /// The shares stay valid until the master password changes.
+    pub fn split_master_key(&self, threshold: u8, share_count: u8) -> Result<Vec<Share>, KeyStoreError> {
+        Ok(split_secret(&self.master_key, threshold, share_count)?)
+    }
+
+    /// The master key as a 24-word checksummed phrase for paper backup.
+    /// Valid until the master password changes.
+    pub fn master_key_mnemonic(&self) -> Result<String, KeyStoreError> {
+        Ok(mnemonic::encode(&self.master_key)?)
+    }
+
+    /// Stores `key` under `id` suffixed with the current UTC timestamp.
+    pub fn store_key(&mut self, id: &str, key: [u8; 32]) -> Result<(), KeyStoreError> {
         let timestamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
         let full_id = format!("{}_{}", id, timestamp);
-        self.keys.insert(full_id, key_array);
-        let serialized_data = bincode::serialize(&self.keys).map_err(|e| PyValueError::new_err(e.to_string()))?;
-        let encrypted_bound = encrypt(py, &serialized_data, &self.master_key)?;
-        let encrypted_data = encrypted_bound.as_bytes();
-        fs::write(&self.path, encrypted_data).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        self.keys.insert(full_id, key);
+        self.save()
+    }
+
+    /// Looks up a key by its full timestamped id, or by the bare id, in which
+    /// case the most recently stored version is returned.
+    pub fn retrieve_key(&self, id: &str) -> Option<[u8; 32]> {
+        if let Some(key) = self.keys.get(id) {
+            return Some(*key);
+        }
+        self.keys
+            .iter()
+            .filter(|(full_id, _)| is_version_of(full_id, id))
+            .max_by(|a, b| a.0.cmp(b.0))
+            .map(|(_, key)| *key)
+    }
+
+    /// Fingerprint of a stored key (resolved like [`KeyStore::retrieve_key`]),
+    /// for checking two stores hold the same key without revealing it.
+    pub fn key_fingerprint(&self, id: &str) -> Option<Fingerprint> {
+        self.retrieve_key(id).map(|key| Fingerprint::of_secret(&key))
+    }
+
+    /// Exports the key `id` (resolved as in [`KeyStore::retrieve_key`]) wrapped
+    /// under `kek` with RFC 3394 AES Key Wrap, for import into systems such as HSMs.
+    pub fn export_wrapped_key(&self, id: &str, kek: &[u8]) -> Result<Vec<u8>, KeyStoreError> {
+        let key = self.retrieve_key(id).ok_or_else(|| KeyStoreError::KeyNotFound(id.to_string()))?;
+        Ok(key_wrap::wrap_key(kek, &key)?)
+    }
+
+    /// Unwraps an RFC 3394 wrapped 32-byte key and stores it under `id`.
+    pub fn import_wrapped_key(&mut self, id: &str, wrapped: &[u8], kek: &[u8]) -> Result<(), KeyStoreError> {
+        let key = key_wrap::unwrap_key(kek, wrapped)?;
+        let key: [u8; 32] = key.as_slice().try_into().map_err(|_| KeyStoreError::InvalidKey)?;
+        self.store_key(id, key)
+    }
+
+    /// Stores an arbitrary secret blob, such as serialized session state, under
+    /// `id`. Unlike keys, records are not versioned: this replaces any previous value.
+    pub fn store_record(&mut self, id: &str, data: &[u8]) -> Result<(), KeyStoreError> {
+        self.records.insert(id.to_string(), data.to_vec());
+        self.save()
+    }
+
+    pub fn retrieve_record(&self, id: &str) -> Option<Vec<u8>> {
+        self.records.get(id).cloned()
+    }
+
+    /// Removes the record `id`, returning whether it existed.
+    pub fn delete_record(&mut self, id: &str) -> Result<bool, KeyStoreError> {
+        let existed = self.records.remove(id).is_some();
+        if existed {
+            self.save()?;
+        }
+        Ok(existed)
+    }
+
+    fn save(&self) -> Result<(), KeyStoreError> {
+        let mut serialized_data = bincode::serialize(&self.keys)?;
+        if !self.records.is_empty() {
+            serialized_data.extend_from_slice(&bincode::serialize(&self.records)?);
+        }
+        let encrypted_data = encrypt(&serialized_data, &self.master_key)?;
+        let params = self.params.to_bytes();
+        let mut out = Vec::with_capacity(2 + params.len() + encrypted_data.len());
+        out.extend_from_slice(&(params.len() as u16).to_be_bytes());
+        out.extend_from_slice(&params);
+        out.extend_from_slice(&encrypted_data);
+        fs::write(&self.path, out)?;
         Ok(())
     }
+}
 
-    pub fn retrieve_key(&self, _py: Python<'_>, id: &str) -> PyResult<Option<Vec<u8>>> {
-        Ok(self.keys.get(id).map(|k| k.to_vec()))
+/// Reads a store file into its KDF parameters and the encrypted key map.
+fn read_store(path: &str) -> Result<(KdfParams, Vec<u8>), KeyStoreError> {
+    let data = fs::read(path)?;
+    if data.len() < 2 {
+        return Err(KeyStoreError::InvalidFormat);
+    }
+    let params_len = u16::from_be_bytes([data[0], data[1]]) as usize;
+    if data.len() < 2 + params_len {
+        return Err(KeyStoreError::InvalidFormat);
     }
-}
\ No newline at end of file
+    let params = KdfParams::from_bytes(&data[2..2 + params_len])?;
+    Ok((params, data[2 + params_len..].to_vec()))
+}
+
+/// Stores written before records existed hold only the key map.
+fn decode_contents(data: &[u8]) -> Result<(KeyMap, RecordMap), KeyStoreError> {
+    let mut reader = data;
+    let keys = bincode::deserialize_from(&mut reader)?;
+    let records = if reader.is_empty() { HashMap::new() } else { bincode::deserialize_from(&mut reader)? };
+    Ok((keys, records))
+}
+
+fn is_version_of(full_id: &str, id: &str) -> bool {
+    full_id
+        .strip_prefix(id)
+        .and_then(|rest| rest.strip_prefix('_'))
+        .is_some_and(|ts| ts.len() == TIMESTAMP_LEN && ts.ends_with('Z') && ts.as_bytes()[8] == b'T')
+}
@@ -0,0 +1,175 @@
+use aes::{Aes128, Aes192, Aes256};
+use aes::cipher::{BlockEncrypt, BlockDecrypt, KeyInit};
+use aes::cipher::generic_array::GenericArray;
+use subtle::{Choice, ConstantTimeEq};
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+
+/// RFC 3394 default initial value.
+const DEFAULT_IV: [u8; 8] = [0xA6; 8];
+/// RFC 5649 alternative initial value prefix, followed by the 32-bit message length.
+const PADDED_IV_PREFIX: [u8; 4] = [0xA6, 0x59, 0x59, 0xA6];
+
+#[derive(thiserror::Error, Debug)]
+pub enum KeyWrapError {
+    #[error("KEK must be 16, 24 or 32 bytes")]
+    InvalidKek,
+    #[error("Invalid key data length: {0}")]
+    InvalidLength(usize),
+    #[error("Integrity check failed (wrong KEK or corrupted data)")]
+    IntegrityCheckFailed,
+}
+
+impl From<KeyWrapError> for PyErr {
+    fn from(err: KeyWrapError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+enum Kek {
+    Aes128(Aes128),
+    Aes192(Aes192),
+    Aes256(Aes256),
+}
+
+impl Kek {
+    fn new(kek: &[u8]) -> Result<Self, KeyWrapError> {
+        match kek.len() {
+            16 => Ok(Kek::Aes128(Aes128::new(GenericArray::from_slice(kek)))),
+            24 => Ok(Kek::Aes192(Aes192::new(GenericArray::from_slice(kek)))),
+            32 => Ok(Kek::Aes256(Aes256::new(GenericArray::from_slice(kek)))),
+            _ => Err(KeyWrapError::InvalidKek),
+        }
+    }
+
+    fn encrypt(&self, block: &mut [u8; 16]) {
+        let block = GenericArray::from_mut_slice(block);
+        match self {
+            Kek::Aes128(c) => c.encrypt_block(block),
+            Kek::Aes192(c) => c.encrypt_block(block),
+            Kek::Aes256(c) => c.encrypt_block(block),
+        }
+    }
+
+    fn decrypt(&self, block: &mut [u8; 16]) {
+        let block = GenericArray::from_mut_slice(block);
+        match self {
+            Kek::Aes128(c) => c.decrypt_block(block),
+            Kek::Aes192(c) => c.decrypt_block(block),
+            Kek::Aes256(c) => c.decrypt_block(block),
+        }
+    }
+}
+
+/// RFC 3394 AES Key Wrap. `key` must be a multiple of 8 bytes and at least 16.
+pub fn wrap_key(kek: &[u8], key: &[u8]) -> Result<Vec<u8>, KeyWrapError> {
+    if key.len() < 16 || !key.len().is_multiple_of(8) {
+        return Err(KeyWrapError::InvalidLength(key.len()));
+    }
+    Ok(wrap_blocks(&Kek::new(kek)?, DEFAULT_IV, key))
+}
+
+/// Inverse of [`wrap_key`].
+pub fn unwrap_key(kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, KeyWrapError> {
+    if wrapped.len() < 24 || !wrapped.len().is_multiple_of(8) {
+        return Err(KeyWrapError::InvalidLength(wrapped.len()));
+    }
+    let (iv, key) = unwrap_blocks(&Kek::new(kek)?, wrapped);
+    if !bool::from(iv.ct_eq(&DEFAULT_IV)) {
+        return Err(KeyWrapError::IntegrityCheckFailed);
+    }
+    Ok(key)
+}
+
+/// RFC 5649 AES Key Wrap with Padding, for key data of any non-zero length.
+pub fn wrap_key_padded(kek: &[u8], key: &[u8]) -> Result<Vec<u8>, KeyWrapError> {
+    let length = u32::try_from(key.len()).map_err(|_| KeyWrapError::InvalidLength(key.len()))?;
+    if key.is_empty() {
+        return Err(KeyWrapError::InvalidLength(0));
+    }
+    let kek = Kek::new(kek)?;
+    let mut iv = [0u8; 8];
+    iv[..4].copy_from_slice(&PADDED_IV_PREFIX);
+    iv[4..].copy_from_slice(&length.to_be_bytes());
+    let mut padded = key.to_vec();
+    padded.resize(key.len().div_ceil(8) * 8, 0);
+
+    if padded.len() == 8 {
+        // A single padded block is encrypted directly as IV | P.
+        let mut block = [0u8; 16];
+        block[..8].copy_from_slice(&iv);
+        block[8..].copy_from_slice(&padded);
+        kek.encrypt(&mut block);
+        return Ok(block.to_vec());
+    }
+    Ok(wrap_blocks(&kek, iv, &padded))
+}
+
+/// Inverse of [`wrap_key_padded`].
+pub fn unwrap_key_padded(kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, KeyWrapError> {
+    if wrapped.len() < 16 || !wrapped.len().is_multiple_of(8) {
+        return Err(KeyWrapError::InvalidLength(wrapped.len()));
+    }
+    let kek = Kek::new(kek)?;
+    let (iv, padded) = if wrapped.len() == 16 {
+        let mut block = [0u8; 16];
+        block.copy_from_slice(wrapped);
+        kek.decrypt(&mut block);
+        (block[..8].try_into().unwrap(), block[8..].to_vec())
+    } else {
+        unwrap_blocks(&kek, wrapped)
+    };
+    let length = u32::from_be_bytes([iv[4], iv[5], iv[6], iv[7]]) as usize;
+    // All three checks are evaluated before branching, the prefix and padding in constant time.
+    let length_ok = Choice::from((length <= padded.len() && length > padded.len() - 8) as u8);
+    let padding = &padded[length.min(padded.len())..];
+    let padding_ok = padding.iter().fold(Choice::from(1), |ok, b| ok & b.ct_eq(&0));
+    if !bool::from(iv[..4].ct_eq(&PADDED_IV_PREFIX) & length_ok & padding_ok) {
+        return Err(KeyWrapError::IntegrityCheckFailed);
+    }
+    Ok(padded[..length].to_vec())
+}
+
+/// The RFC 3394 wrapping process W over 64-bit blocks of `data` with initial value `iv`.
+fn wrap_blocks(kek: &Kek, iv: [u8; 8], data: &[u8]) -> Vec<u8> {
+    let n = data.len() / 8;
+    let mut a = iv;
+    let mut r = data.to_vec();
+    let mut block = [0u8; 16];
+    for j in 0..6 {
+        for i in 0..n {
+            block[..8].copy_from_slice(&a);
+            block[8..].copy_from_slice(&r[i * 8..(i + 1) * 8]);
+            kek.encrypt(&mut block);
+            let t = (n * j + i + 1) as u64;
+            for (a_byte, (b, t_byte)) in a.iter_mut().zip(block[..8].iter().zip(t.to_be_bytes())) {
+                *a_byte = b ^ t_byte;
+            }
+            r[i * 8..(i + 1) * 8].copy_from_slice(&block[8..]);
+        }
+    }
+    let mut out = a.to_vec();
+    out.extend_from_slice(&r);
+    out
+}
+
+/// The RFC 3394 unwrapping process W^-1, returning the recovered initial value and data.
+fn unwrap_blocks(kek: &Kek, wrapped: &[u8]) -> ([u8; 8], Vec<u8>) {
+    let n = wrapped.len() / 8 - 1;
+    let mut a: [u8; 8] = wrapped[..8].try_into().unwrap();
+    let mut r = wrapped[8..].to_vec();
+    let mut block = [0u8; 16];
+    for j in (0..6).rev() {
+        for i in (0..n).rev() {
+            let t = (n * j + i + 1) as u64;
+            for (b, (a_byte, t_byte)) in block[..8].iter_mut().zip(a.iter().zip(t.to_be_bytes())) {
+                *b = a_byte ^ t_byte;
+            }
+            block[8..].copy_from_slice(&r[i * 8..(i + 1) * 8]);
+            kek.decrypt(&mut block);
+            a.copy_from_slice(&block[..8]);
+            r[i * 8..(i + 1) * 8].copy_from_slice(&block[8..]);
+        }
+    }
+    (a, r)
+}
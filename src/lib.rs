@@ -1,5 +1,8 @@
+// pyo3 0.22's #[pyfunction] expansion trips clippy::useless_conversion on every `PyResult` return.
+#![allow(clippy::useless_conversion)]
+
 use pyo3::prelude::*;
-use pyo3::types::PyBytes;
+use pyo3::types::{PyBytes, PyDict};
 use pyo3::exceptions::PyValueError;
 
 pub mod key_store;
@@ -12,6 +15,26 @@ pub mod utils;
 pub mod totp;
 pub mod obfuscation;
 pub mod bucketing;
+pub mod wordlist;
+pub mod shamir;
+pub mod mnemonic;
+pub mod key_wrap;
+pub mod token;
+pub mod paseto;
+pub mod jwt;
+pub mod key_format;
+pub mod key_exchange;
+pub mod hpke;
+pub mod age_format;
+pub mod envelope;
+pub mod fingerprint;
+pub mod transparency_log;
+pub mod x3dh;
+pub mod double_ratchet;
+pub mod ml_kem;
+pub mod hybrid_kem;
+pub mod ml_dsa;
+pub mod noise;
 
 use key_store::{KeyStore, KeyStoreError};
 use file_encryption::FileEncryptionError;
@@ -31,81 +54,921 @@ impl From<FileEncryptionError> for PyErr {
 #[pymodule]
 fn qimem(_py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(py_derive_key, m)?)?;
+    m.add_function(wrap_pyfunction!(py_derive_key_with_context, m)?)?;
+    m.add_function(wrap_pyfunction!(py_derive_key_from_params, m)?)?;
+    m.add_function(wrap_pyfunction!(py_generate_passphrase, m)?)?;
+    m.add_function(wrap_pyfunction!(py_generate_password, m)?)?;
+    m.add_function(wrap_pyfunction!(py_generate_secret, m)?)?;
+    m.add_function(wrap_pyfunction!(py_estimate_password_strength, m)?)?;
     m.add_function(wrap_pyfunction!(py_encrypt, m)?)?;
     m.add_function(wrap_pyfunction!(py_decrypt, m)?)?;
     m.add_function(wrap_pyfunction!(py_encrypt_file, m)?)?;
     m.add_function(wrap_pyfunction!(py_decrypt_file, m)?)?;
+    m.add_function(wrap_pyfunction!(py_encrypt_file_with_password, m)?)?;
+    m.add_function(wrap_pyfunction!(py_decrypt_file_with_password, m)?)?;
+    m.add_function(wrap_pyfunction!(py_encrypt_file_for_recipients, m)?)?;
+    m.add_function(wrap_pyfunction!(py_decrypt_file_for_identity, m)?)?;
+    m.add_function(wrap_pyfunction!(py_add_file_recipients, m)?)?;
+    m.add_function(wrap_pyfunction!(py_age_keypair, m)?)?;
+    m.add_function(wrap_pyfunction!(py_encrypt_file_age, m)?)?;
+    m.add_function(wrap_pyfunction!(py_decrypt_file_age, m)?)?;
     m.add_function(wrap_pyfunction!(py_generate_keypair, m)?)?;
     m.add_function(wrap_pyfunction!(py_sign_message, m)?)?;
     m.add_function(wrap_pyfunction!(py_verify_signature, m)?)?;
-    m.add_function(wrap_pyfunction!(totp::generate_totp_secret, m)?)?;
-    m.add_function(wrap_pyfunction!(totp::get_totp_code, m)?)?;
-    m.add_function(wrap_pyfunction!(totp::verify_totp_code, m)?)?;
+    m.add_function(wrap_pyfunction!(py_issue_token, m)?)?;
+    m.add_function(wrap_pyfunction!(py_verify_token, m)?)?;
+    m.add_function(wrap_pyfunction!(py_verify_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(py_sign_file, m)?)?;
+    m.add_function(wrap_pyfunction!(py_verify_file, m)?)?;
+    m.add_function(wrap_pyfunction!(py_minisign_public_key, m)?)?;
+    m.add_function(wrap_pyfunction!(py_issue_certificate, m)?)?;
+    m.add_function(wrap_pyfunction!(py_certificate_info, m)?)?;
+    m.add_function(wrap_pyfunction!(py_issue_revocation_list, m)?)?;
+    m.add_function(wrap_pyfunction!(py_verify_certificate_chain, m)?)?;
+    m.add_function(wrap_pyfunction!(py_export_private_key, m)?)?;
+    m.add_function(wrap_pyfunction!(py_export_public_key, m)?)?;
+    m.add_function(wrap_pyfunction!(py_import_private_key, m)?)?;
+    m.add_function(wrap_pyfunction!(py_import_public_key, m)?)?;
+    m.add_function(wrap_pyfunction!(py_issue_jwt, m)?)?;
+    m.add_function(wrap_pyfunction!(py_verify_jwt, m)?)?;
+    m.add_function(wrap_pyfunction!(py_jwks, m)?)?;
+    m.add_function(wrap_pyfunction!(py_paseto_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(py_paseto_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(py_paseto_sign, m)?)?;
+    m.add_function(wrap_pyfunction!(py_paseto_verify, m)?)?;
+    m.add_function(wrap_pyfunction!(py_generate_x25519_keypair, m)?)?;
+    m.add_function(wrap_pyfunction!(py_x25519_shared_key, m)?)?;
+    m.add_function(wrap_pyfunction!(py_seal_box, m)?)?;
+    m.add_function(wrap_pyfunction!(py_open_box, m)?)?;
+    m.add_function(wrap_pyfunction!(py_generate_hybrid_keypair, m)?)?;
+    m.add_function(wrap_pyfunction!(py_hybrid_encapsulate, m)?)?;
+    m.add_function(wrap_pyfunction!(py_hybrid_decapsulate, m)?)?;
+    m.add_function(wrap_pyfunction!(py_hpke_seal, m)?)?;
+    m.add_function(wrap_pyfunction!(py_hpke_open, m)?)?;
+    m.add_function(wrap_pyfunction!(py_seal_envelope, m)?)?;
+    m.add_function(wrap_pyfunction!(py_fingerprint, m)?)?;
+    m.add_function(wrap_pyfunction!(py_safety_number, m)?)?;
+    m.add_function(wrap_pyfunction!(py_verify_inclusion, m)?)?;
+    m.add_function(wrap_pyfunction!(py_verify_consistency, m)?)?;
+    m.add_function(wrap_pyfunction!(py_verify_tree_head, m)?)?;
+    m.add_function(wrap_pyfunction!(py_check_tree_head_update, m)?)?;
+    m.add_function(wrap_pyfunction!(py_create_prekey_bundle, m)?)?;
+    m.add_function(wrap_pyfunction!(py_trust_noise_peer, m)?)?;
+    m.add_function(wrap_pyfunction!(py_split_secret, m)?)?;
+    m.add_function(wrap_pyfunction!(py_combine_shares, m)?)?;
+    m.add_function(wrap_pyfunction!(py_mnemonic_encode, m)?)?;
+    m.add_function(wrap_pyfunction!(py_mnemonic_decode, m)?)?;
+    m.add_function(wrap_pyfunction!(py_totp_secret_to_mnemonic, m)?)?;
+    m.add_function(wrap_pyfunction!(py_totp_secret_from_mnemonic, m)?)?;
+    m.add_function(wrap_pyfunction!(py_wrap_key, m)?)?;
+    m.add_function(wrap_pyfunction!(py_unwrap_key, m)?)?;
+    m.add_function(wrap_pyfunction!(py_generate_totp_secret, m)?)?;
+    m.add_function(wrap_pyfunction!(py_get_totp_code, m)?)?;
+    m.add_function(wrap_pyfunction!(py_verify_totp_code, m)?)?;
     m.add_function(wrap_pyfunction!(obfuscation::generate_whitepaper_outline, m)?)?;
     m.add_function(wrap_pyfunction!(obfuscation::anti_debug_check, m)?)?;
     m.add_function(wrap_pyfunction!(bucketing::bucket_sensitive_data, m)?)?;
     m.add_class::<PyKeyStore>()?;
+    m.add_class::<PyHpkeSender>()?;
+    m.add_class::<PyHpkeRecipient>()?;
+    m.add_class::<PyEnvelopeOpener>()?;
+    m.add_class::<PyTransparencyLog>()?;
+    m.add_class::<PyRatchetSession>()?;
+    m.add_class::<PySecureListener>()?;
+    m.add_class::<PySecureChannel>()?;
+    m.add_class::<PyTotpConfig>()?;
     m.add("__version__", "0.1.0")?;
     Ok(())
 }
 
+/// A salt passed from Python: raw bytes, or a `hex:`/`base64:` prefixed string.
+#[derive(FromPyObject)]
+pub enum SaltArg {
+    Raw(Vec<u8>),
+    Encoded(String),
+}
+
 #[pyfunction]
-#[pyo3(signature = (password, salt_phrase=None))]
-pub fn py_derive_key(py: Python<'_>, password: String, salt_phrase: Option<String>) -> PyResult<(Bound<'_, PyBytes>, Bound<'_, PyBytes>)> {
-    q_keygen::derive_key(py, &password, salt_phrase.as_deref())
+#[pyo3(name = "derive_key", signature = (password, salt=None))]
+pub fn py_derive_key(py: Python<'_>, password: String, salt: Option<SaltArg>) -> PyResult<(Bound<'_, PyBytes>, Bound<'_, PyBytes>)> {
+    let salt = match salt {
+        Some(SaltArg::Raw(bytes)) => Some(bytes),
+        Some(SaltArg::Encoded(encoded)) => Some(q_keygen::decode_salt(&encoded)?),
+        None => None,
+    };
+    let (key, salt) = q_keygen::derive_key(&password, salt.as_deref())?;
+    Ok((PyBytes::new_bound(py, &key), PyBytes::new_bound(py, &salt)))
 }
 
 #[pyfunction]
+#[pyo3(name = "derive_key_with_context")]
+pub fn py_derive_key_with_context(py: Python<'_>, password: String, context: Vec<u8>) -> PyResult<(Bound<'_, PyBytes>, Bound<'_, PyBytes>)> {
+    let (key, params) = q_keygen::derive_key_with_context(&password, &context)?;
+    Ok((PyBytes::new_bound(py, &key), PyBytes::new_bound(py, &params)))
+}
+
+#[pyfunction]
+#[pyo3(name = "derive_key_from_params")]
+pub fn py_derive_key_from_params(py: Python<'_>, password: String, params: Vec<u8>) -> PyResult<Bound<'_, PyBytes>> {
+    let key = q_keygen::derive_key_from_params(&password, &params)?;
+    Ok(PyBytes::new_bound(py, &key))
+}
+
+#[pyfunction]
+#[pyo3(name = "generate_passphrase", signature = (words=7, separator="-"))]
+pub fn py_generate_passphrase(words: usize, separator: &str) -> PyResult<(String, f64)> {
+    Ok(q_keygen::generate_passphrase(words, separator)?)
+}
+
+#[pyfunction]
+#[pyo3(
+    name = "generate_password",
+    signature = (length=20, lowercase=true, uppercase=true, digits=true, symbols=true, exclude_ambiguous=false)
+)]
+pub fn py_generate_password(
+    length: usize,
+    lowercase: bool,
+    uppercase: bool,
+    digits: bool,
+    symbols: bool,
+    exclude_ambiguous: bool,
+) -> PyResult<(String, f64)> {
+    let policy = q_keygen::PasswordPolicy { length, lowercase, uppercase, digits, symbols, exclude_ambiguous };
+    Ok(q_keygen::generate_password(&policy)?)
+}
+
+#[pyfunction]
+#[pyo3(name = "generate_secret", signature = (length=32))]
+pub fn py_generate_secret(py: Python<'_>, length: usize) -> PyResult<(Bound<'_, PyBytes>, f64)> {
+    let (secret, entropy) = q_keygen::generate_secret(length)?;
+    Ok((PyBytes::new_bound(py, &secret), entropy))
+}
+
+/// Returns `(score, entropy_bits, feedback)`.
+#[pyfunction]
+#[pyo3(name = "estimate_password_strength")]
+pub fn py_estimate_password_strength(password: &str) -> (u8, f64, Vec<String>) {
+    let strength = q_keygen::estimate_strength(password);
+    (strength.score, strength.entropy_bits, strength.feedback)
+}
+
+#[pyfunction]
+#[pyo3(name = "encrypt")]
 pub fn py_encrypt(py: Python<'_>, data: Vec<u8>, key: Vec<u8>) -> PyResult<Bound<'_, PyBytes>> {
-    let key_array: [u8; 32] = key.try_into()
-        .map_err(|_| PyValueError::new_err("Key must be 32 bytes"))?;
-    q_core::encrypt(py, &data, &key_array)
+    let encrypted = q_core::encrypt(&data, &key)?;
+    Ok(PyBytes::new_bound(py, &encrypted))
 }
 
 #[pyfunction]
+#[pyo3(name = "decrypt")]
 pub fn py_decrypt(py: Python<'_>, encrypted: Vec<u8>, key: Vec<u8>) -> PyResult<Bound<'_, PyBytes>> {
-    let key_array: [u8; 32] = key.try_into()
-        .map_err(|_| PyValueError::new_err("Key must be 32 bytes"))?;
-    q_core::decrypt(py, &encrypted, &key_array)
+    let decrypted = q_core::decrypt(&encrypted, &key)?;
+    Ok(PyBytes::new_bound(py, &decrypted))
+}
+
+#[pyfunction]
+#[pyo3(name = "encrypt_file")]
+fn py_encrypt_file(input_path: String, output_path: String, key: Vec<u8>, salt: Vec<u8>) -> PyResult<()> {
+    file_encryption::encrypt_file(&input_path, &output_path, &key, &salt)?;
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(name = "decrypt_file")]
+fn py_decrypt_file(input_path: String, output_path: String, key: Vec<u8>) -> PyResult<()> {
+    file_encryption::decrypt_file(&input_path, &output_path, &key)?;
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(name = "encrypt_file_with_password", signature = (input_path, output_path, password, min_score=None))]
+fn py_encrypt_file_with_password(input_path: String, output_path: String, password: String, min_score: Option<u8>) -> PyResult<()> {
+    file_encryption::encrypt_file_with_password(&input_path, &output_path, &password, min_score)?;
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(name = "decrypt_file_with_password")]
+fn py_decrypt_file_with_password(input_path: String, output_path: String, password: String) -> PyResult<()> {
+    file_encryption::decrypt_file_with_password(&input_path, &output_path, &password)?;
+    Ok(())
+}
+
+/// Encrypts a file to any mix of X25519 or hybrid public keys and passphrases.
+#[pyfunction]
+#[pyo3(name = "encrypt_file_for_recipients", signature = (input_path, output_path, public_keys=Vec::new(), passphrases=Vec::new()))]
+fn py_encrypt_file_for_recipients(input_path: String, output_path: String, public_keys: Vec<Vec<u8>>, passphrases: Vec<String>) -> PyResult<()> {
+    let recipients = file_recipients(public_keys, passphrases)?;
+    file_encryption::encrypt_file_for_recipients(&input_path, &output_path, &recipients)?;
+    Ok(())
+}
+
+/// Decrypts with an X25519 `secret_key`, a `hybrid_secret_key` or a `passphrase`.
+#[pyfunction]
+#[pyo3(name = "decrypt_file_for_identity", signature = (input_path, output_path, secret_key=None, passphrase=None, hybrid_secret_key=None))]
+fn py_decrypt_file_for_identity(
+    input_path: String,
+    output_path: String,
+    secret_key: Option<Vec<u8>>,
+    passphrase: Option<String>,
+    hybrid_secret_key: Option<Vec<u8>>,
+) -> PyResult<()> {
+    let identity = file_identity(secret_key, hybrid_secret_key, passphrase)?;
+    file_encryption::decrypt_file_for_identity(&input_path, &output_path, &identity)?;
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(name = "add_file_recipients", signature = (path, secret_key=None, passphrase=None, public_keys=Vec::new(), passphrases=Vec::new(), hybrid_secret_key=None))]
+fn py_add_file_recipients(
+    path: String,
+    secret_key: Option<Vec<u8>>,
+    passphrase: Option<String>,
+    public_keys: Vec<Vec<u8>>,
+    passphrases: Vec<String>,
+    hybrid_secret_key: Option<Vec<u8>>,
+) -> PyResult<()> {
+    let identity = file_identity(secret_key, hybrid_secret_key, passphrase)?;
+    let recipients = file_recipients(public_keys, passphrases)?;
+    file_encryption::add_file_recipients(&path, &identity, &recipients)?;
+    Ok(())
 }
 
+/// Returns `(recipient, identity)` as `age1...` and `AGE-SECRET-KEY-1...` strings.
 #[pyfunction]
-fn py_encrypt_file(py: Python<'_>, input_path: String, output_path: String, key: Vec<u8>, salt: Vec<u8>) -> PyResult<()> {
-    let key_array: [u8; 32] = key.try_into()
-        .map_err(|_| PyValueError::new_err("Key must be 32 bytes"))?;
-    let salt_array: [u8; 16] = salt.try_into()
-        .map_err(|_| PyValueError::new_err("Salt must be 16 bytes"))?;
-    file_encryption::encrypt_file(py, &input_path, &output_path, key_array.to_vec(), salt_array.to_vec())?;
+#[pyo3(name = "age_keypair")]
+fn py_age_keypair() -> (String, String) {
+    let (public_key, secret_key) = key_exchange::generate_keypair();
+    (age_format::encode_recipient(&public_key), age_format::encode_identity(&secret_key))
+}
+
+/// Encrypts a file in the age format to `age1...` recipients, or to a single passphrase.
+#[pyfunction]
+#[pyo3(name = "encrypt_file_age", signature = (input_path, output_path, recipients=Vec::new(), passphrase=None, armor=false))]
+fn py_encrypt_file_age(input_path: String, output_path: String, recipients: Vec<String>, passphrase: Option<String>, armor: bool) -> PyResult<()> {
+    let mut parsed = recipients
+        .iter()
+        .map(|r| age_format::decode_recipient(r).map(file_encryption::Recipient::X25519))
+        .collect::<Result<Vec<_>, _>>()?;
+    parsed.extend(passphrase.map(file_encryption::Recipient::Passphrase));
+    file_encryption::encrypt_file_age(&input_path, &output_path, &parsed, armor)?;
     Ok(())
 }
 
+/// `identity` is an `AGE-SECRET-KEY-1...` string or the contents of an age identity file.
 #[pyfunction]
-fn py_decrypt_file(py: Python<'_>, input_path: String, output_path: String, key: Vec<u8>) -> PyResult<()> {
-    let key_array: [u8; 32] = key.try_into()
-        .map_err(|_| PyValueError::new_err("Key must be 32 bytes"))?;
-    file_encryption::decrypt_file(py, &input_path, &output_path, key_array.to_vec())?;
+#[pyo3(name = "decrypt_file_age", signature = (input_path, output_path, identity=None, passphrase=None))]
+fn py_decrypt_file_age(input_path: String, output_path: String, identity: Option<String>, passphrase: Option<String>) -> PyResult<()> {
+    let mut identities = match identity {
+        Some(identity) => age_format::decode_identities(&identity)?
+            .into_iter()
+            .map(file_encryption::Identity::X25519)
+            .collect(),
+        None => Vec::new(),
+    };
+    identities.extend(passphrase.map(file_encryption::Identity::Passphrase));
+    file_encryption::decrypt_file_age(&input_path, &output_path, &identities)?;
     Ok(())
 }
 
+/// Public keys are told apart by length: 32 bytes for X25519, 1216 for hybrid.
+fn file_recipients(public_keys: Vec<Vec<u8>>, passphrases: Vec<String>) -> PyResult<Vec<file_encryption::Recipient>> {
+    let mut recipients = Vec::with_capacity(public_keys.len() + passphrases.len());
+    for public_key in public_keys {
+        let recipient = match public_key.len() {
+            hybrid_kem::PUBLIC_KEY_LEN => file_encryption::Recipient::Hybrid(public_key),
+            _ => file_encryption::Recipient::X25519(public_key.try_into()
+                .map_err(|_| PyValueError::new_err("Public keys must be 32-byte X25519 or 1216-byte hybrid keys"))?),
+        };
+        recipients.push(recipient);
+    }
+    recipients.extend(passphrases.into_iter().map(file_encryption::Recipient::Passphrase));
+    Ok(recipients)
+}
+
+fn file_identity(secret_key: Option<Vec<u8>>, hybrid_secret_key: Option<Vec<u8>>, passphrase: Option<String>) -> PyResult<file_encryption::Identity> {
+    match (secret_key, hybrid_secret_key, passphrase) {
+        (Some(secret_key), None, None) => {
+            let secret_key: [u8; 32] = secret_key.try_into()
+                .map_err(|_| PyValueError::new_err("X25519 keys must be 32 bytes"))?;
+            Ok(file_encryption::Identity::X25519(secret_key))
+        }
+        (None, Some(secret_key), None) => {
+            let secret_key: [u8; 32] = secret_key.try_into().map_err(|_| hybrid_kem::HybridKemError::InvalidSecretKey)?;
+            Ok(file_encryption::Identity::Hybrid(secret_key))
+        }
+        (None, None, Some(passphrase)) => Ok(file_encryption::Identity::Passphrase(passphrase)),
+        _ => Err(PyValueError::new_err("Pass exactly one of secret_key, hybrid_secret_key or passphrase")),
+    }
+}
+
+/// `algorithm` is "ed25519", "ml-dsa-65" or the composite "ed25519+ml-dsa-65".
+#[pyfunction]
+#[pyo3(name = "generate_keypair", signature = (algorithm="ed25519"))]
+fn py_generate_keypair<'py>(py: Python<'py>, algorithm: &str) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+    let (public_key, secret_key) = signing::generate_keypair_with(signing::SignatureAlgorithm::from_name(algorithm)?)?;
+    Ok((PyBytes::new_bound(py, &public_key), PyBytes::new_bound(py, &secret_key)))
+}
+
+#[pyfunction]
+#[pyo3(name = "sign_message", signature = (secret_key, message, algorithm="ed25519"))]
+fn py_sign_message<'py>(py: Python<'py>, secret_key: Vec<u8>, message: Vec<u8>, algorithm: &str) -> PyResult<Bound<'py, PyBytes>> {
+    let signature = signing::sign_message_with(signing::SignatureAlgorithm::from_name(algorithm)?, &secret_key, &message)?;
+    Ok(PyBytes::new_bound(py, &signature))
+}
+
+#[pyfunction]
+#[pyo3(name = "verify_signature", signature = (public_key, message, signature, algorithm="ed25519"))]
+fn py_verify_signature(public_key: Vec<u8>, message: Vec<u8>, signature: Vec<u8>, algorithm: &str) -> PyResult<bool> {
+    let algorithm = signing::SignatureAlgorithm::from_name(algorithm)?;
+    Ok(signing::verify_signature_with(algorithm, &public_key, &message, &signature)?)
+}
+
+#[pyfunction]
+#[pyo3(name = "issue_token", signature = (subject, role, ttl_hours, secret_key, claims=None))]
+fn py_issue_token(
+    subject: &str,
+    role: &str,
+    ttl_hours: u32,
+    secret_key: Vec<u8>,
+    claims: Option<std::collections::BTreeMap<String, String>>,
+) -> PyResult<String> {
+    Ok(token::issue_token(subject, role, ttl_hours, &secret_key, claims.unwrap_or_default())?)
+}
+
+/// Returns the claims as a dict (`sub`, `role`, `iat`, `exp`, `jti` and any custom claims).
+#[pyfunction]
+#[pyo3(name = "verify_token")]
+fn py_verify_token<'py>(py: Python<'py>, token: &str, public_key: Vec<u8>) -> PyResult<Bound<'py, PyDict>> {
+    let claims = token::verify_token(token, &public_key)?;
+    let dict = PyDict::new_bound(py);
+    for (name, value) in &claims.custom {
+        dict.set_item(name, value)?;
+    }
+    dict.set_item("sub", claims.subject)?;
+    dict.set_item("role", claims.role)?;
+    dict.set_item("iat", claims.issued_at)?;
+    dict.set_item("exp", claims.expires_at)?;
+    dict.set_item("jti", claims.token_id)?;
+    Ok(dict)
+}
+
+/// Takes `(public_key, message, signature)` tuples and returns a list of booleans.
+/// The GIL is released while verifying.
 #[pyfunction]
-fn py_generate_keypair(py: Python<'_>) -> PyResult<(Bound<'_, PyBytes>, Bound<'_, PyBytes>)> {
-    signing::generate_keypair(py)
+#[pyo3(name = "verify_batch")]
+fn py_verify_batch(py: Python<'_>, items: Vec<(Vec<u8>, Vec<u8>, Vec<u8>)>) -> Vec<bool> {
+    py.allow_threads(|| {
+        let batch: Vec<signing::BatchItem> = items
+            .iter()
+            .map(|(public_key, message, signature)| signing::BatchItem { public_key, message, signature })
+            .collect();
+        signing::verify_batch(&batch)
+    })
+}
+
+/// Writes `<path>.minisig` and returns its contents.
+#[pyfunction]
+#[pyo3(name = "sign_file", signature = (path, secret_key, trusted_comment=None, prehashed=true))]
+fn py_sign_file(path: &str, secret_key: Vec<u8>, trusted_comment: Option<&str>, prehashed: bool) -> PyResult<String> {
+    Ok(signing::sign_file(path, &secret_key, trusted_comment, prehashed)?)
+}
+
+/// Returns the verified trusted comment.
+#[pyfunction]
+#[pyo3(name = "verify_file", signature = (path, public_key, signature_path=None))]
+fn py_verify_file(path: &str, public_key: Vec<u8>, signature_path: Option<&str>) -> PyResult<String> {
+    Ok(signing::verify_file(path, &public_key, signature_path)?)
+}
+
+#[pyfunction]
+#[pyo3(name = "minisign_public_key")]
+fn py_minisign_public_key(public_key: Vec<u8>) -> PyResult<String> {
+    Ok(signing::minisign_public_key(&public_key)?)
+}
+
+/// `usages` are names such as "digital_signature", "key_agreement", "cert_sign"
+/// and "crl_sign". Returns the encoded certificate.
+#[pyfunction]
+#[pyo3(name = "issue_certificate")]
+fn py_issue_certificate<'py>(
+    py: Python<'py>,
+    issuer_secret_key: Vec<u8>,
+    subject: &str,
+    subject_public_key: Vec<u8>,
+    not_before: i64,
+    not_after: i64,
+    usages: Vec<String>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let usages = usages.iter().map(|name| signing::KeyUsage::from_name(name)).collect::<Result<Vec<_>, _>>()?;
+    let certificate = signing::issue_certificate(&issuer_secret_key, subject, &subject_public_key, not_before, not_after, &usages)?;
+    Ok(PyBytes::new_bound(py, &certificate.to_bytes()))
+}
+
+/// Decodes a certificate (without verifying it) into a dict.
+#[pyfunction]
+#[pyo3(name = "certificate_info")]
+fn py_certificate_info(py: Python<'_>, certificate: Vec<u8>) -> PyResult<Bound<'_, PyDict>> {
+    certificate_dict(py, &signing::Certificate::from_bytes(&certificate)?)
+}
+
+/// `issued_at` defaults to now. Returns the encoded revocation list.
+#[pyfunction]
+#[pyo3(name = "issue_revocation_list", signature = (issuer_secret_key, revoked_serials, issued_at=None))]
+fn py_issue_revocation_list(
+    py: Python<'_>,
+    issuer_secret_key: Vec<u8>,
+    revoked_serials: Vec<Vec<u8>>,
+    issued_at: Option<i64>,
+) -> PyResult<Bound<'_, PyBytes>> {
+    let serials = revoked_serials
+        .iter()
+        .map(|serial| serial.as_slice().try_into().map_err(|_| PyValueError::new_err("Serial must be 16 bytes")))
+        .collect::<PyResult<Vec<_>>>()?;
+    let issued_at = issued_at.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let list = signing::issue_revocation_list(&issuer_secret_key, &serials, issued_at)?;
+    Ok(PyBytes::new_bound(py, &list.to_bytes()))
+}
+
+/// `chain` is leaf first. Returns the verified leaf as a dict.
+#[pyfunction]
+#[pyo3(name = "verify_certificate_chain", signature = (chain, trusted_roots, revocation_lists=Vec::new(), now=None))]
+fn py_verify_certificate_chain(
+    py: Python<'_>,
+    chain: Vec<Vec<u8>>,
+    trusted_roots: Vec<Vec<u8>>,
+    revocation_lists: Vec<Vec<u8>>,
+    now: Option<i64>,
+) -> PyResult<Bound<'_, PyDict>> {
+    let chain = chain.iter().map(|c| signing::Certificate::from_bytes(c)).collect::<Result<Vec<_>, _>>()?;
+    let roots = trusted_roots
+        .iter()
+        .map(|root| root.as_slice().try_into().map_err(|_| signing::SigningError::InvalidPublicKeyLength))
+        .collect::<Result<Vec<[u8; 32]>, _>>()?;
+    let lists = revocation_lists.iter().map(|l| signing::RevocationList::from_bytes(l)).collect::<Result<Vec<_>, _>>()?;
+    let now = now.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    certificate_dict(py, signing::verify_chain_at(&chain, &roots, &lists, now)?)
+}
+
+fn certificate_dict<'py>(py: Python<'py>, certificate: &signing::Certificate) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("serial", PyBytes::new_bound(py, &certificate.serial))?;
+    dict.set_item("subject", &certificate.subject)?;
+    dict.set_item("public_key", PyBytes::new_bound(py, &certificate.public_key))?;
+    dict.set_item("not_before", certificate.not_before)?;
+    dict.set_item("not_after", certificate.not_after)?;
+    dict.set_item("usages", certificate.usages.iter().map(|usage| usage.name()).collect::<Vec<_>>())?;
+    dict.set_item("issuer", certificate.issuer.to_hex())?;
+    Ok(dict)
+}
+
+/// `format` is "der" or "pem" (PKCS#8) or "openssh". Returns bytes for every format.
+#[pyfunction]
+#[pyo3(name = "export_private_key", signature = (secret_key, format="pem", comment="", passphrase=None))]
+fn py_export_private_key<'py>(
+    py: Python<'py>,
+    secret_key: Vec<u8>,
+    format: &str,
+    comment: &str,
+    passphrase: Option<&str>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let exported = key_format::export_private_key(&secret_key, key_format::KeyFormat::parse(format)?, comment, passphrase)?;
+    Ok(PyBytes::new_bound(py, &exported))
+}
+
+/// `format` is "der" or "pem" (SubjectPublicKeyInfo) or "openssh".
+#[pyfunction]
+#[pyo3(name = "export_public_key", signature = (public_key, format="pem", comment=""))]
+fn py_export_public_key<'py>(py: Python<'py>, public_key: Vec<u8>, format: &str, comment: &str) -> PyResult<Bound<'py, PyBytes>> {
+    let exported = key_format::export_public_key(&public_key, key_format::KeyFormat::parse(format)?, comment)?;
+    Ok(PyBytes::new_bound(py, &exported))
+}
+
+#[pyfunction]
+#[pyo3(name = "import_private_key", signature = (data, passphrase=None))]
+fn py_import_private_key<'py>(py: Python<'py>, data: Vec<u8>, passphrase: Option<&str>) -> PyResult<Bound<'py, PyBytes>> {
+    let secret_key = key_format::import_private_key(&data, passphrase)?;
+    Ok(PyBytes::new_bound(py, &secret_key))
+}
+
+#[pyfunction]
+#[pyo3(name = "import_public_key")]
+fn py_import_public_key(py: Python<'_>, data: Vec<u8>) -> PyResult<Bound<'_, PyBytes>> {
+    let public_key = key_format::import_public_key(&data)?;
+    Ok(PyBytes::new_bound(py, &public_key))
+}
+
+/// `claims` is any JSON-serializable dict; it is passed through Python's `json` module.
+#[pyfunction]
+#[pyo3(name = "issue_jwt")]
+fn py_issue_jwt(py: Python<'_>, secret_key: Vec<u8>, claims: &Bound<'_, PyAny>) -> PyResult<String> {
+    let text: String = py.import_bound("json")?.call_method1("dumps", (claims,))?.extract()?;
+    let claims: serde_json::Value = serde_json::from_str(&text)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(jwt::issue_jwt(&secret_key, &claims)?)
+}
+
+/// Returns the verified claims as a dict.
+#[pyfunction]
+#[pyo3(name = "verify_jwt", signature = (token, public_key, issuer=None, audience=None, leeway=jwt::DEFAULT_LEEWAY_SECS, require_exp=true))]
+fn py_verify_jwt<'py>(
+    py: Python<'py>,
+    token: &str,
+    public_key: Vec<u8>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    leeway: i64,
+    require_exp: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let validation = jwt::JwtValidation { issuer, audience, leeway, require_exp };
+    let claims = jwt::verify_jwt(token, &public_key, &validation)?;
+    py.import_bound("json")?.call_method1("loads", (serde_json::Value::Object(claims).to_string(),))
+}
+
+/// The JWK Set for the given Ed25519 public keys, as JSON text.
+#[pyfunction]
+#[pyo3(name = "jwks")]
+fn py_jwks(public_keys: Vec<Vec<u8>>) -> PyResult<String> {
+    let keys = public_keys
+        .into_iter()
+        .map(|key| key.try_into().map_err(|_| PyValueError::new_err("Public key must be 32 bytes")))
+        .collect::<PyResult<Vec<[u8; 32]>>>()?;
+    Ok(jwt::jwks(&keys).to_string())
+}
+
+/// PASETO `v4.local`: symmetric encryption under a 32-byte key.
+#[pyfunction]
+#[pyo3(name = "paseto_encrypt", signature = (key, message, footer=Vec::new(), implicit_assertion=Vec::new()))]
+fn py_paseto_encrypt(key: Vec<u8>, message: Vec<u8>, footer: Vec<u8>, implicit_assertion: Vec<u8>) -> PyResult<String> {
+    Ok(paseto::encrypt_local(&key, &message, &footer, &implicit_assertion)?)
+}
+
+#[pyfunction]
+#[pyo3(name = "paseto_decrypt", signature = (key, token, footer=None, implicit_assertion=Vec::new()))]
+fn py_paseto_decrypt<'py>(
+    py: Python<'py>,
+    key: Vec<u8>,
+    token: &str,
+    footer: Option<Vec<u8>>,
+    implicit_assertion: Vec<u8>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let message = paseto::decrypt_local(&key, token, footer.as_deref(), &implicit_assertion)?;
+    Ok(PyBytes::new_bound(py, &message))
+}
+
+/// PASETO `v4.public`: Ed25519 signatures with a key from `generate_keypair`.
+#[pyfunction]
+#[pyo3(name = "paseto_sign", signature = (secret_key, message, footer=Vec::new(), implicit_assertion=Vec::new()))]
+fn py_paseto_sign(secret_key: Vec<u8>, message: Vec<u8>, footer: Vec<u8>, implicit_assertion: Vec<u8>) -> PyResult<String> {
+    Ok(paseto::sign_public(&secret_key, &message, &footer, &implicit_assertion)?)
+}
+
+#[pyfunction]
+#[pyo3(name = "paseto_verify", signature = (public_key, token, footer=None, implicit_assertion=Vec::new()))]
+fn py_paseto_verify<'py>(
+    py: Python<'py>,
+    public_key: Vec<u8>,
+    token: &str,
+    footer: Option<Vec<u8>>,
+    implicit_assertion: Vec<u8>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let message = paseto::verify_public(&public_key, token, footer.as_deref(), &implicit_assertion)?;
+    Ok(PyBytes::new_bound(py, &message))
+}
+
+/// Returns `(public_key, secret_key)` for X25519 key agreement.
+#[pyfunction]
+#[pyo3(name = "generate_x25519_keypair")]
+fn py_generate_x25519_keypair(py: Python<'_>) -> (Bound<'_, PyBytes>, Bound<'_, PyBytes>) {
+    let (public_key, secret_key) = key_exchange::generate_keypair();
+    (PyBytes::new_bound(py, &public_key), PyBytes::new_bound(py, &secret_key))
+}
+
+#[pyfunction]
+#[pyo3(name = "x25519_shared_key", signature = (secret_key, peer_public_key, info=Vec::new()))]
+fn py_x25519_shared_key(py: Python<'_>, secret_key: Vec<u8>, peer_public_key: Vec<u8>, info: Vec<u8>) -> PyResult<Bound<'_, PyBytes>> {
+    let key = key_exchange::derive_shared_key(&secret_key, &peer_public_key, &info)?;
+    Ok(PyBytes::new_bound(py, &key))
+}
+
+#[pyfunction]
+#[pyo3(name = "seal_box")]
+fn py_seal_box(py: Python<'_>, recipient_public_key: Vec<u8>, plaintext: Vec<u8>) -> PyResult<Bound<'_, PyBytes>> {
+    let sealed = key_exchange::seal(&recipient_public_key, &plaintext)?;
+    Ok(PyBytes::new_bound(py, &sealed))
+}
+
+#[pyfunction]
+#[pyo3(name = "open_box")]
+fn py_open_box(py: Python<'_>, recipient_secret_key: Vec<u8>, sealed: Vec<u8>) -> PyResult<Bound<'_, PyBytes>> {
+    let plaintext = key_exchange::open(&recipient_secret_key, &sealed)?;
+    Ok(PyBytes::new_bound(py, &plaintext))
+}
+
+/// Returns `(public_key, secret_key)` for the X25519 + ML-KEM-768 hybrid KEM.
+#[pyfunction]
+#[pyo3(name = "generate_hybrid_keypair")]
+fn py_generate_hybrid_keypair(py: Python<'_>) -> (Bound<'_, PyBytes>, Bound<'_, PyBytes>) {
+    let (public_key, secret_key) = hybrid_kem::generate_keypair();
+    (PyBytes::new_bound(py, &public_key), PyBytes::new_bound(py, &secret_key))
+}
+
+/// Returns `(ciphertext, shared_secret)`.
+#[pyfunction]
+#[pyo3(name = "hybrid_encapsulate")]
+fn py_hybrid_encapsulate(py: Python<'_>, public_key: Vec<u8>) -> PyResult<(Bound<'_, PyBytes>, Bound<'_, PyBytes>)> {
+    let (ciphertext, shared_secret) = hybrid_kem::encapsulate(&public_key)?;
+    Ok((PyBytes::new_bound(py, &ciphertext), PyBytes::new_bound(py, &shared_secret)))
+}
+
+#[pyfunction]
+#[pyo3(name = "hybrid_decapsulate")]
+fn py_hybrid_decapsulate(py: Python<'_>, secret_key: Vec<u8>, ciphertext: Vec<u8>) -> PyResult<Bound<'_, PyBytes>> {
+    let shared_secret = hybrid_kem::decapsulate(&secret_key, &ciphertext)?;
+    Ok(PyBytes::new_bound(py, &shared_secret))
+}
+
+/// HPKE (RFC 9180) single-shot encryption; returns `(enc, ciphertext)`.
+/// Passing `sender_secret_key` selects auth mode.
+#[pyfunction]
+#[pyo3(name = "hpke_seal", signature = (recipient_public_key, plaintext, info=Vec::new(), aad=Vec::new(), aead="chacha20poly1305", sender_secret_key=None))]
+fn py_hpke_seal<'py>(
+    py: Python<'py>,
+    recipient_public_key: Vec<u8>,
+    plaintext: Vec<u8>,
+    info: Vec<u8>,
+    aad: Vec<u8>,
+    aead: &str,
+    sender_secret_key: Option<Vec<u8>>,
+) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+    let aead = hpke::parse_aead(aead)?;
+    let (enc, ciphertext) = hpke::seal(aead, &recipient_public_key, &info, &aad, &plaintext, sender_secret_key.as_deref())?;
+    Ok((PyBytes::new_bound(py, &enc), PyBytes::new_bound(py, &ciphertext)))
+}
+
+#[pyfunction]
+#[pyo3(name = "hpke_open", signature = (enc, recipient_secret_key, ciphertext, info=Vec::new(), aad=Vec::new(), aead="chacha20poly1305", sender_public_key=None))]
+#[allow(clippy::too_many_arguments)]
+fn py_hpke_open<'py>(
+    py: Python<'py>,
+    enc: Vec<u8>,
+    recipient_secret_key: Vec<u8>,
+    ciphertext: Vec<u8>,
+    info: Vec<u8>,
+    aad: Vec<u8>,
+    aead: &str,
+    sender_public_key: Option<Vec<u8>>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let aead = hpke::parse_aead(aead)?;
+    let plaintext = hpke::open(aead, &enc, &recipient_secret_key, &info, &aad, &ciphertext, sender_public_key.as_deref())?;
+    Ok(PyBytes::new_bound(py, &plaintext))
+}
+
+/// Signs with the sender's Ed25519 key, then encrypts to the recipient's X25519 key.
+#[pyfunction]
+#[pyo3(name = "seal_envelope")]
+fn py_seal_envelope(py: Python<'_>, sender_signing_key: Vec<u8>, recipient_public_key: Vec<u8>, payload: Vec<u8>) -> PyResult<Bound<'_, PyBytes>> {
+    let sealed = envelope::seal(&sender_signing_key, &recipient_public_key, &payload)?;
+    Ok(PyBytes::new_bound(py, &sealed))
+}
+
+/// Returns a dict of renderings: `hex`, `base32`, `key_id`, `words` and `emoji`.
+#[pyfunction]
+#[pyo3(name = "fingerprint")]
+fn py_fingerprint(py: Python<'_>, public_key: Vec<u8>) -> PyResult<Bound<'_, PyDict>> {
+    fingerprint_dict(py, &fingerprint::Fingerprint::of(&public_key))
+}
+
+/// The same value for both parties whichever order the keys are given in.
+#[pyfunction]
+#[pyo3(name = "safety_number")]
+fn py_safety_number(py: Python<'_>, public_key: Vec<u8>, peer_public_key: Vec<u8>) -> PyResult<Bound<'_, PyDict>> {
+    fingerprint_dict(py, &fingerprint::safety_number(&public_key, &peer_public_key))
+}
+
+/// Checks that `entry` is at `index` in the tree with `root_hash`.
+#[pyfunction]
+#[pyo3(name = "verify_inclusion")]
+fn py_verify_inclusion(entry: Vec<u8>, index: u64, tree_size: u64, proof: Vec<Vec<u8>>, root_hash: Vec<u8>) -> PyResult<bool> {
+    let leaf = transparency_log::leaf_hash(&entry);
+    Ok(transparency_log::verify_inclusion(&leaf, index, tree_size, &log_hashes(proof)?, &log_hash(root_hash)?))
+}
+
+#[pyfunction]
+#[pyo3(name = "verify_consistency")]
+fn py_verify_consistency(old_size: u64, new_size: u64, old_root: Vec<u8>, new_root: Vec<u8>, proof: Vec<Vec<u8>>) -> PyResult<bool> {
+    Ok(transparency_log::verify_consistency(old_size, new_size, &log_hash(old_root)?, &log_hash(new_root)?, &log_hashes(proof)?))
+}
+
+/// Returns a dict with `tree_size`, `timestamp` and `root_hash`; raises if the signature is invalid.
+#[pyfunction]
+#[pyo3(name = "verify_tree_head")]
+fn py_verify_tree_head(py: Python<'_>, log_public_key: Vec<u8>, tree_head: Vec<u8>) -> PyResult<Bound<'_, PyDict>> {
+    let head = transparency_log::SignedTreeHead::from_bytes(&tree_head)?;
+    if !head.verify(&log_public_key)? {
+        return Err(transparency_log::LogError::InvalidSignature.into());
+    }
+    let dict = PyDict::new_bound(py);
+    dict.set_item("tree_size", head.tree_size)?;
+    dict.set_item("timestamp", head.timestamp)?;
+    dict.set_item("root_hash", PyBytes::new_bound(py, &head.root_hash))?;
+    Ok(dict)
+}
+
+/// Raises unless `new_head` is signed by the log and extends `trusted_head`.
+#[pyfunction]
+#[pyo3(name = "check_tree_head_update")]
+fn py_check_tree_head_update(log_public_key: Vec<u8>, trusted_head: Vec<u8>, new_head: Vec<u8>, proof: Vec<Vec<u8>>) -> PyResult<()> {
+    let trusted = transparency_log::SignedTreeHead::from_bytes(&trusted_head)?;
+    let new = transparency_log::SignedTreeHead::from_bytes(&new_head)?;
+    Ok(transparency_log::check_tree_head_update(&log_public_key, &trusted, &new, &log_hashes(proof)?)?)
+}
+
+/// Publishes the signed prekey (and optionally a one-time prekey and a hybrid
+/// post-quantum prekey) under the identity keys. Identities are an Ed25519
+/// secret key plus an X25519 secret key.
+#[pyfunction]
+#[pyo3(name = "create_prekey_bundle", signature = (identity_signing_key, identity_dh_key, signed_prekey_id, signed_prekey, one_time_prekey_id=None, one_time_prekey=None, pq_prekey_id=None, pq_prekey=None))]
+#[allow(clippy::too_many_arguments)]
+fn py_create_prekey_bundle(
+    py: Python<'_>,
+    identity_signing_key: Vec<u8>,
+    identity_dh_key: Vec<u8>,
+    signed_prekey_id: u32,
+    signed_prekey: Vec<u8>,
+    one_time_prekey_id: Option<u32>,
+    one_time_prekey: Option<Vec<u8>>,
+    pq_prekey_id: Option<u32>,
+    pq_prekey: Option<Vec<u8>>,
+) -> PyResult<Bound<'_, PyBytes>> {
+    let identity = x3dh_identity(identity_signing_key, identity_dh_key)?;
+    let signed_prekey = x3dh_prekey(signed_prekey_id, signed_prekey)?;
+    let one_time_prekey = one_time_prekey_id.zip(one_time_prekey).map(|(id, key)| x3dh_prekey(id, key)).transpose()?;
+    let mut bundle = x3dh::PreKeyBundle::new(&identity, &signed_prekey, one_time_prekey.as_ref())?;
+    if let Some(pq_prekey) = x3dh_pq_prekey(pq_prekey_id, pq_prekey)? {
+        bundle = bundle.with_pq_prekey(&identity, &pq_prekey)?;
+    }
+    Ok(PyBytes::new_bound(py, &bundle.to_bytes()))
+}
+
+fn x3dh_identity(signing_key: Vec<u8>, dh_key: Vec<u8>) -> PyResult<x3dh::IdentityKeyPair> {
+    Ok(x3dh::IdentityKeyPair {
+        signing_secret_key: signing_key.try_into().map_err(|_| signing::SigningError::InvalidSecretKey)?,
+        dh_secret_key: dh_key.try_into().map_err(|_| key_exchange::KeyExchangeError::InvalidKeyLength)?,
+    })
+}
+
+fn x3dh_prekey(id: u32, secret_key: Vec<u8>) -> PyResult<x3dh::PreKey> {
+    let secret_key = secret_key.try_into().map_err(|_| key_exchange::KeyExchangeError::InvalidKeyLength)?;
+    Ok(x3dh::PreKey { id, secret_key })
+}
+
+fn x3dh_pq_prekey(id: Option<u32>, secret_key: Option<Vec<u8>>) -> PyResult<Option<x3dh::PqPreKey>> {
+    let Some((id, secret_key)) = id.zip(secret_key) else { return Ok(None) };
+    let secret_key = secret_key.try_into().map_err(|_| hybrid_kem::HybridKemError::InvalidSecretKey)?;
+    Ok(Some(x3dh::PqPreKey { id, secret_key }))
+}
+
+fn log_hash(hash: Vec<u8>) -> PyResult<transparency_log::Hash> {
+    hash.try_into().map_err(|_| PyValueError::new_err("Hashes must be 32 bytes"))
+}
+
+fn log_hashes(hashes: Vec<Vec<u8>>) -> PyResult<Vec<transparency_log::Hash>> {
+    hashes.into_iter().map(log_hash).collect()
+}
+
+fn fingerprint_dict<'py>(py: Python<'py>, fp: &fingerprint::Fingerprint) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("hex", fp.to_hex())?;
+    dict.set_item("base32", fp.to_base32())?;
+    dict.set_item("key_id", fp.key_id())?;
+    dict.set_item("words", fp.to_words(fingerprint::DEFAULT_WORDS).join(" "))?;
+    dict.set_item("emoji", fp.to_emoji(fingerprint::DEFAULT_EMOJI))?;
+    Ok(dict)
 }
 
+/// Returns the shares in their `qss1-...` text form.
 #[pyfunction]
-fn py_sign_message(py: Python<'_>, secret_key: Vec<u8>, message: Vec<u8>) -> PyResult<Bound<'_, PyBytes>> {
-    let secret_key_array: [u8; 32] = secret_key.try_into()
-        .map_err(|_| PyValueError::new_err("Secret key must be 32 bytes"))?;
-    signing::sign_message(py, &secret_key_array, &message)
+#[pyo3(name = "split_secret")]
+fn py_split_secret(secret: Vec<u8>, threshold: u8, share_count: u8) -> PyResult<Vec<String>> {
+    let shares = shamir::split_secret(&secret, threshold, share_count)?;
+    Ok(shares.iter().map(shamir::Share::encode).collect())
 }
 
 #[pyfunction]
-fn py_verify_signature(py: Python<'_>, public_key: Vec<u8>, message: Vec<u8>, signature: Vec<u8>) -> PyResult<bool> {
-    let public_key_array: [u8; 32] = public_key.try_into()
-        .map_err(|_| PyValueError::new_err("Public key must be 32 bytes"))?;
-    let signature_array: [u8; 64] = signature.try_into()
-        .map_err(|_| PyValueError::new_err("Signature must be 64 bytes"))?;
-    signing::verify_signature(py, &public_key_array, &message, &signature_array)
+#[pyo3(name = "combine_shares")]
+fn py_combine_shares(py: Python<'_>, shares: Vec<String>) -> PyResult<Bound<'_, PyBytes>> {
+    let shares = decode_shares(&shares)?;
+    let secret = shamir::combine_shares(&shares)?;
+    Ok(PyBytes::new_bound(py, &secret))
+}
+
+fn decode_shares(shares: &[String]) -> PyResult<Vec<shamir::Share>> {
+    Ok(shares.iter().map(|s| shamir::Share::decode(s)).collect::<Result<_, _>>()?)
+}
+
+#[pyfunction]
+#[pyo3(name = "mnemonic_encode")]
+fn py_mnemonic_encode(data: Vec<u8>) -> PyResult<String> {
+    Ok(mnemonic::encode(&data)?)
+}
+
+#[pyfunction]
+#[pyo3(name = "mnemonic_decode")]
+fn py_mnemonic_decode<'py>(py: Python<'py>, phrase: &str) -> PyResult<Bound<'py, PyBytes>> {
+    let data = mnemonic::decode(phrase)?;
+    Ok(PyBytes::new_bound(py, &data))
+}
+
+#[pyfunction]
+#[pyo3(name = "totp_secret_to_mnemonic")]
+fn py_totp_secret_to_mnemonic(secret: &str) -> PyResult<String> {
+    Ok(mnemonic::encode_totp_secret(secret)?)
+}
+
+#[pyfunction]
+#[pyo3(name = "totp_secret_from_mnemonic")]
+fn py_totp_secret_from_mnemonic(phrase: &str) -> PyResult<String> {
+    Ok(mnemonic::decode_totp_secret(phrase)?)
+}
+
+fn totp_config(config: Option<PyRef<'_, PyTotpConfig>>) -> totp::TotpConfig {
+    config.map(|config| config.inner.clone()).unwrap_or_default()
+}
+
+/// A base64 TOTP secret from Python: the bytes [`py_generate_totp_secret`]
+/// returns, or the same text as a str.
+#[derive(FromPyObject)]
+pub enum TotpSecretArg {
+    Raw(Vec<u8>),
+    Text(String),
+}
+
+impl TotpSecretArg {
+    fn into_string(self) -> Result<String, totp::TotpError> {
+        match self {
+            TotpSecretArg::Raw(bytes) => String::from_utf8(bytes).map_err(|_| totp::TotpError::InvalidSecret),
+            TotpSecretArg::Text(text) => Ok(text),
+        }
+    }
+}
+
+/// Returns a base64 secret, as ASCII bytes, sized for the config's algorithm (SHA1 by default).
+#[pyfunction]
+#[pyo3(name = "generate_totp_secret", signature = (config=None))]
+fn py_generate_totp_secret<'py>(py: Python<'py>, config: Option<PyRef<'_, PyTotpConfig>>) -> Bound<'py, PyBytes> {
+    PyBytes::new_bound(py, totp::generate_totp_secret(&totp_config(config)).as_bytes())
+}
+
+/// Returns the code as ASCII bytes. `time` is a Unix timestamp, defaulting to now.
+#[pyfunction]
+#[pyo3(name = "get_totp_code", signature = (secret, config=None, time=None))]
+fn py_get_totp_code<'py>(
+    py: Python<'py>,
+    secret: TotpSecretArg,
+    config: Option<PyRef<'_, PyTotpConfig>>,
+    time: Option<u64>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let (secret, config) = (secret.into_string()?, totp_config(config));
+    let code = match time {
+        Some(time) => totp::get_totp_code_at(&secret, &config, time)?,
+        None => totp::get_totp_code(&secret, &config)?,
+    };
+    Ok(PyBytes::new_bound(py, code.as_bytes()))
+}
+
+/// `secret` and `code` may each be str or bytes.
+#[pyfunction]
+#[pyo3(name = "verify_totp_code", signature = (secret, code, config=None, time=None))]
+fn py_verify_totp_code(secret: TotpSecretArg, code: TotpSecretArg, config: Option<PyRef<'_, PyTotpConfig>>, time: Option<u64>) -> PyResult<bool> {
+    let (secret, code, config) = (secret.into_string()?, code.into_string()?, totp_config(config));
+    Ok(match time {
+        Some(time) => totp::verify_totp_code_at(&secret, &code, &config, time)?,
+        None => totp::verify_totp_code(&secret, &code, &config)?,
+    })
+}
+
+/// AES Key Wrap: RFC 3394 by default, RFC 5649 (any key length) with `padded=True`.
+#[pyfunction]
+#[pyo3(name = "wrap_key", signature = (kek, key, padded=false))]
+fn py_wrap_key(py: Python<'_>, kek: Vec<u8>, key: Vec<u8>, padded: bool) -> PyResult<Bound<'_, PyBytes>> {
+    let wrapped = if padded {
+        key_wrap::wrap_key_padded(&kek, &key)?
+    } else {
+        key_wrap::wrap_key(&kek, &key)?
+    };
+    Ok(PyBytes::new_bound(py, &wrapped))
+}
+
+#[pyfunction]
+#[pyo3(name = "unwrap_key", signature = (kek, wrapped, padded=false))]
+fn py_unwrap_key(py: Python<'_>, kek: Vec<u8>, wrapped: Vec<u8>, padded: bool) -> PyResult<Bound<'_, PyBytes>> {
+    let key = if padded {
+        key_wrap::unwrap_key_padded(&kek, &wrapped)?
+    } else {
+        key_wrap::unwrap_key(&kek, &wrapped)?
+    };
+    Ok(PyBytes::new_bound(py, &key))
 }
 
 #[pyclass(name = "KeyStore")]
@@ -116,19 +979,487 @@ pub struct PyKeyStore {
 #[pymethods]
 impl PyKeyStore {
     #[new]
-    fn new(py: Python<'_>, _path: String, master_password: String) -> PyResult<Self> {
-        let inner = KeyStore::new(py, &_path, &master_password)?;
+    #[pyo3(signature = (path, master_password, min_score=None))]
+    fn new(path: String, master_password: String, min_score: Option<u8>) -> PyResult<Self> {
+        let inner = match min_score {
+            Some(min_score) => KeyStore::new_with_min_score(&path, &master_password, min_score)?,
+            None => KeyStore::new(&path, &master_password)?,
+        };
+        Ok(PyKeyStore { inner })
+    }
+
+    /// Opens an existing store from recovery shares instead of the password.
+    #[staticmethod]
+    fn from_shares(path: String, shares: Vec<String>) -> PyResult<Self> {
+        let inner = KeyStore::open_with_shares(&path, &decode_shares(&shares)?)?;
+        Ok(PyKeyStore { inner })
+    }
+
+    /// Opens an existing store from its master key phrase instead of the password.
+    #[staticmethod]
+    fn from_mnemonic(path: String, phrase: String) -> PyResult<Self> {
+        let inner = KeyStore::open_with_mnemonic(&path, &phrase)?;
         Ok(PyKeyStore { inner })
     }
 
-    fn store_key(&mut self, py: Python<'_>, id: String, key: Vec<u8>) -> PyResult<()> {
+    fn master_key_mnemonic(&self) -> PyResult<String> {
+        Ok(self.inner.master_key_mnemonic()?)
+    }
+
+    fn split_master_key(&self, threshold: u8, share_count: u8) -> PyResult<Vec<String>> {
+        let shares = self.inner.split_master_key(threshold, share_count)?;
+        Ok(shares.iter().map(shamir::Share::encode).collect())
+    }
+
+    fn store_key(&mut self, id: String, key: Vec<u8>) -> PyResult<()> {
         let key_array: [u8; 32] = key.try_into()
             .map_err(|_| PyValueError::new_err("Key must be 32 bytes"))?;
-        self.inner.store_key(py, &id, &key_array)?;
+        self.inner.store_key(&id, key_array)?;
         Ok(())
     }
 
-    fn retrieve_key(&self, py: Python<'_>, id: String) -> PyResult<Option<Vec<u8>>> {
-        self.inner.retrieve_key(py, &id)
+    fn retrieve_key<'py>(&self, py: Python<'py>, id: String) -> Option<Bound<'py, PyBytes>> {
+        self.inner.retrieve_key(&id).map(|key| PyBytes::new_bound(py, &key))
+    }
+
+    fn key_fingerprint<'py>(&self, py: Python<'py>, id: String) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.inner.key_fingerprint(&id).map(|fp| fingerprint_dict(py, &fp)).transpose()
+    }
+
+    fn export_wrapped_key<'py>(&self, py: Python<'py>, id: String, kek: Vec<u8>) -> PyResult<Bound<'py, PyBytes>> {
+        let wrapped = self.inner.export_wrapped_key(&id, &kek)?;
+        Ok(PyBytes::new_bound(py, &wrapped))
+    }
+
+    fn import_wrapped_key(&mut self, id: String, wrapped: Vec<u8>, kek: Vec<u8>) -> PyResult<()> {
+        self.inner.import_wrapped_key(&id, &wrapped, &kek)?;
+        Ok(())
+    }
+
+    /// Stores an arbitrary secret blob under `id`, replacing any previous value.
+    fn store_record(&mut self, id: String, data: Vec<u8>) -> PyResult<()> {
+        Ok(self.inner.store_record(&id, &data)?)
+    }
+
+    fn retrieve_record<'py>(&self, py: Python<'py>, id: String) -> Option<Bound<'py, PyBytes>> {
+        self.inner.retrieve_record(&id).map(|data| PyBytes::new_bound(py, &data))
+    }
+
+    fn delete_record(&mut self, id: String) -> PyResult<bool> {
+        Ok(self.inner.delete_record(&id)?)
+    }
+}
+
+/// Sender side of an HPKE context for encrypting several messages; send `enc` with them.
+#[pyclass(name = "HpkeSender")]
+pub struct PyHpkeSender {
+    enc: Vec<u8>,
+    inner: hpke::SenderContext,
+}
+
+#[pymethods]
+impl PyHpkeSender {
+    #[new]
+    #[pyo3(signature = (recipient_public_key, info=Vec::new(), aead="chacha20poly1305", sender_secret_key=None))]
+    fn new(recipient_public_key: Vec<u8>, info: Vec<u8>, aead: &str, sender_secret_key: Option<Vec<u8>>) -> PyResult<Self> {
+        let aead = hpke::parse_aead(aead)?;
+        let (enc, inner) = match sender_secret_key {
+            Some(sender_secret_key) => hpke::setup_auth_sender(aead, &recipient_public_key, &info, &sender_secret_key)?,
+            None => hpke::setup_base_sender(aead, &recipient_public_key, &info)?,
+        };
+        Ok(PyHpkeSender { enc, inner })
+    }
+
+    #[getter]
+    fn enc<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.enc)
+    }
+
+    #[pyo3(signature = (plaintext, aad=Vec::new()))]
+    fn seal<'py>(&mut self, py: Python<'py>, plaintext: Vec<u8>, aad: Vec<u8>) -> PyResult<Bound<'py, PyBytes>> {
+        let ciphertext = self.inner.seal(&aad, &plaintext)?;
+        Ok(PyBytes::new_bound(py, &ciphertext))
+    }
+
+    fn export<'py>(&self, py: Python<'py>, exporter_context: Vec<u8>, length: usize) -> PyResult<Bound<'py, PyBytes>> {
+        Ok(PyBytes::new_bound(py, &self.inner.export(&exporter_context, length)?))
+    }
+}
+
+#[pyclass(name = "HpkeRecipient")]
+pub struct PyHpkeRecipient {
+    inner: hpke::RecipientContext,
+}
+
+#[pymethods]
+impl PyHpkeRecipient {
+    #[new]
+    #[pyo3(signature = (enc, recipient_secret_key, info=Vec::new(), aead="chacha20poly1305", sender_public_key=None))]
+    fn new(enc: Vec<u8>, recipient_secret_key: Vec<u8>, info: Vec<u8>, aead: &str, sender_public_key: Option<Vec<u8>>) -> PyResult<Self> {
+        let aead = hpke::parse_aead(aead)?;
+        let inner = match sender_public_key {
+            Some(sender_public_key) => hpke::setup_auth_recipient(aead, &enc, &recipient_secret_key, &info, &sender_public_key)?,
+            None => hpke::setup_base_recipient(aead, &enc, &recipient_secret_key, &info)?,
+        };
+        Ok(PyHpkeRecipient { inner })
+    }
+
+    #[pyo3(signature = (ciphertext, aad=Vec::new()))]
+    fn open<'py>(&mut self, py: Python<'py>, ciphertext: Vec<u8>, aad: Vec<u8>) -> PyResult<Bound<'py, PyBytes>> {
+        let plaintext = self.inner.open(&aad, &ciphertext)?;
+        Ok(PyBytes::new_bound(py, &plaintext))
+    }
+
+    fn export<'py>(&self, py: Python<'py>, exporter_context: Vec<u8>, length: usize) -> PyResult<Bound<'py, PyBytes>> {
+        Ok(PyBytes::new_bound(py, &self.inner.export(&exporter_context, length)?))
+    }
+}
+
+/// Opens envelopes for one recipient, refusing any envelope it has already opened.
+#[pyclass(name = "EnvelopeOpener")]
+pub struct PyEnvelopeOpener {
+    recipient_secret_key: Vec<u8>,
+    guard: envelope::ReplayGuard,
+}
+
+#[pymethods]
+impl PyEnvelopeOpener {
+    #[new]
+    #[pyo3(signature = (recipient_secret_key, max_age_secs=envelope::DEFAULT_MAX_AGE_SECS, leeway_secs=envelope::DEFAULT_LEEWAY_SECS))]
+    fn new(recipient_secret_key: Vec<u8>, max_age_secs: i64, leeway_secs: i64) -> PyResult<Self> {
+        let guard = envelope::ReplayGuard::with_window(max_age_secs, leeway_secs)?;
+        Ok(PyEnvelopeOpener { recipient_secret_key, guard })
+    }
+
+    /// Returns a dict with `payload`, `sender_key_id`, `message_id` (hex) and `sent_at`.
+    fn open<'py>(&mut self, py: Python<'py>, sender_public_key: Vec<u8>, sealed: Vec<u8>) -> PyResult<Bound<'py, PyDict>> {
+        self.open_from_any(py, vec![sender_public_key], sealed)
+    }
+
+    /// Like `open`, accepting an envelope from any of `trusted_senders`.
+    fn open_from_any<'py>(&mut self, py: Python<'py>, trusted_senders: Vec<Vec<u8>>, sealed: Vec<u8>) -> PyResult<Bound<'py, PyDict>> {
+        let trusted: Vec<&[u8]> = trusted_senders.iter().map(Vec::as_slice).collect();
+        let opened = envelope::open_from_any(&self.recipient_secret_key, &trusted, &sealed, &mut self.guard)?;
+        let dict = PyDict::new_bound(py);
+        dict.set_item("payload", PyBytes::new_bound(py, &opened.payload))?;
+        dict.set_item("sender_key_id", opened.sender_key_id)?;
+        dict.set_item("message_id", utils::to_hex(&opened.message_id))?;
+        dict.set_item("sent_at", opened.sent_at)?;
+        Ok(dict)
+    }
+}
+
+#[pyclass(name = "TransparencyLog")]
+pub struct PyTransparencyLog {
+    inner: transparency_log::TransparencyLog,
+}
+
+#[pymethods]
+impl PyTransparencyLog {
+    #[new]
+    fn new(path: String) -> PyResult<Self> {
+        Ok(PyTransparencyLog { inner: transparency_log::TransparencyLog::open(path)? })
+    }
+
+    /// Returns the new entry's index.
+    fn append(&mut self, entry: Vec<u8>) -> PyResult<u64> {
+        Ok(self.inner.append(&entry)?)
     }
-}
\ No newline at end of file
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn entry<'py>(&self, py: Python<'py>, index: u64) -> Option<Bound<'py, PyBytes>> {
+        self.inner.entry(index).map(|entry| PyBytes::new_bound(py, entry))
+    }
+
+    /// `tree_size` defaults to the current size, here and below.
+    #[pyo3(signature = (tree_size=None))]
+    fn root_hash<'py>(&self, py: Python<'py>, tree_size: Option<u64>) -> PyResult<Bound<'py, PyBytes>> {
+        let root = self.inner.root_hash(tree_size.unwrap_or(self.inner.size()))?;
+        Ok(PyBytes::new_bound(py, &root))
+    }
+
+    #[pyo3(signature = (index, tree_size=None))]
+    fn inclusion_proof<'py>(&self, py: Python<'py>, index: u64, tree_size: Option<u64>) -> PyResult<Vec<Bound<'py, PyBytes>>> {
+        let proof = self.inner.inclusion_proof(index, tree_size.unwrap_or(self.inner.size()))?;
+        Ok(proof.iter().map(|hash| PyBytes::new_bound(py, hash)).collect())
+    }
+
+    #[pyo3(signature = (old_size, new_size=None))]
+    fn consistency_proof<'py>(&self, py: Python<'py>, old_size: u64, new_size: Option<u64>) -> PyResult<Vec<Bound<'py, PyBytes>>> {
+        let proof = self.inner.consistency_proof(old_size, new_size.unwrap_or(self.inner.size()))?;
+        Ok(proof.iter().map(|hash| PyBytes::new_bound(py, hash)).collect())
+    }
+
+    /// Returns the encoded signed tree head for the current size.
+    fn sign_tree_head<'py>(&self, py: Python<'py>, secret_key: Vec<u8>) -> PyResult<Bound<'py, PyBytes>> {
+        Ok(PyBytes::new_bound(py, &self.inner.sign_tree_head(&secret_key)?.to_bytes()))
+    }
+}
+
+/// A Double Ratchet session. Start one with `initiate` or `respond`, and
+/// persist it after every message with `save` or `to_bytes`.
+#[pyclass(name = "RatchetSession")]
+pub struct PyRatchetSession {
+    inner: double_ratchet::Session,
+}
+
+#[pymethods]
+impl PyRatchetSession {
+    /// Returns `(session, initial_message)`; send the initial message along with the first ciphertext.
+    #[staticmethod]
+    fn initiate<'py>(
+        py: Python<'py>,
+        identity_signing_key: Vec<u8>,
+        identity_dh_key: Vec<u8>,
+        bundle: Vec<u8>,
+    ) -> PyResult<(PyRatchetSession, Bound<'py, PyBytes>)> {
+        let identity = x3dh_identity(identity_signing_key, identity_dh_key)?;
+        let bundle = x3dh::PreKeyBundle::from_bytes(&bundle)?;
+        let (inner, initial_message) = double_ratchet::Session::initiate(&identity, &bundle)?;
+        Ok((PyRatchetSession { inner }, PyBytes::new_bound(py, &initial_message.to_bytes())))
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (identity_signing_key, identity_dh_key, signed_prekey_id, signed_prekey, initial_message, one_time_prekey_id=None, one_time_prekey=None, pq_prekey_id=None, pq_prekey=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn respond(
+        identity_signing_key: Vec<u8>,
+        identity_dh_key: Vec<u8>,
+        signed_prekey_id: u32,
+        signed_prekey: Vec<u8>,
+        initial_message: Vec<u8>,
+        one_time_prekey_id: Option<u32>,
+        one_time_prekey: Option<Vec<u8>>,
+        pq_prekey_id: Option<u32>,
+        pq_prekey: Option<Vec<u8>>,
+    ) -> PyResult<Self> {
+        let identity = x3dh_identity(identity_signing_key, identity_dh_key)?;
+        let signed_prekey = x3dh_prekey(signed_prekey_id, signed_prekey)?;
+        let one_time_prekey = one_time_prekey_id.zip(one_time_prekey).map(|(id, key)| x3dh_prekey(id, key)).transpose()?;
+        let pq_prekey = x3dh_pq_prekey(pq_prekey_id, pq_prekey)?;
+        let message = x3dh::InitialMessage::from_bytes(&initial_message)?;
+        let inner = double_ratchet::Session::respond(&identity, &signed_prekey, one_time_prekey.as_ref(), pq_prekey.as_ref(), &message)?;
+        Ok(PyRatchetSession { inner })
+    }
+
+    fn encrypt<'py>(&mut self, py: Python<'py>, plaintext: Vec<u8>) -> PyResult<Bound<'py, PyBytes>> {
+        Ok(PyBytes::new_bound(py, &self.inner.encrypt(&plaintext)?))
+    }
+
+    fn decrypt<'py>(&mut self, py: Python<'py>, message: Vec<u8>) -> PyResult<Bound<'py, PyBytes>> {
+        Ok(PyBytes::new_bound(py, &self.inner.decrypt(&message)?))
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes())
+    }
+
+    #[staticmethod]
+    fn from_bytes(state: Vec<u8>) -> PyResult<Self> {
+        Ok(PyRatchetSession { inner: double_ratchet::Session::from_bytes(&state)? })
+    }
+
+    fn save(&self, mut store: PyRefMut<'_, PyKeyStore>, id: String) -> PyResult<()> {
+        Ok(self.inner.save(&mut store.inner, &id)?)
+    }
+
+    #[staticmethod]
+    fn load(store: PyRef<'_, PyKeyStore>, id: String) -> PyResult<Self> {
+        Ok(PyRatchetSession { inner: double_ratchet::Session::load(&store.inner, &id)? })
+    }
+}
+
+fn noise_config(
+    store: &PyKeyStore,
+    protocol: &str,
+    static_key_id: &str,
+    identity_key_id: Option<&str>,
+    trusted_peers_id: &str,
+) -> PyResult<noise::ChannelConfig> {
+    let params = noise::NoiseParams::from_name(protocol)?;
+    Ok(noise::ChannelConfig::from_key_store(&store.inner, params, static_key_id, identity_key_id, trusted_peers_id)?)
+}
+
+/// Adds an X25519 static key and/or an Ed25519 identity key to the trusted
+/// peer list stored under `trusted_peers_id`.
+#[pyfunction]
+#[pyo3(name = "trust_noise_peer", signature = (store, trusted_peers_id, static_key=None, identity_key=None))]
+fn py_trust_noise_peer(
+    mut store: PyRefMut<'_, PyKeyStore>,
+    trusted_peers_id: &str,
+    static_key: Option<Vec<u8>>,
+    identity_key: Option<Vec<u8>>,
+) -> PyResult<()> {
+    let mut peers = noise::TrustedPeers::load(&store.inner, trusted_peers_id)?;
+    for (key, keys) in [(static_key, &mut peers.static_keys), (identity_key, &mut peers.identities)] {
+        let Some(key) = key else { continue };
+        let key: [u8; 32] = key.try_into().map_err(|_| PyValueError::new_err("Key must be 32 bytes"))?;
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    Ok(peers.save(&mut store.inner, trusted_peers_id)?)
+}
+
+/// A TCP listener whose connections are upgraded to Noise channels.
+#[pyclass(name = "SecureListener")]
+pub struct PySecureListener {
+    inner: std::net::TcpListener,
+}
+
+#[pymethods]
+impl PySecureListener {
+    #[new]
+    fn new(address: &str) -> PyResult<Self> {
+        let inner = std::net::TcpListener::bind(address).map_err(noise::NoiseError::from)?;
+        Ok(PySecureListener { inner })
+    }
+
+    #[getter]
+    fn local_address(&self) -> PyResult<String> {
+        Ok(self.inner.local_addr().map_err(noise::NoiseError::from)?.to_string())
+    }
+
+    /// Waits for a connection and runs the handshake as responder, releasing the GIL.
+    #[pyo3(signature = (store, static_key_id, trusted_peers_id, protocol="Noise_XX_25519_ChaChaPoly_SHA256", identity_key_id=None))]
+    fn accept(
+        &self,
+        py: Python<'_>,
+        store: PyRef<'_, PyKeyStore>,
+        static_key_id: &str,
+        trusted_peers_id: &str,
+        protocol: &str,
+        identity_key_id: Option<&str>,
+    ) -> PyResult<PySecureChannel> {
+        let config = noise_config(&store, protocol, static_key_id, identity_key_id, trusted_peers_id)?;
+        let inner = py.allow_threads(|| {
+            let (stream, _) = self.inner.accept()?;
+            noise::SecureStream::accept(stream, &config)
+        })?;
+        Ok(PySecureChannel { inner })
+    }
+}
+
+#[pyclass(name = "SecureChannel")]
+pub struct PySecureChannel {
+    inner: noise::SecureStream<std::net::TcpStream>,
+}
+
+#[pymethods]
+impl PySecureChannel {
+    /// Connects and runs the handshake as initiator, releasing the GIL. IK needs
+    /// `remote_static_key`, the responder's X25519 public key.
+    #[staticmethod]
+    #[pyo3(signature = (address, store, static_key_id, trusted_peers_id, protocol="Noise_XX_25519_ChaChaPoly_SHA256", identity_key_id=None, remote_static_key=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn connect(
+        py: Python<'_>,
+        address: &str,
+        store: PyRef<'_, PyKeyStore>,
+        static_key_id: &str,
+        trusted_peers_id: &str,
+        protocol: &str,
+        identity_key_id: Option<&str>,
+        remote_static_key: Option<Vec<u8>>,
+    ) -> PyResult<Self> {
+        let mut config = noise_config(&store, protocol, static_key_id, identity_key_id, trusted_peers_id)?;
+        config.remote_static = remote_static_key
+            .map(|key| key.try_into().map_err(|_| key_exchange::KeyExchangeError::InvalidKeyLength))
+            .transpose()?;
+        let inner = py.allow_threads(|| {
+            let stream = std::net::TcpStream::connect(address)?;
+            noise::SecureStream::connect(stream, &config)
+        })?;
+        Ok(PySecureChannel { inner })
+    }
+
+    fn send(&mut self, py: Python<'_>, message: Vec<u8>) -> PyResult<()> {
+        Ok(py.allow_threads(|| self.inner.send(&message))?)
+    }
+
+    /// Returns the next message, or `None` once the peer has closed the connection.
+    fn recv<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyBytes>>> {
+        let message = py.allow_threads(|| self.inner.receive())?;
+        Ok(message.map(|message| PyBytes::new_bound(py, &message)))
+    }
+
+    fn close(&self) -> PyResult<()> {
+        Ok(self.inner.get_ref().shutdown(std::net::Shutdown::Both).map_err(noise::NoiseError::from)?)
+    }
+
+    #[getter]
+    fn remote_static_key<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.inner.remote_static_key())
+    }
+
+    #[getter]
+    fn remote_identity<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyBytes>> {
+        self.inner.remote_identity().map(|identity| PyBytes::new_bound(py, &identity))
+    }
+
+    #[getter]
+    fn handshake_hash<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, self.inner.handshake_hash())
+    }
+}
+
+#[pyclass(name = "TotpConfig")]
+pub struct PyTotpConfig {
+    inner: totp::TotpConfig,
+}
+
+#[pymethods]
+impl PyTotpConfig {
+    #[new]
+    #[pyo3(signature = (algorithm="SHA1", digits=6, period=30, skew=1, issuer=None, account=String::new()))]
+    fn new(algorithm: &str, digits: usize, period: u64, skew: u8, issuer: Option<String>, account: String) -> PyResult<Self> {
+        let inner = totp::TotpConfig { algorithm: totp::TotpAlgorithm::from_name(algorithm)?, digits, period, skew, issuer, account };
+        inner.validate()?;
+        Ok(PyTotpConfig { inner })
+    }
+
+    /// An `otpauth://totp/` URI holding this config and the base64 `secret`.
+    fn to_uri(&self, secret: TotpSecretArg) -> PyResult<String> {
+        Ok(self.inner.to_uri(&secret.into_string()?)?)
+    }
+
+    /// Returns `(config, secret)`, the secret as base64 bytes like [`py_generate_totp_secret`].
+    #[staticmethod]
+    fn from_uri<'py>(py: Python<'py>, uri: &str) -> PyResult<(PyTotpConfig, Bound<'py, PyBytes>)> {
+        let (inner, secret) = totp::TotpConfig::from_uri(uri)?;
+        Ok((PyTotpConfig { inner }, PyBytes::new_bound(py, secret.as_bytes())))
+    }
+
+    #[getter]
+    fn algorithm(&self) -> &'static str {
+        self.inner.algorithm.name()
+    }
+
+    #[getter]
+    fn digits(&self) -> usize {
+        self.inner.digits
+    }
+
+    #[getter]
+    fn period(&self) -> u64 {
+        self.inner.period
+    }
+
+    #[getter]
+    fn skew(&self) -> u8 {
+        self.inner.skew
+    }
+
+    #[getter]
+    fn issuer(&self) -> Option<String> {
+        self.inner.issuer.clone()
+    }
+
+    #[getter]
+    fn account(&self) -> String {
+        self.inner.account.clone()
+    }
+}
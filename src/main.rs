@@ -1,11 +1,136 @@
 use std::io::{self, Write};
 use base64::{Engine as _, engine::general_purpose};
 
-// Import your existing Rust modules
+// Import your existing Rust modules. The CLI only uses part of each, so
+// unused library items are expected here.
+#[allow(dead_code)]
 mod q_keygen;
+#[allow(dead_code)]
 mod q_core;
+#[allow(dead_code)]
+mod utils;
+#[allow(dead_code)]
+mod wordlist;
+#[allow(dead_code)]
+mod mnemonic;
+#[allow(dead_code)]
+mod fingerprint;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+type CliResult = Result<(), Box<dyn std::error::Error>>;
+
+fn main() -> CliResult {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("generate") => run_generate(&args[1..]),
+        Some("mnemonic") => run_mnemonic(&args[1..]),
+        Some("fingerprint") => run_fingerprint(&args[1..]),
+        Some(other) => Err(format!("Unknown command: {}", other).into()),
+        None => run_interactive(),
+    }
+}
+
+/// `qimem generate passphrase|password|secret [options]`, printing JSON.
+fn run_generate(args: &[String]) -> CliResult {
+    let kind = args.first().map(String::as_str).unwrap_or("passphrase");
+    let options = &args[args.len().min(1)..];
+    let output = match kind {
+        "passphrase" => {
+            let words = option_value(options, "--words")?.unwrap_or(7);
+            let separator = option_str(options, "--separator").unwrap_or("-");
+            let (passphrase, entropy) = q_keygen::generate_passphrase(words, separator)?;
+            serde_json::json!({ "passphrase": passphrase, "entropy_bits": entropy })
+        }
+        "password" => {
+            let policy = q_keygen::PasswordPolicy {
+                length: option_value(options, "--length")?.unwrap_or(20),
+                lowercase: !has_flag(options, "--no-lowercase"),
+                uppercase: !has_flag(options, "--no-uppercase"),
+                digits: !has_flag(options, "--no-digits"),
+                symbols: !has_flag(options, "--no-symbols"),
+                exclude_ambiguous: has_flag(options, "--exclude-ambiguous"),
+            };
+            let (password, entropy) = q_keygen::generate_password(&policy)?;
+            serde_json::json!({ "password": password, "entropy_bits": entropy })
+        }
+        "secret" => {
+            let length = option_value(options, "--bytes")?.unwrap_or(32);
+            let (secret, entropy) = q_keygen::generate_secret(length)?;
+            serde_json::json!({
+                "secret_b64": general_purpose::STANDARD.encode(secret),
+                "entropy_bits": entropy,
+            })
+        }
+        other => return Err(format!("Unknown generate target: {} (expected passphrase, password or secret)", other).into()),
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+/// `qimem mnemonic encode <base64>` or `qimem mnemonic decode <words...>`, printing JSON.
+fn run_mnemonic(args: &[String]) -> CliResult {
+    let output = match args.first().map(String::as_str) {
+        Some("encode") => {
+            let data_b64 = args.get(1).ok_or("mnemonic encode expects base64 data")?;
+            let data = general_purpose::STANDARD.decode(data_b64)?;
+            serde_json::json!({ "mnemonic": mnemonic::encode(&data)? })
+        }
+        Some("decode") => {
+            let data = mnemonic::decode(&args[1..].join(" "))?;
+            serde_json::json!({ "data_b64": general_purpose::STANDARD.encode(data) })
+        }
+        _ => return Err("Usage: qimem mnemonic encode <base64> | decode <words...>".into()),
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+/// `qimem fingerprint <public_key_b64> [<peer_public_key_b64>]`, printing JSON.
+/// With a peer key it prints the safety number both sides should see.
+fn run_fingerprint(args: &[String]) -> CliResult {
+    let key = args.first().ok_or("Usage: qimem fingerprint <public_key_b64> [<peer_public_key_b64>]")?;
+    let key = general_purpose::STANDARD.decode(key)?;
+    let output = match args.get(1) {
+        Some(peer) => {
+            let number = fingerprint::safety_number(&key, &general_purpose::STANDARD.decode(peer)?);
+            serde_json::json!({
+                "safety_number_words": number.to_words(fingerprint::DEFAULT_WORDS).join(" "),
+                "safety_number_emoji": number.to_emoji(fingerprint::DEFAULT_EMOJI),
+            })
+        }
+        None => {
+            let fp = fingerprint::Fingerprint::of(&key);
+            serde_json::json!({
+                "fingerprint": fp.to_string(),
+                "hex": fp.to_hex(),
+                "base32": fp.to_base32(),
+                "key_id": fp.key_id(),
+                "words": fp.to_words(fingerprint::DEFAULT_WORDS).join(" "),
+                "emoji": fp.to_emoji(fingerprint::DEFAULT_EMOJI),
+            })
+        }
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|a| a == flag)
+}
+
+fn option_str<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+fn option_value(args: &[String], name: &str) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+    option_str(args, name)
+        .map(|v| v.parse().map_err(|_| format!("{} expects a number, got {}", name, v).into()))
+        .transpose()
+}
+
+fn run_interactive() -> CliResult {
     println!("--- QIMEM CLI ---");
 
     print!("Enter a password to derive a key: ");
@@ -15,7 +140,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let password = password.trim();
 
     // Use your Rust q_keygen module directly
-    let (key_bytes, salt_bytes) = q_keygen::derive_key_simple(password, None)?;
+    let (key_bytes, salt_bytes) = q_keygen::derive_key(password, None)?;
     
     // Display first 8 bytes of key and full salt in base64
     let key_preview = general_purpose::STANDARD.encode(&key_bytes[..8.min(key_bytes.len())]);
@@ -0,0 +1,470 @@
+use rand::RngCore;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::{Shake128, Shake256};
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+
+// ML-DSA-65 parameters (FIPS 204 section 4).
+const N: usize = 256;
+const Q: u32 = 8380417;
+const D: u32 = 13;
+const K: usize = 6;
+const L: usize = 5;
+const ETA: u32 = 4;
+const TAU: usize = 49;
+const BETA: u32 = TAU as u32 * ETA;
+const GAMMA1: u32 = 1 << 19;
+const GAMMA2: u32 = (Q - 1) / 32;
+const OMEGA: usize = 55;
+/// Length of the commitment hash c~ (lambda / 4).
+const CHALLENGE_LEN: usize = 48;
+const T1_BITS: u32 = 10;
+const ETA_BITS: u32 = 4;
+const Z_BITS: u32 = 20;
+const W1_BITS: u32 = 4;
+/// 256^-1 mod q, to finish the inverse NTT.
+const NTT_SCALE: u32 = 8347681;
+pub const SEED_LEN: usize = 32;
+pub const PUBLIC_KEY_LEN: usize = 32 + K * 32 * T1_BITS as usize;
+pub const SECRET_KEY_LEN: usize = 128 + 32 * ((K + L) * ETA_BITS as usize + K * D as usize);
+pub const SIGNATURE_LEN: usize = CHALLENGE_LEN + L * 32 * Z_BITS as usize + OMEGA + K;
+pub const MAX_CONTEXT_LEN: usize = 255;
+
+type Poly = [u32; N];
+type Hint = [[bool; N]; K];
+
+#[derive(thiserror::Error, Debug)]
+pub enum MlDsaError {
+    #[error("ML-DSA-65 public key must be {PUBLIC_KEY_LEN} bytes")]
+    InvalidPublicKey,
+    #[error("ML-DSA-65 secret key must be {SECRET_KEY_LEN} bytes")]
+    InvalidSecretKey,
+    #[error("ML-DSA-65 signature must be {SIGNATURE_LEN} bytes")]
+    InvalidSignature,
+    #[error("Context string must be at most {MAX_CONTEXT_LEN} bytes")]
+    ContextTooLong,
+}
+
+impl From<MlDsaError> for PyErr {
+    fn from(err: MlDsaError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// 1753^bitrev8(k) mod q, the twiddle factors of the NTT.
+const ZETAS: [u32; N] = {
+    let mut zetas = [0u32; N];
+    let mut k = 0;
+    while k < N {
+        let mut exponent = (k as u8).reverse_bits();
+        let mut zeta = 1u64;
+        while exponent > 0 {
+            zeta = zeta * 1753 % Q as u64;
+            exponent -= 1;
+        }
+        zetas[k] = zeta as u32;
+        k += 1;
+    }
+    zetas
+};
+
+/// Subtracts q from `r < 2q` if needed, without branching.
+fn csub(r: u32) -> u32 {
+    r - (Q & (((Q - 1).wrapping_sub(r) as i32) >> 31) as u32)
+}
+
+/// `a mod q` by Barrett reduction, avoiding a division whose timing could
+/// depend on secret values.
+fn reduce(a: u64) -> u32 {
+    const M: u128 = (1u128 << 64) / Q as u128;
+    let t = ((a as u128 * M) >> 64) as u64;
+    csub((a - t * Q as u64) as u32)
+}
+
+fn add(a: u32, b: u32) -> u32 {
+    csub(a + b)
+}
+
+fn sub(a: u32, b: u32) -> u32 {
+    csub(a + Q - b)
+}
+
+fn mul(a: u32, b: u32) -> u32 {
+    reduce(a as u64 * b as u64)
+}
+
+/// |r| for the representative of `r` in (-q/2, q/2].
+fn centered_abs(r: u32) -> u32 {
+    if r > (Q - 1) / 2 { Q - r } else { r }
+}
+
+fn exceeds(polys: &[Poly], bound: u32) -> bool {
+    polys.iter().flatten().any(|&c| centered_abs(c) >= bound)
+}
+
+/// Algorithm 41.
+fn ntt(mut w: Poly) -> Poly {
+    let mut m = 0;
+    let mut len = 128;
+    while len >= 1 {
+        for start in (0..N).step_by(2 * len) {
+            m += 1;
+            for j in start..start + len {
+                let t = mul(ZETAS[m], w[j + len]);
+                w[j + len] = sub(w[j], t);
+                w[j] = add(w[j], t);
+            }
+        }
+        len /= 2;
+    }
+    w
+}
+
+/// Algorithm 42.
+fn ntt_inverse(mut w: Poly) -> Poly {
+    let mut m = N;
+    let mut len = 1;
+    while len < N {
+        for start in (0..N).step_by(2 * len) {
+            m -= 1;
+            let zeta = Q - ZETAS[m];
+            for j in start..start + len {
+                let t = w[j];
+                w[j] = add(t, w[j + len]);
+                w[j + len] = mul(zeta, sub(t, w[j + len]));
+            }
+        }
+        len *= 2;
+    }
+    w.map(|c| mul(c, NTT_SCALE))
+}
+
+fn pointwise(a: &Poly, b: &Poly) -> Poly {
+    std::array::from_fn(|i| mul(a[i], b[i]))
+}
+
+fn add_poly(a: &Poly, b: &Poly) -> Poly {
+    std::array::from_fn(|i| add(a[i], b[i]))
+}
+
+fn sub_poly(a: &Poly, b: &Poly) -> Poly {
+    std::array::from_fn(|i| sub(a[i], b[i]))
+}
+
+/// `A * v` in the NTT domain.
+fn matrix_vector(a: &[[Poly; L]; K], v: &[Poly; L]) -> [Poly; K] {
+    std::array::from_fn(|i| (0..L).fold([0; N], |acc, j| add_poly(&acc, &pointwise(&a[i][j], &v[j]))))
+}
+
+/// Algorithm 30 (RejNTTPoly), for entry (r, s) of the matrix A.
+fn rej_ntt_poly(rho: &[u8], s: u8, r: u8) -> Poly {
+    let mut reader = Shake128::default().chain(rho).chain([s, r]).finalize_xof();
+    let mut a = [0; N];
+    let mut n = 0;
+    let mut block = [0u8; 168];
+    while n < N {
+        reader.read(&mut block);
+        for c in block.chunks_exact(3) {
+            let z = c[0] as u32 | (c[1] as u32) << 8 | ((c[2] & 0x7F) as u32) << 16;
+            if z < Q && n < N {
+                a[n] = z;
+                n += 1;
+            }
+        }
+    }
+    a
+}
+
+/// Algorithm 31 (RejBoundedPoly) with eta = 4.
+fn rej_bounded_poly(rho: &[u8], nonce: u16) -> Poly {
+    let mut reader = Shake256::default().chain(rho).chain(nonce.to_le_bytes()).finalize_xof();
+    let mut a = [0; N];
+    let mut n = 0;
+    let mut block = [0u8; 136];
+    while n < N {
+        reader.read(&mut block);
+        for z in block.iter().flat_map(|b| [b & 0x0F, b >> 4]) {
+            if z < 9 && n < N {
+                a[n] = csub(Q + ETA - z as u32);
+                n += 1;
+            }
+        }
+    }
+    a
+}
+
+fn expand_a(rho: &[u8]) -> [[Poly; L]; K] {
+    std::array::from_fn(|r| std::array::from_fn(|s| rej_ntt_poly(rho, s as u8, r as u8)))
+}
+
+/// Algorithm 34 (ExpandMask).
+fn expand_mask(rho: &[u8], kappa: u16) -> [Poly; L] {
+    std::array::from_fn(|r| {
+        let mut v = [0u8; 32 * Z_BITS as usize];
+        Shake256::default().chain(rho).chain(kappa.wrapping_add(r as u16).to_le_bytes()).finalize_xof().read(&mut v);
+        bit_unpack(&v, Z_BITS, GAMMA1)
+    })
+}
+
+/// Algorithm 29 (SampleInBall): a polynomial with TAU coefficients of +-1.
+fn sample_in_ball(seed: &[u8]) -> Poly {
+    let mut reader = Shake256::default().chain(seed).finalize_xof();
+    let mut signs = [0u8; 8];
+    reader.read(&mut signs);
+    let signs = u64::from_le_bytes(signs);
+    let mut c = [0; N];
+    for (k, i) in (N - TAU..N).enumerate() {
+        let j = loop {
+            let mut byte = [0u8];
+            reader.read(&mut byte);
+            if byte[0] as usize <= i {
+                break byte[0] as usize;
+            }
+        };
+        c[i] = c[j];
+        c[j] = if (signs >> k) & 1 == 1 { Q - 1 } else { 1 };
+    }
+    c
+}
+
+/// Algorithm 35: splits `r` into `(r1, r0)` with `r = r1 * 2^d + r0`.
+fn power2round(r: u32) -> (u32, u32) {
+    let r1 = (r + (1 << (D - 1)) - 1) >> D;
+    (r1, csub(r + Q - (r1 << D)))
+}
+
+/// Algorithm 36 for gamma2 = (q - 1) / 32: `(r1, r0)` with `r0` centred.
+fn decompose(r: u32) -> (u32, i32) {
+    let r1 = (r as i32 + 127) >> 7;
+    let r1 = ((r1 * 1025 + (1 << 21)) >> 22) & 15;
+    let r0 = r as i32 - r1 * 2 * GAMMA2 as i32;
+    let r0 = r0 - ((((Q as i32 - 1) / 2 - r0) >> 31) & Q as i32);
+    (r1 as u32, r0)
+}
+
+fn high_bits(r: u32) -> u32 {
+    decompose(r).0
+}
+
+/// Algorithm 40 (UseHint).
+fn use_hint(hint: bool, r: u32) -> u32 {
+    let (r1, r0) = decompose(r);
+    match (hint, r0 > 0) {
+        (false, _) => r1,
+        (true, true) => (r1 + 1) & 15,
+        (true, false) => (r1 + 15) & 15,
+    }
+}
+
+/// Packs `bits`-bit values little-endian (SimpleBitPack).
+fn pack(values: impl IntoIterator<Item = u32>, bits: u32, out: &mut Vec<u8>) {
+    let (mut acc, mut filled) = (0u64, 0u32);
+    for value in values {
+        acc |= (value as u64) << filled;
+        filled += bits;
+        while filled >= 8 {
+            out.push(acc as u8);
+            acc >>= 8;
+            filled -= 8;
+        }
+    }
+}
+
+/// Reads one polynomial of `bits`-bit values from the start of `bytes`.
+fn unpack(bytes: &[u8], bits: u32) -> Poly {
+    let mask = (1u64 << bits) - 1;
+    let (mut acc, mut filled, mut bytes) = (0u64, 0u32, bytes.iter());
+    std::array::from_fn(|_| {
+        while filled < bits {
+            acc |= (*bytes.next().unwrap() as u64) << filled;
+            filled += 8;
+        }
+        let value = (acc & mask) as u32;
+        acc >>= bits;
+        filled -= bits;
+        value
+    })
+}
+
+/// BitPack: coefficients in [-a, b] are stored as `b - w`.
+fn bit_pack(w: &Poly, b: u32, bits: u32, out: &mut Vec<u8>) {
+    pack(w.iter().map(|&c| sub(b, c)), bits, out);
+}
+
+fn bit_unpack(bytes: &[u8], bits: u32, b: u32) -> Poly {
+    unpack(bytes, bits).map(|x| csub(b + Q - x))
+}
+
+fn hint_bit_pack(hint: &Hint, out: &mut Vec<u8>) {
+    let mut y = [0u8; OMEGA + K];
+    let mut index = 0;
+    for (i, row) in hint.iter().enumerate() {
+        for (j, _) in row.iter().enumerate().filter(|(_, set)| **set) {
+            y[index] = j as u8;
+            index += 1;
+        }
+        y[OMEGA + i] = index as u8;
+    }
+    out.extend_from_slice(&y);
+}
+
+/// Algorithm 21. Rejects any encoding but the canonical one, so signatures
+/// are strongly unforgeable.
+fn hint_bit_unpack(y: &[u8]) -> Option<Hint> {
+    let mut hint = [[false; N]; K];
+    let mut index = 0;
+    for (i, row) in hint.iter_mut().enumerate() {
+        let end = y[OMEGA + i] as usize;
+        if end < index || end > OMEGA {
+            return None;
+        }
+        let first = index;
+        while index < end {
+            if index > first && y[index - 1] >= y[index] {
+                return None;
+            }
+            row[y[index] as usize] = true;
+            index += 1;
+        }
+    }
+    y[index..OMEGA].iter().all(|&b| b == 0).then_some(hint)
+}
+
+fn w1_encode(w1: &[Poly; K]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(K * 32 * W1_BITS as usize);
+    w1.iter().for_each(|p| pack(p.iter().copied(), W1_BITS, &mut out));
+    out
+}
+
+fn shake256<const LEN: usize>(data: &[&[u8]]) -> [u8; LEN] {
+    let mut out = [0u8; LEN];
+    data.iter().fold(Shake256::default(), |h, d| h.chain(d)).finalize_xof().read(&mut out);
+    out
+}
+
+/// mu = H(tr || M', 64) where M' = 0 || |ctx| || ctx || M, the pure (not
+/// pre-hashed) message encoding of FIPS 204 Algorithm 2.
+fn message_representative(tr: &[u8], message: &[u8], context: &[u8]) -> Result<[u8; 64], MlDsaError> {
+    let context_len = u8::try_from(context.len()).map_err(|_| MlDsaError::ContextTooLong)?;
+    Ok(shake256(&[tr, &[0, context_len], context, message]))
+}
+
+/// Returns `(public_key, seed)`. The 32-byte seed is the compact form of the
+/// secret key; [`keypair_from_seed`] expands it.
+pub fn generate_keypair() -> (Vec<u8>, [u8; SEED_LEN]) {
+    let mut seed = [0u8; SEED_LEN];
+    rand::thread_rng().fill_bytes(&mut seed);
+    (keypair_from_seed(&seed).0, seed)
+}
+
+/// ML-DSA.KeyGen_internal: returns `(public_key, secret_key)`.
+pub fn keypair_from_seed(seed: &[u8; SEED_LEN]) -> (Vec<u8>, Vec<u8>) {
+    let expanded: [u8; 128] = shake256(&[seed, &[K as u8, L as u8]]);
+    let (rho, rest) = expanded.split_at(32);
+    let (rho_prime, key) = rest.split_at(64);
+    let a = expand_a(rho);
+    let s1: [Poly; L] = std::array::from_fn(|r| rej_bounded_poly(rho_prime, r as u16));
+    let s2: [Poly; K] = std::array::from_fn(|r| rej_bounded_poly(rho_prime, (L + r) as u16));
+    let as1 = matrix_vector(&a, &s1.map(ntt));
+    let t: [Poly; K] = std::array::from_fn(|i| add_poly(&ntt_inverse(as1[i]), &s2[i]));
+    let t1 = t.map(|p| p.map(|c| power2round(c).0));
+    let t0 = t.map(|p| p.map(|c| power2round(c).1));
+
+    let mut public_key = rho.to_vec();
+    t1.iter().for_each(|p| pack(p.iter().copied(), T1_BITS, &mut public_key));
+    let tr: [u8; 64] = shake256(&[&public_key]);
+    let mut secret_key = [rho, key, &tr].concat();
+    s1.iter().chain(&s2).for_each(|p| bit_pack(p, ETA, ETA_BITS, &mut secret_key));
+    t0.iter().for_each(|p| bit_pack(p, 1 << (D - 1), D, &mut secret_key));
+    (public_key, secret_key)
+}
+
+/// Signs with fresh randomness (the hedged variant), as FIPS 204 recommends.
+pub fn sign(secret_key: &[u8], message: &[u8], context: &[u8]) -> Result<Vec<u8>, MlDsaError> {
+    let mut rnd = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut rnd);
+    sign_with_randomness(secret_key, message, context, &rnd)
+}
+
+/// ML-DSA.Sign with caller-chosen `rnd`; all zeros gives the deterministic variant.
+pub(crate) fn sign_with_randomness(secret_key: &[u8], message: &[u8], context: &[u8], rnd: &[u8; 32]) -> Result<Vec<u8>, MlDsaError> {
+    if secret_key.len() != SECRET_KEY_LEN {
+        return Err(MlDsaError::InvalidSecretKey);
+    }
+    let (rho, rest) = secret_key.split_at(32);
+    let (key, rest) = rest.split_at(32);
+    let (tr, rest) = rest.split_at(64);
+    let eta_len = 32 * ETA_BITS as usize;
+    let s1: [Poly; L] = std::array::from_fn(|i| ntt(bit_unpack(&rest[eta_len * i..], ETA_BITS, ETA)));
+    let rest = &rest[eta_len * L..];
+    let s2: [Poly; K] = std::array::from_fn(|i| ntt(bit_unpack(&rest[eta_len * i..], ETA_BITS, ETA)));
+    let rest = &rest[eta_len * K..];
+    let t0: [Poly; K] = std::array::from_fn(|i| ntt(bit_unpack(&rest[32 * D as usize * i..], D, 1 << (D - 1))));
+
+    let a = expand_a(rho);
+    let mu = message_representative(tr, message, context)?;
+    let rho_prime: [u8; 64] = shake256(&[key, rnd, &mu]);
+    let mut kappa = 0u16;
+    loop {
+        let y = expand_mask(&rho_prime, kappa);
+        kappa = kappa.wrapping_add(L as u16);
+        let w = matrix_vector(&a, &y.map(ntt)).map(ntt_inverse);
+        let c_tilde: [u8; CHALLENGE_LEN] = shake256(&[&mu, &w1_encode(&w.map(|p| p.map(high_bits)))]);
+        let c = ntt(sample_in_ball(&c_tilde));
+
+        let z: [Poly; L] = std::array::from_fn(|i| add_poly(&y[i], &ntt_inverse(pointwise(&c, &s1[i]))));
+        if exceeds(&z, GAMMA1 - BETA) {
+            continue;
+        }
+        let r: [Poly; K] = std::array::from_fn(|i| sub_poly(&w[i], &ntt_inverse(pointwise(&c, &s2[i]))));
+        if r.iter().flatten().any(|&c| decompose(c).1.unsigned_abs() >= GAMMA2 - BETA) {
+            continue;
+        }
+        let ct0: [Poly; K] = std::array::from_fn(|i| ntt_inverse(pointwise(&c, &t0[i])));
+        if exceeds(&ct0, GAMMA2) {
+            continue;
+        }
+        // MakeHint(-ct0, w - cs2 + ct0): whether adding ct0 changes the high bits.
+        let hint: Hint = std::array::from_fn(|i| std::array::from_fn(|j| high_bits(add(r[i][j], ct0[i][j])) != high_bits(r[i][j])));
+        if hint.iter().flatten().filter(|set| **set).count() > OMEGA {
+            continue;
+        }
+
+        let mut signature = c_tilde.to_vec();
+        z.iter().for_each(|p| bit_pack(p, GAMMA1, Z_BITS, &mut signature));
+        hint_bit_pack(&hint, &mut signature);
+        return Ok(signature);
+    }
+}
+
+/// ML-DSA.Verify. Malformed keys and context strings are errors; a
+/// signature that doesn't verify, including a malformed one, is `Ok(false)`.
+pub fn verify(public_key: &[u8], message: &[u8], context: &[u8], signature: &[u8]) -> Result<bool, MlDsaError> {
+    if public_key.len() != PUBLIC_KEY_LEN {
+        return Err(MlDsaError::InvalidPublicKey);
+    }
+    if signature.len() != SIGNATURE_LEN {
+        return Err(MlDsaError::InvalidSignature);
+    }
+    let (rho, t1) = public_key.split_at(32);
+    let t1_len = 32 * T1_BITS as usize;
+    let t1: [Poly; K] = std::array::from_fn(|i| ntt(unpack(&t1[t1_len * i..], T1_BITS).map(|c| c << D)));
+    let (c_tilde, rest) = signature.split_at(CHALLENGE_LEN);
+    let z_len = 32 * Z_BITS as usize;
+    let z: [Poly; L] = std::array::from_fn(|i| bit_unpack(&rest[z_len * i..], Z_BITS, GAMMA1));
+    let Some(hint) = hint_bit_unpack(&rest[z_len * L..]) else { return Ok(false) };
+    let mu = message_representative(&shake256::<64>(&[public_key]), message, context)?;
+    if exceeds(&z, GAMMA1 - BETA) {
+        return Ok(false);
+    }
+
+    let a = expand_a(rho);
+    let c = ntt(sample_in_ball(c_tilde));
+    let az = matrix_vector(&a, &z.map(ntt));
+    let w1: [Poly; K] = std::array::from_fn(|i| {
+        let w = ntt_inverse(sub_poly(&az[i], &pointwise(&c, &t1[i])));
+        std::array::from_fn(|j| use_hint(hint[i][j], w[j]))
+    });
+    let expected: [u8; CHALLENGE_LEN] = shake256(&[&mu, &w1_encode(&w1)]);
+    Ok(expected == c_tilde)
+}
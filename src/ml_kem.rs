@@ -0,0 +1,374 @@
+use rand::RngCore;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::{Digest, Sha3_256, Sha3_512, Shake128, Shake256};
+use subtle::{ConditionallySelectable, ConstantTimeEq};
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+
+// ML-KEM-768 parameters (FIPS 203 section 8).
+const N: usize = 256;
+const Q: u32 = 3329;
+const K: usize = 3;
+const DU: u32 = 10;
+const DV: u32 = 4;
+const POLY_BYTES: usize = 384;
+pub const PUBLIC_KEY_LEN: usize = POLY_BYTES * K + 32;
+pub const SECRET_KEY_LEN: usize = 2 * POLY_BYTES * K + 96;
+pub const CIPHERTEXT_LEN: usize = 32 * (DU as usize * K + DV as usize);
+pub const SHARED_SECRET_LEN: usize = 32;
+/// Multiplies by 128^-1 mod q to finish the inverse NTT.
+const NTT_SCALE: u32 = 3303;
+
+type Poly = [u16; N];
+type PolyVec = [Poly; K];
+
+#[derive(thiserror::Error, Debug)]
+pub enum MlKemError {
+    #[error("ML-KEM-768 public key must be {PUBLIC_KEY_LEN} bytes with coefficients below q")]
+    InvalidPublicKey,
+    #[error("ML-KEM-768 secret key must be {SECRET_KEY_LEN} bytes with a matching public key hash")]
+    InvalidSecretKey,
+    #[error("ML-KEM-768 ciphertext must be {CIPHERTEXT_LEN} bytes")]
+    InvalidCiphertext,
+}
+
+impl From<MlKemError> for PyErr {
+    fn from(err: MlKemError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// Powers of the root of unity 17 in bit-reversed order, for the NTT.
+const ZETAS: [u16; 128] = {
+    let mut zetas = [0u16; 128];
+    let mut i = 0;
+    while i < 128 {
+        zetas[i] = pow17(bit_reverse7(i as u32)) as u16;
+        i += 1;
+    }
+    zetas
+};
+
+/// 17^(2 * bitrev7(i) + 1), the moduli of the degree-one factors used in [`multiply_ntts`].
+const GAMMAS: [u16; 128] = {
+    let mut gammas = [0u16; 128];
+    let mut i = 0;
+    while i < 128 {
+        gammas[i] = pow17(2 * bit_reverse7(i as u32) + 1) as u16;
+        i += 1;
+    }
+    gammas
+};
+
+const fn bit_reverse7(x: u32) -> u32 {
+    x.reverse_bits() >> 25
+}
+
+const fn pow17(exponent: u32) -> u32 {
+    let mut result = 1;
+    let mut i = 0;
+    while i < exponent {
+        result = result * 17 % Q;
+        i += 1;
+    }
+    result
+}
+
+/// `floor(a / q)` for any `u32`, by multiplication rather than a division
+/// instruction whose timing could depend on secret values.
+fn div_q(a: u32) -> u32 {
+    const M: u64 = (1 << 36) / Q as u64;
+    let t = ((a as u64 * M) >> 36) as u32;
+    let r = a - t * Q;
+    // r < 2q; add one more if r >= q, without branching.
+    t + ((Q - 1).wrapping_sub(r) >> 31)
+}
+
+fn reduce(a: u32) -> u16 {
+    (a - div_q(a) * Q) as u16
+}
+
+fn ntt(f: &mut Poly) {
+    let mut k = 1;
+    let mut len = 128;
+    while len >= 2 {
+        for start in (0..N).step_by(2 * len) {
+            let zeta = ZETAS[k] as u32;
+            k += 1;
+            for j in start..start + len {
+                let t = reduce(zeta * f[j + len] as u32) as u32;
+                f[j + len] = reduce(f[j] as u32 + Q - t);
+                f[j] = reduce(f[j] as u32 + t);
+            }
+        }
+        len /= 2;
+    }
+}
+
+fn ntt_inverse(f: &mut Poly) {
+    let mut k = 127;
+    let mut len = 2;
+    while len <= 128 {
+        for start in (0..N).step_by(2 * len) {
+            let zeta = ZETAS[k] as u32;
+            k -= 1;
+            for j in start..start + len {
+                let t = f[j] as u32;
+                f[j] = reduce(t + f[j + len] as u32);
+                f[j + len] = reduce(zeta * reduce(f[j + len] as u32 + Q - t) as u32);
+            }
+        }
+        len *= 2;
+    }
+    for c in f.iter_mut() {
+        *c = reduce(*c as u32 * NTT_SCALE);
+    }
+}
+
+fn multiply_ntts(f: &Poly, g: &Poly) -> Poly {
+    let mut h = [0u16; N];
+    for i in 0..128 {
+        let (a0, a1, b0, b1) = (f[2 * i] as u32, f[2 * i + 1] as u32, g[2 * i] as u32, g[2 * i + 1] as u32);
+        h[2 * i] = reduce(a0 * b0 + reduce(a1 * b1) as u32 * GAMMAS[i] as u32);
+        h[2 * i + 1] = reduce(a0 * b1 + a1 * b0);
+    }
+    h
+}
+
+fn add(f: &Poly, g: &Poly) -> Poly {
+    std::array::from_fn(|i| reduce(f[i] as u32 + g[i] as u32))
+}
+
+fn sub(f: &Poly, g: &Poly) -> Poly {
+    std::array::from_fn(|i| reduce(f[i] as u32 + Q - g[i] as u32))
+}
+
+/// Sum of `a[i] * b[i]` in the NTT domain.
+fn inner_product(a: &PolyVec, b: &PolyVec) -> Poly {
+    (0..K).fold([0u16; N], |acc, i| add(&acc, &multiply_ntts(&a[i], &b[i])))
+}
+
+/// Algorithm 7: a uniform NTT-domain polynomial from SHAKE128(rho || j || i).
+fn sample_ntt(rho: &[u8], j: u8, i: u8) -> Poly {
+    let mut xof = Shake128::default();
+    xof.update(rho);
+    xof.update(&[j, i]);
+    let mut reader = xof.finalize_xof();
+    let mut f = [0u16; N];
+    let mut n = 0;
+    let mut block = [0u8; 168];
+    while n < N {
+        reader.read(&mut block);
+        for c in block.chunks_exact(3) {
+            let d1 = c[0] as u32 | ((c[1] as u32 & 0x0F) << 8);
+            let d2 = (c[1] as u32 >> 4) | ((c[2] as u32) << 4);
+            for d in [d1, d2] {
+                if d < Q && n < N {
+                    f[n] = d as u16;
+                    n += 1;
+                }
+            }
+        }
+    }
+    f
+}
+
+/// Algorithm 8 with eta = 2 (both noise parameters of ML-KEM-768).
+fn sample_cbd(bytes: &[u8]) -> Poly {
+    let bit = |k: usize| (bytes[k / 8] >> (k % 8)) as u32 & 1;
+    std::array::from_fn(|i| {
+        let x = bit(4 * i) + bit(4 * i + 1);
+        let y = bit(4 * i + 2) + bit(4 * i + 3);
+        reduce(x + Q - y)
+    })
+}
+
+/// PRF_2(s, b) = SHAKE256(s || b), 128 bytes.
+fn prf(seed: &[u8], nonce: u8) -> [u8; 128] {
+    let mut out = [0u8; 128];
+    Shake256::default().chain(seed).chain([nonce]).finalize_xof().read(&mut out);
+    out
+}
+
+fn sha3_256(data: &[&[u8]]) -> [u8; 32] {
+    data.iter().fold(Sha3_256::new(), Digest::chain_update).finalize().into()
+}
+
+fn sha3_512(data: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    let out = data.iter().fold(Sha3_512::new(), Digest::chain_update).finalize();
+    (out[..32].try_into().unwrap(), out[32..].try_into().unwrap())
+}
+
+/// Algorithm 5: packs `d`-bit coefficients little-endian.
+fn byte_encode(f: &Poly, d: u32, out: &mut Vec<u8>) {
+    let (mut acc, mut bits) = (0u32, 0u32);
+    for &c in f {
+        acc |= (c as u32) << bits;
+        bits += d;
+        while bits >= 8 {
+            out.push(acc as u8);
+            acc >>= 8;
+            bits -= 8;
+        }
+    }
+}
+
+/// Algorithm 6. Twelve-bit coefficients are reduced mod q.
+fn byte_decode(bytes: &[u8], d: u32) -> Poly {
+    let mask = (1u32 << d) - 1;
+    let (mut acc, mut bits, mut bytes) = (0u32, 0u32, bytes.iter());
+    std::array::from_fn(|_| {
+        while bits < d {
+            acc |= (*bytes.next().unwrap() as u32) << bits;
+            bits += 8;
+        }
+        let value = acc & mask;
+        acc >>= d;
+        bits -= d;
+        if d == 12 { reduce(value) } else { value as u16 }
+    })
+}
+
+fn compress(f: &Poly, d: u32) -> Poly {
+    std::array::from_fn(|i| (div_q(((f[i] as u32) << d) + Q / 2) & ((1 << d) - 1)) as u16)
+}
+
+fn decompress(f: &Poly, d: u32) -> Poly {
+    std::array::from_fn(|i| ((f[i] as u32 * Q + (1 << (d - 1))) >> d) as u16)
+}
+
+fn matrix(rho: &[u8]) -> [PolyVec; K] {
+    std::array::from_fn(|i| std::array::from_fn(|j| sample_ntt(rho, j as u8, i as u8)))
+}
+
+fn sample_vector(seed: &[u8], first_nonce: u8) -> PolyVec {
+    std::array::from_fn(|i| sample_cbd(&prf(seed, first_nonce + i as u8)))
+}
+
+/// Algorithm 13 (K-PKE.KeyGen): returns `(ek, dk_pke)`.
+fn pke_keygen(d: &[u8; 32]) -> (Vec<u8>, Vec<u8>) {
+    let (rho, sigma) = sha3_512(&[d, &[K as u8]]);
+    let a = matrix(&rho);
+    let mut s = sample_vector(&sigma, 0);
+    let mut e = sample_vector(&sigma, K as u8);
+    s.iter_mut().chain(e.iter_mut()).for_each(ntt);
+    let mut ek = Vec::with_capacity(PUBLIC_KEY_LEN);
+    for i in 0..K {
+        byte_encode(&add(&inner_product(&a[i], &s), &e[i]), 12, &mut ek);
+    }
+    ek.extend_from_slice(&rho);
+    let mut dk = Vec::with_capacity(POLY_BYTES * K);
+    s.iter().for_each(|p| byte_encode(p, 12, &mut dk));
+    (ek, dk)
+}
+
+/// Algorithm 14 (K-PKE.Encrypt).
+fn pke_encrypt(ek: &[u8], m: &[u8; 32], r: &[u8; 32]) -> Vec<u8> {
+    let t: PolyVec = std::array::from_fn(|i| byte_decode(&ek[POLY_BYTES * i..POLY_BYTES * (i + 1)], 12));
+    let a = matrix(&ek[POLY_BYTES * K..]);
+    let mut y = sample_vector(r, 0);
+    let e1 = sample_vector(r, K as u8);
+    let e2 = sample_cbd(&prf(r, 2 * K as u8));
+    y.iter_mut().for_each(ntt);
+
+    let mut c = Vec::with_capacity(CIPHERTEXT_LEN);
+    for i in 0..K {
+        let column: PolyVec = std::array::from_fn(|j| a[j][i]);
+        let mut u = inner_product(&column, &y);
+        ntt_inverse(&mut u);
+        byte_encode(&compress(&add(&u, &e1[i]), DU), DU, &mut c);
+    }
+    let mut v = inner_product(&t, &y);
+    ntt_inverse(&mut v);
+    let mu = decompress(&byte_decode(m, 1), 1);
+    byte_encode(&compress(&add(&add(&v, &e2), &mu), DV), DV, &mut c);
+    c
+}
+
+/// Algorithm 15 (K-PKE.Decrypt).
+fn pke_decrypt(dk_pke: &[u8], c: &[u8]) -> [u8; 32] {
+    let u_len = 32 * DU as usize;
+    let mut u: PolyVec = std::array::from_fn(|i| decompress(&byte_decode(&c[u_len * i..u_len * (i + 1)], DU), DU));
+    let v = decompress(&byte_decode(&c[u_len * K..], DV), DV);
+    let s: PolyVec = std::array::from_fn(|i| byte_decode(&dk_pke[POLY_BYTES * i..POLY_BYTES * (i + 1)], 12));
+    u.iter_mut().for_each(ntt);
+    let mut su = inner_product(&s, &u);
+    ntt_inverse(&mut su);
+    let mut m = Vec::with_capacity(32);
+    byte_encode(&compress(&sub(&v, &su), 1), 1, &mut m);
+    m.try_into().unwrap()
+}
+
+/// Returns `(encapsulation_key, decapsulation_key)`.
+pub fn generate_keypair() -> (Vec<u8>, Vec<u8>) {
+    let mut seed = [0u8; 64];
+    rand::thread_rng().fill_bytes(&mut seed);
+    keypair_from_seed(&seed)
+}
+
+/// ML-KEM.KeyGen_internal from the 64-byte seed `d || z`.
+pub fn keypair_from_seed(seed: &[u8; 64]) -> (Vec<u8>, Vec<u8>) {
+    let (d, z) = seed.split_at(32);
+    let (ek, mut dk) = pke_keygen(d.try_into().unwrap());
+    dk.extend_from_slice(&ek);
+    dk.extend_from_slice(&sha3_256(&[&ek]));
+    dk.extend_from_slice(z);
+    (ek, dk)
+}
+
+/// Returns `(ciphertext, shared_secret)`.
+pub fn encapsulate(encapsulation_key: &[u8]) -> Result<(Vec<u8>, [u8; SHARED_SECRET_LEN]), MlKemError> {
+    let mut m = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut m);
+    encapsulate_deterministic(encapsulation_key, &m)
+}
+
+/// ML-KEM.Encaps_internal with caller-chosen randomness `m`, for known-answer tests.
+pub(crate) fn encapsulate_deterministic(
+    encapsulation_key: &[u8],
+    m: &[u8; 32],
+) -> Result<(Vec<u8>, [u8; SHARED_SECRET_LEN]), MlKemError> {
+    check_encapsulation_key(encapsulation_key)?;
+    let (shared_secret, r) = sha3_512(&[m, &sha3_256(&[encapsulation_key])]);
+    Ok((pke_encrypt(encapsulation_key, m, &r), shared_secret))
+}
+
+/// Never fails on a well-formed but wrong ciphertext: per FIPS 203 it then
+/// returns a pseudorandom secret derived from `z`, so the caller learns nothing.
+pub fn decapsulate(decapsulation_key: &[u8], ciphertext: &[u8]) -> Result<[u8; SHARED_SECRET_LEN], MlKemError> {
+    if ciphertext.len() != CIPHERTEXT_LEN {
+        return Err(MlKemError::InvalidCiphertext);
+    }
+    if decapsulation_key.len() != SECRET_KEY_LEN {
+        return Err(MlKemError::InvalidSecretKey);
+    }
+    let (dk_pke, rest) = decapsulation_key.split_at(POLY_BYTES * K);
+    let (ek, rest) = rest.split_at(PUBLIC_KEY_LEN);
+    let (h, z) = rest.split_at(32);
+    if sha3_256(&[ek]) != h {
+        return Err(MlKemError::InvalidSecretKey);
+    }
+
+    let m = pke_decrypt(dk_pke, ciphertext);
+    let (shared_secret, r) = sha3_512(&[&m, h]);
+    let mut rejected = [0u8; SHARED_SECRET_LEN];
+    Shake256::default().chain(z).chain(ciphertext).finalize_xof().read(&mut rejected);
+    let matches = pke_encrypt(ek, &m, &r).ct_eq(ciphertext);
+    Ok(std::array::from_fn(|i| u8::conditional_select(&rejected[i], &shared_secret[i], matches)))
+}
+
+/// The input check of FIPS 203 section 7.2: right length, and every
+/// coefficient survives decoding unchanged (is below q).
+fn check_encapsulation_key(ek: &[u8]) -> Result<(), MlKemError> {
+    if ek.len() != PUBLIC_KEY_LEN {
+        return Err(MlKemError::InvalidPublicKey);
+    }
+    let mut reencoded = Vec::with_capacity(POLY_BYTES * K);
+    for chunk in ek[..POLY_BYTES * K].chunks_exact(POLY_BYTES) {
+        byte_encode(&byte_decode(chunk, 12), 12, &mut reencoded);
+    }
+    if reencoded != ek[..POLY_BYTES * K] {
+        return Err(MlKemError::InvalidPublicKey);
+    }
+    Ok(())
+}
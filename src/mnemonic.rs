@@ -0,0 +1,154 @@
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use sha2::{Sha256, Digest};
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::wordlist;
+
+// Every word in the list is identified by its first four letters.
+const PREFIX_LEN: usize = 4;
+const MAX_SUGGESTIONS: usize = 3;
+
+#[derive(thiserror::Error, Debug)]
+pub enum MnemonicError {
+    #[error("Data must be 16, 20, 24, 28 or 32 bytes, got {0}")]
+    InvalidLength(usize),
+    #[error("Expected 12, 15, 18, 21 or 24 words, got {0}")]
+    InvalidWordCount(usize),
+    #[error("Word {position} ({word:?}) is not in the word list{}", suggestion_text(.suggestions))]
+    UnknownWord { position: usize, word: String, suggestions: Vec<String> },
+    #[error("Checksum mismatch: a word is wrong or the words are out of order")]
+    ChecksumMismatch,
+    #[error("Invalid TOTP secret")]
+    InvalidSecret,
+}
+
+impl From<MnemonicError> for PyErr {
+    fn from(err: MnemonicError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+fn suggestion_text(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!("; did you mean {}?", suggestions.join(", "))
+    }
+}
+
+/// Encodes 16-32 bytes (in steps of 4) as a BIP39 English phrase: the data is
+/// followed by the first `len / 4` bits of its SHA-256 and split into 11-bit word indices.
+pub fn encode(data: &[u8]) -> Result<String, MnemonicError> {
+    if !(16..=32).contains(&data.len()) || !data.len().is_multiple_of(4) {
+        return Err(MnemonicError::InvalidLength(data.len()));
+    }
+    let checksum_bits = data.len() / 4;
+    let checksum = Sha256::digest(data)[0];
+    let bit = |i: usize| -> u16 {
+        if i < data.len() * 8 {
+            ((data[i / 8] >> (7 - i % 8)) & 1) as u16
+        } else {
+            let j = i - data.len() * 8;
+            ((checksum >> (7 - j)) & 1) as u16
+        }
+    };
+    let word_count = (data.len() * 8 + checksum_bits) / 11;
+    let words = wordlist::words();
+    let phrase = (0..word_count)
+        .map(|w| {
+            let index = (0..11).fold(0u16, |acc, b| (acc << 1) | bit(w * 11 + b));
+            words[index as usize]
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    Ok(phrase)
+}
+
+/// Decodes a phrase produced by [`encode`]. Words are matched case-insensitively
+/// and may be shortened to their first four letters. Unknown words are reported
+/// with their position and the closest list words; other typos fail the checksum.
+pub fn decode(phrase: &str) -> Result<Vec<u8>, MnemonicError> {
+    let tokens: Vec<String> = phrase.split_whitespace().map(str::to_lowercase).collect();
+    if !matches!(tokens.len(), 12 | 15 | 18 | 21 | 24) {
+        return Err(MnemonicError::InvalidWordCount(tokens.len()));
+    }
+    let indices = tokens
+        .iter()
+        .enumerate()
+        .map(|(i, token)| {
+            lookup(token).ok_or_else(|| MnemonicError::UnknownWord {
+                position: i + 1,
+                word: token.clone(),
+                suggestions: suggest(token),
+            })
+        })
+        .collect::<Result<Vec<u16>, _>>()?;
+
+    let total_bits = indices.len() * 11;
+    let checksum_bits = total_bits / 33;
+    let data_len = (total_bits - checksum_bits) / 8;
+    let bit = |i: usize| -> u8 { ((indices[i / 11] >> (10 - i % 11)) & 1) as u8 };
+    let data: Vec<u8> = (0..data_len)
+        .map(|byte| (0..8).fold(0u8, |acc, b| (acc << 1) | bit(byte * 8 + b)))
+        .collect();
+    let checksum = (0..checksum_bits).fold(0u8, |acc, b| (acc << 1) | bit(data_len * 8 + b));
+    if Sha256::digest(&data)[0] >> (8 - checksum_bits) != checksum {
+        return Err(MnemonicError::ChecksumMismatch);
+    }
+    Ok(data)
+}
+
+/// Encodes a base64 TOTP secret, as returned by [`crate::totp::generate_totp_secret`].
+pub fn encode_totp_secret(secret: &str) -> Result<String, MnemonicError> {
+    let bytes = BASE64_STANDARD.decode(secret.trim()).map_err(|_| MnemonicError::InvalidSecret)?;
+    encode(&bytes)
+}
+
+/// Inverse of [`encode_totp_secret`], returning the base64 secret.
+pub fn decode_totp_secret(phrase: &str) -> Result<String, MnemonicError> {
+    Ok(BASE64_STANDARD.encode(decode(phrase)?))
+}
+
+fn lookup(token: &str) -> Option<u16> {
+    if let Some(index) = wordlist::index_of(token) {
+        return Some(index as u16);
+    }
+    if token.chars().count() < PREFIX_LEN {
+        return None;
+    }
+    let prefix: String = token.chars().take(PREFIX_LEN).collect();
+    let mut matching = wordlist::words()
+        .iter()
+        .enumerate()
+        .filter(|(_, w)| w.starts_with(&prefix) && (token.len() == PREFIX_LEN || w.starts_with(token)));
+    match (matching.next(), matching.next()) {
+        (Some((index, _)), None) => Some(index as u16),
+        _ => None,
+    }
+}
+
+/// Closest list words by edit distance (at most 2).
+fn suggest(token: &str) -> Vec<String> {
+    let mut scored: Vec<(usize, &str)> = wordlist::words()
+        .iter()
+        .map(|w| (edit_distance(token, w), *w))
+        .filter(|(d, _)| *d <= 2)
+        .collect();
+    scored.sort();
+    scored.into_iter().take(MAX_SUGGESTIONS).map(|(_, w)| w.to_string()).collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
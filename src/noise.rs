@@ -0,0 +1,690 @@
+use std::io::{self, Read, Write};
+use blake2::{Blake2b512, Blake2s256};
+use hkdf::SimpleHkdf;
+use sha2::digest::core_api::BlockSizeUser;
+use sha2::{Digest, Sha256};
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::key_exchange::{self, KeyExchangeError};
+use crate::key_store::{KeyStore, KeyStoreError};
+use crate::q_core::{aead_open, aead_seal, AeadAlgorithm, QCoreError, NONCE_LEN};
+use crate::signing::{sign_message, verify_signature, SigningError};
+use crate::utils::take;
+
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+/// The largest Noise message; each is sent with a u16 length prefix.
+pub const MAX_MESSAGE_LEN: usize = 65535;
+pub const MAX_PAYLOAD_LEN: usize = MAX_MESSAGE_LEN - TAG_LEN;
+const PROLOGUE: &[u8] = b"qimem-noise-v1";
+const IDENTITY_PROOF_CONTEXT: &[u8] = b"qimem-noise-static-key";
+const IDENTITY_PROOF_LEN: usize = KEY_LEN + 64;
+const TRUSTED_PEERS_VERSION: u8 = 1;
+
+#[derive(thiserror::Error, Debug)]
+pub enum NoiseError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Unsupported Noise protocol {0:?}")]
+    UnsupportedProtocol(String),
+    #[error("The IK pattern needs the responder's static key")]
+    MissingRemoteStatic,
+    #[error("Handshake message out of order")]
+    UnexpectedMessage,
+    #[error("Malformed handshake message")]
+    MalformedMessage,
+    #[error("Message exceeds {MAX_MESSAGE_LEN} bytes")]
+    MessageTooLong,
+    #[error("Decryption failed")]
+    DecryptionFailed,
+    #[error("Nonce space exhausted; start a new session")]
+    NonceExhausted,
+    #[error("Peer's static key is not trusted")]
+    UntrustedPeer,
+    #[error("Invalid identity proof from peer")]
+    InvalidIdentityProof,
+    #[error("No key with id {0} in the KeyStore")]
+    KeyNotFound(String),
+    #[error("Invalid trusted peer list")]
+    InvalidTrustedPeers,
+    #[error("Key exchange error: {0}")]
+    KeyExchange(#[from] KeyExchangeError),
+    #[error("Encryption error: {0}")]
+    Encryption(#[from] QCoreError),
+    #[error("Signing error: {0}")]
+    Signing(#[from] SigningError),
+    #[error("KeyStore error: {0}")]
+    KeyStore(#[from] KeyStoreError),
+}
+
+impl From<NoiseError> for PyErr {
+    fn from(err: NoiseError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandshakePattern {
+    /// Both sides send their static keys during the handshake.
+    XX,
+    /// The initiator already knows the responder's static key.
+    IK,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashFunction {
+    Sha256,
+    Blake2s,
+    Blake2b,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Token {
+    E,
+    S,
+    EE,
+    ES,
+    SE,
+    SS,
+}
+
+impl HandshakePattern {
+    fn messages(self) -> &'static [&'static [Token]] {
+        match self {
+            HandshakePattern::XX => &[&[Token::E], &[Token::E, Token::EE, Token::S, Token::ES], &[Token::S, Token::SE]],
+            HandshakePattern::IK => &[&[Token::E, Token::ES, Token::S, Token::SS], &[Token::E, Token::EE, Token::SE]],
+        }
+    }
+}
+
+impl HashFunction {
+    fn name(self) -> &'static str {
+        match self {
+            HashFunction::Sha256 => "SHA256",
+            HashFunction::Blake2s => "BLAKE2s",
+            HashFunction::Blake2b => "BLAKE2b",
+        }
+    }
+
+    fn len(self) -> usize {
+        match self {
+            HashFunction::Blake2b => 64,
+            _ => 32,
+        }
+    }
+
+    fn hash(self, data: &[&[u8]]) -> Vec<u8> {
+        fn digest<H: Digest>(data: &[&[u8]]) -> Vec<u8> {
+            data.iter().fold(H::new(), |h, d| h.chain_update(d)).finalize().to_vec()
+        }
+        match self {
+            HashFunction::Sha256 => digest::<Sha256>(data),
+            HashFunction::Blake2s => digest::<Blake2s256>(data),
+            HashFunction::Blake2b => digest::<Blake2b512>(data),
+        }
+    }
+
+    /// The Noise HKDF with two outputs, which is HKDF-SHA* with an empty info.
+    fn hkdf(self, chaining_key: &[u8], input_key_material: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        fn expand<H: Digest + BlockSizeUser + Clone>(chaining_key: &[u8], input_key_material: &[u8], out: &mut [u8]) {
+            SimpleHkdf::<H>::new(Some(chaining_key), input_key_material).expand(&[], out).expect("two hash lengths is a valid HKDF output");
+        }
+        let mut first = vec![0u8; 2 * self.len()];
+        match self {
+            HashFunction::Sha256 => expand::<Sha256>(chaining_key, input_key_material, &mut first),
+            HashFunction::Blake2s => expand::<Blake2s256>(chaining_key, input_key_material, &mut first),
+            HashFunction::Blake2b => expand::<Blake2b512>(chaining_key, input_key_material, &mut first),
+        }
+        let second = first.split_off(self.len());
+        (first, second)
+    }
+}
+
+/// A Noise protocol with the DH and cipher fixed to 25519 and ChaChaPoly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoiseParams {
+    pub pattern: HandshakePattern,
+    pub hash: HashFunction,
+}
+
+impl NoiseParams {
+    pub fn new(pattern: HandshakePattern, hash: HashFunction) -> Self {
+        NoiseParams { pattern, hash }
+    }
+
+    /// The full protocol name, e.g. `Noise_XX_25519_ChaChaPoly_SHA256`.
+    pub fn name(&self) -> String {
+        format!("Noise_{:?}_25519_ChaChaPoly_{}", self.pattern, self.hash.name())
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, NoiseError> {
+        let unsupported = || NoiseError::UnsupportedProtocol(name.to_string());
+        let parts: Vec<&str> = name.split('_').collect();
+        let ["Noise", pattern, "25519", "ChaChaPoly", hash] = parts[..] else { return Err(unsupported()) };
+        let pattern = match pattern {
+            "XX" => HandshakePattern::XX,
+            "IK" => HandshakePattern::IK,
+            _ => return Err(unsupported()),
+        };
+        let hash = [HashFunction::Sha256, HashFunction::Blake2s, HashFunction::Blake2b]
+            .into_iter()
+            .find(|h| h.name() == hash)
+            .ok_or_else(unsupported)?;
+        Ok(NoiseParams { pattern, hash })
+    }
+}
+
+/// A ChaChaPoly key with its message counter; without a key it passes data through.
+struct CipherState {
+    key: Option<[u8; KEY_LEN]>,
+    nonce: u64,
+}
+
+impl CipherState {
+    fn new(key: Option<[u8; KEY_LEN]>) -> Self {
+        CipherState { key, nonce: 0 }
+    }
+
+    /// 32 bits of zeros followed by the little-endian counter.
+    fn next_nonce(&mut self) -> Result<[u8; NONCE_LEN], NoiseError> {
+        if self.nonce == u64::MAX {
+            return Err(NoiseError::NonceExhausted);
+        }
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[4..].copy_from_slice(&self.nonce.to_le_bytes());
+        self.nonce += 1;
+        Ok(nonce)
+    }
+
+    fn encrypt_with_ad(&mut self, ad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let Some(key) = self.key else { return Ok(plaintext.to_vec()) };
+        let nonce = self.next_nonce()?;
+        Ok(aead_seal(AeadAlgorithm::ChaCha20Poly1305, &key, &nonce, ad, plaintext)?)
+    }
+
+    /// The counter only advances when decryption succeeds.
+    fn decrypt_with_ad(&mut self, ad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let Some(key) = self.key else { return Ok(ciphertext.to_vec()) };
+        let counter = self.nonce;
+        let nonce = self.next_nonce()?;
+        aead_open(AeadAlgorithm::ChaCha20Poly1305, &key, &nonce, ad, ciphertext).map_err(|_| {
+            self.nonce = counter;
+            NoiseError::DecryptionFailed
+        })
+    }
+}
+
+struct SymmetricState {
+    hash: HashFunction,
+    chaining_key: Vec<u8>,
+    handshake_hash: Vec<u8>,
+    cipher: CipherState,
+}
+
+impl SymmetricState {
+    fn new(params: &NoiseParams) -> Self {
+        let name = params.name();
+        let handshake_hash = match name.len() <= params.hash.len() {
+            true => {
+                let mut padded = name.into_bytes();
+                padded.resize(params.hash.len(), 0);
+                padded
+            }
+            false => params.hash.hash(&[name.as_bytes()]),
+        };
+        SymmetricState { hash: params.hash, chaining_key: handshake_hash.clone(), handshake_hash, cipher: CipherState::new(None) }
+    }
+
+    fn mix_key(&mut self, input_key_material: &[u8]) {
+        let (chaining_key, key) = self.hash.hkdf(&self.chaining_key, input_key_material);
+        self.chaining_key = chaining_key;
+        self.cipher = CipherState::new(Some(key[..KEY_LEN].try_into().unwrap()));
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        self.handshake_hash = self.hash.hash(&[&self.handshake_hash, data]);
+    }
+
+    fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let ciphertext = self.cipher.encrypt_with_ad(&self.handshake_hash, plaintext)?;
+        self.mix_hash(&ciphertext);
+        Ok(ciphertext)
+    }
+
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let plaintext = self.cipher.decrypt_with_ad(&self.handshake_hash, ciphertext)?;
+        self.mix_hash(ciphertext);
+        Ok(plaintext)
+    }
+
+    fn split(&self) -> (CipherState, CipherState) {
+        let (first, second) = self.hash.hkdf(&self.chaining_key, &[]);
+        (
+            CipherState::new(Some(first[..KEY_LEN].try_into().unwrap())),
+            CipherState::new(Some(second[..KEY_LEN].try_into().unwrap())),
+        )
+    }
+}
+
+/// One side of a Noise handshake. Call [`HandshakeState::write_message`] and
+/// [`HandshakeState::read_message`] in turn until [`HandshakeState::is_finished`],
+/// then switch to [`HandshakeState::into_transport`].
+pub struct HandshakeState {
+    pattern: HandshakePattern,
+    initiator: bool,
+    symmetric: SymmetricState,
+    static_secret: [u8; KEY_LEN],
+    static_public: [u8; KEY_LEN],
+    ephemeral: Option<([u8; KEY_LEN], [u8; KEY_LEN])>,
+    remote_static: Option<[u8; KEY_LEN]>,
+    remote_ephemeral: Option<[u8; KEY_LEN]>,
+    message_index: usize,
+}
+
+impl HandshakeState {
+    /// `remote_static` is required for an IK initiator and ignored otherwise.
+    pub fn new(
+        params: NoiseParams,
+        initiator: bool,
+        prologue: &[u8],
+        static_secret: [u8; KEY_LEN],
+        remote_static: Option<[u8; KEY_LEN]>,
+    ) -> Result<Self, NoiseError> {
+        let mut symmetric = SymmetricState::new(&params);
+        symmetric.mix_hash(prologue);
+        let static_public = key_exchange::public_key(&static_secret)?;
+        let mut remote = None;
+        // IK's pre-message: the responder's static key is already known.
+        if params.pattern == HandshakePattern::IK {
+            let responder_static = match initiator {
+                true => *remote.insert(remote_static.ok_or(NoiseError::MissingRemoteStatic)?),
+                false => static_public,
+            };
+            symmetric.mix_hash(&responder_static);
+        }
+        Ok(HandshakeState {
+            pattern: params.pattern,
+            initiator,
+            symmetric,
+            static_secret,
+            static_public,
+            ephemeral: None,
+            remote_static: remote,
+            remote_ephemeral: None,
+            message_index: 0,
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.message_index == self.pattern.messages().len()
+    }
+
+    /// Whether the next handshake message is ours to send.
+    pub fn is_my_turn(&self) -> bool {
+        !self.is_finished() && self.message_index.is_multiple_of(2) == self.initiator
+    }
+
+    /// Whether the next message is the last one the given side sends.
+    fn is_last_message_of(&self, initiator: bool) -> bool {
+        let count = self.pattern.messages().len();
+        let last = if count.is_multiple_of(2) != initiator { count - 1 } else { count - 2 };
+        self.message_index == last
+    }
+
+    pub fn remote_static(&self) -> Option<[u8; KEY_LEN]> {
+        self.remote_static
+    }
+
+    pub fn write_message(&mut self, payload: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if !self.is_my_turn() {
+            return Err(NoiseError::UnexpectedMessage);
+        }
+        let mut message = Vec::new();
+        for token in self.pattern.messages()[self.message_index] {
+            match token {
+                Token::E => {
+                    let (public, secret) = key_exchange::generate_keypair();
+                    self.ephemeral = Some((secret, public));
+                    message.extend_from_slice(&public);
+                    self.symmetric.mix_hash(&public);
+                }
+                Token::S => {
+                    let encrypted = self.symmetric.encrypt_and_hash(&self.static_public)?;
+                    message.extend_from_slice(&encrypted);
+                }
+                token => self.mix_dh(*token)?,
+            }
+        }
+        message.extend_from_slice(&self.symmetric.encrypt_and_hash(payload)?);
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(NoiseError::MessageTooLong);
+        }
+        self.message_index += 1;
+        Ok(message)
+    }
+
+    /// Returns the message's payload.
+    pub fn read_message(&mut self, message: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if self.is_finished() || self.is_my_turn() {
+            return Err(NoiseError::UnexpectedMessage);
+        }
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(NoiseError::MessageTooLong);
+        }
+        let mut rest = message;
+        for token in self.pattern.messages()[self.message_index] {
+            match token {
+                Token::E => {
+                    let public: [u8; KEY_LEN] = take(&mut rest, KEY_LEN).ok_or(NoiseError::MalformedMessage)?.try_into().unwrap();
+                    self.remote_ephemeral = Some(public);
+                    self.symmetric.mix_hash(&public);
+                }
+                Token::S => {
+                    let len = KEY_LEN + if self.symmetric.cipher.key.is_some() { TAG_LEN } else { 0 };
+                    let encrypted = take(&mut rest, len).ok_or(NoiseError::MalformedMessage)?;
+                    self.remote_static = Some(self.symmetric.decrypt_and_hash(encrypted)?.try_into().unwrap());
+                }
+                token => self.mix_dh(*token)?,
+            }
+        }
+        let payload = self.symmetric.decrypt_and_hash(rest)?;
+        self.message_index += 1;
+        Ok(payload)
+    }
+
+    /// `ES` is DH(initiator's ephemeral, responder's static) from either side, and so on.
+    fn mix_dh(&mut self, token: Token) -> Result<(), NoiseError> {
+        let ephemeral = self.ephemeral.map(|(secret, _)| secret);
+        let (local, remote) = match (token, self.initiator) {
+            (Token::EE, _) => (ephemeral, self.remote_ephemeral),
+            (Token::ES, true) | (Token::SE, false) => (ephemeral, self.remote_static),
+            (Token::ES, false) | (Token::SE, true) => (Some(self.static_secret), self.remote_ephemeral),
+            (Token::SS, _) => (Some(self.static_secret), self.remote_static),
+            _ => unreachable!("E and S are not DH tokens"),
+        };
+        let (local, remote) = local.zip(remote).ok_or(NoiseError::UnexpectedMessage)?;
+        self.symmetric.mix_key(&key_exchange::diffie_hellman(&local, &remote)?);
+        Ok(())
+    }
+
+    pub fn into_transport(self) -> Result<TransportState, NoiseError> {
+        let remote_static = match (self.is_finished(), self.remote_static) {
+            (true, Some(remote_static)) => remote_static,
+            _ => return Err(NoiseError::UnexpectedMessage),
+        };
+        let (initiator_to_responder, responder_to_initiator) = self.symmetric.split();
+        let (send, receive) = match self.initiator {
+            true => (initiator_to_responder, responder_to_initiator),
+            false => (responder_to_initiator, initiator_to_responder),
+        };
+        Ok(TransportState { send, receive, remote_static, handshake_hash: self.symmetric.handshake_hash })
+    }
+}
+
+/// The two cipher states of a finished handshake.
+pub struct TransportState {
+    send: CipherState,
+    receive: CipherState,
+    remote_static: [u8; KEY_LEN],
+    handshake_hash: Vec<u8>,
+}
+
+impl TransportState {
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if plaintext.len() > MAX_PAYLOAD_LEN {
+            return Err(NoiseError::MessageTooLong);
+        }
+        self.send.encrypt_with_ad(&[], plaintext)
+    }
+
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        self.receive.decrypt_with_ad(&[], ciphertext)
+    }
+
+    pub fn remote_static(&self) -> [u8; KEY_LEN] {
+        self.remote_static
+    }
+
+    /// Unique to this session; both sides can compare it for channel binding.
+    pub fn handshake_hash(&self) -> &[u8] {
+        &self.handshake_hash
+    }
+}
+
+/// The peers a channel accepts: X25519 static keys trusted directly, and
+/// Ed25519 identity keys that vouch for whatever static key the peer uses.
+/// Kept in the KeyStore as a record.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrustedPeers {
+    pub static_keys: Vec<[u8; KEY_LEN]>,
+    pub identities: Vec<[u8; KEY_LEN]>,
+}
+
+impl TrustedPeers {
+    /// `version u8 | static key count u16 | static keys | identity count u16 | identities`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![TRUSTED_PEERS_VERSION];
+        for keys in [&self.static_keys, &self.identities] {
+            out.extend_from_slice(&(keys.len() as u16).to_be_bytes());
+            keys.iter().for_each(|key| out.extend_from_slice(key));
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, NoiseError> {
+        let mut rest = bytes;
+        let mut field = |len| take(&mut rest, len).ok_or(NoiseError::InvalidTrustedPeers);
+        if field(1)? != [TRUSTED_PEERS_VERSION] {
+            return Err(NoiseError::InvalidTrustedPeers);
+        }
+        let mut keys = || -> Result<Vec<[u8; KEY_LEN]>, NoiseError> {
+            let count = u16::from_be_bytes(field(2)?.try_into().unwrap());
+            (0..count).map(|_| Ok(field(KEY_LEN)?.try_into().unwrap())).collect()
+        };
+        let static_keys = keys()?;
+        let identities = keys()?;
+        if !rest.is_empty() {
+            return Err(NoiseError::InvalidTrustedPeers);
+        }
+        Ok(TrustedPeers { static_keys, identities })
+    }
+
+    pub fn save(&self, store: &mut KeyStore, id: &str) -> Result<(), NoiseError> {
+        Ok(store.store_record(id, &self.to_bytes())?)
+    }
+
+    /// A missing record is an empty list.
+    pub fn load(store: &KeyStore, id: &str) -> Result<Self, NoiseError> {
+        store.retrieve_record(id).map_or(Ok(TrustedPeers::default()), |bytes| TrustedPeers::from_bytes(&bytes))
+    }
+}
+
+/// How one end of a [`SecureStream`] authenticates itself and its peer.
+#[derive(Clone)]
+pub struct ChannelConfig {
+    pub params: NoiseParams,
+    pub static_secret: [u8; KEY_LEN],
+    /// An Ed25519 secret key; if set, the handshake carries its signature over
+    /// our static key so peers can trust the identity instead of the static key.
+    pub identity_secret: Option<[u8; KEY_LEN]>,
+    /// The responder's static key, needed to initiate IK. It is trusted as-is.
+    pub remote_static: Option<[u8; KEY_LEN]>,
+    pub trusted_peers: TrustedPeers,
+}
+
+impl ChannelConfig {
+    /// Loads the X25519 static key, the optional Ed25519 identity key and the
+    /// trusted peer list from `store`.
+    pub fn from_key_store(
+        store: &KeyStore,
+        params: NoiseParams,
+        static_key_id: &str,
+        identity_key_id: Option<&str>,
+        trusted_peers_id: &str,
+    ) -> Result<Self, NoiseError> {
+        let key = |id: &str| store.retrieve_key(id).ok_or_else(|| NoiseError::KeyNotFound(id.to_string()));
+        Ok(ChannelConfig {
+            params,
+            static_secret: key(static_key_id)?,
+            identity_secret: identity_key_id.map(key).transpose()?,
+            remote_static: None,
+            trusted_peers: TrustedPeers::load(store, trusted_peers_id)?,
+        })
+    }
+
+    /// `identity public key | signature over our static key`, or nothing.
+    fn identity_proof(&self) -> Result<Vec<u8>, NoiseError> {
+        let Some(identity_secret) = self.identity_secret else { return Ok(Vec::new()) };
+        let static_public = key_exchange::public_key(&self.static_secret)?;
+        let identity_public = ed25519_dalek::SigningKey::from_bytes(&identity_secret).verifying_key().to_bytes();
+        let signature = sign_message(&identity_secret, &[IDENTITY_PROOF_CONTEXT, &static_public].concat())?;
+        Ok([identity_public.as_slice(), &signature].concat())
+    }
+
+    /// Returns the peer's proven Ed25519 identity, if it sent one.
+    fn authenticate_peer(&self, remote_static: &[u8; KEY_LEN], proof: &[u8]) -> Result<Option<[u8; KEY_LEN]>, NoiseError> {
+        let identity = match proof.len() {
+            0 => None,
+            IDENTITY_PROOF_LEN => {
+                let (identity, signature) = proof.split_at(KEY_LEN);
+                if !verify_signature(identity, &[IDENTITY_PROOF_CONTEXT, remote_static].concat(), signature)? {
+                    return Err(NoiseError::InvalidIdentityProof);
+                }
+                Some(identity.try_into().unwrap())
+            }
+            _ => return Err(NoiseError::InvalidIdentityProof),
+        };
+        let trusted = self.remote_static.as_ref() == Some(remote_static)
+            || self.trusted_peers.static_keys.contains(remote_static)
+            || identity.is_some_and(|identity| self.trusted_peers.identities.contains(&identity));
+        if !trusted {
+            return Err(NoiseError::UntrustedPeer);
+        }
+        Ok(identity)
+    }
+}
+
+/// A Noise-encrypted, mutually authenticated byte stream over any blocking
+/// transport, such as a `TcpStream` or `UnixStream`. Each Noise message is sent
+/// as `length (u16 BE) | message`; handshake payloads carry only identity proofs,
+/// sent in each side's last handshake message so they are encrypted.
+pub struct SecureStream<S> {
+    stream: S,
+    transport: TransportState,
+    remote_identity: Option<[u8; KEY_LEN]>,
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl<S: Read + Write> SecureStream<S> {
+    /// Runs the handshake as initiator.
+    pub fn connect(stream: S, config: &ChannelConfig) -> Result<Self, NoiseError> {
+        Self::handshake(stream, config, true)
+    }
+
+    /// Runs the handshake as responder.
+    pub fn accept(stream: S, config: &ChannelConfig) -> Result<Self, NoiseError> {
+        Self::handshake(stream, config, false)
+    }
+
+    /// The peer is checked as soon as its last handshake message arrives, so
+    /// an initiator never reveals its identity to an untrusted responder.
+    fn handshake(mut stream: S, config: &ChannelConfig, initiator: bool) -> Result<Self, NoiseError> {
+        let remote_static = if initiator { config.remote_static } else { None };
+        let mut state = HandshakeState::new(config.params, initiator, PROLOGUE, config.static_secret, remote_static)?;
+        let mut remote_identity = None;
+        while !state.is_finished() {
+            if state.is_my_turn() {
+                let payload = if state.is_last_message_of(initiator) { config.identity_proof()? } else { Vec::new() };
+                write_frame(&mut stream, &state.write_message(&payload)?)?;
+            } else {
+                let last = state.is_last_message_of(!initiator);
+                let payload = state.read_message(&read_frame(&mut stream)?.ok_or(NoiseError::MalformedMessage)?)?;
+                if last {
+                    let remote_static = state.remote_static().ok_or(NoiseError::UnexpectedMessage)?;
+                    remote_identity = config.authenticate_peer(&remote_static, &payload)?;
+                } else if !payload.is_empty() {
+                    return Err(NoiseError::MalformedMessage);
+                }
+            }
+        }
+        Ok(SecureStream { stream, transport: state.into_transport()?, remote_identity, buffer: Vec::new(), position: 0 })
+    }
+
+    /// Sends one message of at most [`MAX_PAYLOAD_LEN`] bytes.
+    pub fn send(&mut self, message: &[u8]) -> Result<(), NoiseError> {
+        let ciphertext = self.transport.encrypt(message)?;
+        write_frame(&mut self.stream, &ciphertext)
+    }
+
+    /// Receives one message, or `None` if the peer closed the connection.
+    pub fn receive(&mut self) -> Result<Option<Vec<u8>>, NoiseError> {
+        match read_frame(&mut self.stream)? {
+            Some(ciphertext) => Ok(Some(self.transport.decrypt(&ciphertext)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn remote_static_key(&self) -> [u8; KEY_LEN] {
+        self.transport.remote_static()
+    }
+
+    /// The peer's Ed25519 identity key, if it proved one.
+    pub fn remote_identity(&self) -> Option<[u8; KEY_LEN]> {
+        self.remote_identity
+    }
+
+    pub fn handshake_hash(&self) -> &[u8] {
+        self.transport.handshake_hash()
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<S: Read + Write> Read for SecureStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.buffer.len() {
+            match self.receive().map_err(io::Error::other)? {
+                Some(message) => (self.buffer, self.position) = (message, 0),
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.buffer.len() - self.position);
+        buf[..len].copy_from_slice(&self.buffer[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl<S: Read + Write> Write for SecureStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(MAX_PAYLOAD_LEN);
+        self.send(&buf[..len]).map_err(io::Error::other)?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+fn write_frame(stream: &mut impl Write, message: &[u8]) -> Result<(), NoiseError> {
+    let len = u16::try_from(message.len()).map_err(|_| NoiseError::MessageTooLong)?;
+    stream.write_all(&[len.to_be_bytes().as_slice(), message].concat())?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// `None` on a clean end of stream before the next frame.
+fn read_frame(stream: &mut impl Read) -> Result<Option<Vec<u8>>, NoiseError> {
+    let mut len = [0u8; 2];
+    match stream.read_exact(&mut len) {
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let mut message = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut message)?;
+    Ok(Some(message))
+}
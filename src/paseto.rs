@@ -0,0 +1,197 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use blake2::Blake2bMac;
+use blake2::digest::{Mac, consts::{U32, U56}};
+use chacha20::XChaCha20;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use rand::RngCore;
+use subtle::ConstantTimeEq;
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::signing::{sign_message, verify_signature, SigningError};
+
+const LOCAL_HEADER: &str = "v4.local.";
+const PUBLIC_HEADER: &str = "v4.public.";
+const NONCE_LEN: usize = 32;
+const TAG_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+
+#[derive(thiserror::Error, Debug)]
+pub enum PasetoError {
+    #[error("Key must be 32 bytes")]
+    InvalidKey,
+    #[error("Token does not start with {0}")]
+    InvalidHeader(&'static str),
+    #[error("Malformed token")]
+    Malformed,
+    #[error("Footer does not match the expected footer")]
+    FooterMismatch,
+    #[error("Authentication tag does not match (wrong key, footer or implicit assertion)")]
+    InvalidTag,
+    #[error("Signature does not match (wrong key, footer or implicit assertion)")]
+    InvalidSignature,
+    #[error("Signing error: {0}")]
+    Signing(#[from] SigningError),
+}
+
+impl From<PasetoError> for PyErr {
+    fn from(err: PasetoError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// Encrypts `message` as a `v4.local` token under the 32-byte symmetric `key`.
+/// The footer is authenticated and sent in the clear; the implicit assertion is
+/// authenticated but never sent, so the verifier must supply the same bytes.
+pub fn encrypt_local(key: &[u8], message: &[u8], footer: &[u8], implicit: &[u8]) -> Result<String, PasetoError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    encrypt_local_with_nonce(key, message, footer, implicit, &nonce)
+}
+
+/// [`encrypt_local`] with a caller-chosen nonce, for the deterministic test vectors.
+pub(crate) fn encrypt_local_with_nonce(
+    key: &[u8],
+    message: &[u8],
+    footer: &[u8],
+    implicit: &[u8],
+    nonce: &[u8; NONCE_LEN],
+) -> Result<String, PasetoError> {
+    let keys = split_key(key, nonce)?;
+    let mut ciphertext = message.to_vec();
+    keys.cipher().apply_keystream(&mut ciphertext);
+    let tag = local_tag(&keys.auth_key, nonce, &ciphertext, footer, implicit);
+
+    let mut body = Vec::with_capacity(NONCE_LEN + ciphertext.len() + TAG_LEN);
+    body.extend_from_slice(nonce);
+    body.extend_from_slice(&ciphertext);
+    body.extend_from_slice(&tag);
+    Ok(assemble(LOCAL_HEADER, &body, footer))
+}
+
+/// Decrypts a `v4.local` token. When `footer` is given it must match the token's
+/// footer exactly; otherwise any footer is accepted (it is still authenticated).
+pub fn decrypt_local(key: &[u8], token: &str, footer: Option<&[u8]>, implicit: &[u8]) -> Result<Vec<u8>, PasetoError> {
+    let (body, token_footer) = split_token(token, LOCAL_HEADER, footer)?;
+    if body.len() < NONCE_LEN + TAG_LEN {
+        return Err(PasetoError::Malformed);
+    }
+    let (nonce, rest) = body.split_at(NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+    let nonce: [u8; NONCE_LEN] = nonce.try_into().unwrap();
+    let keys = split_key(key, &nonce)?;
+    let expected = local_tag(&keys.auth_key, &nonce, ciphertext, &token_footer, implicit);
+    if !bool::from(expected.ct_eq(tag)) {
+        return Err(PasetoError::InvalidTag);
+    }
+    let mut message = ciphertext.to_vec();
+    keys.cipher().apply_keystream(&mut message);
+    Ok(message)
+}
+
+/// Signs `message` as a `v4.public` token with a 32-byte Ed25519 secret key.
+pub fn sign_public(secret_key: &[u8], message: &[u8], footer: &[u8], implicit: &[u8]) -> Result<String, PasetoError> {
+    let signature = sign_message(secret_key, &pae(&[PUBLIC_HEADER.as_bytes(), message, footer, implicit]))?;
+    let mut body = message.to_vec();
+    body.extend_from_slice(&signature);
+    Ok(assemble(PUBLIC_HEADER, &body, footer))
+}
+
+/// Verifies a `v4.public` token and returns its message. `footer` is checked as in [`decrypt_local`].
+pub fn verify_public(public_key: &[u8], token: &str, footer: Option<&[u8]>, implicit: &[u8]) -> Result<Vec<u8>, PasetoError> {
+    let (body, token_footer) = split_token(token, PUBLIC_HEADER, footer)?;
+    if body.len() < SIGNATURE_LEN {
+        return Err(PasetoError::Malformed);
+    }
+    let (message, signature) = body.split_at(body.len() - SIGNATURE_LEN);
+    let signed = pae(&[PUBLIC_HEADER.as_bytes(), message, &token_footer, implicit]);
+    if !verify_signature(public_key, &signed, signature)? {
+        return Err(PasetoError::InvalidSignature);
+    }
+    Ok(message.to_vec())
+}
+
+/// Returns a token's footer without verifying anything, e.g. to read a key id
+/// before choosing the key. Do not trust its contents until the token verifies.
+pub fn untrusted_footer(token: &str) -> Result<Vec<u8>, PasetoError> {
+    match token.splitn(4, '.').nth(3) {
+        Some(footer) => BASE64_URL.decode(footer).map_err(|_| PasetoError::Malformed),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Pre-Authentication Encoding: the piece count, then each piece prefixed with
+/// its length, all lengths as 64-bit little-endian with the top bit clear.
+fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+    let le64 = |n: usize| ((n as u64) & (u64::MAX >> 1)).to_le_bytes();
+    let mut out = le64(pieces.len()).to_vec();
+    for piece in pieces {
+        out.extend_from_slice(&le64(piece.len()));
+        out.extend_from_slice(piece);
+    }
+    out
+}
+
+/// Per-token keys derived from the shared key and the token nonce.
+struct LocalKeys {
+    encryption_key: [u8; 32],
+    counter_nonce: [u8; 24],
+    auth_key: [u8; 32],
+}
+
+impl LocalKeys {
+    fn cipher(&self) -> XChaCha20 {
+        XChaCha20::new(&self.encryption_key.into(), &self.counter_nonce.into())
+    }
+}
+
+fn split_key(key: &[u8], nonce: &[u8; NONCE_LEN]) -> Result<LocalKeys, PasetoError> {
+    if key.len() != 32 {
+        return Err(PasetoError::InvalidKey);
+    }
+    let mut mac = <Blake2bMac<U56> as Mac>::new_from_slice(key).map_err(|_| PasetoError::InvalidKey)?;
+    mac.update(b"paseto-encryption-key");
+    mac.update(nonce);
+    let derived = mac.finalize().into_bytes();
+
+    let mut mac = <Blake2bMac<U32> as Mac>::new_from_slice(key).map_err(|_| PasetoError::InvalidKey)?;
+    mac.update(b"paseto-auth-key-for-aead");
+    mac.update(nonce);
+    let auth_key = mac.finalize().into_bytes();
+
+    Ok(LocalKeys {
+        encryption_key: derived[..32].try_into().unwrap(),
+        counter_nonce: derived[32..].try_into().unwrap(),
+        auth_key: auth_key.into(),
+    })
+}
+
+fn local_tag(auth_key: &[u8; 32], nonce: &[u8], ciphertext: &[u8], footer: &[u8], implicit: &[u8]) -> [u8; TAG_LEN] {
+    let mut mac = <Blake2bMac<U32> as Mac>::new_from_slice(auth_key).expect("32-byte BLAKE2b key");
+    mac.update(&pae(&[LOCAL_HEADER.as_bytes(), nonce, ciphertext, footer, implicit]));
+    mac.finalize().into_bytes().into()
+}
+
+fn assemble(header: &str, body: &[u8], footer: &[u8]) -> String {
+    let mut token = format!("{}{}", header, BASE64_URL.encode(body));
+    if !footer.is_empty() {
+        token.push('.');
+        token.push_str(&BASE64_URL.encode(footer));
+    }
+    token
+}
+
+/// Checks the header and optional expected footer, returning the decoded body and footer.
+fn split_token(token: &str, header: &'static str, expected_footer: Option<&[u8]>) -> Result<(Vec<u8>, Vec<u8>), PasetoError> {
+    let rest = token.strip_prefix(header).ok_or(PasetoError::InvalidHeader(header))?;
+    let (body, footer) = match rest.split_once('.') {
+        Some((body, footer)) => (body, BASE64_URL.decode(footer).map_err(|_| PasetoError::Malformed)?),
+        None => (rest, Vec::new()),
+    };
+    if let Some(expected) = expected_footer {
+        if !bool::from(expected.ct_eq(&footer)) {
+            return Err(PasetoError::FooterMismatch);
+        }
+    }
+    let body = BASE64_URL.decode(body).map_err(|_| PasetoError::Malformed)?;
+    Ok((body, footer))
+}
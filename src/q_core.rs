@@ -1,13 +1,13 @@
-use pyo3::prelude::*;
-use pyo3::types::PyBytes;
+use pyo3::PyErr;
 use chacha20poly1305::{
-    aead::{Aead, KeyInit},
-    ChaCha20Poly1305, Nonce,
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305,
 };
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
 use pyo3::exceptions::PyValueError;
 use rand::RngCore;
 use aes::Aes256;
-use aes::cipher::{BlockEncrypt, BlockDecrypt, KeyInit as AesKeyInit};
+use aes::cipher::{BlockEncrypt, BlockDecrypt};
 use aes::cipher::generic_array::GenericArray;
 
 #[derive(thiserror::Error, Debug)]
@@ -16,6 +16,10 @@ pub enum QCoreError {
     EncryptionFailed,
     #[error("Decryption failed")]
     DecryptionFailed,
+    #[error("Invalid key length")]
+    InvalidKey,
+    #[error("Invalid encrypted data")]
+    InvalidData,
 }
 
 impl From<QCoreError> for PyErr {
@@ -24,38 +28,87 @@ impl From<QCoreError> for PyErr {
     }
 }
 
-#[pyfunction]
-pub fn encrypt<'py>(py: Python<'py>, data: &[u8], key: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
-    let key_array: [u8; 32] = key
-        .try_into()
-        .map_err(|_| PyValueError::new_err("Key must be 32 bytes"))?;
-    let cipher = ChaCha20Poly1305::new(&key_array.into());
-    let mut nonce = [0u8; 12];
+pub const NONCE_LEN: usize = 12;
+
+/// The AEADs available to higher-level protocols. [`encrypt`] and [`decrypt`]
+/// always use ChaCha20-Poly1305.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AeadAlgorithm {
+    ChaCha20Poly1305,
+    Aes128Gcm,
+    Aes256Gcm,
+}
+
+impl AeadAlgorithm {
+    pub fn key_len(self) -> usize {
+        match self {
+            AeadAlgorithm::Aes128Gcm => 16,
+            AeadAlgorithm::ChaCha20Poly1305 | AeadAlgorithm::Aes256Gcm => 32,
+        }
+    }
+}
+
+/// AEAD encryption with an explicit nonce and associated data. The caller
+/// must never reuse a nonce under the same key.
+pub fn aead_seal(
+    algorithm: AeadAlgorithm,
+    key: &[u8],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, QCoreError> {
+    let payload = Payload { msg: plaintext, aad };
+    let result = match algorithm {
+        AeadAlgorithm::ChaCha20Poly1305 => aead_cipher::<ChaCha20Poly1305>(key)?.encrypt(nonce.into(), payload),
+        AeadAlgorithm::Aes128Gcm => aead_cipher::<Aes128Gcm>(key)?.encrypt(nonce.into(), payload),
+        AeadAlgorithm::Aes256Gcm => aead_cipher::<Aes256Gcm>(key)?.encrypt(nonce.into(), payload),
+    };
+    result.map_err(|_| QCoreError::EncryptionFailed)
+}
+
+/// Inverse of [`aead_seal`].
+pub fn aead_open(
+    algorithm: AeadAlgorithm,
+    key: &[u8],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, QCoreError> {
+    let payload = Payload { msg: ciphertext, aad };
+    let result = match algorithm {
+        AeadAlgorithm::ChaCha20Poly1305 => aead_cipher::<ChaCha20Poly1305>(key)?.decrypt(nonce.into(), payload),
+        AeadAlgorithm::Aes128Gcm => aead_cipher::<Aes128Gcm>(key)?.decrypt(nonce.into(), payload),
+        AeadAlgorithm::Aes256Gcm => aead_cipher::<Aes256Gcm>(key)?.decrypt(nonce.into(), payload),
+    };
+    result.map_err(|_| QCoreError::DecryptionFailed)
+}
+
+fn aead_cipher<C: KeyInit>(key: &[u8]) -> Result<C, QCoreError> {
+    C::new_from_slice(key).map_err(|_| QCoreError::InvalidKey)
+}
+
+/// ChaCha20-Poly1305 with a random nonce: `nonce (12) | ciphertext`.
+pub fn encrypt(data: &[u8], key: &[u8]) -> Result<Vec<u8>, QCoreError> {
+    if key.len() != 32 {
+        return Err(QCoreError::InvalidKey);
+    }
+    let mut nonce = [0u8; NONCE_LEN];
     rand::thread_rng().fill_bytes(&mut nonce);
-    let nonce = Nonce::from_slice(&nonce);
-    let ciphertext = cipher
-        .encrypt(nonce, data)
-        .map_err(|_| QCoreError::EncryptionFailed)?;
+    let ciphertext = aead_seal(AeadAlgorithm::ChaCha20Poly1305, key, &nonce, &[], data)?;
     let mut output = nonce.to_vec();
     output.extend_from_slice(&ciphertext);
-    Ok(PyBytes::new_bound(py, &output))
+    Ok(output)
 }
 
-#[pyfunction]
-pub fn decrypt<'py>(py: Python<'py>, encrypted: &[u8], key: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
-    let key_array: [u8; 32] = key
-        .try_into()
-        .map_err(|_| PyValueError::new_err("Key must be 32 bytes"))?;
-    if encrypted.len() < 12 {
-        return Err(PyValueError::new_err("Invalid encrypted data"));
+pub fn decrypt(encrypted: &[u8], key: &[u8]) -> Result<Vec<u8>, QCoreError> {
+    if key.len() != 32 {
+        return Err(QCoreError::InvalidKey);
+    }
+    if encrypted.len() < NONCE_LEN {
+        return Err(QCoreError::InvalidData);
     }
-    let nonce = Nonce::from_slice(&encrypted[..12]);
-    let ciphertext = &encrypted[12..];
-    let cipher = ChaCha20Poly1305::new(&key_array.into());
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext)
-        .map_err(|_| QCoreError::DecryptionFailed)?;
-    Ok(PyBytes::new_bound(py, &plaintext))
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+    aead_open(AeadAlgorithm::ChaCha20Poly1305, key, nonce.try_into().unwrap(), &[], ciphertext)
 }
 
 // Simple versions for non-Python use
@@ -87,7 +140,7 @@ pub fn decrypt_simple(encrypted: &[u8], key: &[u8]) -> Result<Vec<u8>, Box<dyn s
         return Err("Key must be 32 bytes for AES-256".into());
     }
 
-    if encrypted.len() % 16 != 0 {
+    if !encrypted.len().is_multiple_of(16) {
         return Err("Encrypted data length must be multiple of 16".into());
     }
 
@@ -1,9 +1,26 @@
-use pyo3::prelude::*;
-use pyo3::types::PyBytes;
-use argon2::{Argon2, Algorithm, Version, Params};
-use rand::RngCore;
+use std::collections::HashMap;
+use argon2::{Argon2, Algorithm, Version, ParamsBuilder, AssociatedData};
+use base64::{Engine as _, engine::general_purpose};
+use rand::{Rng, RngCore};
 use sha2::{Sha256, Digest};
+use pyo3::PyErr;
 use pyo3::exceptions::PyValueError;
+use crate::utils::from_hex;
+use crate::wordlist;
+
+pub const KEY_LEN: usize = 32;
+pub const SALT_LEN: usize = 16;
+pub const MIN_SALT_LEN: usize = 8;
+pub const MAX_SALT_LEN: usize = 64;
+
+const DEFAULT_M_COST: u32 = 32768;
+const DEFAULT_T_COST: u32 = 4;
+const DEFAULT_P_COST: u32 = 1;
+// Refuse parameter blobs asking for more than 1 GiB of memory, 64 passes or 16 lanes.
+const MAX_M_COST: u32 = 1 << 20;
+const MAX_T_COST: u32 = 64;
+const MAX_P_COST: u32 = 16;
+const PARAMS_VERSION: u8 = 1;
 
 #[derive(thiserror::Error, Debug)]
 pub enum KeyGenError {
@@ -11,6 +28,12 @@ pub enum KeyGenError {
     SaltInvalid(String),
     #[error("Hash error: {0}")]
     HashError(String),
+    #[error("Invalid KDF parameters: {0}")]
+    InvalidParams(String),
+    #[error("Invalid generator options: {0}")]
+    InvalidOptions(String),
+    #[error("Password too weak (score {score}/4): {feedback}")]
+    WeakPassword { score: u8, feedback: String },
 }
 
 impl From<KeyGenError> for PyErr {
@@ -19,49 +42,644 @@ impl From<KeyGenError> for PyErr {
     }
 }
 
-#[pyfunction]
-#[pyo3(signature = (password, salt_phrase=None))]
-pub fn derive_key<'py>(py: Python<'py>, password: &str, salt_phrase: Option<&str>) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
-    let mut salt = [0u8; 16];
-    if let Some(phrase) = salt_phrase {
-        if phrase.len() < 8 || !phrase.chars().all(|c| c.is_alphanumeric()) {
-            return Err(PyValueError::new_err("Salt must be 8+ alphanumeric chars"));
+/// Everything needed to re-derive a key from a password: Argon2id costs, the
+/// random per-key salt and an optional deterministic context.
+///
+/// The salt must be unique per key and is never derived from user input. The
+/// context is a fixed, application-chosen label (e.g. `b"qimem-keystore"`)
+/// that domain-separates keys derived from the same password and salt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KdfParams {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub salt: Vec<u8>,
+    pub context: Vec<u8>,
+}
+
+impl KdfParams {
+    /// Default costs with a fresh random salt.
+    pub fn generate() -> Self {
+        let mut salt = vec![0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        KdfParams {
+            m_cost: DEFAULT_M_COST,
+            t_cost: DEFAULT_T_COST,
+            p_cost: DEFAULT_P_COST,
+            salt,
+            context: Vec::new(),
+        }
+    }
+
+    /// Default costs with a caller-supplied raw salt.
+    pub fn with_salt(salt: &[u8]) -> Result<Self, KeyGenError> {
+        check_salt(salt)?;
+        Ok(KdfParams { salt: salt.to_vec(), ..Self::generate() })
+    }
+
+    pub fn with_context(mut self, context: &[u8]) -> Self {
+        self.context = context.to_vec();
+        self
+    }
+
+    pub fn derive_key(&self, password: &str) -> Result<[u8; KEY_LEN], KeyGenError> {
+        check_salt(&self.salt)?;
+        if self.context.len() > u16::MAX as usize {
+            return Err(KeyGenError::InvalidParams("Context too long".to_string()));
+        }
+        let mut builder = ParamsBuilder::new();
+        builder
+            .m_cost(self.m_cost)
+            .t_cost(self.t_cost)
+            .p_cost(self.p_cost)
+            .output_len(KEY_LEN);
+        if !self.context.is_empty() {
+            // Argon2 associated data is capped at 32 bytes, so bind a digest of the context.
+            let digest = Sha256::digest(&self.context);
+            let data = AssociatedData::new(&digest).map_err(|e| KeyGenError::HashError(e.to_string()))?;
+            builder.data(data);
+        }
+        let params = builder.build().map_err(|e| KeyGenError::InvalidParams(e.to_string()))?;
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        let mut key = [0u8; KEY_LEN];
+        argon2
+            .hash_password_into(password.as_bytes(), &self.salt, &mut key)
+            .map_err(|e| KeyGenError::HashError(e.to_string()))?;
+        Ok(key)
+    }
+
+    /// Serializes as `version | m_cost | t_cost | p_cost | salt_len | salt | context_len | context`
+    /// with big-endian integers (u32 costs, u8 salt length, u16 context length).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(16 + self.salt.len() + self.context.len());
+        out.push(PARAMS_VERSION);
+        out.extend_from_slice(&self.m_cost.to_be_bytes());
+        out.extend_from_slice(&self.t_cost.to_be_bytes());
+        out.extend_from_slice(&self.p_cost.to_be_bytes());
+        out.push(self.salt.len() as u8);
+        out.extend_from_slice(&self.salt);
+        out.extend_from_slice(&(self.context.len() as u16).to_be_bytes());
+        out.extend_from_slice(&self.context);
+        out
+    }
+
+    pub fn from_bytes(blob: &[u8]) -> Result<Self, KeyGenError> {
+        let truncated = || KeyGenError::InvalidParams("Truncated parameter blob".to_string());
+        let (&version, rest) = blob.split_first().ok_or_else(truncated)?;
+        if version != PARAMS_VERSION {
+            return Err(KeyGenError::InvalidParams(format!("Unsupported version {}", version)));
+        }
+        if rest.len() < 13 {
+            return Err(truncated());
+        }
+        let read_u32 = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+        let m_cost = read_u32(&rest[0..4]);
+        let t_cost = read_u32(&rest[4..8]);
+        let p_cost = read_u32(&rest[8..12]);
+        let salt_len = rest[12] as usize;
+        let rest = &rest[13..];
+        if rest.len() < salt_len + 2 {
+            return Err(truncated());
+        }
+        let salt = rest[..salt_len].to_vec();
+        let context_len = u16::from_be_bytes([rest[salt_len], rest[salt_len + 1]]) as usize;
+        let rest = &rest[salt_len + 2..];
+        if rest.len() != context_len {
+            return Err(KeyGenError::InvalidParams("Context length mismatch".to_string()));
+        }
+        if m_cost > MAX_M_COST {
+            return Err(KeyGenError::InvalidParams("Memory cost too large".to_string()));
+        }
+        if t_cost > MAX_T_COST {
+            return Err(KeyGenError::InvalidParams("Time cost too large".to_string()));
         }
-        let mut hasher = Sha256::new();
-        hasher.update(phrase.as_bytes());
-        salt.copy_from_slice(&hasher.finalize()[..16]);
+        if p_cost > MAX_P_COST {
+            return Err(KeyGenError::InvalidParams("Parallelism too large".to_string()));
+        }
+        check_salt(&salt)?;
+        Ok(KdfParams { m_cost, t_cost, p_cost, salt, context: rest.to_vec() })
+    }
+}
+
+fn check_salt(salt: &[u8]) -> Result<(), KeyGenError> {
+    if salt.len() < MIN_SALT_LEN || salt.len() > MAX_SALT_LEN {
+        return Err(KeyGenError::SaltInvalid(format!(
+            "Salt must be {}-{} bytes, got {}", MIN_SALT_LEN, MAX_SALT_LEN, salt.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Decodes a salt given as `hex:<hex>` or `base64:<base64>` (standard or
+/// URL-safe, padding optional). The prefix is required: a bare string such as
+/// an old salt phrase is refused rather than guessed at, since guessing could
+/// quietly derive a different key. Pass phrase salts as raw bytes instead.
+pub fn decode_salt(encoded: &str) -> Result<Vec<u8>, KeyGenError> {
+    let encoded = encoded.trim();
+    let invalid = |encoding| KeyGenError::SaltInvalid(format!("Salt is not valid {encoding}"));
+    let salt = if let Some(hex) = encoded.strip_prefix("hex:") {
+        from_hex(hex).ok_or_else(|| invalid("hex"))?
+    } else if let Some(b64) = encoded.strip_prefix("base64:") {
+        decode_base64(b64).ok_or_else(|| invalid("base64"))?
     } else {
-        rand::thread_rng().fill_bytes(&mut salt);
+        return Err(KeyGenError::SaltInvalid(
+            "String salts need a 'hex:' or 'base64:' prefix; pass a phrase salt as bytes".to_string(),
+        ));
+    };
+    check_salt(&salt)?;
+    Ok(salt)
+}
+
+fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=');
+    general_purpose::STANDARD_NO_PAD
+        .decode(s)
+        .or_else(|_| general_purpose::URL_SAFE_NO_PAD.decode(s))
+        .ok()
+}
+
+/// Derives a 32-byte key from `password` and returns `(key, salt)`.
+///
+/// With `salt = None` a fresh random salt is generated; otherwise the raw salt
+/// bytes are used as-is, so the same password and salt give the same key.
+pub fn derive_key(password: &str, salt: Option<&[u8]>) -> Result<(Vec<u8>, Vec<u8>), KeyGenError> {
+    let params = match salt {
+        Some(salt) => KdfParams::with_salt(salt)?,
+        None => KdfParams::generate(),
+    };
+    let key = params.derive_key(password)?;
+    Ok((key.to_vec(), params.salt))
+}
+
+/// Derives a key under a fresh random salt bound to `context` and returns
+/// `(key, params)`, where `params` is the serialized [`KdfParams`] blob to
+/// store next to whatever the key protects.
+pub fn derive_key_with_context(password: &str, context: &[u8]) -> Result<(Vec<u8>, Vec<u8>), KeyGenError> {
+    let params = KdfParams::generate().with_context(context);
+    let key = params.derive_key(password)?;
+    Ok((key.to_vec(), params.to_bytes()))
+}
+
+/// Re-derives the key described by a serialized [`KdfParams`] blob.
+pub fn derive_key_from_params(password: &str, params: &[u8]) -> Result<Vec<u8>, KeyGenError> {
+    let params = KdfParams::from_bytes(params)?;
+    Ok(params.derive_key(password)?.to_vec())
+}
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+const AMBIGUOUS: &str = "0O1lI|`'\"";
+
+/// Character classes for [`generate_password`]. Every enabled class is
+/// guaranteed to appear at least once in the result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    /// Drop look-alike characters such as `0`/`O` and `1`/`l`/`I`.
+    pub exclude_ambiguous: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            length: 20,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            exclude_ambiguous: false,
+        }
+    }
+}
+
+/// Generates a diceware-style passphrase of `word_count` words drawn uniformly
+/// from the embedded word list. Returns `(passphrase, entropy_bits)`.
+pub fn generate_passphrase(word_count: usize, separator: &str) -> Result<(String, f64), KeyGenError> {
+    if word_count == 0 {
+        return Err(KeyGenError::InvalidOptions("Word count must be at least 1".to_string()));
+    }
+    let words = wordlist::words();
+    let mut rng = rand::thread_rng();
+    let passphrase = (0..word_count)
+        .map(|_| words[rng.gen_range(0..words.len())])
+        .collect::<Vec<_>>()
+        .join(separator);
+    let entropy = word_count as f64 * (words.len() as f64).log2();
+    Ok((passphrase, entropy))
+}
+
+/// Generates a random password following `policy`. Returns `(password, entropy_bits)`,
+/// where the entropy accounts for the at-least-one-of-each-class requirement.
+pub fn generate_password(policy: &PasswordPolicy) -> Result<(String, f64), KeyGenError> {
+    let classes: Vec<Vec<char>> = [
+        (policy.lowercase, LOWERCASE),
+        (policy.uppercase, UPPERCASE),
+        (policy.digits, DIGITS),
+        (policy.symbols, SYMBOLS),
+    ]
+    .iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, chars)| {
+        chars
+            .chars()
+            .filter(|c| !(policy.exclude_ambiguous && AMBIGUOUS.contains(*c)))
+            .collect()
+    })
+    .collect();
+    if classes.is_empty() {
+        return Err(KeyGenError::InvalidOptions("At least one character class is required".to_string()));
+    }
+    if policy.length < classes.len() {
+        return Err(KeyGenError::InvalidOptions(format!(
+            "Length must be at least {} to include every character class", classes.len()
+        )));
+    }
+    let pool: Vec<char> = classes.iter().flatten().copied().collect();
+    let mut rng = rand::thread_rng();
+    // Rejection sampling keeps the result uniform over all passwords that satisfy the policy.
+    let password = loop {
+        let candidate: Vec<char> = (0..policy.length)
+            .map(|_| pool[rng.gen_range(0..pool.len())])
+            .collect();
+        if classes.iter().all(|class| candidate.iter().any(|c| class.contains(c))) {
+            break candidate.into_iter().collect::<String>();
+        }
+    };
+    Ok((password, password_entropy(&classes, policy.length)))
+}
+
+/// log2 of the number of length-`length` strings over the union of `classes`
+/// that contain every class, by inclusion-exclusion on the missing classes.
+fn password_entropy(classes: &[Vec<char>], length: usize) -> f64 {
+    let pool = classes.iter().map(Vec::len).sum::<usize>() as f64;
+    let mut valid_fraction = 0.0;
+    for missing in 0u32..(1 << classes.len()) {
+        let excluded: usize = (0..classes.len())
+            .filter(|i| missing & (1 << i) != 0)
+            .map(|i| classes[i].len())
+            .sum();
+        let term = ((pool - excluded as f64) / pool).powi(length as i32);
+        if missing.count_ones() % 2 == 0 {
+            valid_fraction += term;
+        } else {
+            valid_fraction -= term;
+        }
+    }
+    length as f64 * pool.log2() + valid_fraction.log2()
+}
+
+/// Generates `length` random bytes for use as a raw key or secret.
+/// Returns `(secret, entropy_bits)`.
+pub fn generate_secret(length: usize) -> Result<(Vec<u8>, f64), KeyGenError> {
+    if length == 0 {
+        return Err(KeyGenError::InvalidOptions("Secret length must be at least 1".to_string()));
+    }
+    let mut secret = vec![0u8; length];
+    rand::thread_rng().fill_bytes(&mut secret);
+    Ok((secret, (length * 8) as f64))
+}
+
+pub const MAX_STRENGTH_SCORE: u8 = 4;
+// Entropy needed for scores 1..=4; anything below the first is score 0.
+const SCORE_THRESHOLDS: [f64; 4] = [25.0, 40.0, 55.0, 70.0];
+const RECOMMENDED_LENGTH: usize = 12;
+// Like zxcvbn, only the first 100 characters are analysed; the pattern search
+// is superlinear and anything longer already scores on its prefix.
+const MAX_ANALYSED_LEN: usize = 100;
+const KEYBOARD_ROWS: [(&str, &str, f64); 4] = [
+    ("`1234567890-=", "~!@#$%^&*()_+", 0.0),
+    ("qwertyuiop[]\\", "QWERTYUIOP{}|", 1.5),
+    ("asdfghjkl;'", "ASDFGHJKL:\"", 1.75),
+    ("zxcvbnm,./", "ZXCVBNM<>?", 2.25),
+];
+
+/// Result of [`estimate_strength`]: a 0-4 score, the estimated entropy of the
+/// cheapest way to guess the password, and human-readable feedback.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasswordStrength {
+    pub score: u8,
+    pub entropy_bits: f64,
+    pub feedback: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pattern {
+    CommonPassword,
+    Dictionary,
+    Repeat,
+    Sequence,
+    KeyboardWalk,
+    Year,
+}
+
+#[derive(Clone, Debug)]
+struct PatternMatch {
+    start: usize,
+    end: usize,
+    bits: f64,
+    pattern: Pattern,
+}
+
+/// Estimates how hard `password` is to guess.
+///
+/// The password is split into the cheapest sequence of known patterns (common
+/// passwords, word-list words with capitalisation and l33t substitutions,
+/// repeats, alphabetic/numeric sequences, keyboard walks and years) and
+/// brute-forced characters; the summed cost in bits maps to the score. Only
+/// the first 100 characters are considered.
+pub fn estimate_strength(password: &str) -> PasswordStrength {
+    let chars: Vec<char> = password.chars().collect();
+    let (entropy_bits, used) = cheapest_cover(&chars[..chars.len().min(MAX_ANALYSED_LEN)], &mut HashMap::new());
+    let score = SCORE_THRESHOLDS.iter().filter(|&&t| entropy_bits >= t).count() as u8;
+
+    let mut feedback = Vec::new();
+    let weak = score < 3;
+    for pattern in [
+        Pattern::CommonPassword,
+        Pattern::Dictionary,
+        Pattern::KeyboardWalk,
+        Pattern::Repeat,
+        Pattern::Sequence,
+        Pattern::Year,
+    ] {
+        if weak && used.contains(&pattern) {
+            feedback.push(pattern_warning(pattern).to_string());
+        }
+    }
+    if chars.len() < RECOMMENDED_LENGTH {
+        feedback.push(format!("Use at least {} characters", RECOMMENDED_LENGTH));
+    }
+    if weak {
+        feedback.push("Add more uncommon words, or use a generated passphrase".to_string());
+    }
+    PasswordStrength { score, entropy_bits, feedback }
+}
+
+/// Fails with [`KeyGenError::WeakPassword`] when `password` scores below `min_score`.
+pub fn require_strength(password: &str, min_score: u8) -> Result<PasswordStrength, KeyGenError> {
+    let strength = estimate_strength(password);
+    if strength.score < min_score {
+        return Err(KeyGenError::WeakPassword {
+            score: strength.score,
+            feedback: strength.feedback.join("; "),
+        });
+    }
+    Ok(strength)
+}
+
+fn pattern_warning(pattern: Pattern) -> &'static str {
+    match pattern {
+        Pattern::CommonPassword => "This is a very common password",
+        Pattern::Dictionary => "Single dictionary words are easy to guess, even with capitals or l33t substitutions",
+        Pattern::KeyboardWalk => "Keyboard patterns like \"qwerty\" are easy to guess",
+        Pattern::Repeat => "Repeated characters or blocks like \"aaa\" or \"abcabc\" are easy to guess",
+        Pattern::Sequence => "Sequences like \"abc\" or \"6543\" are easy to guess",
+        Pattern::Year => "Years are easy to guess",
+    }
+}
+
+/// Minimum-cost segmentation of `chars` into patterns and brute-forced characters.
+/// Returns the total bits and the patterns used. `block_costs` memoises the
+/// cost of repeated blocks, which would otherwise be recomputed exponentially.
+fn cheapest_cover(chars: &[char], block_costs: &mut HashMap<Vec<char>, f64>) -> (f64, Vec<Pattern>) {
+    let n = chars.len();
+    let mut matches: Vec<PatternMatch> = Vec::new();
+    dictionary_matches(chars, &mut matches);
+    repeat_matches(chars, &mut matches, block_costs);
+    sequence_matches(chars, &mut matches);
+    keyboard_matches(chars, &mut matches);
+    year_matches(chars, &mut matches);
+
+    // best[i] is the cheapest cover of chars[..i]; via[i] is the match ending there, if any.
+    let mut best = vec![f64::INFINITY; n + 1];
+    let mut via: Vec<Option<usize>> = vec![None; n + 1];
+    best[0] = 0.0;
+    for i in 0..n {
+        let brute = best[i] + brute_force_bits(chars[i]);
+        if brute < best[i + 1] {
+            best[i + 1] = brute;
+            via[i + 1] = None;
+        }
+        for (idx, m) in matches.iter().enumerate().filter(|(_, m)| m.start == i) {
+            let cost = best[i] + m.bits;
+            if cost < best[m.end] {
+                best[m.end] = cost;
+                via[m.end] = Some(idx);
+            }
+        }
+    }
+
+    let mut used = Vec::new();
+    let mut pos = n;
+    while pos > 0 {
+        match via[pos] {
+            Some(idx) => {
+                used.push(matches[idx].pattern);
+                pos = matches[idx].start;
+            }
+            None => pos -= 1,
+        }
     }
-    let params = Params::new(32768, 4, 1, Some(32)).map_err(|e| KeyGenError::HashError(e.to_string()))?;
-    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
-    let mut key = [0u8; 32];
-    argon2
-        .hash_password_into(password.as_bytes(), &salt, &mut key)
-        .map_err(|e| KeyGenError::HashError(e.to_string()))?;
-    Ok((PyBytes::new_bound(py, &key), PyBytes::new_bound(py, &salt)))
-}
-
-// Simple version for non-Python use
-pub fn derive_key_simple(password: &str, salt_phrase: Option<&str>) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
-    let mut salt = [0u8; 16];
-    if let Some(phrase) = salt_phrase {
-        if phrase.len() < 8 || !phrase.chars().all(|c| c.is_alphanumeric()) {
-            return Err("Salt must be 8+ alphanumeric chars".into());
-        }
-        let mut hasher = Sha256::new();
-        hasher.update(phrase.as_bytes());
-        salt.copy_from_slice(&hasher.finalize()[..16]);
+    (best[n], used)
+}
+
+fn brute_force_bits(c: char) -> f64 {
+    let charset: f64 = if c.is_ascii_lowercase() || c.is_ascii_uppercase() {
+        26.0
+    } else if c.is_ascii_digit() {
+        10.0
+    } else if c.is_ascii() {
+        33.0
     } else {
-        rand::thread_rng().fill_bytes(&mut salt);
+        128.0
+    };
+    charset.log2()
+}
+
+fn unleet(c: char, one_as: char) -> char {
+    match c {
+        '4' | '@' => 'a',
+        '3' => 'e',
+        '1' | '!' => one_as,
+        '0' => 'o',
+        '5' | '$' => 's',
+        '7' | '+' => 't',
+        _ => c.to_ascii_lowercase(),
     }
-    
-    let params = Params::new(32768, 4, 1, Some(32))
-        .map_err(|e| format!("Params error: {}", e))?;
-    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
-    let mut key = [0u8; 32];
-    argon2.hash_password_into(password.as_bytes(), &salt, &mut key)
-        .map_err(|e| format!("Hash error: {}", e))?;
-    
-    Ok((key.to_vec(), salt.to_vec()))
-}
\ No newline at end of file
+}
+
+fn dictionary_matches(chars: &[char], out: &mut Vec<PatternMatch>) {
+    let common = wordlist::common_passwords();
+    let words = wordlist::words();
+    for start in 0..chars.len() {
+        for end in (start + 3)..=chars.len().min(start + 20) {
+            let token = &chars[start..end];
+            let upper = token.iter().filter(|c| c.is_uppercase()).count();
+            let case_bits = if upper == 0 {
+                0.0
+            } else if upper == token.len() || (upper == 1 && token[0].is_uppercase()) {
+                1.0
+            } else {
+                upper as f64
+            };
+            let plain: String = token.iter().map(|c| c.to_ascii_lowercase()).collect();
+            let mut best: Option<(f64, Pattern)> = None;
+            for one_as in ['i', 'l'] {
+                let normalized: String = token.iter().map(|&c| unleet(c, one_as)).collect();
+                let leet_bits = normalized.chars().zip(plain.chars()).filter(|(a, b)| a != b).count() as f64;
+                for (candidate, extra) in [(&plain, 0.0), (&normalized, leet_bits)] {
+                    let found = if let Some(rank) = common.iter().position(|p| p == candidate) {
+                        Some(((rank as f64 + 2.0).log2(), Pattern::CommonPassword))
+                    } else if candidate.len() >= 4 && words.binary_search(&candidate.as_str()).is_ok() {
+                        Some(((words.len() as f64).log2(), Pattern::Dictionary))
+                    } else {
+                        None
+                    };
+                    if let Some((bits, pattern)) = found {
+                        let bits = bits + case_bits + extra;
+                        if best.is_none_or(|(b, _)| bits < b) {
+                            best = Some((bits, pattern));
+                        }
+                    }
+                }
+            }
+            if let Some((bits, pattern)) = best {
+                out.push(PatternMatch { start, end, bits, pattern });
+            }
+        }
+    }
+}
+
+fn repeat_matches(chars: &[char], out: &mut Vec<PatternMatch>, block_costs: &mut HashMap<Vec<char>, f64>) {
+    let n = chars.len();
+    for start in 0..n {
+        for block in 1..=(n - start) / 2 {
+            let mut count = 1;
+            while start + (count + 1) * block <= n
+                && chars[start + count * block..start + (count + 1) * block] == chars[start..start + block]
+            {
+                count += 1;
+            }
+            if count < 2 || (block == 1 && count < 3) {
+                continue;
+            }
+            let block_chars = &chars[start..start + block];
+            let block_bits = match block_costs.get(block_chars) {
+                Some(&bits) => bits,
+                None => {
+                    let bits = cheapest_cover(block_chars, block_costs).0;
+                    block_costs.insert(block_chars.to_vec(), bits);
+                    bits
+                }
+            };
+            for reps in 2..=count {
+                if block == 1 && reps < 3 {
+                    continue;
+                }
+                out.push(PatternMatch {
+                    start,
+                    end: start + reps * block,
+                    bits: block_bits + (reps as f64).log2(),
+                    pattern: Pattern::Repeat,
+                });
+            }
+        }
+    }
+}
+
+fn sequence_matches(chars: &[char], out: &mut Vec<PatternMatch>) {
+    let class_size = |c: char| {
+        if c.is_ascii_lowercase() || c.is_ascii_uppercase() {
+            Some(26.0f64)
+        } else if c.is_ascii_digit() {
+            Some(10.0)
+        } else {
+            None
+        }
+    };
+    for start in 0..chars.len() {
+        let Some(size) = class_size(chars[start]) else { continue };
+        let mut direction = 0i32;
+        let mut end = start + 1;
+        while end < chars.len() {
+            let step = chars[end] as i32 - chars[end - 1] as i32;
+            let same_class = class_size(chars[end]) == Some(size)
+                && chars[end].is_ascii_uppercase() == chars[start].is_ascii_uppercase();
+            if !same_class || step.abs() != 1 || (direction != 0 && step != direction) {
+                break;
+            }
+            direction = step;
+            end += 1;
+            if end - start >= 3 {
+                let descending = if direction < 0 { 1.0 } else { 0.0 };
+                out.push(PatternMatch {
+                    start,
+                    end,
+                    bits: size.log2() + ((end - start) as f64).log2() + descending,
+                    pattern: Pattern::Sequence,
+                });
+            }
+        }
+    }
+}
+
+/// Physical (row, x, shifted) position of `c` on a US QWERTY keyboard.
+fn key_position(c: char) -> Option<(i32, f64, bool)> {
+    KEYBOARD_ROWS.iter().enumerate().find_map(|(row, (plain, shifted, offset))| {
+        plain
+            .chars()
+            .position(|k| k == c)
+            .map(|col| (row as i32, col as f64 + offset, false))
+            .or_else(|| shifted.chars().position(|k| k == c).map(|col| (row as i32, col as f64 + offset, true)))
+    })
+}
+
+fn keyboard_matches(chars: &[char], out: &mut Vec<PatternMatch>) {
+    let key_count: f64 = KEYBOARD_ROWS.iter().map(|(plain, _, _)| plain.len() as f64).sum();
+    let positions: Vec<Option<(i32, f64, bool)>> = chars.iter().map(|&c| key_position(c)).collect();
+    for start in 0..chars.len() {
+        let mut turns = 0;
+        let mut last_dir: Option<(i32, i32)> = None;
+        for end in (start + 1)..chars.len() {
+            let (Some(a), Some(b)) = (positions[end - 1], positions[end]) else { break };
+            let (dy, dx) = (b.0 - a.0, b.1 - a.1);
+            let adjacent = (dy == 0 && dx.abs() == 1.0) || (dy.abs() == 1 && dx.abs() <= 1.0);
+            if !adjacent {
+                break;
+            }
+            let dir = (dy, dx.signum() as i32);
+            if last_dir.is_some_and(|d| d != dir) {
+                turns += 1;
+            }
+            last_dir = Some(dir);
+            let len = end + 1 - start;
+            if len >= 4 {
+                let shifted = positions[start..=end].iter().flatten().filter(|p| p.2).count();
+                let shift_bits = if shifted == 0 || shifted == len { shifted.min(1) as f64 } else { shifted as f64 };
+                out.push(PatternMatch {
+                    start,
+                    end: end + 1,
+                    bits: key_count.log2() + (len as f64).log2() + turns as f64 * 3.0 + shift_bits,
+                    pattern: Pattern::KeyboardWalk,
+                });
+            }
+        }
+    }
+}
+
+fn year_matches(chars: &[char], out: &mut Vec<PatternMatch>) {
+    for start in 0..chars.len().saturating_sub(3) {
+        let token: String = chars[start..start + 4].iter().collect();
+        if let Ok(year) = token.parse::<u32>() {
+            if (1900..2100).contains(&year) {
+                out.push(PatternMatch { start, end: start + 4, bits: 200f64.log2(), pattern: Pattern::Year });
+            }
+        }
+    }
+}
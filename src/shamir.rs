@@ -0,0 +1,215 @@
+use rand::RngCore;
+use sha2::{Sha256, Digest};
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::utils::{from_hex, to_hex};
+
+const SHARE_VERSION: u8 = 1;
+const SHARE_PREFIX: &str = "qss1-";
+const CHECKSUM_LEN: usize = 4;
+// version | set id (2) | threshold | index
+const HEADER_LEN: usize = 5;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ShamirError {
+    #[error("Threshold must be at least 2 and no more than the number of shares (max 255)")]
+    InvalidThreshold,
+    #[error("Secret must not be empty")]
+    EmptySecret,
+    #[error("Need {needed} shares, got {got}")]
+    NotEnoughShares { needed: u8, got: usize },
+    #[error("Share {0} was given more than once")]
+    DuplicateShare(u8),
+    #[error("Shares come from different splits")]
+    MismatchedShares,
+    #[error("Shares do not describe the same secret")]
+    InconsistentShares,
+    #[error("Invalid share: {0}")]
+    InvalidShare(String),
+}
+
+impl From<ShamirError> for PyErr {
+    fn from(err: ShamirError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// One share of a Shamir split. `index` is the x coordinate (1-255) and
+/// `set_id` ties together shares produced by the same [`split_secret`] call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Share {
+    pub set_id: u16,
+    pub threshold: u8,
+    pub index: u8,
+    pub data: Vec<u8>,
+}
+
+impl Share {
+    /// `version | set_id (u16 BE) | threshold | index | data | checksum`, where the
+    /// checksum is the first 4 bytes of SHA-256 over everything before it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + self.data.len() + CHECKSUM_LEN);
+        out.push(SHARE_VERSION);
+        out.extend_from_slice(&self.set_id.to_be_bytes());
+        out.push(self.threshold);
+        out.push(self.index);
+        out.extend_from_slice(&self.data);
+        let checksum = Sha256::digest(&out);
+        out.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ShamirError> {
+        if bytes.len() <= HEADER_LEN + CHECKSUM_LEN {
+            return Err(ShamirError::InvalidShare("Share is too short".to_string()));
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        if Sha256::digest(body)[..CHECKSUM_LEN] != *checksum {
+            return Err(ShamirError::InvalidShare("Checksum mismatch (typo or corrupted share?)".to_string()));
+        }
+        if body[0] != SHARE_VERSION {
+            return Err(ShamirError::InvalidShare(format!("Unsupported version {}", body[0])));
+        }
+        let share = Share {
+            set_id: u16::from_be_bytes([body[1], body[2]]),
+            threshold: body[3],
+            index: body[4],
+            data: body[HEADER_LEN..].to_vec(),
+        };
+        if share.index == 0 || share.threshold < 2 {
+            return Err(ShamirError::InvalidShare("Bad index or threshold".to_string()));
+        }
+        Ok(share)
+    }
+
+    /// Text form for copying between people: `qss1-` followed by the hex of [`Share::to_bytes`].
+    pub fn encode(&self) -> String {
+        format!("{}{}", SHARE_PREFIX, to_hex(&self.to_bytes()))
+    }
+
+    /// Parses [`Share::encode`] output, ignoring surrounding whitespace and inner spaces or dashes
+    /// after the prefix so hand-grouped shares are accepted.
+    pub fn decode(encoded: &str) -> Result<Self, ShamirError> {
+        let body = encoded
+            .trim()
+            .strip_prefix(SHARE_PREFIX)
+            .ok_or_else(|| ShamirError::InvalidShare(format!("Missing {} prefix", SHARE_PREFIX)))?;
+        let hex: String = body.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+        let bytes = from_hex(&hex).ok_or_else(|| ShamirError::InvalidShare("Not valid hex".to_string()))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Splits `secret` into `share_count` shares, any `threshold` of which recover it.
+pub fn split_secret(secret: &[u8], threshold: u8, share_count: u8) -> Result<Vec<Share>, ShamirError> {
+    if threshold < 2 || threshold > share_count {
+        return Err(ShamirError::InvalidThreshold);
+    }
+    if secret.is_empty() {
+        return Err(ShamirError::EmptySecret);
+    }
+    let mut rng = rand::thread_rng();
+    let set_id = rng.next_u32() as u16;
+    // coefficients[i] holds the non-constant coefficients of the polynomial for secret[i].
+    let mut coefficients = vec![0u8; secret.len() * (threshold as usize - 1)];
+    rng.fill_bytes(&mut coefficients);
+    let per_byte = threshold as usize - 1;
+
+    let shares = (1..=share_count)
+        .map(|x| {
+            let data = secret
+                .iter()
+                .enumerate()
+                .map(|(i, &s)| {
+                    let coeffs = &coefficients[i * per_byte..(i + 1) * per_byte];
+                    // Horner's rule, highest degree first.
+                    let high = coeffs.iter().rev().fold(0u8, |acc, &c| gf_mul(acc, x) ^ c);
+                    gf_mul(high, x) ^ s
+                })
+                .collect();
+            Share { set_id, threshold, index: x, data }
+        })
+        .collect();
+    Ok(shares)
+}
+
+/// Recovers the secret from at least `threshold` shares of one split. Any shares
+/// beyond the threshold are checked against the recovered polynomial.
+pub fn combine_shares(shares: &[Share]) -> Result<Vec<u8>, ShamirError> {
+    let first = shares.first().ok_or(ShamirError::NotEnoughShares { needed: 2, got: 0 })?;
+    // `Share` fields are public, so hand-built shares get the same checks as parsed ones.
+    if first.threshold < 2 {
+        return Err(ShamirError::InvalidThreshold);
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if share.index == 0 {
+            return Err(ShamirError::InvalidShare("Index 0 would be the secret itself".to_string()));
+        }
+        if share.set_id != first.set_id || share.threshold != first.threshold || share.data.len() != first.data.len() {
+            return Err(ShamirError::MismatchedShares);
+        }
+        if shares[..i].iter().any(|s| s.index == share.index) {
+            return Err(ShamirError::DuplicateShare(share.index));
+        }
+    }
+    let threshold = first.threshold as usize;
+    if shares.len() < threshold {
+        return Err(ShamirError::NotEnoughShares { needed: first.threshold, got: shares.len() });
+    }
+    let (used, extra) = shares.split_at(threshold);
+    for share in extra {
+        if interpolate(used, share.index) != share.data {
+            return Err(ShamirError::InconsistentShares);
+        }
+    }
+    Ok(interpolate(used, 0))
+}
+
+/// Evaluates at `x` the polynomials (one per byte) passing through `shares`.
+fn interpolate(shares: &[Share], x: u8) -> Vec<u8> {
+    let mut result = vec![0u8; shares[0].data.len()];
+    for (j, share_j) in shares.iter().enumerate() {
+        // Lagrange basis polynomial l_j(x) = prod_{m != j} (x - x_m) / (x_j - x_m); subtraction is XOR.
+        let mut numerator = 1u8;
+        let mut denominator = 1u8;
+        for (m, share_m) in shares.iter().enumerate() {
+            if m != j {
+                numerator = gf_mul(numerator, x ^ share_m.index);
+                denominator = gf_mul(denominator, share_j.index ^ share_m.index);
+            }
+        }
+        let basis = gf_mul(numerator, gf_inv(denominator));
+        for (out, &y) in result.iter_mut().zip(&share_j.data) {
+            *out ^= gf_mul(basis, y);
+        }
+    }
+    result
+}
+
+/// Multiplication in GF(2^8) modulo the AES polynomial x^8 + x^4 + x^3 + x + 1,
+/// without data-dependent branches.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// Multiplicative inverse as a^254 (a^255 = 1 for non-zero a).
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exp = 254u8;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
@@ -1,38 +1,692 @@
-use pyo3::prelude::*;
-use pyo3::types::PyBytes;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use blake2::{Blake2b512, Digest};
+use chrono::Utc;
 use ed25519_dalek::{Signer, Verifier, Signature, SigningKey, VerifyingKey};
 use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha256;
+use crate::fingerprint::Fingerprint;
+use crate::ml_dsa::{self, MlDsaError};
+use crate::utils::take;
+use pyo3::PyErr;
 use pyo3::exceptions::PyValueError;
 
-#[pyfunction]
-pub fn generate_keypair<'py>(py: Python<'py>) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+#[derive(thiserror::Error, Debug)]
+pub enum SigningError {
+    #[error("Secret key must be 32 bytes")]
+    InvalidSecretKey,
+    #[error("Public key must be 32 bytes")]
+    InvalidPublicKeyLength,
+    #[error("Invalid public key")]
+    InvalidPublicKey,
+    #[error("Signature must be 64 bytes")]
+    InvalidSignature,
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid signature file: {0}")]
+    InvalidSignatureFile(String),
+    #[error("Trusted comment must be a single line")]
+    InvalidTrustedComment,
+    #[error("Signature was made by a different key")]
+    KeyIdMismatch,
+    #[error("Signature verification failed")]
+    VerificationFailed,
+    #[error("Unknown signature algorithm {0:?}")]
+    UnknownAlgorithm(String),
+    #[error("{algorithm} {what} must be {expected} bytes")]
+    InvalidLength { algorithm: &'static str, what: &'static str, expected: usize },
+    #[error("ML-DSA error: {0}")]
+    MlDsa(#[from] MlDsaError),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CertificateError {
+    #[error("Malformed certificate")]
+    Malformed,
+    #[error("Malformed revocation list")]
+    MalformedRevocationList,
+    #[error("Subject name must be 1 to 255 bytes")]
+    InvalidSubject,
+    #[error("Certificate is not valid until after its expiry")]
+    InvalidValidity,
+    #[error("Unknown key usage: {0}")]
+    UnknownKeyUsage(String),
+    #[error("Certificate chain is empty")]
+    EmptyChain,
+    #[error("Certificate for {0} is not yet valid")]
+    NotYetValid(String),
+    #[error("Certificate for {0} has expired")]
+    Expired(String),
+    #[error("Certificate for {0} was not issued by the next certificate in the chain")]
+    IssuerMismatch(String),
+    #[error("Chain does not lead to a trusted root")]
+    UntrustedRoot,
+    #[error("Certificate for {0} may not issue certificates")]
+    NotACertificateAuthority(String),
+    #[error("Certificate for {0} may not sign revocation lists")]
+    NotARevocationAuthority(String),
+    #[error("Invalid signature on certificate for {0}")]
+    InvalidSignature(String),
+    #[error("Invalid signature on revocation list")]
+    InvalidRevocationListSignature,
+    #[error("Certificate for {0} has been revoked")]
+    Revoked(String),
+    #[error("Signing error: {0}")]
+    Signing(#[from] SigningError),
+}
+
+impl From<CertificateError> for PyErr {
+    fn from(err: CertificateError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// One entry for [`verify_batch`].
+#[derive(Clone, Copy, Debug)]
+pub struct BatchItem<'a> {
+    pub public_key: &'a [u8],
+    pub message: &'a [u8],
+    pub signature: &'a [u8],
+}
+
+// minisign signature algorithm ids: "Ed" signs the file itself, "ED" signs its BLAKE2b-512 hash.
+const MINISIGN_LEGACY: [u8; 2] = *b"Ed";
+const MINISIGN_PREHASHED: [u8; 2] = *b"ED";
+const TRUSTED_COMMENT_PREFIX: &str = "trusted comment: ";
+const UNTRUSTED_COMMENT_PREFIX: &str = "untrusted comment: ";
+
+/// Both halves of a composite signature sign under this context, so neither
+/// verifies as a plain Ed25519 or ML-DSA signature of the same message.
+const COMPOSITE_CONTEXT: &[u8] = b"qimem-ed25519-ml-dsa-65-v1";
+const ED25519_SIGNATURE_LEN: usize = 64;
+
+const CERTIFICATE_VERSION: u8 = 1;
+const CERTIFICATE_CONTEXT: &[u8] = b"qimem-certificate-v1";
+const REVOCATION_LIST_CONTEXT: &[u8] = b"qimem-revocation-list-v1";
+pub const SERIAL_LEN: usize = 16;
+
+impl From<SigningError> for PyErr {
+    fn from(err: SigningError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// Returns `(public_key, secret_key)`.
+pub fn generate_keypair() -> Result<([u8; 32], [u8; 32]), SigningError> {
     let mut csprng = OsRng;
     let signing_key = SigningKey::generate(&mut csprng);
     let verifying_key = signing_key.verifying_key();
-    Ok((
-        PyBytes::new_bound(py, verifying_key.as_bytes()),
-        PyBytes::new_bound(py, signing_key.as_bytes()),
-    ))
+    Ok((verifying_key.to_bytes(), signing_key.to_bytes()))
 }
 
-#[pyfunction]
-pub fn sign_message<'py>(py: Python<'py>, secret_key: &[u8], message: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+pub fn sign_message(secret_key: &[u8], message: &[u8]) -> Result<Vec<u8>, SigningError> {
     let secret_key_array: [u8; 32] = secret_key.try_into()
-        .map_err(|_| PyValueError::new_err("Secret key must be 32 bytes"))?;
+        .map_err(|_| SigningError::InvalidSecretKey)?;
     let signing_key = SigningKey::from_bytes(&secret_key_array);
     let signature = signing_key.sign(message);
-    Ok(PyBytes::new_bound(py, signature.to_bytes().as_ref()))
+    Ok(signature.to_bytes().to_vec())
 }
 
-#[pyfunction]
-pub fn verify_signature<'py>(_py: Python<'py>, public_key: &[u8], message: &[u8], signature: &[u8]) -> PyResult<bool> {
+pub fn verify_signature(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<bool, SigningError> {
     let public_key_array: [u8; 32] = public_key.try_into()
-        .map_err(|_| PyValueError::new_err("Public key must be 32 bytes"))?;
+        .map_err(|_| SigningError::InvalidPublicKeyLength)?;
     let signature_array: [u8; 64] = signature.try_into()
-        .map_err(|_| PyValueError::new_err("Signature must be 64 bytes"))?;
+        .map_err(|_| SigningError::InvalidSignature)?;
     let verifying_key = VerifyingKey::from_bytes(&public_key_array)
-        .map_err(|_| PyValueError::new_err("Invalid public key"))?;
-    let signature = Signature::try_from(signature_array)
-        .map_err(|_| PyValueError::new_err("Invalid signature"))?;
+        .map_err(|_| SigningError::InvalidPublicKey)?;
+    let signature = Signature::from_bytes(&signature_array);
     Ok(verifying_key.verify(message, &signature).is_ok())
-}
\ No newline at end of file
+}
+
+/// A signature scheme for [`generate_keypair_with`], [`sign_message_with`] and
+/// [`verify_signature_with`].
+///
+/// ML-DSA-65 secret keys are the 32-byte FIPS 204 seed; public keys and
+/// signatures are the standard encodings. Composite keys and signatures are
+/// the Ed25519 part followed by the ML-DSA-65 part, and verify only if both do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureAlgorithm {
+    Ed25519,
+    MlDsa65,
+    Ed25519MlDsa65,
+}
+
+impl SignatureAlgorithm {
+    pub const ALL: [SignatureAlgorithm; 3] =
+        [SignatureAlgorithm::Ed25519, SignatureAlgorithm::MlDsa65, SignatureAlgorithm::Ed25519MlDsa65];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SignatureAlgorithm::Ed25519 => "ed25519",
+            SignatureAlgorithm::MlDsa65 => "ml-dsa-65",
+            SignatureAlgorithm::Ed25519MlDsa65 => "ed25519+ml-dsa-65",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<SignatureAlgorithm, SigningError> {
+        SignatureAlgorithm::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name() == name)
+            .ok_or_else(|| SigningError::UnknownAlgorithm(name.to_string()))
+    }
+
+    fn check_length(self, what: &'static str, bytes: &[u8], expected: usize) -> Result<(), SigningError> {
+        if bytes.len() != expected {
+            return Err(SigningError::InvalidLength { algorithm: self.name(), what, expected });
+        }
+        Ok(())
+    }
+}
+
+/// Returns `(public_key, secret_key)` for `algorithm`.
+pub fn generate_keypair_with(algorithm: SignatureAlgorithm) -> Result<(Vec<u8>, Vec<u8>), SigningError> {
+    Ok(match algorithm {
+        SignatureAlgorithm::Ed25519 => {
+            let (public_key, secret_key) = generate_keypair()?;
+            (public_key.to_vec(), secret_key.to_vec())
+        }
+        SignatureAlgorithm::MlDsa65 => {
+            let (public_key, seed) = ml_dsa::generate_keypair();
+            (public_key, seed.to_vec())
+        }
+        SignatureAlgorithm::Ed25519MlDsa65 => {
+            let (ed_public, ed_secret) = generate_keypair()?;
+            let (ml_public, ml_seed) = ml_dsa::generate_keypair();
+            ([ed_public.as_slice(), &ml_public].concat(), [ed_secret, ml_seed].concat())
+        }
+    })
+}
+
+pub fn sign_message_with(algorithm: SignatureAlgorithm, secret_key: &[u8], message: &[u8]) -> Result<Vec<u8>, SigningError> {
+    match algorithm {
+        SignatureAlgorithm::Ed25519 => sign_message(secret_key, message),
+        SignatureAlgorithm::MlDsa65 => {
+            algorithm.check_length("secret key", secret_key, ml_dsa::SEED_LEN)?;
+            let (_, expanded) = ml_dsa::keypair_from_seed(secret_key.try_into().unwrap());
+            Ok(ml_dsa::sign(&expanded, message, b"")?)
+        }
+        SignatureAlgorithm::Ed25519MlDsa65 => {
+            algorithm.check_length("secret key", secret_key, 32 + ml_dsa::SEED_LEN)?;
+            let (ed_secret, ml_seed) = secret_key.split_at(32);
+            let (_, expanded) = ml_dsa::keypair_from_seed(ml_seed.try_into().unwrap());
+            let mut signature = sign_message(ed_secret, &[COMPOSITE_CONTEXT, message].concat())?;
+            signature.extend_from_slice(&ml_dsa::sign(&expanded, message, COMPOSITE_CONTEXT)?);
+            Ok(signature)
+        }
+    }
+}
+
+pub fn verify_signature_with(algorithm: SignatureAlgorithm, public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<bool, SigningError> {
+    match algorithm {
+        SignatureAlgorithm::Ed25519 => verify_signature(public_key, message, signature),
+        SignatureAlgorithm::MlDsa65 => Ok(ml_dsa::verify(public_key, message, b"", signature)?),
+        SignatureAlgorithm::Ed25519MlDsa65 => {
+            algorithm.check_length("public key", public_key, 32 + ml_dsa::PUBLIC_KEY_LEN)?;
+            algorithm.check_length("signature", signature, ED25519_SIGNATURE_LEN + ml_dsa::SIGNATURE_LEN)?;
+            let (ed_public, ml_public) = public_key.split_at(32);
+            let (ed_signature, ml_signature) = signature.split_at(ED25519_SIGNATURE_LEN);
+            let ed_valid = verify_signature(ed_public, &[COMPOSITE_CONTEXT, message].concat(), ed_signature)?;
+            let ml_valid = ml_dsa::verify(ml_public, message, COMPOSITE_CONTEXT, ml_signature)?;
+            Ok(ed_valid && ml_valid)
+        }
+    }
+}
+
+/// Verifies many signatures at once, returning one result per item in order.
+/// Well-formed items are checked together with ed25519-dalek's batch
+/// verification; only if the batch fails is each item re-checked on its own to
+/// find the bad ones. Malformed keys or signatures simply report `false`.
+pub fn verify_batch(items: &[BatchItem]) -> Vec<bool> {
+    let mut results = vec![false; items.len()];
+    let mut indices = Vec::with_capacity(items.len());
+    let mut messages = Vec::with_capacity(items.len());
+    let mut signatures = Vec::with_capacity(items.len());
+    let mut keys = Vec::with_capacity(items.len());
+    for (i, item) in items.iter().enumerate() {
+        let key = <[u8; 32]>::try_from(item.public_key).ok().and_then(|k| VerifyingKey::from_bytes(&k).ok());
+        let signature = <[u8; 64]>::try_from(item.signature).ok().map(|s| Signature::from_bytes(&s));
+        if let (Some(key), Some(signature)) = (key, signature) {
+            indices.push(i);
+            messages.push(item.message);
+            signatures.push(signature);
+            keys.push(key);
+        }
+    }
+    if ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_ok() {
+        for i in indices {
+            results[i] = true;
+        }
+    } else {
+        for (n, i) in indices.into_iter().enumerate() {
+            results[i] = keys[n].verify(messages[n], &signatures[n]).is_ok();
+        }
+    }
+    results
+}
+
+/// The 8-byte minisign key id for `public_key`: the first 8 bytes of its SHA-256.
+pub fn minisign_key_id(public_key: &[u8; 32]) -> [u8; 8] {
+    Sha256::digest(public_key)[..8].try_into().unwrap()
+}
+
+/// The public key in minisign's `minisign.pub` format, for distributing to minisign verifiers.
+pub fn minisign_public_key(public_key: &[u8]) -> Result<String, SigningError> {
+    let public_key: [u8; 32] = public_key.try_into().map_err(|_| SigningError::InvalidPublicKeyLength)?;
+    let key_id = minisign_key_id(&public_key);
+    let mut blob = MINISIGN_LEGACY.to_vec();
+    blob.extend_from_slice(&key_id);
+    blob.extend_from_slice(&public_key);
+    Ok(format!(
+        "{}minisign public key {:016X}\n{}\n",
+        UNTRUSTED_COMMENT_PREFIX,
+        u64::from_le_bytes(key_id),
+        BASE64_STANDARD.encode(blob)
+    ))
+}
+
+/// Signs the file at `path` and writes a minisign-compatible detached signature
+/// to `<path>.minisig`, returning its contents. In prehashed mode (minisign's
+/// default) the file is streamed through BLAKE2b-512 and the hash is signed;
+/// otherwise the whole file is read and signed directly. The trusted comment
+/// defaults to the timestamp and file name, as minisign writes it.
+pub fn sign_file(
+    path: &str,
+    secret_key: &[u8],
+    trusted_comment: Option<&str>,
+    prehashed: bool,
+) -> Result<String, SigningError> {
+    let secret_key: [u8; 32] = secret_key.try_into().map_err(|_| SigningError::InvalidSecretKey)?;
+    let signing_key = SigningKey::from_bytes(&secret_key);
+    let trusted_comment = match trusted_comment {
+        Some(comment) => comment.to_string(),
+        None => default_trusted_comment(path, prehashed),
+    };
+    if trusted_comment.contains(['\r', '\n']) {
+        return Err(SigningError::InvalidTrustedComment);
+    }
+
+    let (algorithm, signed) = if prehashed {
+        (MINISIGN_PREHASHED, hash_file(path)?)
+    } else {
+        (MINISIGN_LEGACY, fs::read(path)?)
+    };
+    let signature = signing_key.sign(&signed).to_bytes();
+    let mut global = signature.to_vec();
+    global.extend_from_slice(trusted_comment.as_bytes());
+    let global_signature = signing_key.sign(&global).to_bytes();
+
+    let key_id = minisign_key_id(&signing_key.verifying_key().to_bytes());
+    let mut blob = algorithm.to_vec();
+    blob.extend_from_slice(&key_id);
+    blob.extend_from_slice(&signature);
+    let contents = format!(
+        "{}signature from qimem secret key\n{}\n{}{}\n{}\n",
+        UNTRUSTED_COMMENT_PREFIX,
+        BASE64_STANDARD.encode(blob),
+        TRUSTED_COMMENT_PREFIX,
+        trusted_comment,
+        BASE64_STANDARD.encode(global_signature)
+    );
+    fs::write(format!("{}.minisig", path), &contents)?;
+    Ok(contents)
+}
+
+/// Verifies `path` against its minisign signature (`<path>.minisig` unless
+/// `signature_path` is given) and returns the trusted comment. Both the file
+/// signature and the signature over the trusted comment must be valid.
+pub fn verify_file(path: &str, public_key: &[u8], signature_path: Option<&str>) -> Result<String, SigningError> {
+    let public_key: [u8; 32] = public_key.try_into().map_err(|_| SigningError::InvalidPublicKeyLength)?;
+    let verifying_key = VerifyingKey::from_bytes(&public_key).map_err(|_| SigningError::InvalidPublicKey)?;
+    let signature_path = signature_path.map_or_else(|| format!("{}.minisig", path), str::to_string);
+    let contents = fs::read_to_string(signature_path)?;
+
+    let invalid = |reason: &str| SigningError::InvalidSignatureFile(reason.to_string());
+    let mut lines = contents.lines();
+    lines.next().filter(|l| l.starts_with(UNTRUSTED_COMMENT_PREFIX)).ok_or_else(|| invalid("missing untrusted comment"))?;
+    let blob = lines.next().and_then(|l| BASE64_STANDARD.decode(l.trim()).ok()).ok_or_else(|| invalid("bad signature line"))?;
+    let trusted_comment = lines
+        .next()
+        .and_then(|l| l.strip_prefix(TRUSTED_COMMENT_PREFIX))
+        .ok_or_else(|| invalid("missing trusted comment"))?;
+    let global = lines.next().and_then(|l| BASE64_STANDARD.decode(l.trim()).ok()).ok_or_else(|| invalid("bad global signature line"))?;
+    if blob.len() != 74 || global.len() != 64 {
+        return Err(invalid("wrong signature length"));
+    }
+
+    if blob[2..10] != minisign_key_id(&public_key) {
+        return Err(SigningError::KeyIdMismatch);
+    }
+    let signed = match [blob[0], blob[1]] {
+        MINISIGN_PREHASHED => hash_file(path)?,
+        MINISIGN_LEGACY => fs::read(path)?,
+        _ => return Err(invalid("unsupported algorithm")),
+    };
+    let signature = Signature::from_bytes(&blob[10..74].try_into().unwrap());
+    verifying_key.verify(&signed, &signature).map_err(|_| SigningError::VerificationFailed)?;
+    let mut signed_comment = blob[10..74].to_vec();
+    signed_comment.extend_from_slice(trusted_comment.as_bytes());
+    let global = Signature::from_bytes(&global.try_into().unwrap());
+    verifying_key.verify(&signed_comment, &global).map_err(|_| SigningError::VerificationFailed)?;
+    Ok(trusted_comment.to_string())
+}
+
+fn hash_file(path: &str) -> Result<Vec<u8>, SigningError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Blake2b512::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+fn default_trusted_comment(path: &str, prehashed: bool) -> String {
+    let file_name = std::path::Path::new(path).file_name().map_or_else(|| path.into(), |n| n.to_string_lossy());
+    let mut comment = format!("timestamp:{}\tfile:{}", Utc::now().timestamp(), file_name);
+    if prehashed {
+        comment.push_str("\thashed");
+    }
+    comment
+}
+
+/// What a certified key may be used for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyUsage {
+    DigitalSignature,
+    KeyAgreement,
+    /// May issue certificates, i.e. act as an intermediate authority.
+    CertSign,
+    /// May sign revocation lists for the certificates it issued.
+    CrlSign,
+}
+
+impl KeyUsage {
+    pub const ALL: [KeyUsage; 4] = [KeyUsage::DigitalSignature, KeyUsage::KeyAgreement, KeyUsage::CertSign, KeyUsage::CrlSign];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyUsage::DigitalSignature => "digital_signature",
+            KeyUsage::KeyAgreement => "key_agreement",
+            KeyUsage::CertSign => "cert_sign",
+            KeyUsage::CrlSign => "crl_sign",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<KeyUsage, CertificateError> {
+        KeyUsage::ALL
+            .into_iter()
+            .find(|usage| usage.name() == name)
+            .ok_or_else(|| CertificateError::UnknownKeyUsage(name.to_string()))
+    }
+
+    fn bit(self) -> u8 {
+        1 << KeyUsage::ALL.iter().position(|usage| *usage == self).unwrap()
+    }
+}
+
+/// A subject's Ed25519 public key, vouched for by an issuer's signature.
+///
+/// Encoded as: version | serial | subject length u8 | subject | public key |
+/// not_before i64 | not_after i64 | usage bits u8 | issuer fingerprint | signature.
+/// The signature covers everything before it, prefixed with a context string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Certificate {
+    pub serial: [u8; SERIAL_LEN],
+    pub subject: String,
+    pub public_key: [u8; 32],
+    /// Validity window in Unix seconds, both ends inclusive.
+    pub not_before: i64,
+    pub not_after: i64,
+    pub usages: Vec<KeyUsage>,
+    /// [`Fingerprint::of`] the issuer's public key.
+    pub issuer: Fingerprint,
+    pub signature: [u8; 64],
+}
+
+impl Certificate {
+    pub fn allows(&self, usage: KeyUsage) -> bool {
+        self.usages.contains(&usage)
+    }
+
+    pub fn is_self_signed(&self) -> bool {
+        self.issuer == Fingerprint::of(&self.public_key)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.signed_bytes();
+        out.extend_from_slice(&self.signature);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Certificate, CertificateError> {
+        let malformed = || CertificateError::Malformed;
+        let mut rest = bytes;
+        if take(&mut rest, 1).ok_or_else(malformed)? != [CERTIFICATE_VERSION] {
+            return Err(malformed());
+        }
+        let serial = take(&mut rest, SERIAL_LEN).ok_or_else(malformed)?.try_into().unwrap();
+        let subject_len = take(&mut rest, 1).ok_or_else(malformed)?[0] as usize;
+        let subject = take(&mut rest, subject_len).ok_or_else(malformed)?;
+        let subject = String::from_utf8(subject.to_vec()).map_err(|_| malformed())?;
+        let public_key = take(&mut rest, 32).ok_or_else(malformed)?.try_into().unwrap();
+        let not_before = i64::from_be_bytes(take(&mut rest, 8).ok_or_else(malformed)?.try_into().unwrap());
+        let not_after = i64::from_be_bytes(take(&mut rest, 8).ok_or_else(malformed)?.try_into().unwrap());
+        let bits = take(&mut rest, 1).ok_or_else(malformed)?[0];
+        let usages: Vec<KeyUsage> = KeyUsage::ALL.into_iter().filter(|usage| bits & usage.bit() != 0).collect();
+        if usages.iter().map(|usage| usage.bit()).sum::<u8>() != bits {
+            return Err(malformed());
+        }
+        let issuer = Fingerprint(take(&mut rest, 32).ok_or_else(malformed)?.try_into().unwrap());
+        let signature = rest.try_into().map_err(|_| malformed())?;
+        Ok(Certificate { serial, subject, public_key, not_before, not_after, usages, issuer, signature })
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut out = vec![CERTIFICATE_VERSION];
+        out.extend_from_slice(&self.serial);
+        out.push(self.subject.len() as u8);
+        out.extend_from_slice(self.subject.as_bytes());
+        out.extend_from_slice(&self.public_key);
+        out.extend_from_slice(&self.not_before.to_be_bytes());
+        out.extend_from_slice(&self.not_after.to_be_bytes());
+        out.push(self.usages.iter().fold(0, |bits, usage| bits | usage.bit()));
+        out.extend_from_slice(&self.issuer.0);
+        out
+    }
+
+    fn verify_issued_by(&self, issuer_public_key: &[u8; 32]) -> Result<bool, SigningError> {
+        let signed = [CERTIFICATE_CONTEXT, &self.signed_bytes()].concat();
+        verify_signature(issuer_public_key, &signed, &self.signature)
+    }
+}
+
+/// Serials of certificates an issuer has revoked, signed by that issuer.
+///
+/// Encoded as: issuer fingerprint | issued_at i64 | count u32 | serials | signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RevocationList {
+    pub issuer: Fingerprint,
+    pub issued_at: i64,
+    pub revoked: Vec<[u8; SERIAL_LEN]>,
+    pub signature: [u8; 64],
+}
+
+impl RevocationList {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.signed_bytes();
+        out.extend_from_slice(&self.signature);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<RevocationList, CertificateError> {
+        let malformed = || CertificateError::MalformedRevocationList;
+        let mut rest = bytes;
+        let issuer = Fingerprint(take(&mut rest, 32).ok_or_else(malformed)?.try_into().unwrap());
+        let issued_at = i64::from_be_bytes(take(&mut rest, 8).ok_or_else(malformed)?.try_into().unwrap());
+        let count = u32::from_be_bytes(take(&mut rest, 4).ok_or_else(malformed)?.try_into().unwrap()) as usize;
+        if rest.len() != count.checked_mul(SERIAL_LEN).and_then(|n| n.checked_add(64)).ok_or_else(malformed)? {
+            return Err(malformed());
+        }
+        let revoked = (0..count).map(|_| take(&mut rest, SERIAL_LEN).unwrap().try_into().unwrap()).collect();
+        Ok(RevocationList { issuer, issued_at, revoked, signature: rest.try_into().unwrap() })
+    }
+
+    pub fn is_revoked(&self, certificate: &Certificate) -> bool {
+        self.issuer == certificate.issuer && self.revoked.contains(&certificate.serial)
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut out = self.issuer.0.to_vec();
+        out.extend_from_slice(&self.issued_at.to_be_bytes());
+        out.extend_from_slice(&(self.revoked.len() as u32).to_be_bytes());
+        for serial in &self.revoked {
+            out.extend_from_slice(serial);
+        }
+        out
+    }
+
+    fn verify_issued_by(&self, issuer_public_key: &[u8; 32]) -> Result<bool, SigningError> {
+        let signed = [REVOCATION_LIST_CONTEXT, &self.signed_bytes()].concat();
+        verify_signature(issuer_public_key, &signed, &self.signature)
+    }
+}
+
+/// Issues a certificate for `subject_public_key` signed by `issuer_secret_key`,
+/// with a fresh random serial. Passing the subject's own secret key makes a
+/// self-signed root.
+pub fn issue_certificate(
+    issuer_secret_key: &[u8],
+    subject: &str,
+    subject_public_key: &[u8],
+    not_before: i64,
+    not_after: i64,
+    usages: &[KeyUsage],
+) -> Result<Certificate, CertificateError> {
+    let issuer_secret_key: [u8; 32] = issuer_secret_key.try_into().map_err(|_| SigningError::InvalidSecretKey)?;
+    let public_key: [u8; 32] = subject_public_key.try_into().map_err(|_| SigningError::InvalidPublicKeyLength)?;
+    VerifyingKey::from_bytes(&public_key).map_err(|_| SigningError::InvalidPublicKey)?;
+    if subject.is_empty() || subject.len() > u8::MAX as usize {
+        return Err(CertificateError::InvalidSubject);
+    }
+    if not_after < not_before {
+        return Err(CertificateError::InvalidValidity);
+    }
+    let signing_key = SigningKey::from_bytes(&issuer_secret_key);
+    let mut serial = [0u8; SERIAL_LEN];
+    OsRng.fill_bytes(&mut serial);
+    let mut certificate = Certificate {
+        serial,
+        subject: subject.to_string(),
+        public_key,
+        not_before,
+        not_after,
+        usages: KeyUsage::ALL.into_iter().filter(|usage| usages.contains(usage)).collect(),
+        issuer: Fingerprint::of(&signing_key.verifying_key().to_bytes()),
+        signature: [0; 64],
+    };
+    let signed = [CERTIFICATE_CONTEXT, &certificate.signed_bytes()].concat();
+    certificate.signature = signing_key.sign(&signed).to_bytes();
+    Ok(certificate)
+}
+
+/// Signs a revocation list of certificate serials with the issuer's key.
+pub fn issue_revocation_list(
+    issuer_secret_key: &[u8],
+    revoked: &[[u8; SERIAL_LEN]],
+    issued_at: i64,
+) -> Result<RevocationList, CertificateError> {
+    let issuer_secret_key: [u8; 32] = issuer_secret_key.try_into().map_err(|_| SigningError::InvalidSecretKey)?;
+    let signing_key = SigningKey::from_bytes(&issuer_secret_key);
+    let mut list = RevocationList {
+        issuer: Fingerprint::of(&signing_key.verifying_key().to_bytes()),
+        issued_at,
+        revoked: revoked.to_vec(),
+        signature: [0; 64],
+    };
+    let signed = [REVOCATION_LIST_CONTEXT, &list.signed_bytes()].concat();
+    list.signature = signing_key.sign(&signed).to_bytes();
+    Ok(list)
+}
+
+/// Validates `chain` (leaf first, each certificate followed by its issuer)
+/// against the trusted root public keys and returns the leaf.
+pub fn verify_chain<'a>(
+    chain: &'a [Certificate],
+    trusted_roots: &[[u8; 32]],
+    revocation_lists: &[RevocationList],
+) -> Result<&'a Certificate, CertificateError> {
+    verify_chain_at(chain, trusted_roots, revocation_lists, Utc::now().timestamp())
+}
+
+/// Like [`verify_chain`] with an explicit current time in seconds.
+///
+/// The chain may stop at a certificate issued by a root or include the root's
+/// own self-signed certificate. Every certificate must be within its validity
+/// window, every issuer but a root must hold [`KeyUsage::CertSign`], and none
+/// may appear in a revocation list from its issuer. A revocation list from an
+/// issuer in the chain must carry a valid signature, and if that issuer is an
+/// intermediate it must also hold [`KeyUsage::CrlSign`]. Lists from other
+/// issuers are ignored.
+pub fn verify_chain_at<'a>(
+    chain: &'a [Certificate],
+    trusted_roots: &[[u8; 32]],
+    revocation_lists: &[RevocationList],
+    now: i64,
+) -> Result<&'a Certificate, CertificateError> {
+    let leaf = chain.first().ok_or(CertificateError::EmptyChain)?;
+    for (i, certificate) in chain.iter().enumerate() {
+        if trusted_roots.contains(&certificate.public_key) && i > 0 {
+            // Reached the root's own certificate: trust comes from its key, not from it.
+            return Ok(leaf);
+        }
+        let subject = &certificate.subject;
+        if now < certificate.not_before {
+            return Err(CertificateError::NotYetValid(subject.clone()));
+        }
+        if now > certificate.not_after {
+            return Err(CertificateError::Expired(subject.clone()));
+        }
+
+        let issuer_certificate = chain.get(i + 1);
+        let issuer_key = match issuer_certificate {
+            Some(issuer) if trusted_roots.contains(&issuer.public_key) => issuer.public_key,
+            Some(issuer) => {
+                if !issuer.allows(KeyUsage::CertSign) {
+                    return Err(CertificateError::NotACertificateAuthority(issuer.subject.clone()));
+                }
+                issuer.public_key
+            }
+            None => *trusted_roots
+                .iter()
+                .find(|root| Fingerprint::of(*root) == certificate.issuer)
+                .ok_or(CertificateError::UntrustedRoot)?,
+        };
+        if Fingerprint::of(&issuer_key) != certificate.issuer {
+            return Err(CertificateError::IssuerMismatch(subject.clone()));
+        }
+        if !certificate.verify_issued_by(&issuer_key)? {
+            return Err(CertificateError::InvalidSignature(subject.clone()));
+        }
+
+        for list in revocation_lists.iter().filter(|list| list.issuer == certificate.issuer) {
+            if let Some(issuer) = issuer_certificate.filter(|issuer| !trusted_roots.contains(&issuer.public_key)) {
+                if !issuer.allows(KeyUsage::CrlSign) {
+                    return Err(CertificateError::NotARevocationAuthority(issuer.subject.clone()));
+                }
+            }
+            if !list.verify_issued_by(&issuer_key)? {
+                return Err(CertificateError::InvalidRevocationListSignature);
+            }
+            if list.is_revoked(certificate) {
+                return Err(CertificateError::Revoked(subject.clone()));
+            }
+        }
+    }
+    Ok(leaf)
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::str::FromStr;
+use age::secrecy::SecretString;
+use sha2::{Digest, Sha256};
+use crate::age_format::{decode_identities, decode_recipient, decrypt, encode_identity, encode_recipient, encrypt_with_work_factor, AgeError};
+use crate::file_encryption::{Identity, Recipient};
+use crate::key_exchange::{generate_keypair, public_key};
+use crate::utils::to_hex;
+
+// The age test kit from C2SP/CCTV, as shipped with the age 0.11 crate.
+const TESTKIT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/vectors/age_testkit");
+
+#[test]
+fn test_testkit() {
+    let mut count = 0;
+    for entry in fs::read_dir(TESTKIT_DIR).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let contents = fs::read(&path).unwrap();
+        let split = contents.windows(2).position(|w| w == b"\n\n").unwrap();
+        let (mut expect, mut payload, mut identities) = (String::new(), String::new(), Vec::new());
+        for line in std::str::from_utf8(&contents[..split]).unwrap().lines() {
+            let (key, value) = line.split_once(": ").unwrap();
+            match key {
+                "expect" => expect = value.to_string(),
+                "payload" => payload = value.to_string(),
+                "identity" => identities.push(Identity::X25519(decode_identities(value).unwrap()[0])),
+                "passphrase" => identities.push(Identity::Passphrase(value.to_string())),
+                _ => {}
+            }
+        }
+
+        let result = decrypt(&contents[split + 2..], &identities);
+        match expect.as_str() {
+            "success" => assert_eq!(to_hex(&Sha256::digest(result.unwrap())), payload, "{name}"),
+            "no match" => assert!(matches!(result, Err(AgeError::NoMatch)), "{name}: {result:?}"),
+            "HMAC failure" => assert!(matches!(result, Err(AgeError::HeaderMac)), "{name}: {result:?}"),
+            "header failure" => assert!(matches!(result, Err(AgeError::Header(_))), "{name}: {result:?}"),
+            "payload failure" => assert!(matches!(result, Err(AgeError::Payload)), "{name}: {result:?}"),
+            "armor failure" => assert!(matches!(result, Err(AgeError::Armor)), "{name}: {result:?}"),
+            other => panic!("{name}: unknown expectation {other}"),
+        }
+        count += 1;
+    }
+    assert_eq!(count, 114);
+}
+
+#[test]
+fn test_key_encoding() {
+    let identity = age::x25519::Identity::generate();
+    let encoded = age::secrecy::ExposeSecret::expose_secret(&identity.to_string()).to_string();
+    let secret_key = decode_identities(&format!("# created by age-keygen\n{encoded}\n")).unwrap()[0];
+    assert_eq!(encode_identity(&secret_key), encoded);
+    let recipient = encode_recipient(&public_key(&secret_key).unwrap());
+    assert_eq!(recipient, identity.to_public().to_string());
+    assert_eq!(decode_recipient(&recipient).unwrap(), public_key(&secret_key).unwrap());
+
+    assert!(matches!(decode_recipient(&encoded), Err(AgeError::InvalidRecipient(_))));
+    assert!(matches!(decode_identities(&recipient), Err(AgeError::InvalidIdentity)));
+    assert!(matches!(decode_identities("# only a comment\n"), Err(AgeError::InvalidIdentity)));
+}
+
+#[test]
+fn test_interop_with_age() {
+    // Two full chunks plus a partial one.
+    let plaintext: Vec<u8> = (0..150_000u32).map(|i| i as u8).collect();
+    let (public, secret) = generate_keypair();
+    let identity = age::x25519::Identity::from_str(&encode_identity(&secret)).unwrap();
+
+    for armor in [false, true] {
+        let ours = encrypt_with_work_factor(&plaintext, &[Recipient::X25519(public)], armor, 10).unwrap();
+        assert_eq!(age::decrypt(&identity, &ours).unwrap(), plaintext);
+    }
+    let theirs = age::encrypt(&identity.to_public(), &plaintext).unwrap();
+    assert_eq!(decrypt(&theirs, &[Identity::X25519(secret)]).unwrap(), plaintext);
+    let armored = age::encrypt_and_armor(&identity.to_public(), b"armored").unwrap();
+    assert_eq!(decrypt(armored.as_bytes(), &[Identity::X25519(secret)]).unwrap(), b"armored");
+
+    let ours = encrypt_with_work_factor(b"by passphrase", &[Recipient::Passphrase("hunter2".into())], false, 10).unwrap();
+    assert_eq!(age::decrypt(&age::scrypt::Identity::new(SecretString::from("hunter2")), &ours).unwrap(), b"by passphrase");
+    let mut recipient = age::scrypt::Recipient::new(SecretString::from("hunter2"));
+    recipient.set_work_factor(10);
+    let theirs = age::encrypt(&recipient, b"by passphrase").unwrap();
+    assert_eq!(decrypt(&theirs, &[Identity::Passphrase("hunter2".into())]).unwrap(), b"by passphrase");
+}
+
+#[test]
+fn test_recipient_rules() {
+    let (alice_public, alice_secret) = generate_keypair();
+    let (bob_public, bob_secret) = generate_keypair();
+    let (_, carol_secret) = generate_keypair();
+    let both = [Recipient::X25519(alice_public), Recipient::X25519(bob_public)];
+    let encrypted = encrypt_with_work_factor(b"", &both, false, 10).unwrap();
+    assert_eq!(decrypt(&encrypted, &[Identity::X25519(bob_secret)]).unwrap(), b"");
+    assert_eq!(decrypt(&encrypted, &[Identity::X25519(carol_secret), Identity::X25519(alice_secret)]).unwrap(), b"");
+    assert!(matches!(decrypt(&encrypted, &[Identity::X25519(carol_secret)]), Err(AgeError::NoMatch)));
+
+    let mixed = [Recipient::X25519(alice_public), Recipient::Passphrase("pw".into())];
+    assert!(matches!(encrypt_with_work_factor(b"x", &mixed, false, 10), Err(AgeError::PassphraseNotAlone)));
+    assert!(matches!(encrypt_with_work_factor(b"x", &[], false, 10), Err(AgeError::NoRecipients)));
+}
@@ -12,8 +12,8 @@ fn test_encrypt_decrypt() {
 
 #[test]
 fn test_key_derivation() {
-    let (key1, salt1) = derive_key("password", Some("salt")).unwrap();
-    let (key2, salt2) = derive_key("password", Some("salt")).unwrap();
+    let (key1, salt1) = derive_key("password", Some(b"saltsalt")).unwrap();
+    let (key2, salt2) = derive_key("password", Some(b"saltsalt")).unwrap();
     assert_eq!(key1, key2);
     assert_eq!(salt1, salt2);
 }
\ No newline at end of file
@@ -0,0 +1,192 @@
+use crate::double_ratchet::{RatchetError, Session, ENCRYPTED_HEADER_LEN, MAX_SKIP};
+use crate::key_store::KeyStore;
+use crate::x3dh::{self, IdentityKeyPair, InitialMessage, PqPreKey, PreKey, PreKeyBundle, X3dhError};
+
+/// Alice starts a session from Bob's published bundle; Bob answers her first message.
+fn start_session(use_one_time_prekey: bool) -> (Session, Session) {
+    let alice = IdentityKeyPair::generate();
+    let bob = IdentityKeyPair::generate();
+    let signed_prekey = PreKey::generate(1);
+    let one_time_prekey = PreKey::generate(100);
+    let bundle = PreKeyBundle::new(&bob, &signed_prekey, use_one_time_prekey.then_some(&one_time_prekey)).unwrap();
+    let bundle = PreKeyBundle::from_bytes(&bundle.to_bytes()).unwrap();
+
+    let (alice_session, initial) = Session::initiate(&alice, &bundle).unwrap();
+    let initial = InitialMessage::from_bytes(&initial.to_bytes()).unwrap();
+    let bob_session = Session::respond(&bob, &signed_prekey, Some(&one_time_prekey), None, &initial).unwrap();
+    (alice_session, bob_session)
+}
+
+#[test]
+fn test_x3dh() {
+    let alice = IdentityKeyPair::generate();
+    let bob = IdentityKeyPair::generate();
+    let signed_prekey = PreKey::generate(7);
+    let one_time_prekey = PreKey::generate(8);
+    let bundle = PreKeyBundle::new(&bob, &signed_prekey, Some(&one_time_prekey)).unwrap();
+
+    let (alice_secret, initial) = x3dh::initiate(&alice, &bundle).unwrap();
+    let bob_secret = x3dh::respond(&bob, &signed_prekey, Some(&one_time_prekey), None, &initial).unwrap();
+    assert_eq!(alice_secret.key, bob_secret.key);
+    assert_eq!(alice_secret.associated_data, [alice.dh_public_key(), bob.dh_public_key()].concat());
+    assert_eq!(alice_secret.associated_data, bob_secret.associated_data);
+
+    // The one-time prekey must be the one named, and can't be left out.
+    assert!(matches!(x3dh::respond(&bob, &signed_prekey, None, None, &initial), Err(X3dhError::MissingOneTimePrekey(8))));
+    assert!(matches!(x3dh::respond(&bob, &PreKey::generate(9), None, None, &initial), Err(X3dhError::UnknownSignedPrekey(7))));
+
+    // A bundle whose prekey was swapped by a server is refused.
+    let mut tampered = bundle.clone();
+    tampered.signed_prekey = PreKey::generate(7).public_key();
+    assert!(matches!(x3dh::initiate(&alice, &tampered), Err(X3dhError::InvalidPrekeySignature)));
+    let mut impersonated = bundle.clone();
+    impersonated.identity_key = alice.dh_public_key();
+    assert!(matches!(x3dh::initiate(&alice, &impersonated), Err(X3dhError::InvalidPrekeySignature)));
+}
+
+#[test]
+fn test_conversation() {
+    for use_one_time_prekey in [true, false] {
+        let (mut alice, mut bob) = start_session(use_one_time_prekey);
+        assert!(matches!(bob.encrypt(b"too early"), Err(RatchetError::NotInitialized)));
+
+        for round in 0..3 {
+            for i in 0..3 {
+                let message = alice.encrypt(format!("alice {round}.{i}").as_bytes()).unwrap();
+                assert_eq!(bob.decrypt(&message).unwrap(), format!("alice {round}.{i}").as_bytes());
+            }
+            let reply = bob.encrypt(format!("bob {round}").as_bytes()).unwrap();
+            assert_eq!(alice.decrypt(&reply).unwrap(), format!("bob {round}").as_bytes());
+        }
+    }
+}
+
+#[test]
+fn test_out_of_order_and_replay() {
+    let (mut alice, mut bob) = start_session(true);
+    let first: Vec<Vec<u8>> = (0..4).map(|i| alice.encrypt(&[i]).unwrap()).collect();
+    assert_eq!(bob.decrypt(&first[2]).unwrap(), [2]);
+    let reply = bob.encrypt(b"ack").unwrap();
+    assert_eq!(alice.decrypt(&reply).unwrap(), b"ack");
+    // Alice's next chain starts; messages from her old chain still open late.
+    let second = alice.encrypt(b"new chain").unwrap();
+    assert_eq!(bob.decrypt(&second).unwrap(), b"new chain");
+    assert_eq!(bob.decrypt(&first[0]).unwrap(), [0]);
+    assert_eq!(bob.decrypt(&first[3]).unwrap(), [3]);
+    assert_eq!(bob.decrypt(&first[1]).unwrap(), [1]);
+
+    // Each message opens exactly once.
+    assert!(bob.decrypt(&first[1]).is_err());
+    assert!(matches!(bob.decrypt(&second), Err(RatchetError::Replayed)));
+
+    // Headers are encrypted: the ratchet key doesn't appear in the message.
+    let message = alice.encrypt(b"x").unwrap();
+    assert_eq!(message.len(), ENCRYPTED_HEADER_LEN + 1 + 16);
+}
+
+#[test]
+fn test_rejects_bad_messages_without_losing_state() {
+    let (mut alice, mut bob) = start_session(true);
+    let message = alice.encrypt(b"hello").unwrap();
+    let before = bob.clone();
+
+    let mut tampered = message.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(matches!(bob.decrypt(&tampered), Err(RatchetError::DecryptionFailed)));
+    let mut bad_header = message.clone();
+    bad_header[20] ^= 1;
+    assert!(matches!(bob.decrypt(&bad_header), Err(RatchetError::InvalidHeader)));
+    assert!(matches!(bob.decrypt(&message[..10]), Err(RatchetError::Malformed)));
+    assert_eq!(bob, before);
+    assert_eq!(bob.decrypt(&message).unwrap(), b"hello");
+
+    // Skipping more than MAX_SKIP messages in one chain is refused.
+    for _ in 0..=MAX_SKIP {
+        alice.encrypt(b"lost").unwrap();
+    }
+    let far_ahead = alice.encrypt(b"far ahead").unwrap();
+    assert!(matches!(bob.decrypt(&far_ahead), Err(RatchetError::TooManySkipped)));
+}
+
+#[test]
+fn test_session_persistence() {
+    let path = "/tmp/qimem_test_ratchet_store.bin";
+    let _ = std::fs::remove_file(path);
+    let (mut alice, mut bob) = start_session(true);
+    let pending = alice.encrypt(b"sent while bob was offline").unwrap();
+    let skipped = alice.encrypt(b"skipped").unwrap();
+    let latest = alice.encrypt(b"latest").unwrap();
+    assert_eq!(bob.decrypt(&latest).unwrap(), b"latest");
+
+    let mut store = KeyStore::new(path, "correct horse battery staple").unwrap();
+    store.store_key("unrelated", [9; 32]).unwrap();
+    bob.save(&mut store, "session:alice").unwrap();
+    let store = KeyStore::new(path, "correct horse battery staple").unwrap();
+    assert_eq!(store.retrieve_key("unrelated"), Some([9; 32]));
+    let mut restored = Session::load(&store, "session:alice").unwrap();
+    assert_eq!(restored, bob);
+    assert_eq!(Session::from_bytes(&bob.to_bytes()).unwrap(), bob);
+
+    // Skipped keys survive the round trip.
+    assert_eq!(restored.decrypt(&skipped).unwrap(), b"skipped");
+    assert_eq!(restored.decrypt(&pending).unwrap(), b"sent while bob was offline");
+    assert!(matches!(Session::load(&store, "session:carol"), Err(RatchetError::SessionNotFound(_))));
+    assert!(matches!(Session::from_bytes(&bob.to_bytes()[..50]), Err(RatchetError::InvalidState)));
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_post_quantum_prekey() {
+    let alice = IdentityKeyPair::generate();
+    let bob = IdentityKeyPair::generate();
+    let signed_prekey = PreKey::generate(1);
+    let one_time_prekey = PreKey::generate(2);
+    let pq_prekey = PqPreKey::generate(3);
+    let bundle = PreKeyBundle::new(&bob, &signed_prekey, Some(&one_time_prekey)).unwrap().with_pq_prekey(&bob, &pq_prekey).unwrap();
+    assert_eq!(PreKeyBundle::from_bytes(&bundle.to_bytes()).unwrap(), bundle);
+    let mut without_one_time = bundle.clone();
+    without_one_time.one_time_prekey = None;
+    assert_eq!(PreKeyBundle::from_bytes(&without_one_time.to_bytes()).unwrap(), without_one_time);
+
+    let (alice_secret, initial) = x3dh::initiate(&alice, &bundle).unwrap();
+    assert_eq!(initial.pq_prekey.as_ref().map(|(id, _)| *id), Some(3));
+    let initial = InitialMessage::from_bytes(&initial.to_bytes()).unwrap();
+    let bob_secret = x3dh::respond(&bob, &signed_prekey, Some(&one_time_prekey), Some(&pq_prekey), &initial).unwrap();
+    assert_eq!(alice_secret.key, bob_secret.key);
+    assert!(matches!(
+        x3dh::respond(&bob, &signed_prekey, Some(&one_time_prekey), None, &initial),
+        Err(X3dhError::MissingPqPrekey(3))
+    ));
+    // The wrong PQ key gives a different secret, so the first message won't open.
+    let wrong = PqPreKey { id: 3, ..PqPreKey::generate(0) };
+    let bob_wrong = x3dh::respond(&bob, &signed_prekey, Some(&one_time_prekey), Some(&wrong), &initial).unwrap();
+    assert_ne!(alice_secret.key, bob_wrong.key);
+
+    // A server can't swap in its own PQ prekey.
+    let mut tampered = bundle.clone();
+    tampered.pq_prekey.as_mut().unwrap().public_key = PqPreKey::generate(3).public_key();
+    assert!(matches!(x3dh::initiate(&alice, &tampered), Err(X3dhError::InvalidPrekeySignature)));
+
+    // Nor strip it: Bob refuses a classical handshake once he has published one.
+    let mut stripped = bundle.clone();
+    stripped.pq_prekey = None;
+    let stripped = PreKeyBundle::from_bytes(&stripped.to_bytes()).unwrap();
+    let (_, classical) = x3dh::initiate(&alice, &stripped).unwrap();
+    assert!(classical.pq_prekey.is_none());
+    assert!(matches!(
+        x3dh::respond(&bob, &signed_prekey, Some(&one_time_prekey), Some(&pq_prekey), &classical),
+        Err(X3dhError::MissingPqCiphertext)
+    ));
+    let mut truncated = initial.to_bytes();
+    truncated.truncate(truncated.len() - 4 - crate::hybrid_kem::CIPHERTEXT_LEN);
+    let truncated = InitialMessage::from_bytes(&truncated).unwrap();
+    assert!(matches!(
+        Session::respond(&bob, &signed_prekey, Some(&one_time_prekey), Some(&pq_prekey), &truncated),
+        Err(RatchetError::X3dh(X3dhError::MissingPqCiphertext))
+    ));
+
+    let (mut alice_session, initial) = Session::initiate(&alice, &bundle).unwrap();
+    let mut bob_session = Session::respond(&bob, &signed_prekey, Some(&one_time_prekey), Some(&pq_prekey), &initial).unwrap();
+    let message = alice_session.encrypt(b"hybrid hello").unwrap();
+    assert_eq!(bob_session.decrypt(&message).unwrap(), b"hybrid hello");
+}
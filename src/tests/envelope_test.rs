@@ -0,0 +1,113 @@
+use crate::envelope::{open, open_at, open_from_any, seal, seal_at, EnvelopeError, ReplayGuard, MAX_WINDOW_SECS};
+use crate::fingerprint::Fingerprint;
+use crate::key_exchange;
+use crate::signing;
+
+const NOW: i64 = 1_700_000_000;
+
+#[test]
+fn test_seal_open() {
+    let (alice_verify, alice_sign) = signing::generate_keypair().unwrap();
+    let (bob_public, bob_secret) = key_exchange::generate_keypair();
+    let mut guard = ReplayGuard::default();
+
+    let sealed = seal(&alice_sign, &bob_public, b"transfer 10 credits").unwrap();
+    let opened = open(&bob_secret, &alice_verify, &sealed, &mut guard).unwrap();
+    assert_eq!(opened.payload, b"transfer 10 credits");
+    // The same envelope is only accepted once.
+    assert!(matches!(open(&bob_secret, &alice_verify, &sealed, &mut guard), Err(EnvelopeError::Replayed)));
+    // A fresh envelope with the same payload has a new id.
+    let again = seal(&alice_sign, &bob_public, b"transfer 10 credits").unwrap();
+    assert_ne!(open(&bob_secret, &alice_verify, &again, &mut guard).unwrap().message_id, opened.message_id);
+}
+
+#[test]
+fn test_identity_binding() {
+    let (alice_verify, alice_sign) = signing::generate_keypair().unwrap();
+    let (mallory_verify, _) = signing::generate_keypair().unwrap();
+    let (bob_public, bob_secret) = key_exchange::generate_keypair();
+    let (mallory_public, mallory_secret) = key_exchange::generate_keypair();
+    let sealed = seal(&alice_sign, &mallory_public, b"for mallory").unwrap();
+
+    // Only the addressed recipient can open it, and only as coming from Alice.
+    assert!(matches!(open(&bob_secret, &alice_verify, &sealed, &mut ReplayGuard::default()), Err(EnvelopeError::KeyExchange(_))));
+    assert!(matches!(
+        open(&mallory_secret, &mallory_verify, &sealed, &mut ReplayGuard::default()),
+        Err(EnvelopeError::UnexpectedSender(_))
+    ));
+
+    // Mallory re-sealing Alice's signed contents to Bob is caught: the signature names Mallory as recipient.
+    let inner = key_exchange::open(&mallory_secret, &sealed).unwrap();
+    let forwarded = key_exchange::seal(&bob_public, &inner).unwrap();
+    assert!(matches!(
+        open(&bob_secret, &alice_verify, &forwarded, &mut ReplayGuard::default()),
+        Err(EnvelopeError::InvalidSignature)
+    ));
+
+    // Any tampering with the signed contents breaks the signature.
+    let mut tampered = inner.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    let tampered = key_exchange::seal(&mallory_public, &tampered).unwrap();
+    assert!(matches!(
+        open(&mallory_secret, &alice_verify, &tampered, &mut ReplayGuard::default()),
+        Err(EnvelopeError::InvalidSignature)
+    ));
+}
+
+#[test]
+fn test_age_window() {
+    let (alice_verify, alice_sign) = signing::generate_keypair().unwrap();
+    let (bob_public, bob_secret) = key_exchange::generate_keypair();
+    let mut guard = ReplayGuard::new(300).with_leeway(0);
+
+    let old = seal_at(&alice_sign, &bob_public, b"old", [1; 16], NOW - 301).unwrap();
+    assert!(matches!(open_at(&bob_secret, &alice_verify, &old, &mut guard, NOW), Err(EnvelopeError::Expired { .. })));
+    let future = seal_at(&alice_sign, &bob_public, b"future", [2; 16], NOW + 1).unwrap();
+    assert!(matches!(open_at(&bob_secret, &alice_verify, &future, &mut guard, NOW), Err(EnvelopeError::SentInFuture { .. })));
+
+    let recent = seal_at(&alice_sign, &bob_public, b"recent", [3; 16], NOW - 300).unwrap();
+    assert_eq!(open_at(&bob_secret, &alice_verify, &recent, &mut guard, NOW).unwrap().sent_at, NOW - 300);
+    // Once it has aged out, a replay is refused as expired rather than remembered forever.
+    assert!(matches!(open_at(&bob_secret, &alice_verify, &recent, &mut guard, NOW + 1), Err(EnvelopeError::Expired { .. })));
+}
+
+#[test]
+fn test_window_limits() {
+    assert!(ReplayGuard::with_window(300, 60).is_ok());
+    assert!(ReplayGuard::with_window(MAX_WINDOW_SECS, MAX_WINDOW_SECS).is_ok());
+    for (max_age, leeway) in [(-1, 60), (300, -1), (MAX_WINDOW_SECS + 1, 60), (300, i64::MAX), (i64::MIN, 0)] {
+        assert!(matches!(ReplayGuard::with_window(max_age, leeway), Err(EnvelopeError::InvalidWindow(_))));
+    }
+
+    // Clock values at the ends of the range neither overflow nor wrap the window.
+    let (alice_verify, alice_sign) = signing::generate_keypair().unwrap();
+    let (bob_public, bob_secret) = key_exchange::generate_keypair();
+    let mut guard = ReplayGuard::with_window(MAX_WINDOW_SECS, MAX_WINDOW_SECS).unwrap();
+    let earliest = seal_at(&alice_sign, &bob_public, b"earliest", [4; 16], i64::MIN).unwrap();
+    assert_eq!(open_at(&bob_secret, &alice_verify, &earliest, &mut guard, i64::MIN).unwrap().sent_at, i64::MIN);
+    let latest = seal_at(&alice_sign, &bob_public, b"latest", [5; 16], i64::MAX).unwrap();
+    assert_eq!(open_at(&bob_secret, &alice_verify, &latest, &mut guard, i64::MAX - 1).unwrap().sent_at, i64::MAX);
+    assert!(matches!(open_at(&bob_secret, &alice_verify, &earliest, &mut guard, i64::MAX), Err(EnvelopeError::Expired { .. })));
+}
+
+#[test]
+fn test_open_from_keyring() {
+    let (alice_verify, alice_sign) = signing::generate_keypair().unwrap();
+    let (carol_verify, carol_sign) = signing::generate_keypair().unwrap();
+    let (mallory_verify, mallory_sign) = signing::generate_keypair().unwrap();
+    let (bob_public, bob_secret) = key_exchange::generate_keypair();
+    let trusted: [&[u8]; 2] = [&alice_verify, &carol_verify];
+    let mut guard = ReplayGuard::default();
+
+    let from_carol = seal(&carol_sign, &bob_public, b"hi").unwrap();
+    let opened = open_from_any(&bob_secret, &trusted, &from_carol, &mut guard).unwrap();
+    assert_eq!(opened.sender_key_id, Fingerprint::of(&carol_verify).key_id());
+    let from_alice = seal(&alice_sign, &bob_public, b"hi").unwrap();
+    assert_eq!(open_from_any(&bob_secret, &trusted, &from_alice, &mut guard).unwrap().sender_key_id, Fingerprint::of(&alice_verify).key_id());
+
+    let from_mallory = seal(&mallory_sign, &bob_public, b"hi").unwrap();
+    match open_from_any(&bob_secret, &trusted, &from_mallory, &mut guard) {
+        Err(EnvelopeError::UnexpectedSender(key_id)) => assert_eq!(key_id, Fingerprint::of(&mallory_verify).key_id()),
+        other => panic!("expected UnexpectedSender, got {other:?}"),
+    }
+}
@@ -23,4 +23,134 @@ fn test_encrypt_decrypt_file() {
     fs::remove_file(input_path).unwrap();
     fs::remove_file(encrypted_path).unwrap();
     fs::remove_file(decrypted_path).unwrap();
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_password_file_encryption() {
+    use crate::file_encryption::{encrypt_file_with_password, decrypt_file_with_password, FileEncryptionError};
+
+    let input_path = "test_pw_input.txt";
+    let encrypted_path = "test_pw_encrypted.bin";
+    let decrypted_path = "test_pw_decrypted.txt";
+    fs::write(input_path, b"hello password encryption").unwrap();
+
+    assert!(matches!(
+        encrypt_file_with_password(input_path, encrypted_path, "123456", Some(2)),
+        Err(FileEncryptionError::KeyGen(_))
+    ));
+    encrypt_file_with_password(input_path, encrypted_path, "ember-quarry-lantern-violet", Some(2)).unwrap();
+    assert!(decrypt_file_with_password(encrypted_path, decrypted_path, "wrong password").is_err());
+    decrypt_file_with_password(encrypted_path, decrypted_path, "ember-quarry-lantern-violet").unwrap();
+    assert_eq!(fs::read(decrypted_path).unwrap(), b"hello password encryption");
+
+    fs::remove_file(input_path).unwrap();
+    fs::remove_file(encrypted_path).unwrap();
+    fs::remove_file(decrypted_path).unwrap();
+}
+
+#[test]
+fn test_multi_recipient_file_encryption() {
+    use crate::file_encryption::{add_file_recipients, decrypt_file_for_identity, encrypt_file_for_recipients, FileEncryptionError, Identity, Recipient};
+    use crate::key_exchange::generate_keypair;
+
+    let input_path = "test_mr_input.txt";
+    let encrypted_path = "test_mr_encrypted.bin";
+    let decrypted_path = "test_mr_decrypted.txt";
+    fs::write(input_path, b"shared with three teams").unwrap();
+    let (alice_public, alice_secret) = generate_keypair();
+    let (bob_public, bob_secret) = generate_keypair();
+    let (carol_public, carol_secret) = generate_keypair();
+
+    assert!(matches!(encrypt_file_for_recipients(input_path, encrypted_path, &[]), Err(FileEncryptionError::NoRecipients)));
+    encrypt_file_for_recipients(
+        input_path,
+        encrypted_path,
+        &[Recipient::X25519(alice_public), Recipient::X25519(bob_public), Recipient::Passphrase("ops-team-phrase".into())],
+    ).unwrap();
+    for identity in [Identity::X25519(alice_secret), Identity::X25519(bob_secret), Identity::Passphrase("ops-team-phrase".into())] {
+        decrypt_file_for_identity(encrypted_path, decrypted_path, &identity).unwrap();
+        assert_eq!(fs::read(decrypted_path).unwrap(), b"shared with three teams");
+    }
+    assert!(matches!(
+        decrypt_file_for_identity(encrypted_path, decrypted_path, &Identity::X25519(carol_secret)),
+        Err(FileEncryptionError::NoMatchingRecipient)
+    ));
+
+    // Adding a recipient rewrites only the header.
+    let body_before = fs::read(encrypted_path).unwrap();
+    add_file_recipients(encrypted_path, &Identity::X25519(bob_secret), &[Recipient::X25519(carol_public)]).unwrap();
+    let body_after = fs::read(encrypted_path).unwrap();
+    let body_len = "shared with three teams".len() + 12 + 16;
+    assert_eq!(body_before[body_before.len() - body_len..], body_after[body_after.len() - body_len..]);
+    decrypt_file_for_identity(encrypted_path, decrypted_path, &Identity::X25519(carol_secret)).unwrap();
+    assert_eq!(fs::read(decrypted_path).unwrap(), b"shared with three teams");
+
+    // A single-key file is not a multi-recipient file.
+    fs::write(encrypted_path, b"not a header").unwrap();
+    assert!(matches!(
+        decrypt_file_for_identity(encrypted_path, decrypted_path, &Identity::X25519(alice_secret)),
+        Err(FileEncryptionError::InvalidFormat)
+    ));
+
+    fs::remove_file(input_path).unwrap();
+    fs::remove_file(encrypted_path).unwrap();
+    fs::remove_file(decrypted_path).unwrap();
+}
+
+#[test]
+fn test_multi_recipient_header_checks() {
+    use crate::file_encryption::{add_file_recipients, decrypt_file_for_identity, encrypt_file_for_recipients, FileEncryptionError, Identity, Recipient};
+    use crate::key_exchange::generate_keypair;
+
+    let input_path = "test_mr_header_input.txt";
+    let encrypted_path = "test_mr_header_encrypted.bin";
+    let decrypted_path = "test_mr_header_decrypted.txt";
+    fs::write(input_path, b"header checks").unwrap();
+    let (alice_public, alice_secret) = generate_keypair();
+    let (bob_public, _) = generate_keypair();
+    let body_len = "header checks".len() + 12 + 16;
+
+    // Changing another recipient's stanza breaks the header MAC for everyone.
+    encrypt_file_for_recipients(input_path, encrypted_path, &[Recipient::X25519(alice_public), Recipient::X25519(bob_public)]).unwrap();
+    let mut data = fs::read(encrypted_path).unwrap();
+    let mac_offset = data.len() - body_len - 32;
+    data[mac_offset - 1] ^= 1;
+    fs::write(encrypted_path, &data).unwrap();
+    assert!(matches!(
+        decrypt_file_for_identity(encrypted_path, decrypted_path, &Identity::X25519(alice_secret)),
+        Err(FileEncryptionError::HeaderMac)
+    ));
+    data[mac_offset - 1] ^= 1;
+    data[mac_offset] ^= 1;
+    fs::write(encrypted_path, &data).unwrap();
+    assert!(matches!(
+        decrypt_file_for_identity(encrypted_path, decrypted_path, &Identity::X25519(alice_secret)),
+        Err(FileEncryptionError::HeaderMac)
+    ));
+
+    // Only one passphrase stanza per file.
+    let passphrase = || Recipient::Passphrase("ops-team-phrase".into());
+    assert!(matches!(
+        encrypt_file_for_recipients(input_path, encrypted_path, &[passphrase(), passphrase()]),
+        Err(FileEncryptionError::MultiplePassphrases)
+    ));
+    encrypt_file_for_recipients(input_path, encrypted_path, &[passphrase(), Recipient::X25519(alice_public)]).unwrap();
+    assert!(matches!(
+        add_file_recipients(encrypted_path, &Identity::X25519(alice_secret), &[passphrase()]),
+        Err(FileEncryptionError::MultiplePassphrases)
+    ));
+
+    // A passphrase stanza asking for too many Argon2 passes is someone else's,
+    // not an error, and is never derived. Its time cost is at stanza offset 7.
+    let mut data = fs::read(encrypted_path).unwrap();
+    data[13 + 7..13 + 11].copy_from_slice(&64u32.to_be_bytes());
+    fs::write(encrypted_path, &data).unwrap();
+    assert!(matches!(
+        decrypt_file_for_identity(encrypted_path, decrypted_path, &Identity::Passphrase("ops-team-phrase".into())),
+        Err(FileEncryptionError::NoMatchingRecipient)
+    ));
+
+    fs::remove_file(input_path).unwrap();
+    fs::remove_file(encrypted_path).unwrap();
+    let _ = fs::remove_file(decrypted_path);
+}
@@ -0,0 +1,46 @@
+use std::collections::HashSet;
+use crate::fingerprint::{safety_number, Fingerprint, DEFAULT_EMOJI, DEFAULT_WORDS};
+use crate::utils::from_hex;
+
+// RFC 8032 section 7.1, test 1 public key; expected values computed with Python's hashlib and base64.
+const PUBLIC_KEY: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+
+#[test]
+fn test_renderings() {
+    let fingerprint = Fingerprint::of(&from_hex(PUBLIC_KEY).unwrap());
+    assert_eq!(fingerprint.to_hex(), "21fe31dfa154a261626bf854046fd2271b7bed4b6abe45aa58877ef47f9721b9");
+    assert_eq!(fingerprint.to_base32(), "EH7DDX5BKSRGCYTL7BKAI36SE4NXX3KLNK7ELKSYQ57PI74XEG4Q");
+    assert_eq!(fingerprint.key_id(), "21fe31dfa154a261");
+    assert_eq!(fingerprint.to_words(DEFAULT_WORDS), ["capable", "various", "jewel", "dress", "enforce", "cost"]);
+    assert_eq!(fingerprint.to_emoji(DEFAULT_EMOJI), "🐨🐞🍍🍋🍑🥕🦊🦅🐬🐡🦊");
+    assert!(fingerprint.to_string().starts_with("SHA256:21fe 31df a154 "));
+    assert_eq!(fingerprint.to_words(100).len(), 23);
+    assert_eq!(fingerprint.to_emoji(100).chars().count(), 42);
+
+    // Every emoji is distinct and a single code point.
+    let emoji: HashSet<char> = (0..=255u8).flat_map(|b| Fingerprint([b; 32]).to_emoji(42).chars().collect::<Vec<_>>()).collect();
+    assert_eq!(emoji.len(), 64);
+}
+
+#[test]
+fn test_safety_number() {
+    let alice = [1u8; 32];
+    let bob = [2u8; 32];
+    assert_eq!(safety_number(&alice, &bob), safety_number(&bob, &alice));
+    assert_ne!(safety_number(&alice, &bob), safety_number(&alice, &[3u8; 32]));
+    assert_ne!(safety_number(&alice, &bob), Fingerprint::of(&[alice, bob].concat()));
+    // Secret fingerprints are domain-separated from public ones.
+    assert_ne!(Fingerprint::of_secret(&alice), Fingerprint::of(&alice));
+}
+
+#[test]
+fn test_keystore_fingerprints() {
+    use crate::key_store::KeyStore;
+    let path = "/tmp/qimem_test_fingerprint_keys.bin";
+    let _ = std::fs::remove_file(path);
+    let mut keystore = KeyStore::new(path, "very-strong-password").unwrap();
+    keystore.store_key("api", [7; 32]).unwrap();
+    assert_eq!(keystore.key_fingerprint("api"), Some(Fingerprint::of_secret(&[7; 32])));
+    assert_eq!(keystore.key_fingerprint("missing"), None);
+    let _ = std::fs::remove_file(path);
+}
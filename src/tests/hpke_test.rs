@@ -0,0 +1,175 @@
+use crate::hpke::{derive_keypair, open, parse_aead, seal, setup_auth_recipient, setup_auth_sender, setup_base_recipient, setup_base_sender, setup_sender_with_ephemeral, HpkeError};
+use crate::key_exchange::generate_keypair;
+use crate::q_core::AeadAlgorithm;
+use crate::utils::{from_hex, to_hex};
+
+// RFC 9180 appendix A: "Ode on a Grecian Urn", sequence number 0.
+const INFO: &str = "4f6465206f6e2061204772656369616e2055726e";
+const PLAINTEXT: &[u8] = b"Beauty is truth, truth beauty";
+const AAD: &[u8] = b"Count-0";
+
+struct Vector {
+    aead: AeadAlgorithm,
+    ikm_e: &'static str,
+    sk_e: &'static str,
+    enc: &'static str,
+    sk_r: &'static str,
+    ciphertext: &'static str,
+    export: &'static str,
+}
+
+const VECTORS: [Vector; 2] = [
+    // A.1.1: DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, AES-128-GCM.
+    Vector {
+        aead: AeadAlgorithm::Aes128Gcm,
+        ikm_e: "7268600d403fce431561aef583ee1613527cff655c1343f29812e66706df3234",
+        sk_e: "52c4a758a802cd8b936eceea314432798d5baf2d7e9235dc084ab1b9cfa2f736",
+        enc: "37fda3567bdbd628e88668c3c8d7e97d1d1253b6d4ea6d44c150f741f1bf4431",
+        sk_r: "4612c550263fc8ad58375df3f557aac531d26850903e55a9f23f21d8534e8ac8",
+        ciphertext: "f938558b5d72f1a23810b4be2ab4f84331acc02fc97babc53a52ae8218a355a96d8770ac83d07bea87e13c512a",
+        export: "3853fe2b4035195a573ffc53856e77058e15d9ea064de3e59f4961d0095250ee",
+    },
+    // A.2.1: DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, ChaCha20-Poly1305.
+    Vector {
+        aead: AeadAlgorithm::ChaCha20Poly1305,
+        ikm_e: "909a9b35d3dc4713a5e72a4da274b55d3d3821a37e5d099e74a647db583a904b",
+        sk_e: "f4ec9b33b792c372c1d2c2063507b684ef925b8c75a42dbcbf57d63ccd381600",
+        enc: "1afa08d3dec047a643885163f1180476fa7ddb54c6a8029ea33f95796bf2ac4a",
+        sk_r: "8057991eef8f1f1af18f4a9491d16a1ce333f695d4db8e38da75975c4478e0fb",
+        ciphertext: "1c5250d8034ec2b784ba2cfd69dbdb8af406cfe3ff938e131f0def8c8b60b4db21993c62ce81883d2dd1b51a28",
+        export: "4bbd6243b8bb54cec311fac9df81841b6fd61f56538a775e7c80a9f40160606e",
+    },
+];
+
+#[test]
+fn test_rfc9180_vectors() {
+    let info = from_hex(INFO).unwrap();
+    for vector in VECTORS {
+        let (_, sk_e) = derive_keypair(&from_hex(vector.ikm_e).unwrap()).unwrap();
+        assert_eq!(to_hex(&sk_e), vector.sk_e);
+        let sk_r = from_hex(vector.sk_r).unwrap();
+        let pk_r = crate::key_exchange::public_key(&sk_r).unwrap();
+
+        let (enc, mut sender) = setup_sender_with_ephemeral(vector.aead, &pk_r, &info, None, &sk_e).unwrap();
+        assert_eq!(to_hex(&enc), vector.enc);
+        let ciphertext = sender.seal(AAD, PLAINTEXT).unwrap();
+        assert_eq!(to_hex(&ciphertext), vector.ciphertext);
+        assert_eq!(to_hex(&sender.export(b"", 32).unwrap()), vector.export);
+
+        let mut recipient = setup_base_recipient(vector.aead, &enc, &sk_r, &info).unwrap();
+        assert_eq!(recipient.open(AAD, &ciphertext).unwrap(), PLAINTEXT);
+        assert_eq!(to_hex(&recipient.export(b"", 32).unwrap()), vector.export);
+    }
+}
+
+struct AuthVector {
+    aead: AeadAlgorithm,
+    sk_e: &'static str,
+    enc: &'static str,
+    sk_r: &'static str,
+    sk_s: &'static str,
+    pk_s: &'static str,
+    ciphertext: &'static str,
+    export: &'static str,
+}
+
+const AUTH_VECTORS: [AuthVector; 2] = [
+    // A.1.3: DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, AES-128-GCM, auth mode.
+    AuthVector {
+        aead: AeadAlgorithm::Aes128Gcm,
+        sk_e: "ff4442ef24fbc3c1ff86375b0be1e77e88a0de1e79b30896d73411c5ff4c3518",
+        enc: "23fb952571a14a25e3d678140cd0e5eb47a0961bb18afcf85896e5453c312e76",
+        sk_r: "fdea67cf831f1ca98d8e27b1f6abeb5b7745e9d35348b80fa407ff6958f9137e",
+        sk_s: "dc4a146313cce60a278a5323d321f051c5707e9c45ba21a3479fecdf76fc69dd",
+        pk_s: "8b0c70873dc5aecb7f9ee4e62406a397b350e57012be45cf53b7105ae731790b",
+        ciphertext: "5fd92cc9d46dbf8943e72a07e42f363ed5f721212cd90bcfd072bfd9f44e06b80fd17824947496e21b680c141b",
+        export: "28c70088017d70c896a8420f04702c5a321d9cbf0279fba899b59e51bac72c85",
+    },
+    // A.2.3: DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, ChaCha20-Poly1305, auth mode.
+    AuthVector {
+        aead: AeadAlgorithm::ChaCha20Poly1305,
+        sk_e: "c94619e1af28971c8fa7957192b7e62a71ca2dcdde0a7cc4a8a9e741d600ab13",
+        enc: "f7674cc8cd7baa5872d1f33dbaffe3314239f6197ddf5ded1746760bfc847e0e",
+        sk_r: "77d114e0212be51cb1d76fa99dd41cfd4d0166b08caa09074430a6c59ef17879",
+        sk_s: "2def0cb58ffcf83d1062dd085c8aceca7f4c0c3fd05912d847b61f3e54121f05",
+        pk_s: "f0f4f9e96c54aeed3f323de8534fffd7e0577e4ce269896716bcb95643c8712b",
+        ciphertext: "f1992bc4c91cdb5b35344d5ca04ab2cb2202f4a7af4a57e0cd2b016352f8034f7c3bc4e00df8ec3e5bec5ca574",
+        export: "caf279effa1affe5e55efcf09e9aefeb3430f680c962450b1109194dc856bc48",
+    },
+];
+
+#[test]
+fn test_rfc9180_auth_vectors() {
+    let info = from_hex(INFO).unwrap();
+    for vector in AUTH_VECTORS {
+        let sk_e = from_hex(vector.sk_e).unwrap();
+        let sk_r = from_hex(vector.sk_r).unwrap();
+        let sk_s = from_hex(vector.sk_s).unwrap();
+        let pk_r = crate::key_exchange::public_key(&sk_r).unwrap();
+        let pk_s = from_hex(vector.pk_s).unwrap();
+        assert_eq!(crate::key_exchange::public_key(&sk_s).unwrap().to_vec(), pk_s);
+
+        let (enc, mut sender) = setup_sender_with_ephemeral(vector.aead, &pk_r, &info, Some(&sk_s), &sk_e).unwrap();
+        assert_eq!(to_hex(&enc), vector.enc);
+        let ciphertext = sender.seal(AAD, PLAINTEXT).unwrap();
+        assert_eq!(to_hex(&ciphertext), vector.ciphertext);
+        assert_eq!(to_hex(&sender.export(b"", 32).unwrap()), vector.export);
+
+        let mut recipient = setup_auth_recipient(vector.aead, &enc, &sk_r, &info, &pk_s).unwrap();
+        assert_eq!(recipient.open(AAD, &ciphertext).unwrap(), PLAINTEXT);
+        assert_eq!(to_hex(&recipient.export(b"", 32).unwrap()), vector.export);
+        // The same encapsulation opened in base mode derives a different context.
+        let mut base = setup_base_recipient(vector.aead, &enc, &sk_r, &info).unwrap();
+        assert!(base.open(AAD, &ciphertext).is_err());
+    }
+}
+
+#[test]
+fn test_context_sequence() {
+    let (public_key, secret_key) = generate_keypair();
+    let (enc, mut sender) = setup_base_sender(AeadAlgorithm::Aes256Gcm, &public_key, b"stream").unwrap();
+    let first = sender.seal(b"", b"one").unwrap();
+    let second = sender.seal(b"", b"two").unwrap();
+
+    let mut recipient = setup_base_recipient(AeadAlgorithm::Aes256Gcm, &enc, &secret_key, b"stream").unwrap();
+    // Out of order fails and does not advance the sequence.
+    assert!(recipient.open(b"", &second).is_err());
+    assert_eq!(recipient.open(b"", &first).unwrap(), b"one");
+    assert_eq!(recipient.open(b"", &second).unwrap(), b"two");
+}
+
+#[test]
+fn test_auth_mode() {
+    let (recipient_public, recipient_secret) = generate_keypair();
+    let (sender_public, sender_secret) = generate_keypair();
+    let (other_public, _) = generate_keypair();
+    let aead = AeadAlgorithm::ChaCha20Poly1305;
+
+    let (enc, ciphertext) = seal(aead, &recipient_public, b"info", b"aad", b"hello", Some(&sender_secret)).unwrap();
+    assert_eq!(open(aead, &enc, &recipient_secret, b"info", b"aad", &ciphertext, Some(&sender_public)).unwrap(), b"hello");
+    // Wrong sender, or opening in base mode, fails.
+    assert!(open(aead, &enc, &recipient_secret, b"info", b"aad", &ciphertext, Some(&other_public)).is_err());
+    assert!(open(aead, &enc, &recipient_secret, b"info", b"aad", &ciphertext, None).is_err());
+
+    let (enc, mut sender) = setup_auth_sender(aead, &recipient_public, b"", &sender_secret).unwrap();
+    let recipient = setup_auth_recipient(aead, &enc, &recipient_secret, b"", &sender_public).unwrap();
+    assert_eq!(sender.export(b"label", 64).unwrap(), recipient.export(b"label", 64).unwrap());
+    assert!(sender.seal(b"", b"x").is_ok());
+}
+
+#[test]
+fn test_tampering_and_bad_input() {
+    let (public_key, secret_key) = generate_keypair();
+    let aead = AeadAlgorithm::Aes128Gcm;
+    let (enc, ciphertext) = seal(aead, &public_key, b"info", b"aad", b"message", None).unwrap();
+    let mut tampered = ciphertext.clone();
+    tampered[0] ^= 1;
+    assert!(matches!(open(aead, &enc, &secret_key, b"info", b"aad", &tampered, None), Err(HpkeError::Aead(_))));
+    assert!(open(aead, &enc, &secret_key, b"other", b"aad", &ciphertext, None).is_err());
+    assert!(open(aead, &enc, &secret_key, b"info", b"", &ciphertext, None).is_err());
+    assert!(open(AeadAlgorithm::Aes256Gcm, &enc, &secret_key, b"info", b"aad", &ciphertext, None).is_err());
+    assert!(matches!(open(aead, &enc[..16], &secret_key, b"info", b"aad", &ciphertext, None), Err(HpkeError::KeyExchange(_))));
+
+    assert_eq!(parse_aead("AES-256-GCM").unwrap(), AeadAlgorithm::Aes256Gcm);
+    assert!(matches!(parse_aead("aes-ocb"), Err(HpkeError::UnknownAead(_))));
+}
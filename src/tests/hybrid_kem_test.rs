@@ -0,0 +1,57 @@
+use std::fs;
+use crate::file_encryption::{decrypt_file_for_identity, encrypt_file_for_recipients, FileEncryptionError, Identity, Recipient};
+use crate::hybrid_kem::{decapsulate, encapsulate, generate_keypair, open, public_key, seal, HybridKemError, CIPHERTEXT_LEN, PUBLIC_KEY_LEN};
+use crate::ml_kem;
+
+#[test]
+fn test_hybrid_kem() {
+    let (public, secret) = generate_keypair();
+    assert_eq!(public.len(), PUBLIC_KEY_LEN);
+    assert_eq!(public_key(&secret).unwrap(), public);
+    let (ciphertext, shared_secret) = encapsulate(&public).unwrap();
+    assert_eq!(ciphertext.len(), CIPHERTEXT_LEN);
+    assert_eq!(decapsulate(&secret, &ciphertext).unwrap(), shared_secret);
+    assert_ne!(encapsulate(&public).unwrap().1, shared_secret);
+
+    // Tampering with either half changes the secret rather than failing.
+    for position in [0, ml_kem::CIPHERTEXT_LEN + 1] {
+        let mut tampered = ciphertext.clone();
+        tampered[position] ^= 1;
+        assert_ne!(decapsulate(&secret, &tampered).unwrap(), shared_secret);
+    }
+    let (_, other_secret) = generate_keypair();
+    assert_ne!(decapsulate(&other_secret, &ciphertext).unwrap(), shared_secret);
+    assert!(matches!(encapsulate(&public[..32]), Err(HybridKemError::InvalidPublicKey)));
+    assert!(matches!(decapsulate(&secret, &ciphertext[1..]), Err(HybridKemError::InvalidCiphertext)));
+
+    let sealed = seal(&public, b"post-quantum secret").unwrap();
+    assert_eq!(open(&secret, &sealed).unwrap(), b"post-quantum secret");
+    assert!(open(&other_secret, &sealed).is_err());
+}
+
+#[test]
+fn test_hybrid_file_recipient() {
+    let input_path = "test_hybrid_input.txt";
+    let encrypted_path = "test_hybrid_encrypted.bin";
+    let decrypted_path = "test_hybrid_decrypted.txt";
+    fs::write(input_path, b"archived for decades").unwrap();
+    let (alice_public, alice_secret) = generate_keypair();
+    let (_, mallory_secret) = generate_keypair();
+    let (bob_public, bob_secret) = crate::key_exchange::generate_keypair();
+
+    encrypt_file_for_recipients(input_path, encrypted_path, &[Recipient::Hybrid(alice_public), Recipient::X25519(bob_public)]).unwrap();
+    for identity in [Identity::Hybrid(alice_secret), Identity::X25519(bob_secret)] {
+        decrypt_file_for_identity(encrypted_path, decrypted_path, &identity).unwrap();
+        assert_eq!(fs::read(decrypted_path).unwrap(), b"archived for decades");
+    }
+    assert!(matches!(
+        decrypt_file_for_identity(encrypted_path, decrypted_path, &Identity::Hybrid(mallory_secret)),
+        Err(FileEncryptionError::NoMatchingRecipient)
+    ));
+    // The hybrid key's secret is not an X25519 key for the same file.
+    assert!(decrypt_file_for_identity(encrypted_path, decrypted_path, &Identity::X25519(alice_secret)).is_err());
+
+    fs::remove_file(input_path).unwrap();
+    fs::remove_file(encrypted_path).unwrap();
+    fs::remove_file(decrypted_path).unwrap();
+}
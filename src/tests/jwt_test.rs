@@ -0,0 +1,77 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use serde_json::json;
+use crate::jwt::{issue_jwt, sign_compact, verify_jwt, verify_jwt_at, jwks, jwk_thumbprint, JwtError, JwtValidation};
+use crate::signing::{generate_keypair, sign_message};
+
+// RFC 8037 appendix A.1 key and A.3/A.4 thumbprint and signature.
+const RFC8037_D: &str = "nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A";
+const RFC8037_X: &str = "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo";
+const RFC8037_THUMBPRINT: &str = "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k";
+const RFC8037_JWS: &str = "eyJhbGciOiJFZERTQSJ9.RXhhbXBsZSBvZiBFZDI1NTE5IHNpZ25pbmc.hgyY0il_MGCjP0JzlnLWG1PPOt7-09PGcvMg3AIbQR6dWbhijcNR4ki4iylGjg5BhVsPt9g7sVvpAr_MuM0KAg";
+
+#[test]
+fn test_rfc8037_vectors() {
+    let secret_key = BASE64_URL.decode(RFC8037_D).unwrap();
+    let public_key: [u8; 32] = BASE64_URL.decode(RFC8037_X).unwrap().try_into().unwrap();
+    assert_eq!(jwk_thumbprint(&public_key), RFC8037_THUMBPRINT);
+    let jws = sign_compact(&secret_key, &json!({"alg": "EdDSA"}), b"Example of Ed25519 signing").unwrap();
+    assert_eq!(jws, RFC8037_JWS);
+
+    let set = jwks(&[public_key]);
+    assert_eq!(set["keys"][0]["x"], RFC8037_X);
+    assert_eq!(set["keys"][0]["kid"], RFC8037_THUMBPRINT);
+    assert_eq!(set["keys"][0]["crv"], "Ed25519");
+}
+
+#[test]
+fn test_issue_and_validate_claims() {
+    let (public_key, secret_key) = generate_keypair().unwrap();
+    let now = 1_700_000_000;
+    let claims = json!({"sub": "alice", "iss": "qimem", "aud": ["api", "web"], "iat": now, "nbf": now, "exp": now + 3600});
+    let token = issue_jwt(&secret_key, &claims).unwrap();
+    let validation = JwtValidation {
+        issuer: Some("qimem".to_string()),
+        audience: Some("api".to_string()),
+        ..JwtValidation::default()
+    };
+    assert_eq!(verify_jwt_at(&token, &public_key, &validation, now).unwrap()["sub"], "alice");
+
+    let at = |time| verify_jwt_at(&token, &public_key, &validation, time);
+    assert!(matches!(at(now + 3600 + 60), Err(JwtError::Expired { .. })));
+    assert!(matches!(at(now - 61), Err(JwtError::NotYetValid { .. })));
+
+    let wrong_issuer = JwtValidation { issuer: Some("other".to_string()), ..validation.clone() };
+    assert!(matches!(verify_jwt_at(&token, &public_key, &wrong_issuer, now), Err(JwtError::InvalidIssuer)));
+    let wrong_audience = JwtValidation { audience: Some("mobile".to_string()), ..validation.clone() };
+    assert!(matches!(verify_jwt_at(&token, &public_key, &wrong_audience, now), Err(JwtError::InvalidAudience)));
+    // A token addressed to an audience is not accepted by a verifier that names none.
+    let no_audience = JwtValidation { audience: None, ..validation.clone() };
+    assert!(matches!(verify_jwt_at(&token, &public_key, &no_audience, now), Err(JwtError::InvalidAudience)));
+
+    let (other_public_key, _) = generate_keypair().unwrap();
+    assert!(matches!(verify_jwt_at(&token, &other_public_key, &validation, now), Err(JwtError::InvalidSignature)));
+}
+
+#[test]
+fn test_header_and_required_claims() {
+    let (public_key, secret_key) = generate_keypair().unwrap();
+    let token = issue_jwt(&secret_key, &json!({"sub": "bob"})).unwrap();
+    let header: serde_json::Value = serde_json::from_slice(&BASE64_URL.decode(token.split('.').next().unwrap()).unwrap()).unwrap();
+    assert_eq!(header["alg"], "EdDSA");
+    assert_eq!(header["kid"], jwk_thumbprint(&public_key));
+
+    assert!(matches!(verify_jwt(&token, &public_key, &JwtValidation::default()), Err(JwtError::MissingClaim("exp"))));
+    let lenient = JwtValidation { require_exp: false, ..JwtValidation::default() };
+    assert!(verify_jwt(&token, &public_key, &lenient).unwrap()["iat"].is_i64());
+
+    // alg "none" and HMAC headers are refused before the signature is looked at.
+    for alg in ["none", "HS256"] {
+        let header = BASE64_URL.encode(json!({"alg": alg}).to_string());
+        let payload = BASE64_URL.encode(r#"{"sub":"bob"}"#);
+        let signing_input = format!("{}.{}", header, payload);
+        let signature = BASE64_URL.encode(sign_message(&secret_key, signing_input.as_bytes()).unwrap());
+        let forged = format!("{}.{}", signing_input, signature);
+        assert!(matches!(verify_jwt(&forged, &public_key, &lenient), Err(JwtError::UnsupportedAlgorithm(_))));
+    }
+    assert!(matches!(issue_jwt(&secret_key, &json!(["not", "an", "object"])), Err(JwtError::InvalidClaims)));
+}
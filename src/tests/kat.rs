@@ -0,0 +1,26 @@
+//! Shared reader for the `name = value` known-answer files in `vectors/`.
+
+use std::collections::HashMap;
+use sha3::{Digest, Sha3_256};
+use crate::utils::to_hex;
+
+/// Splits a vector file into blank-line separated blocks of `name = value`
+/// lines. `#` comment lines and empty values (`msg =`) are allowed.
+pub fn parse(text: &str) -> Vec<HashMap<&str, &str>> {
+    text.split("\n\n")
+        .map(|block| {
+            block
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .filter_map(|line| line.split_once(" ="))
+                .map(|(name, value)| (name, value.trim()))
+                .collect::<HashMap<_, _>>()
+        })
+        .filter(|vector| !vector.is_empty())
+        .collect()
+}
+
+/// Hex SHA3-256 digest, used for keys and ciphertexts too large to inline.
+pub fn sha3_hex(data: &[u8]) -> String {
+    to_hex(&Sha3_256::digest(data))
+}
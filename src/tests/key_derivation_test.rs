@@ -1,9 +1,9 @@
-use crate::q_keygen::derive_key;
+use crate::q_keygen::{derive_key, derive_key_with_context, derive_key_from_params, decode_salt, KdfParams, KeyGenError};
 
 #[test]
 fn test_user_salt() {
-    let (key1, salt1) = derive_key("password", Some("saltphrase")).unwrap();
-    let (key2, salt2) = derive_key("password", Some("saltphrase")).unwrap();
+    let (key1, salt1) = derive_key("password", Some(b"saltphrase")).unwrap();
+    let (key2, salt2) = derive_key("password", Some(b"saltphrase")).unwrap();
     assert_eq!(key1, key2);
     assert_eq!(salt1, salt2);
 }
@@ -18,8 +18,52 @@ fn test_random_salt() {
 
 #[test]
 fn test_invalid_salt() {
-    let (key1, salt1) = derive_key("password", Some("saltphrase1")).unwrap();
-    let (key2, salt2) = derive_key("password", Some("saltphrase2")).unwrap();
+    let (key1, salt1) = derive_key("password", Some(b"saltphrase1")).unwrap();
+    let (key2, salt2) = derive_key("password", Some(b"saltphrase2")).unwrap();
     assert_ne!(key1, key2);
     assert_ne!(salt1, salt2);
-}
\ No newline at end of file
+    assert!(derive_key("password", Some(b"short")).is_err());
+}
+
+#[test]
+fn test_params_round_trip() {
+    let (key, params) = derive_key_with_context("password", b"qimem-test").unwrap();
+    assert_eq!(KdfParams::from_bytes(&params).unwrap().context, b"qimem-test");
+    assert_eq!(derive_key_from_params("password", &params).unwrap(), key);
+    assert_ne!(derive_key_from_params("wrong", &params).unwrap(), key);
+    assert!(derive_key_from_params("password", &params[..params.len() - 1]).is_err());
+}
+
+#[test]
+fn test_params_cost_limits() {
+    let (_, params) = derive_key_with_context("password", b"").unwrap();
+    // m_cost, t_cost and p_cost sit at bytes 1..5, 5..9 and 9..13.
+    for offset in [1, 5, 9] {
+        let mut crafted = params.clone();
+        crafted[offset..offset + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(KdfParams::from_bytes(&crafted), Err(KeyGenError::InvalidParams(_))));
+        assert!(derive_key_from_params("password", &crafted).is_err());
+    }
+}
+
+#[test]
+fn test_context_separates_keys() {
+    let params = KdfParams::with_salt(b"0123456789abcdef").unwrap();
+    let plain = params.derive_key("password").unwrap();
+    let a = params.clone().with_context(b"app-a").derive_key("password").unwrap();
+    let b = params.with_context(b"app-b").derive_key("password").unwrap();
+    assert_ne!(plain, a);
+    assert_ne!(a, b);
+}
+
+#[test]
+fn test_decode_salt() {
+    let raw = b"0123456789abcdef".to_vec();
+    assert_eq!(decode_salt("hex:30313233343536373839616263646566").unwrap(), raw);
+    assert_eq!(decode_salt("base64:MDEyMzQ1Njc4OWFiY2RlZg==").unwrap(), raw);
+    assert_eq!(decode_salt("base64:MDEyMzQ1Njc4OWFiY2RlZg").unwrap(), raw);
+    assert!(decode_salt("hex:not a salt!").is_err());
+    // Unprefixed strings are never guessed at, even when they'd decode.
+    assert!(decode_salt("30313233343536373839616263646566").is_err());
+    assert!(matches!(decode_salt("companysalt2024"), Err(KeyGenError::SaltInvalid(_))));
+}
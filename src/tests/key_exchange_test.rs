@@ -0,0 +1,48 @@
+use crate::key_exchange::{generate_keypair, public_key, diffie_hellman, derive_shared_key, seal, open, KeyExchangeError};
+use crate::utils::{from_hex, to_hex};
+
+// RFC 7748 section 6.1.
+const ALICE_SECRET: &str = "77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a";
+const ALICE_PUBLIC: &str = "8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a";
+const BOB_SECRET: &str = "5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb";
+const BOB_PUBLIC: &str = "de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f";
+const SHARED: &str = "4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742";
+
+#[test]
+fn test_rfc7748_vector() {
+    let alice = from_hex(ALICE_SECRET).unwrap();
+    let bob = from_hex(BOB_SECRET).unwrap();
+    assert_eq!(to_hex(&public_key(&alice).unwrap()), ALICE_PUBLIC);
+    assert_eq!(to_hex(&public_key(&bob).unwrap()), BOB_PUBLIC);
+    assert_eq!(to_hex(&diffie_hellman(&alice, &from_hex(BOB_PUBLIC).unwrap()).unwrap()), SHARED);
+    assert_eq!(to_hex(&diffie_hellman(&bob, &from_hex(ALICE_PUBLIC).unwrap()).unwrap()), SHARED);
+}
+
+#[test]
+fn test_shared_key_agreement() {
+    let (alice_public, alice_secret) = generate_keypair();
+    let (bob_public, bob_secret) = generate_keypair();
+    let alice_key = derive_shared_key(&alice_secret, &bob_public, b"session").unwrap();
+    assert_eq!(alice_key, derive_shared_key(&bob_secret, &alice_public, b"session").unwrap());
+    assert_ne!(alice_key, derive_shared_key(&bob_secret, &alice_public, b"other").unwrap());
+
+    // The all-zero point (and other low-order points) are refused.
+    assert!(matches!(diffie_hellman(&alice_secret, &[0u8; 32]), Err(KeyExchangeError::LowOrderPoint)));
+    assert!(matches!(derive_shared_key(&alice_secret, &[1u8; 16], b""), Err(KeyExchangeError::InvalidKeyLength)));
+}
+
+#[test]
+fn test_sealed_box() {
+    let (public, secret) = generate_keypair();
+    let (_, other_secret) = generate_keypair();
+    let sealed = seal(&public, b"for your eyes only").unwrap();
+    assert_eq!(open(&secret, &sealed).unwrap(), b"for your eyes only");
+    // Fresh ephemeral key every time.
+    assert_ne!(sealed, seal(&public, b"for your eyes only").unwrap());
+
+    assert!(matches!(open(&other_secret, &sealed), Err(KeyExchangeError::Encryption(_))));
+    let mut tampered = sealed.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(open(&secret, &tampered).is_err());
+    assert!(matches!(open(&secret, &sealed[..20]), Err(KeyExchangeError::InvalidData)));
+}
@@ -0,0 +1,84 @@
+use crate::key_wrap::{wrap_key, unwrap_key, wrap_key_padded, unwrap_key_padded, KeyWrapError};
+use crate::key_store::KeyStore;
+use crate::utils::{from_hex, to_hex};
+
+// RFC 3394 section 4: (KEK, key data, wrapped).
+const RFC3394_VECTORS: [(&str, &str, &str); 4] = [
+    ("000102030405060708090A0B0C0D0E0F",
+     "00112233445566778899AABBCCDDEEFF",
+     "1fa68b0a8112b447aef34bd8fb5a7b829d3e862371d2cfe5"),
+    ("000102030405060708090A0B0C0D0E0F1011121314151617",
+     "00112233445566778899AABBCCDDEEFF",
+     "96778b25ae6ca435f92b5b97c050aed2468ab8a17ad84e5d"),
+    ("000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F",
+     "00112233445566778899AABBCCDDEEFF",
+     "64e8c3f9ce0f5ba263e9777905818a2a93c8191e7d6e8ae7"),
+    ("000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F",
+     "00112233445566778899AABBCCDDEEFF000102030405060708090A0B0C0D0E0F",
+     "28c9f404c4b810f4cbccb35cfb87f8263f5786e2d80ed326cbc7f0e71a99f43bfb988b9b7a02dd21"),
+];
+
+// RFC 5649 section 6.
+const RFC5649_KEK: &str = "5840df6e29b02af1ab493b705bf16ea1ae8338f4dcc176a8";
+const RFC5649_VECTORS: [(&str, &str); 2] = [
+    ("c37b7e6492584340bed12207808941155068f738",
+     "138bdeaa9b8fa7fc61f97742e72248ee5ae6ae5360d1ae6a5f54f373fa543b6a"),
+    ("466f7250617369", "afbeb0f07dfbf5419200f2ccb50bb24f"),
+];
+
+#[test]
+fn test_rfc3394_vectors() {
+    for (kek, key, wrapped) in RFC3394_VECTORS {
+        let kek = from_hex(&kek.to_lowercase()).unwrap();
+        let key = from_hex(&key.to_lowercase()).unwrap();
+        assert_eq!(to_hex(&wrap_key(&kek, &key).unwrap()), wrapped);
+        assert_eq!(unwrap_key(&kek, &from_hex(wrapped).unwrap()).unwrap(), key);
+    }
+}
+
+#[test]
+fn test_rfc5649_vectors() {
+    let kek = from_hex(RFC5649_KEK).unwrap();
+    for (key, wrapped) in RFC5649_VECTORS {
+        let key = from_hex(key).unwrap();
+        assert_eq!(to_hex(&wrap_key_padded(&kek, &key).unwrap()), wrapped);
+        assert_eq!(unwrap_key_padded(&kek, &from_hex(wrapped).unwrap()).unwrap(), key);
+    }
+}
+
+#[test]
+fn test_tampering_and_bad_input() {
+    let kek = [7u8; 32];
+    let mut wrapped = wrap_key(&kek, &[1u8; 32]).unwrap();
+    wrapped[10] ^= 1;
+    assert!(matches!(unwrap_key(&kek, &wrapped), Err(KeyWrapError::IntegrityCheckFailed)));
+    assert!(matches!(unwrap_key(&[8u8; 32], &wrap_key(&kek, &[1u8; 32]).unwrap()), Err(KeyWrapError::IntegrityCheckFailed)));
+
+    // A padded blob must not unwrap as an unpadded one and vice versa.
+    let padded = wrap_key_padded(&kek, &[1u8; 32]).unwrap();
+    assert!(unwrap_key(&kek, &padded).is_err());
+    assert!(unwrap_key_padded(&kek, &wrap_key(&kek, &[1u8; 32]).unwrap()).is_err());
+
+    assert!(matches!(wrap_key(&[0u8; 20], &[1u8; 16]), Err(KeyWrapError::InvalidKek)));
+    assert!(matches!(wrap_key(&kek, &[1u8; 12]), Err(KeyWrapError::InvalidLength(12))));
+    assert!(matches!(wrap_key_padded(&kek, &[]), Err(KeyWrapError::InvalidLength(0))));
+}
+
+#[test]
+fn test_keystore_wrapped_export() {
+    let path = "/tmp/qimem_test_key_wrap_keys.bin";
+    let _ = std::fs::remove_file(path);
+    let kek = [3u8; 32];
+    let mut keystore = KeyStore::new(path, "correct horse battery staple").unwrap();
+    keystore.store_key("partner", [5; 32]).unwrap();
+
+    let wrapped = keystore.export_wrapped_key("partner", &kek).unwrap();
+    assert_eq!(unwrap_key(&kek, &wrapped).unwrap(), vec![5; 32]);
+    assert!(keystore.export_wrapped_key("missing", &kek).is_err());
+
+    keystore.import_wrapped_key("imported", &wrapped, &kek).unwrap();
+    assert_eq!(keystore.retrieve_key("imported"), Some([5; 32]));
+    assert!(keystore.import_wrapped_key("bad", &wrapped, &[4u8; 32]).is_err());
+
+    let _ = std::fs::remove_file(path);
+}
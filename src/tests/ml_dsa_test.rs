@@ -0,0 +1,50 @@
+use crate::ml_dsa::{
+    generate_keypair, keypair_from_seed, sign, sign_with_randomness, verify, MlDsaError, PUBLIC_KEY_LEN, SECRET_KEY_LEN,
+    SIGNATURE_LEN,
+};
+use crate::tests::kat::{parse, sha3_hex};
+use crate::utils::from_hex;
+
+const VECTORS: &str = include_str!("vectors/ml_dsa_65.txt");
+
+#[test]
+fn test_known_answers() {
+    let vectors = parse(VECTORS);
+    assert_eq!(vectors.len(), 16);
+    for vector in vectors {
+        let hex = |name| from_hex(vector[name]).unwrap();
+        let (public_key, secret_key) = keypair_from_seed(&hex("xi").try_into().unwrap());
+        assert_eq!(sha3_hex(&public_key), vector["pk_sha3_256"], "count {}", vector["count"]);
+        assert_eq!(sha3_hex(&secret_key), vector["sk_sha3_256"], "count {}", vector["count"]);
+
+        let (message, context) = (hex("msg"), hex("ctx"));
+        let signature = sign_with_randomness(&secret_key, &message, &context, &hex("rnd").try_into().unwrap()).unwrap();
+        assert_eq!(sha3_hex(&signature), vector["sig_sha3_256"], "count {}", vector["count"]);
+        assert!(verify(&public_key, &message, &context, &signature).unwrap());
+        assert!(!verify(&public_key, &message, b"other context", &signature).unwrap());
+    }
+}
+
+#[test]
+fn test_sign_and_verify() {
+    let (public_key, seed) = generate_keypair();
+    let (expanded_public, secret_key) = keypair_from_seed(&seed);
+    assert_eq!(expanded_public, public_key);
+    assert_eq!((public_key.len(), secret_key.len()), (PUBLIC_KEY_LEN, SECRET_KEY_LEN));
+    let signature = sign(&secret_key, b"release v2.0", b"").unwrap();
+    assert_eq!(signature.len(), SIGNATURE_LEN);
+    assert!(verify(&public_key, b"release v2.0", b"", &signature).unwrap());
+    // Hedged signing: the same message signs differently each time.
+    assert_ne!(sign(&secret_key, b"release v2.0", b"").unwrap(), signature);
+    assert!(!verify(&public_key, b"release v2.1", b"", &signature).unwrap());
+    assert!(!verify(&generate_keypair().0, b"release v2.0", b"", &signature).unwrap());
+
+    for position in [0, 100, SIGNATURE_LEN - 1] {
+        let mut tampered = signature.clone();
+        tampered[position] ^= 1;
+        assert!(!verify(&public_key, b"release v2.0", b"", &tampered).unwrap(), "byte {position}");
+    }
+    assert!(matches!(verify(&public_key, b"m", b"", &signature[1..]), Err(MlDsaError::InvalidSignature)));
+    assert!(matches!(verify(&public_key[1..], b"m", b"", &signature), Err(MlDsaError::InvalidPublicKey)));
+    assert!(matches!(sign(&secret_key, b"m", &[0; 256]), Err(MlDsaError::ContextTooLong)));
+}
@@ -0,0 +1,53 @@
+use crate::ml_kem::{
+    decapsulate, encapsulate, encapsulate_deterministic, generate_keypair, keypair_from_seed, MlKemError, CIPHERTEXT_LEN,
+    PUBLIC_KEY_LEN, SECRET_KEY_LEN,
+};
+use crate::tests::kat::{parse, sha3_hex};
+use crate::utils::{from_hex, to_hex};
+
+const VECTORS: &str = include_str!("vectors/ml_kem_768.txt");
+
+#[test]
+fn test_openssl_known_answers() {
+    let vectors = parse(VECTORS);
+    assert_eq!(vectors.len(), 20);
+    for vector in vectors {
+        let hex = |name| from_hex(vector[name]).unwrap();
+        let seed: [u8; 64] = [hex("d"), hex("z")].concat().try_into().unwrap();
+        let (ek, dk) = keypair_from_seed(&seed);
+        assert_eq!(sha3_hex(&ek), vector["ek_sha3_256"], "count {}", vector["count"]);
+        assert_eq!(sha3_hex(&dk), vector["dk_sha3_256"], "count {}", vector["count"]);
+
+        let (ciphertext, shared_secret) = encapsulate_deterministic(&ek, &hex("m").try_into().unwrap()).unwrap();
+        assert_eq!(sha3_hex(&ciphertext), vector["c_sha3_256"], "count {}", vector["count"]);
+        assert_eq!(to_hex(&shared_secret), vector["k"]);
+        assert_eq!(to_hex(&decapsulate(&dk, &ciphertext).unwrap()), vector["k"]);
+
+        let flipped: usize = vector["flipped_bit"].parse().unwrap();
+        let mut tampered = ciphertext.clone();
+        tampered[flipped / 8] ^= 1 << (flipped % 8);
+        assert_eq!(to_hex(&decapsulate(&dk, &tampered).unwrap()), vector["k_rejected"]);
+    }
+}
+
+#[test]
+fn test_input_checks() {
+    let (ek, dk) = generate_keypair();
+    assert_eq!((ek.len(), dk.len()), (PUBLIC_KEY_LEN, SECRET_KEY_LEN));
+    let (ciphertext, shared_secret) = encapsulate(&ek).unwrap();
+    assert_eq!(ciphertext.len(), CIPHERTEXT_LEN);
+    assert_eq!(decapsulate(&dk, &ciphertext).unwrap(), shared_secret);
+
+    // A coefficient of 4095 (>= q) fails the modulus check.
+    let mut unreduced = ek.clone();
+    unreduced[0] = 0xFF;
+    unreduced[1] |= 0x0F;
+    assert!(matches!(encapsulate(&unreduced), Err(MlKemError::InvalidPublicKey)));
+    assert!(matches!(encapsulate(&ek[1..]), Err(MlKemError::InvalidPublicKey)));
+    assert!(matches!(decapsulate(&dk, &ciphertext[1..]), Err(MlKemError::InvalidCiphertext)));
+
+    // The hash of the embedded public key must match.
+    let mut corrupted = dk.clone();
+    corrupted[1200] ^= 1;
+    assert!(matches!(decapsulate(&corrupted, &ciphertext), Err(MlKemError::InvalidSecretKey)));
+}
@@ -0,0 +1,79 @@
+use crate::mnemonic::{encode, decode, encode_totp_secret, decode_totp_secret, MnemonicError};
+use crate::utils::from_hex;
+use crate::key_store::KeyStore;
+
+// Vectors from the BIP39 reference test suite (English, entropy -> mnemonic).
+const VECTORS: [(&str, &str); 6] = [
+    ("00000000000000000000000000000000",
+     "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"),
+    ("7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+     "legal winner thank year wave sausage worth useful legal winner thank yellow"),
+    ("ffffffffffffffffffffffffffffffffffffffffffffffff",
+     "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo when"),
+    ("9e885d952ad362caeb4efe34a8e91bd2",
+     "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic"),
+    ("6610b25967cdcca9d59875f5cb50b0ea75433311869e930b",
+     "gravity machine north sort system female filter attitude volume fold club stay feature office ecology stable narrow fog"),
+    ("68a79eaca2324873eacc50cb9c6eca8cc68ea5d936f98787c60c7ebc74e6ce7c",
+     "hamster diagram private dutch cause delay private meat slide toddler razor book happy fancy gospel tennis maple dilemma loan word shrug inflict delay length"),
+];
+
+#[test]
+fn test_bip39_vectors() {
+    for (hex, phrase) in VECTORS {
+        let data = from_hex(hex).unwrap();
+        assert_eq!(encode(&data).unwrap(), phrase);
+        assert_eq!(decode(phrase).unwrap(), data);
+    }
+}
+
+#[test]
+fn test_key_round_trip_and_abbreviations() {
+    let key: Vec<u8> = (100..132).collect();
+    let phrase = encode(&key).unwrap();
+    assert_eq!(phrase.split(' ').count(), 24);
+    let abbreviated: Vec<String> = phrase
+        .split(' ')
+        .map(|w| w.chars().take(4).collect::<String>().to_uppercase())
+        .collect();
+    assert_eq!(decode(&abbreviated.join("  ")).unwrap(), key);
+}
+
+#[test]
+fn test_typo_detection() {
+    let phrase = VECTORS[3].1;
+    let misspelt = phrase.replace("drill", "drlil");
+    match decode(&misspelt) {
+        Err(MnemonicError::UnknownWord { position, suggestions, .. }) => {
+            assert_eq!(position, 2);
+            assert!(suggestions.contains(&"drill".to_string()));
+        }
+        other => panic!("expected unknown word, got {:?}", other),
+    }
+    let swapped = phrase.replace("ozone drill", "drill ozone");
+    assert!(matches!(decode(&swapped), Err(MnemonicError::ChecksumMismatch)));
+    assert!(matches!(decode("abandon about"), Err(MnemonicError::InvalidWordCount(2))));
+    assert!(matches!(encode(&[0u8; 17]), Err(MnemonicError::InvalidLength(17))));
+}
+
+#[test]
+fn test_totp_secret() {
+    let secret = "AAECAwQFBgcICQoLDA0ODxAREhM=";
+    let phrase = encode_totp_secret(secret).unwrap();
+    assert_eq!(phrase.split(' ').count(), 15);
+    assert_eq!(decode_totp_secret(&phrase).unwrap(), secret);
+}
+
+#[test]
+fn test_keystore_mnemonic_unlock() {
+    let path = "/tmp/qimem_test_mnemonic_keys.bin";
+    let _ = std::fs::remove_file(path);
+    let mut keystore = KeyStore::new(path, "correct horse battery staple").unwrap();
+    keystore.store_key("backup", [5; 32]).unwrap();
+
+    let phrase = keystore.master_key_mnemonic().unwrap();
+    let restored = KeyStore::open_with_mnemonic(path, &phrase).unwrap();
+    assert_eq!(restored.retrieve_key("backup"), Some([5; 32]));
+
+    let _ = std::fs::remove_file(path);
+}
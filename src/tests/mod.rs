@@ -1,5 +1,7 @@
 // This file declares all the other files in `src/tests` as modules.
 
+#[cfg(test)]
+pub mod kat;
 #[cfg(test)]
 pub mod cipher_test;
 #[cfg(test)]
@@ -8,6 +10,48 @@ pub mod file_encryption_test;
 pub mod key_derivation_test;
 #[cfg(test)]
 pub mod signing_test;
+#[cfg(test)]
+pub mod secret_generation_test;
+#[cfg(test)]
+pub mod password_strength_test;
+#[cfg(test)]
+pub mod shamir_test;
+#[cfg(test)]
+pub mod mnemonic_test;
+#[cfg(test)]
+pub mod key_wrap_test;
+#[cfg(test)]
+pub mod token_test;
+#[cfg(test)]
+pub mod paseto_test;
+#[cfg(test)]
+pub mod jwt_test;
+#[cfg(test)]
+pub mod key_format_test;
+#[cfg(test)]
+pub mod key_exchange_test;
+#[cfg(test)]
+pub mod hpke_test;
+#[cfg(test)]
+pub mod age_format_test;
+#[cfg(test)]
+pub mod envelope_test;
+#[cfg(test)]
+pub mod fingerprint_test;
+#[cfg(test)]
+pub mod transparency_log_test;
+#[cfg(test)]
+pub mod double_ratchet_test;
+#[cfg(test)]
+pub mod ml_kem_test;
+#[cfg(test)]
+pub mod hybrid_kem_test;
+#[cfg(test)]
+pub mod ml_dsa_test;
+#[cfg(test)]
+pub mod noise_test;
+#[cfg(test)]
+pub mod totp_test;
 
 // Add a dummy test for the KeyStore to fix the final error
 #[cfg(test)]
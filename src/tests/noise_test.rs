@@ -0,0 +1,201 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::net::UnixStream;
+use std::thread;
+use crate::key_exchange;
+use crate::key_store::KeyStore;
+use crate::noise::{ChannelConfig, HandshakePattern, HandshakeState, HashFunction, NoiseError, NoiseParams, SecureStream, TrustedPeers};
+use crate::signing;
+
+const PROTOCOLS: [&str; 6] = [
+    "Noise_XX_25519_ChaChaPoly_SHA256",
+    "Noise_XX_25519_ChaChaPoly_BLAKE2s",
+    "Noise_XX_25519_ChaChaPoly_BLAKE2b",
+    "Noise_IK_25519_ChaChaPoly_SHA256",
+    "Noise_IK_25519_ChaChaPoly_BLAKE2s",
+    "Noise_IK_25519_ChaChaPoly_BLAKE2b",
+];
+
+fn config(params: NoiseParams, static_secret: [u8; 32]) -> ChannelConfig {
+    ChannelConfig { params, static_secret, identity_secret: None, remote_static: None, trusted_peers: TrustedPeers::default() }
+}
+
+/// Runs a handshake between our implementation (on the side given by
+/// `initiator`) and snow, then exchanges transport messages both ways.
+fn interop(name: &str, initiator: bool) {
+    let params = NoiseParams::from_name(name).unwrap();
+    assert_eq!(params.name(), name);
+    let (ours_public, ours_secret) = key_exchange::generate_keypair();
+    let (theirs_public, theirs_secret) = key_exchange::generate_keypair();
+    let ik = params.pattern == HandshakePattern::IK;
+
+    let mut builder = snow::Builder::new(name.parse().unwrap()).local_private_key(&theirs_secret).prologue(b"interop");
+    if ik && !initiator {
+        builder = builder.remote_public_key(&ours_public);
+    }
+    let mut snow = if initiator { builder.build_responder() } else { builder.build_initiator() }.unwrap();
+    let remote = (ik && initiator).then_some(theirs_public);
+    let mut ours = HandshakeState::new(params, initiator, b"interop", ours_secret, remote).unwrap();
+
+    let mut buffer = vec![0u8; 65535];
+    while !ours.is_finished() {
+        if ours.is_my_turn() {
+            let message = ours.write_message(b"from qimem").unwrap();
+            let len = snow.read_message(&message, &mut buffer).unwrap();
+            assert_eq!(&buffer[..len], b"from qimem");
+        } else {
+            let len = snow.write_message(b"from snow", &mut buffer).unwrap();
+            assert_eq!(ours.read_message(&buffer[..len]).unwrap(), b"from snow");
+        }
+    }
+    assert!(snow.is_handshake_finished());
+    assert_eq!(ours.remote_static(), Some(theirs_public));
+    assert_eq!(snow.get_remote_static().unwrap(), ours_public);
+    let handshake_hash = snow.get_handshake_hash().to_vec();
+    let mut ours = ours.into_transport().unwrap();
+    let mut snow = snow.into_transport_mode().unwrap();
+    assert_eq!(ours.handshake_hash(), handshake_hash);
+
+    for i in 0..3u8 {
+        let ciphertext = ours.encrypt(&[i; 100]).unwrap();
+        let len = snow.read_message(&ciphertext, &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], [i; 100]);
+        let len = snow.write_message(&[i; 10], &mut buffer).unwrap();
+        assert_eq!(ours.decrypt(&buffer[..len]).unwrap(), [i; 10]);
+    }
+}
+
+#[test]
+fn test_snow_interop() {
+    for name in PROTOCOLS {
+        interop(name, true);
+        interop(name, false);
+    }
+}
+
+#[test]
+fn test_handshake_errors() {
+    assert!(matches!(NoiseParams::from_name("Noise_NN_25519_ChaChaPoly_SHA256"), Err(NoiseError::UnsupportedProtocol(_))));
+    assert!(matches!(NoiseParams::from_name("Noise_XX_448_ChaChaPoly_SHA256"), Err(NoiseError::UnsupportedProtocol(_))));
+    let ik = NoiseParams::new(HandshakePattern::IK, HashFunction::Sha256);
+    let (_, secret) = key_exchange::generate_keypair();
+    assert!(matches!(HandshakeState::new(ik, true, b"", secret, None), Err(NoiseError::MissingRemoteStatic)));
+
+    let xx = NoiseParams::new(HandshakePattern::XX, HashFunction::Blake2s);
+    let mut initiator = HandshakeState::new(xx, true, b"", secret, None).unwrap();
+    let mut responder = HandshakeState::new(xx, false, b"", key_exchange::generate_keypair().1, None).unwrap();
+    assert!(matches!(responder.write_message(b""), Err(NoiseError::UnexpectedMessage)));
+    responder.read_message(&initiator.write_message(b"").unwrap()).unwrap();
+    let mut message = responder.write_message(b"").unwrap();
+    assert!(matches!(initiator.read_message(&message[..40]), Err(NoiseError::MalformedMessage)));
+    message[40] ^= 1;
+    assert!(matches!(initiator.read_message(&message), Err(NoiseError::DecryptionFailed)));
+    assert!(matches!(responder.into_transport(), Err(NoiseError::UnexpectedMessage)));
+
+    // The initiator's IK view of the responder must match its real static key.
+    let (_, responder_secret) = key_exchange::generate_keypair();
+    let (wrong_public, _) = key_exchange::generate_keypair();
+    let mut initiator = HandshakeState::new(ik, true, b"", secret, Some(wrong_public)).unwrap();
+    let mut responder = HandshakeState::new(ik, false, b"", responder_secret, None).unwrap();
+    assert!(matches!(responder.read_message(&initiator.write_message(b"").unwrap()), Err(NoiseError::DecryptionFailed)));
+}
+
+#[test]
+fn test_secure_stream_over_tcp() {
+    let path = "/tmp/qimem_test_noise_store.bin";
+    let _ = std::fs::remove_file(path);
+    let (server_static_public, server_static) = key_exchange::generate_keypair();
+    let (client_static_public, client_static) = key_exchange::generate_keypair();
+    let (client_identity_public, client_identity) = signing::generate_keypair().unwrap();
+
+    // The server trusts the client's Ed25519 identity, not its static key.
+    let mut store = KeyStore::new(path, "correct horse battery staple").unwrap();
+    store.store_key("noise:static", server_static).unwrap();
+    let trusted = TrustedPeers { static_keys: Vec::new(), identities: vec![client_identity_public] };
+    trusted.save(&mut store, "noise:trusted").unwrap();
+    assert_eq!(TrustedPeers::load(&store, "noise:trusted").unwrap(), trusted);
+    assert_eq!(TrustedPeers::load(&store, "noise:missing").unwrap(), TrustedPeers::default());
+    assert!(matches!(TrustedPeers::from_bytes(&trusted.to_bytes()[1..]), Err(NoiseError::InvalidTrustedPeers)));
+    let store = KeyStore::new(path, "correct horse battery staple").unwrap();
+
+    for name in PROTOCOLS {
+        let params = NoiseParams::from_name(name).unwrap();
+        let server_config = ChannelConfig::from_key_store(&store, params, "noise:static", None, "noise:trusted").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut stream = SecureStream::accept(listener.accept().unwrap().0, &server_config).unwrap();
+            assert_eq!(stream.remote_static_key(), client_static_public);
+            assert_eq!(stream.remote_identity(), Some(client_identity_public));
+            let mut request = vec![0u8; 100_000];
+            stream.read_exact(&mut request).unwrap();
+            stream.write_all(&request.iter().map(|b| b.wrapping_add(1)).collect::<Vec<u8>>()).unwrap();
+            stream.send(b"bye").unwrap();
+        });
+
+        let mut client_config = config(params, client_static);
+        client_config.identity_secret = Some(client_identity);
+        client_config.remote_static = Some(server_static_public);
+        if params.pattern == HandshakePattern::XX {
+            client_config.remote_static = None;
+            client_config.trusted_peers.static_keys.push(server_static_public);
+        }
+        let mut stream = SecureStream::connect(TcpStream::connect(address).unwrap(), &client_config).unwrap();
+        assert_eq!(stream.remote_static_key(), server_static_public);
+        assert_eq!(stream.remote_identity(), None);
+        let request: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        stream.write_all(&request).unwrap();
+        let mut response = vec![0u8; request.len()];
+        stream.read_exact(&mut response).unwrap();
+        assert!(response.iter().zip(&request).all(|(r, q)| *r == q.wrapping_add(1)));
+        assert_eq!(stream.receive().unwrap().unwrap(), b"bye");
+        server.join().unwrap();
+        assert_eq!(stream.receive().unwrap(), None);
+    }
+    assert!(matches!(
+        ChannelConfig::from_key_store(&store, NoiseParams::from_name(PROTOCOLS[0]).unwrap(), "noise:absent", None, "noise:trusted"),
+        Err(NoiseError::KeyNotFound(_))
+    ));
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_secure_stream_rejects_untrusted_peers() {
+    let params = NoiseParams::new(HandshakePattern::XX, HashFunction::Sha256);
+    let (server_public, server_static) = key_exchange::generate_keypair();
+    let (client_public, client_static) = key_exchange::generate_keypair();
+
+    // The client does not trust the server, so it aborts before sending its static key.
+    let (client_socket, server_socket) = UnixStream::pair().unwrap();
+    let mut server_config = config(params, server_static);
+    server_config.trusted_peers.static_keys.push(client_public);
+    let server = thread::spawn(move || SecureStream::accept(server_socket, &server_config).map(|_| ()));
+    let result = SecureStream::connect(client_socket, &config(params, client_static));
+    assert!(matches!(result, Err(NoiseError::UntrustedPeer)));
+    assert!(server.join().unwrap().is_err());
+
+    // A proof signed by an untrusted identity doesn't help either.
+    let (client_socket, server_socket) = UnixStream::pair().unwrap();
+    let mut client_config = config(params, client_static);
+    client_config.trusted_peers.static_keys.push(server_public);
+    client_config.identity_secret = Some(signing::generate_keypair().unwrap().1);
+    let server_config = config(params, server_static);
+    let server = thread::spawn(move || SecureStream::accept(server_socket, &server_config).map(|_| ()));
+    let client = SecureStream::connect(client_socket, &client_config);
+    assert!(matches!(server.join().unwrap(), Err(NoiseError::UntrustedPeer)));
+    drop(client);
+
+    // Mutually trusted peers get a working channel over a Unix socket.
+    let (client_socket, server_socket) = UnixStream::pair().unwrap();
+    let mut server_config = config(params, server_static);
+    server_config.trusted_peers.static_keys.push(client_public);
+    let server = thread::spawn(move || {
+        let mut stream = SecureStream::accept(server_socket, &server_config).unwrap();
+        let message = stream.receive().unwrap().unwrap();
+        stream.send(&message).unwrap();
+    });
+    let mut client = SecureStream::connect(client_socket, &client_config).unwrap();
+    client.send(b"over a unix socket").unwrap();
+    assert_eq!(client.receive().unwrap().unwrap(), b"over a unix socket");
+    server.join().unwrap();
+}
@@ -0,0 +1,108 @@
+use serde_json::Value;
+use crate::paseto::{encrypt_local, encrypt_local_with_nonce, decrypt_local, sign_public, verify_public, untrusted_footer, PasetoError};
+use crate::key_format::{import_private_key, import_public_key};
+use crate::signing::generate_keypair;
+use crate::utils::from_hex;
+
+// The PASETO v4 test suite (test-vectors/v4.json): 4-E-* and 4-S-* must
+// round-trip exactly, 4-F-* (`expect-fail`) must be rejected.
+const VECTORS: &str = include_str!("vectors/paseto_v4.json");
+// Our own negative cases, in the same layout but outside the upstream IDs.
+const EXTRA_VECTORS: &str = include_str!("vectors/paseto_v4_qimem.json");
+
+fn field<'a>(test: &'a Value, name: &str) -> &'a str {
+    test[name].as_str().unwrap()
+}
+
+fn assert_rejected(test: &Value) {
+    let name = field(test, "name");
+    let token = field(test, "token");
+    let footer = field(test, "footer").as_bytes();
+    let implicit = field(test, "implicit-assertion").as_bytes();
+    assert!(test["payload"].is_null(), "{name}");
+    let result = match test.get("key") {
+        Some(key) => decrypt_local(&from_hex(key.as_str().unwrap()).unwrap(), token, Some(footer), implicit),
+        None => verify_public(&from_hex(field(test, "public-key")).unwrap(), token, Some(footer), implicit),
+    };
+    assert!(result.is_err(), "{name} should fail");
+}
+
+#[test]
+fn test_official_vectors() {
+    let vectors: Value = serde_json::from_str(VECTORS).unwrap();
+    for test in vectors["tests"].as_array().unwrap() {
+        let name = field(test, "name");
+        let token = field(test, "token");
+        let footer = field(test, "footer").as_bytes();
+        let implicit = field(test, "implicit-assertion").as_bytes();
+        if test["expect-fail"].as_bool().unwrap() {
+            assert_rejected(test);
+            continue;
+        }
+        let payload = field(test, "payload").as_bytes();
+        if let Some(key) = test.get("key") {
+            let key = from_hex(key.as_str().unwrap()).unwrap();
+            let nonce: [u8; 32] = from_hex(field(test, "nonce")).unwrap().try_into().unwrap();
+            assert_eq!(encrypt_local_with_nonce(&key, payload, footer, implicit, &nonce).unwrap(), token, "{name}");
+            assert_eq!(decrypt_local(&key, token, Some(footer), implicit).unwrap(), payload);
+        } else {
+            // The suite's secret keys are seed || public key; signing takes the seed,
+            // which the PEM encodings carry too.
+            let secret_key = from_hex(field(test, "secret-key")).unwrap();
+            let seed = from_hex(field(test, "secret-key-seed")).unwrap();
+            let public_key = from_hex(field(test, "public-key")).unwrap();
+            assert_eq!(secret_key[..32], seed);
+            assert_eq!(import_private_key(field(test, "secret-key-pem").as_bytes(), None).unwrap()[..], seed);
+            assert_eq!(import_public_key(field(test, "public-key-pem").as_bytes()).unwrap()[..], public_key);
+            assert_eq!(sign_public(&seed, payload, footer, implicit).unwrap(), token, "{name}");
+            assert_eq!(verify_public(&public_key, token, Some(footer), implicit).unwrap(), payload);
+        }
+    }
+}
+
+#[test]
+fn test_extra_failure_vectors() {
+    let vectors: Value = serde_json::from_str(EXTRA_VECTORS).unwrap();
+    let tests = vectors["tests"].as_array().unwrap();
+    assert_eq!(tests.len(), 5);
+    for test in tests {
+        assert!(field(test, "name").starts_with("qimem-F-"));
+        assert!(test["expect-fail"].as_bool().unwrap());
+        assert_rejected(test);
+    }
+}
+
+#[test]
+fn test_local_rejects_tampering() {
+    let key = [7u8; 32];
+    let token = encrypt_local(&key, b"payload", b"kid-1", b"context").unwrap();
+    assert_eq!(untrusted_footer(&token).unwrap(), b"kid-1");
+    assert_eq!(decrypt_local(&key, &token, None, b"context").unwrap(), b"payload");
+
+    assert!(matches!(decrypt_local(&[8u8; 32], &token, None, b"context"), Err(PasetoError::InvalidTag)));
+    assert!(matches!(decrypt_local(&key, &token, None, b"other"), Err(PasetoError::InvalidTag)));
+    assert!(matches!(decrypt_local(&key, &token, Some(b"kid-2"), b"context"), Err(PasetoError::FooterMismatch)));
+
+    // Swapping the footer for another one invalidates the tag.
+    let (body, _) = token.rsplit_once('.').unwrap();
+    let swapped = format!("{}.a2lkLTI", body);
+    assert!(matches!(decrypt_local(&key, &swapped, None, b"context"), Err(PasetoError::InvalidTag)));
+    assert!(matches!(decrypt_local(&key, &token.replacen("v4.local.", "v3.local.", 1), None, b"context"),
+        Err(PasetoError::InvalidHeader(_))));
+    assert!(matches!(decrypt_local(&[1u8; 16], &token, None, b""), Err(PasetoError::InvalidKey)));
+}
+
+#[test]
+fn test_public_rejects_tampering() {
+    let (public_key, secret_key) = generate_keypair().unwrap();
+    let (other_public_key, _) = generate_keypair().unwrap();
+    let token = sign_public(&secret_key, b"payload", b"", b"context").unwrap();
+    assert_eq!(verify_public(&public_key, &token, Some(b""), b"context").unwrap(), b"payload");
+
+    assert!(matches!(verify_public(&other_public_key, &token, None, b"context"), Err(PasetoError::InvalidSignature)));
+    assert!(matches!(verify_public(&public_key, &token, None, b""), Err(PasetoError::InvalidSignature)));
+    assert!(matches!(verify_public(&public_key, &format!("{}.Zm9v", token), None, b"context"), Err(PasetoError::InvalidSignature)));
+    // A local token never verifies as a public one.
+    let local = encrypt_local(&[7u8; 32], b"payload", b"", b"").unwrap();
+    assert!(matches!(verify_public(&public_key, &local, None, b""), Err(PasetoError::InvalidHeader(_))));
+}
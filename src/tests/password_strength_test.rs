@@ -0,0 +1,51 @@
+use crate::q_keygen::{estimate_strength, generate_passphrase, require_strength, KeyGenError};
+use crate::key_store::{KeyStore, KeyStoreError};
+
+#[test]
+fn test_weak_patterns() {
+    for password in ["password", "P@ssw0rd", "qwertyuiop", "aaaaaaaaaaaa", "abcabcabcabc", "monkey1987", "zxcvbnm,./asdf"] {
+        let strength = estimate_strength(password);
+        assert_eq!(strength.score, 0, "{} scored {:?}", password, strength);
+        assert!(!strength.feedback.is_empty());
+    }
+    let keyboard = estimate_strength("zxcvbnm,./asdf");
+    assert!(keyboard.feedback.iter().any(|f| f.contains("Keyboard")));
+    let common = estimate_strength("P@ssw0rd");
+    assert!(common.feedback.iter().any(|f| f.contains("very common")));
+}
+
+#[test]
+fn test_strong_passwords() {
+    let random = estimate_strength("x7#Kq!2mZp9$Lw");
+    assert!(random.score >= 3, "{:?}", random);
+    let (passphrase, _) = generate_passphrase(7, "-").unwrap();
+    let strength = estimate_strength(&passphrase);
+    assert_eq!(strength.score, 4);
+    assert!(strength.feedback.is_empty());
+}
+
+#[test]
+fn test_long_passwords_are_capped() {
+    // Only the first 100 characters are analysed, which bounds the pattern
+    // search however long the input is.
+    for password in ["ab".repeat(500), "a".repeat(1000), "abc123".repeat(22), "password".repeat(16)] {
+        let strength = estimate_strength(&password);
+        assert!(strength.score <= 2, "{:?}", strength);
+        assert_eq!(strength, estimate_strength(&password[..100]));
+    }
+    // Anything past the cap, however random, doesn't change the estimate.
+    let padded = format!("{}x7#Kq!2mZp9$Lw", "a".repeat(100));
+    assert_eq!(estimate_strength(&padded), estimate_strength(&"a".repeat(100)));
+}
+
+#[test]
+fn test_min_score_policy() {
+    assert!(matches!(require_strength("letmein", 2), Err(KeyGenError::WeakPassword { score: 0, .. })));
+    assert!(require_strength("letmein", 0).is_ok());
+
+    let path = "/tmp/qimem_test_weak_keys.bin";
+    let _ = std::fs::remove_file(path);
+    let result = KeyStore::new_with_min_score(path, "letmein", 3);
+    assert!(matches!(result, Err(KeyStoreError::KeyGen(KeyGenError::WeakPassword { .. }))));
+    assert!(!std::path::Path::new(path).exists());
+}
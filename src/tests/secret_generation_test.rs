@@ -0,0 +1,46 @@
+use crate::q_keygen::{generate_passphrase, generate_password, generate_secret, PasswordPolicy};
+use crate::wordlist;
+
+#[test]
+fn test_passphrase() {
+    let (passphrase, entropy) = generate_passphrase(6, " ").unwrap();
+    let words: Vec<&str> = passphrase.split(' ').collect();
+    assert_eq!(words.len(), 6);
+    assert!(words.iter().all(|w| wordlist::index_of(w).is_some()));
+    assert_eq!(entropy, 66.0);
+    assert!(generate_passphrase(0, " ").is_err());
+}
+
+#[test]
+fn test_password_classes() {
+    let policy = PasswordPolicy { length: 8, symbols: false, exclude_ambiguous: true, ..Default::default() };
+    for _ in 0..50 {
+        let (password, _) = generate_password(&policy).unwrap();
+        assert_eq!(password.chars().count(), 8);
+        assert!(password.chars().any(|c| c.is_ascii_lowercase()));
+        assert!(password.chars().any(|c| c.is_ascii_uppercase()));
+        assert!(password.chars().any(|c| c.is_ascii_digit()));
+        assert!(password.chars().all(|c| c.is_ascii_alphanumeric() && !"0O1lI".contains(c)));
+    }
+}
+
+#[test]
+fn test_password_entropy() {
+    let digits_only = PasswordPolicy { length: 6, lowercase: false, uppercase: false, symbols: false, ..Default::default() };
+    let (_, entropy) = generate_password(&digits_only).unwrap();
+    assert!((entropy - 6.0 * 10f64.log2()).abs() < 1e-9);
+
+    let (_, full) = generate_password(&PasswordPolicy::default()).unwrap();
+    assert!(full < 20.0 * 94f64.log2() && full > 20.0 * 94f64.log2() - 1.0);
+
+    let too_short = PasswordPolicy { length: 3, ..Default::default() };
+    assert!(generate_password(&too_short).is_err());
+}
+
+#[test]
+fn test_secret() {
+    let (secret, entropy) = generate_secret(32).unwrap();
+    assert_eq!(secret.len(), 32);
+    assert_eq!(entropy, 256.0);
+    assert_ne!(secret, generate_secret(32).unwrap().0);
+}
@@ -0,0 +1,83 @@
+use crate::shamir::{split_secret, combine_shares, Share, ShamirError};
+use crate::key_store::KeyStore;
+
+#[test]
+fn test_split_combine_every_subset() {
+    let secret: Vec<u8> = (0..32).collect();
+    let shares = split_secret(&secret, 3, 5).unwrap();
+    assert_eq!(shares.len(), 5);
+    for a in 0..5 {
+        for b in (a + 1)..5 {
+            for c in (b + 1)..5 {
+                let subset = [shares[c].clone(), shares[a].clone(), shares[b].clone()];
+                assert_eq!(combine_shares(&subset).unwrap(), secret);
+            }
+        }
+    }
+    assert_eq!(combine_shares(&shares).unwrap(), secret);
+}
+
+#[test]
+fn test_below_threshold_and_bad_inputs() {
+    let shares = split_secret(b"top secret", 3, 4).unwrap();
+    assert!(matches!(
+        combine_shares(&shares[..2]),
+        Err(ShamirError::NotEnoughShares { needed: 3, got: 2 })
+    ));
+    let dup = [shares[0].clone(), shares[0].clone(), shares[1].clone()];
+    assert!(matches!(combine_shares(&dup), Err(ShamirError::DuplicateShare(1))));
+
+    let other = split_secret(b"top secret", 3, 4).unwrap();
+    let mixed = [shares[0].clone(), shares[1].clone(), other[2].clone()];
+    assert!(combine_shares(&mixed).is_err());
+
+    let mut tampered = shares.clone();
+    tampered[3].data[0] ^= 1;
+    assert!(matches!(combine_shares(&tampered), Err(ShamirError::InconsistentShares)));
+
+    assert!(matches!(split_secret(b"x", 1, 3), Err(ShamirError::InvalidThreshold)));
+    assert!(matches!(split_secret(b"x", 4, 3), Err(ShamirError::InvalidThreshold)));
+
+    // Hand-built shares skip `from_bytes`, so `combine_shares` checks them too.
+    let zero_threshold = Share { set_id: 7, threshold: 0, index: 1, data: vec![1, 2] };
+    assert!(matches!(combine_shares(&[zero_threshold]), Err(ShamirError::InvalidThreshold)));
+    let mut at_zero = shares[..3].to_vec();
+    at_zero[0].index = 0;
+    assert!(matches!(combine_shares(&at_zero), Err(ShamirError::InvalidShare(_))));
+}
+
+#[test]
+fn test_share_encoding() {
+    let shares = split_secret(&[7u8; 32], 2, 3).unwrap();
+    let encoded = shares[1].encode();
+    assert!(encoded.starts_with("qss1-"));
+    assert_eq!(Share::decode(&encoded).unwrap(), shares[1]);
+
+    let grouped: String = encoded.as_bytes()[5..]
+        .chunks(4)
+        .map(|c| std::str::from_utf8(c).unwrap())
+        .collect::<Vec<_>>()
+        .join(" ");
+    assert_eq!(Share::decode(&format!("qss1-{}", grouped)).unwrap(), shares[1]);
+
+    let mut typo = encoded.clone().into_bytes();
+    let last = typo.len() - 10;
+    typo[last] = if typo[last] == b'0' { b'1' } else { b'0' };
+    let err = Share::decode(std::str::from_utf8(&typo).unwrap()).unwrap_err();
+    assert!(err.to_string().contains("Checksum"));
+}
+
+#[test]
+fn test_keystore_share_unlock() {
+    let path = "/tmp/qimem_test_shamir_keys.bin";
+    let _ = std::fs::remove_file(path);
+    let mut keystore = KeyStore::new(path, "correct horse battery staple").unwrap();
+    keystore.store_key("backup", [9; 32]).unwrap();
+    let shares = keystore.split_master_key(2, 3).unwrap();
+
+    let recovered = KeyStore::open_with_shares(path, &[shares[2].clone(), shares[0].clone()]).unwrap();
+    assert_eq!(recovered.retrieve_key("backup"), Some([9; 32]));
+    assert!(KeyStore::open_with_shares(path, &shares[..1]).is_err());
+
+    let _ = std::fs::remove_file(path);
+}
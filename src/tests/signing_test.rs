@@ -12,4 +12,166 @@ fn test_sign_verify() {
         .expect("Verification failed");
         
     assert!(result, "Signature verification failed");
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_minisign_file_signatures() {
+    use crate::signing::{sign_file, verify_file, minisign_public_key, SigningError};
+
+    let path = "/tmp/qimem_test_release.tar";
+    std::fs::write(path, vec![42u8; 200_000]).unwrap();
+    let (public_key, secret_key) = generate_keypair().unwrap();
+    let (other_public_key, _) = generate_keypair().unwrap();
+    let minisign_key = minisign_verify::PublicKey::decode(&minisign_public_key(&public_key).unwrap()).unwrap();
+
+    for prehashed in [true, false] {
+        let contents = sign_file(path, &secret_key, Some("release 1.2.0"), prehashed).unwrap();
+        assert_eq!(verify_file(path, &public_key, None).unwrap(), "release 1.2.0");
+
+        // An independent minisign implementation accepts the signature.
+        let signature = minisign_verify::Signature::decode(&contents).unwrap();
+        assert_eq!(signature.trusted_comment(), "release 1.2.0");
+        minisign_key.verify(&std::fs::read(path).unwrap(), &signature, !prehashed).unwrap();
+    }
+
+    let default_comment = {
+        sign_file(path, &secret_key, None, true).unwrap();
+        verify_file(path, &public_key, None).unwrap()
+    };
+    assert!(default_comment.starts_with("timestamp:") && default_comment.ends_with("\tfile:qimem_test_release.tar\thashed"));
+    assert!(matches!(verify_file(path, &other_public_key, None), Err(SigningError::KeyIdMismatch)));
+
+    // Editing the trusted comment or the file invalidates the signature.
+    let signature_path = format!("{}.minisig", path);
+    let contents = std::fs::read_to_string(&signature_path).unwrap();
+    std::fs::write(&signature_path, contents.replace("timestamp:", "timestamp:1")).unwrap();
+    assert!(matches!(verify_file(path, &public_key, None), Err(SigningError::VerificationFailed)));
+    std::fs::write(&signature_path, contents).unwrap();
+    std::fs::write(path, vec![43u8; 200_000]).unwrap();
+    assert!(matches!(verify_file(path, &public_key, None), Err(SigningError::VerificationFailed)));
+    assert!(matches!(sign_file(path, &secret_key, Some("two\nlines"), true), Err(SigningError::InvalidTrustedComment)));
+
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(signature_path);
+}
+
+#[test]
+fn test_batch_verification() {
+    use crate::signing::{verify_batch, BatchItem};
+
+    let keypairs: Vec<_> = (0..16).map(|_| generate_keypair().unwrap()).collect();
+    let messages: Vec<Vec<u8>> = (0..16u8).map(|i| vec![i; 10 + i as usize]).collect();
+    let mut signatures: Vec<Vec<u8>> = keypairs
+        .iter()
+        .zip(&messages)
+        .map(|((_, secret_key), message)| sign_message(secret_key, message).unwrap())
+        .collect();
+    let items = |signatures: &[Vec<u8>]| -> Vec<bool> {
+        let batch: Vec<BatchItem> = (0..16)
+            .map(|i| BatchItem { public_key: &keypairs[i].0, message: &messages[i], signature: &signatures[i] })
+            .collect();
+        verify_batch(&batch)
+    };
+    assert_eq!(items(&signatures), vec![true; 16]);
+
+    // Corrupt two signatures and truncate a third; the rest still verify.
+    signatures[3][0] ^= 1;
+    signatures[9] = signatures[10].clone();
+    signatures[12].truncate(10);
+    let results = items(&signatures);
+    for (i, ok) in results.iter().enumerate() {
+        assert_eq!(*ok, ![3, 9, 12].contains(&i), "item {}", i);
+    }
+    assert!(verify_batch(&[]).is_empty());
+}
+
+#[test]
+fn test_certificate_chain() {
+    use crate::signing::{issue_certificate, issue_revocation_list, verify_chain_at, Certificate, CertificateError, KeyUsage};
+
+    const NOW: i64 = 1_700_000_000;
+    const YEAR: i64 = 365 * 24 * 3600;
+    let (root_public, root_secret) = generate_keypair().unwrap();
+    let (ca_public, ca_secret) = generate_keypair().unwrap();
+    let (service_public, _) = generate_keypair().unwrap();
+    let authority = [KeyUsage::CertSign, KeyUsage::CrlSign];
+
+    let root = issue_certificate(&root_secret, "root", &root_public, NOW - YEAR, NOW + 10 * YEAR, &authority).unwrap();
+    let ca = issue_certificate(&root_secret, "services ca", &ca_public, NOW - YEAR, NOW + YEAR, &authority).unwrap();
+    let service = issue_certificate(&ca_secret, "api.example", &service_public, NOW, NOW + 90 * 24 * 3600, &[KeyUsage::DigitalSignature]).unwrap();
+    assert!(root.is_self_signed() && !ca.is_self_signed());
+    assert_eq!(Certificate::from_bytes(&service.to_bytes()).unwrap(), service);
+
+    let roots = [root_public];
+    let chain = [service.clone(), ca.clone()];
+    assert_eq!(verify_chain_at(&chain, &roots, &[], NOW).unwrap().subject, "api.example");
+    assert!(verify_chain_at(&[service.clone(), ca.clone(), root.clone()], &roots, &[], NOW).unwrap().allows(KeyUsage::DigitalSignature));
+    assert!(verify_chain_at(std::slice::from_ref(&root), &roots, &[], NOW).is_ok());
+
+    // Time, trust and structure failures.
+    assert!(matches!(verify_chain_at(&chain, &roots, &[], NOW - 1), Err(CertificateError::NotYetValid(s)) if s == "api.example"));
+    assert!(matches!(verify_chain_at(&chain, &roots, &[], NOW + 2 * YEAR), Err(CertificateError::Expired(_))));
+    assert!(matches!(verify_chain_at(&chain, &[service_public], &[], NOW), Err(CertificateError::UntrustedRoot)));
+    assert!(matches!(verify_chain_at(std::slice::from_ref(&service), &roots, &[], NOW), Err(CertificateError::UntrustedRoot)));
+    assert!(matches!(verify_chain_at(&[], &roots, &[], NOW), Err(CertificateError::EmptyChain)));
+    assert!(matches!(verify_chain_at(&[service.clone(), root.clone()], &roots, &[], NOW), Err(CertificateError::IssuerMismatch(_))));
+
+    // A leaf without CertSign can't issue, even with a valid signature.
+    let (other_public, _) = generate_keypair().unwrap();
+    let (leaf_public, leaf_secret) = generate_keypair().unwrap();
+    let leaf = issue_certificate(&ca_secret, "leaf", &leaf_public, NOW, NOW + YEAR, &[KeyUsage::DigitalSignature]).unwrap();
+    let issued_by_leaf = issue_certificate(&leaf_secret, "rogue", &other_public, NOW, NOW + YEAR, &[KeyUsage::DigitalSignature]).unwrap();
+    assert!(matches!(
+        verify_chain_at(&[issued_by_leaf, leaf, ca.clone()], &roots, &[], NOW),
+        Err(CertificateError::NotACertificateAuthority(s)) if s == "leaf"
+    ));
+
+    let mut tampered = service.clone();
+    tampered.not_after += YEAR;
+    assert!(matches!(verify_chain_at(&[tampered, ca.clone()], &roots, &[], NOW), Err(CertificateError::InvalidSignature(_))));
+
+    // Revocation by the CA, and by the root for the CA itself.
+    let crl = issue_revocation_list(&ca_secret, &[service.serial], NOW).unwrap();
+    assert!(matches!(verify_chain_at(&chain, &roots, std::slice::from_ref(&crl), NOW), Err(CertificateError::Revoked(s)) if s == "api.example"));
+    let root_crl = issue_revocation_list(&root_secret, &[ca.serial], NOW).unwrap();
+    assert!(matches!(verify_chain_at(&chain, &roots, &[root_crl], NOW), Err(CertificateError::Revoked(s)) if s == "services ca"));
+    let unrelated = issue_revocation_list(&root_secret, &[[7; 16]], NOW).unwrap();
+    assert!(verify_chain_at(&chain, &roots, &[unrelated], NOW).is_ok());
+    let mut forged = crl.clone();
+    forged.revoked.push([9; 16]);
+    assert!(matches!(verify_chain_at(&chain, &roots, &[forged], NOW), Err(CertificateError::InvalidRevocationListSignature)));
+    assert_eq!(crate::signing::RevocationList::from_bytes(&crl.to_bytes()).unwrap(), crl);
+    assert!(matches!(Certificate::from_bytes(&service.to_bytes()[..100]), Err(CertificateError::Malformed)));
+}
+
+#[test]
+fn test_post_quantum_and_composite_signatures() {
+    use crate::ml_dsa;
+    use crate::signing::{generate_keypair_with, sign_message_with, verify_signature_with, SignatureAlgorithm, SigningError};
+
+    for algorithm in SignatureAlgorithm::ALL {
+        assert_eq!(SignatureAlgorithm::from_name(algorithm.name()).unwrap(), algorithm);
+        let (public_key, secret_key) = generate_keypair_with(algorithm).unwrap();
+        let signature = sign_message_with(algorithm, &secret_key, b"release v3.1.0").unwrap();
+        assert!(verify_signature_with(algorithm, &public_key, b"release v3.1.0", &signature).unwrap(), "{}", algorithm.name());
+        assert!(!verify_signature_with(algorithm, &public_key, b"release v3.1.1", &signature).unwrap());
+    }
+    assert!(matches!(SignatureAlgorithm::from_name("rsa"), Err(SigningError::UnknownAlgorithm(_))));
+
+    // A composite signature needs both halves; neither half stands alone.
+    let composite = SignatureAlgorithm::Ed25519MlDsa65;
+    let (public_key, secret_key) = generate_keypair_with(composite).unwrap();
+    let signature = sign_message_with(composite, &secret_key, b"artifact").unwrap();
+    assert_eq!(signature.len(), 64 + ml_dsa::SIGNATURE_LEN);
+    for position in [10, 64 + 10] {
+        let mut tampered = signature.clone();
+        tampered[position] ^= 1;
+        assert!(!verify_signature_with(composite, &public_key, b"artifact", &tampered).unwrap());
+    }
+    assert!(!verify_signature(&public_key[..32], b"artifact", &signature[..64]).unwrap());
+    assert!(!ml_dsa::verify(&public_key[32..], b"artifact", b"", &signature[64..]).unwrap());
+    assert!(matches!(
+        verify_signature_with(composite, &public_key[..32], b"artifact", &signature),
+        Err(SigningError::InvalidLength { what: "public key", .. })
+    ));
+}
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+use crate::signing::generate_keypair;
+use crate::token::{issue_token, verify_token, verify_token_at, TokenError, DEFAULT_LEEWAY_SECS};
+
+#[test]
+fn test_issue_and_verify() {
+    let (public_key, secret_key) = generate_keypair().unwrap();
+    let custom = BTreeMap::from([("tenant".to_string(), "acme".to_string())]);
+    let token = issue_token("alice", "admin", 24, &secret_key, custom.clone()).unwrap();
+    assert!(token.starts_with("qt1."));
+
+    let claims = verify_token(&token, &public_key).unwrap();
+    assert_eq!(claims.subject, "alice");
+    assert_eq!(claims.role, "admin");
+    assert_eq!(claims.expires_at - claims.issued_at, 24 * 3600);
+    assert_eq!(claims.token_id.len(), 32);
+    assert_eq!(claims.custom, custom);
+
+    let other = issue_token("alice", "admin", 24, &secret_key, BTreeMap::new()).unwrap();
+    assert_ne!(verify_token(&other, &public_key).unwrap().token_id, claims.token_id);
+}
+
+#[test]
+fn test_failure_reasons() {
+    let (public_key, secret_key) = generate_keypair().unwrap();
+    let (other_public_key, _) = generate_keypair().unwrap();
+    let token = issue_token("bob", "reader", 1, &secret_key, BTreeMap::new()).unwrap();
+    let claims = verify_token(&token, &public_key).unwrap();
+
+    assert!(matches!(verify_token(&token, &other_public_key), Err(TokenError::InvalidSignature)));
+
+    // Swapping in different claims breaks the signature.
+    let forged = issue_token("bob", "admin", 1, &secret_key, BTreeMap::new()).unwrap();
+    let forged_payload = forged.split('.').nth(1).unwrap();
+    let parts: Vec<&str> = token.split('.').collect();
+    let tampered = format!("{}.{}.{}", parts[0], forged_payload, parts[2]);
+    assert!(matches!(verify_token(&tampered, &public_key), Err(TokenError::InvalidSignature)));
+
+    let expiry = claims.expires_at;
+    assert!(verify_token_at(&token, &public_key, expiry + DEFAULT_LEEWAY_SECS - 1, DEFAULT_LEEWAY_SECS).is_ok());
+    assert!(matches!(
+        verify_token_at(&token, &public_key, expiry + DEFAULT_LEEWAY_SECS, DEFAULT_LEEWAY_SECS),
+        Err(TokenError::Expired { .. })
+    ));
+
+    let issued = claims.issued_at;
+    assert!(verify_token_at(&token, &public_key, issued - DEFAULT_LEEWAY_SECS, DEFAULT_LEEWAY_SECS).is_ok());
+    assert!(matches!(
+        verify_token_at(&token, &public_key, issued - DEFAULT_LEEWAY_SECS - 1, DEFAULT_LEEWAY_SECS),
+        Err(TokenError::IssuedInFuture { .. })
+    ));
+
+    assert!(matches!(verify_token("not-a-token", &public_key), Err(TokenError::Malformed)));
+    assert!(matches!(verify_token(&token.replacen("qt1", "qt9", 1), &public_key), Err(TokenError::UnsupportedVersion)));
+}
+
+#[test]
+fn test_reserved_claims_rejected() {
+    let (_, secret_key) = generate_keypair().unwrap();
+    let custom = BTreeMap::from([("exp".to_string(), "never".to_string())]);
+    assert!(matches!(issue_token("eve", "admin", 1, &secret_key, custom), Err(TokenError::InvalidClaims(_))));
+}
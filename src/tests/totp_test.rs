@@ -1,11 +1,135 @@
-#[cfg(test)]
-mod tests {
-    use crate::totp::{generate_totp_secret, get_totp_code, verify_totp_code};
-
-    #[test]
-    fn test_totp() {
-        let secret = generate_totp_secret().unwrap();
-        let code = get_totp_code(&secret).unwrap();
-        assert!(verify_totp_code(&secret, &code).unwrap());
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use crate::totp::{generate_totp_secret, get_totp_code, get_totp_code_at, verify_totp_code, verify_totp_code_at, TotpAlgorithm, TotpConfig, TotpError};
+
+fn config(algorithm: TotpAlgorithm, digits: usize, period: u64) -> TotpConfig {
+    TotpConfig { algorithm, digits, period, ..Default::default() }
+}
+
+#[test]
+fn test_totp() {
+    let config = TotpConfig::default();
+    let secret = generate_totp_secret(&config);
+    let code = get_totp_code(&secret, &config).unwrap();
+    assert_eq!(code.len(), 6);
+    assert!(verify_totp_code(&secret, &code, &config).unwrap());
+}
+
+/// The test vectors from RFC 6238 Appendix B (8 digits, 30-second steps).
+#[test]
+fn test_rfc6238_vectors() {
+    let secrets = [
+        (TotpAlgorithm::Sha1, &b"12345678901234567890"[..]),
+        (TotpAlgorithm::Sha256, b"12345678901234567890123456789012"),
+        (TotpAlgorithm::Sha512, b"1234567890123456789012345678901234567890123456789012345678901234"),
+    ];
+    let vectors: [(u64, [&str; 3]); 6] = [
+        (59, ["94287082", "46119246", "90693936"]),
+        (1111111109, ["07081804", "68084774", "25091201"]),
+        (1111111111, ["14050471", "67062674", "99943326"]),
+        (1234567890, ["89005924", "91819424", "93441116"]),
+        (2000000000, ["69279037", "90698825", "38618901"]),
+        (20000000000, ["65353130", "77737706", "47863826"]),
+    ];
+    for (i, (algorithm, secret)) in secrets.into_iter().enumerate() {
+        let config = config(algorithm, 8, 30);
+        let secret = BASE64_STANDARD.encode(secret);
+        for (time, codes) in vectors {
+            assert_eq!(get_totp_code_at(&secret, &config, time).unwrap(), codes[i]);
+            assert!(verify_totp_code_at(&secret, codes[i], &config, time).unwrap());
+        }
+    }
+}
+
+#[test]
+fn test_period_and_skew() {
+    let mut config = config(TotpAlgorithm::Sha256, 8, 60);
+    let secret = generate_totp_secret(&config);
+    assert_eq!(BASE64_STANDARD.decode(&secret).unwrap().len(), 32);
+    let code = get_totp_code_at(&secret, &config, 6000).unwrap();
+    assert_eq!(get_totp_code_at(&secret, &config, 6059).unwrap(), code);
+    assert_ne!(get_totp_code_at(&secret, &config, 6060).unwrap(), code);
+
+    // One step of skew either way by default, none or more when configured.
+    assert!(verify_totp_code_at(&secret, &code, &config, 6119).unwrap());
+    assert!(verify_totp_code_at(&secret, &code, &config, 5940).unwrap());
+    assert!(!verify_totp_code_at(&secret, &code, &config, 6120).unwrap());
+    config.skew = 0;
+    assert!(!verify_totp_code_at(&secret, &code, &config, 6060).unwrap());
+    config.skew = 2;
+    assert!(verify_totp_code_at(&secret, &code, &config, 6179).unwrap());
+    // Near the epoch the window is clamped rather than underflowing.
+    let first = get_totp_code_at(&secret, &config, 0).unwrap();
+    assert!(verify_totp_code_at(&secret, &first, &config, 10).unwrap());
+    assert!(!verify_totp_code_at(&secret, &code[..7], &config, 6000).unwrap());
+}
+
+#[test]
+fn test_verify_near_u64_max() {
+    // The last steps' start times overflow a u64 once multiplied back out; they're skipped.
+    let config = TotpConfig { period: u64::MAX / 2, skew: 3, ..Default::default() };
+    let secret = generate_totp_secret(&config);
+    let code = get_totp_code_at(&secret, &config, u64::MAX).unwrap();
+    assert!(verify_totp_code_at(&secret, &code, &config, u64::MAX).unwrap());
+    assert!(verify_totp_code_at(&secret, &code, &config, u64::MAX - 1).unwrap());
+    let config = TotpConfig { skew: u8::MAX, ..Default::default() };
+    let code = get_totp_code_at(&secret, &config, u64::MAX).unwrap();
+    assert!(verify_totp_code_at(&secret, &code, &config, u64::MAX).unwrap());
+}
+
+#[test]
+fn test_invalid_config() {
+    let secret = generate_totp_secret(&TotpConfig::default());
+    assert!(matches!(get_totp_code(&secret, &config(TotpAlgorithm::Sha1, 5, 30)), Err(TotpError::InvalidDigits(5))));
+    assert!(matches!(get_totp_code(&secret, &config(TotpAlgorithm::Sha1, 9, 30)), Err(TotpError::InvalidDigits(9))));
+    assert!(matches!(get_totp_code(&secret, &config(TotpAlgorithm::Sha1, 6, 0)), Err(TotpError::InvalidPeriod)));
+    assert!(matches!(get_totp_code("not base64!", &TotpConfig::default()), Err(TotpError::InvalidSecret)));
+    assert!(matches!(get_totp_code(&BASE64_STANDARD.encode([0u8; 15]), &TotpConfig::default()), Err(TotpError::InvalidSecret)));
+    assert!(matches!(TotpAlgorithm::from_name("MD5"), Err(TotpError::UnknownAlgorithm(_))));
+    assert_eq!(TotpAlgorithm::from_name("sha512").unwrap(), TotpAlgorithm::Sha512);
+}
+
+#[test]
+fn test_uri_round_trip() {
+    let config = TotpConfig {
+        algorithm: TotpAlgorithm::Sha256,
+        digits: 8,
+        period: 60,
+        skew: 2,
+        issuer: Some("Acme Co".to_string()),
+        account: "alice@example.com".to_string(),
+    };
+    let secret = BASE64_STANDARD.encode(b"12345678901234567890");
+    let uri = config.to_uri(&secret).unwrap();
+    assert_eq!(
+        uri,
+        "otpauth://totp/Acme%20Co:alice%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+         &issuer=Acme%20Co&algorithm=SHA256&digits=8&period=60&skew=2"
+    );
+    assert_eq!(TotpConfig::from_uri(&uri).unwrap(), (config, secret.clone()));
+
+    // A minimal URI from another provider: defaults, lowercase base32, label issuer.
+    let (parsed, parsed_secret) = TotpConfig::from_uri("otpauth://totp/Example:bob?secret=gezdgnbvgy3tqojqgezdgnbvgy3tqojq&image=x").unwrap();
+    assert_eq!(parsed_secret, secret);
+    assert_eq!(parsed, TotpConfig { issuer: Some("Example".to_string()), account: "bob".to_string(), ..Default::default() });
+    // Encoded colons belong to their part, and '+' in the label is a literal plus.
+    let (parsed, _) = TotpConfig::from_uri("otpauth://totp/ACME%3AEU:bob+tag%3Aa@example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=ACME+Co").unwrap();
+    assert_eq!(parsed.issuer.as_deref(), Some("ACME Co"));
+    assert_eq!(parsed.account, "bob+tag:a@example.com");
+    let (parsed, _) = TotpConfig::from_uri("otpauth://totp/ACME%3AEU:bob?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap();
+    assert_eq!(parsed.issuer.as_deref(), Some("ACME:EU"));
+    let anonymous = TotpConfig::default();
+    assert_eq!(TotpConfig::from_uri(&anonymous.to_uri(&secret).unwrap()).unwrap(), (anonymous, secret));
+
+    for uri in [
+        "otpauth://hotp/bob?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+        "otpauth://totp/bob?digits=8",
+        "otpauth://totp/bob?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&period=x",
+        "otpauth://totp/b%zzob?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+    ] {
+        assert!(matches!(TotpConfig::from_uri(uri), Err(TotpError::InvalidUri(_))), "{uri}");
     }
-}
\ No newline at end of file
+    assert!(matches!(
+        TotpConfig::from_uri("otpauth://totp/bob?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&digits=10"),
+        Err(TotpError::InvalidDigits(10))
+    ));
+}
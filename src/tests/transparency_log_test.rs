@@ -0,0 +1,127 @@
+use crate::signing::generate_keypair;
+use crate::transparency_log::{
+    check_tree_head_update, leaf_hash, root_hash, verify_consistency, verify_inclusion, LogError, SignedTreeHead, TransparencyLog,
+};
+use crate::utils::to_hex;
+
+// The eight-leaf reference tree from the certificate-transparency test suite.
+const LEAVES: [&[u8]; 8] = [
+    b"",
+    b"\x00",
+    b"\x10",
+    b"\x20\x21",
+    b"\x30\x31",
+    b"\x40\x41\x42\x43",
+    b"\x50\x51\x52\x53\x54\x55\x56\x57",
+    b"\x60\x61\x62\x63\x64\x65\x66\x67\x68\x69\x6a\x6b\x6c\x6d\x6e\x6f",
+];
+const ROOTS: [&str; 8] = [
+    "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+    "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+    "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+    "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+    "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+    "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+    "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+    "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+];
+
+fn reference_log(path: &str) -> TransparencyLog {
+    let _ = std::fs::remove_file(path);
+    let mut log = TransparencyLog::open(path).unwrap();
+    for (i, leaf) in LEAVES.iter().enumerate() {
+        assert_eq!(log.append(leaf).unwrap(), i as u64);
+    }
+    log
+}
+
+#[test]
+fn test_reference_tree() {
+    let path = "/tmp/qimem_test_log_reference";
+    let log = reference_log(path);
+    assert_eq!(to_hex(&root_hash(&[])), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    for (size, expected) in ROOTS.iter().enumerate() {
+        assert_eq!(to_hex(&log.root_hash(size as u64 + 1).unwrap()), *expected);
+    }
+    let proof: Vec<String> = log.inclusion_proof(0, 8).unwrap().iter().map(|h| to_hex(h)).collect();
+    assert_eq!(
+        proof,
+        [
+            "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7",
+            "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+            "6b47aaf29ee3c2af9af889bc1fb9254dabd31177f16232dd6aab035ca39bf6e4",
+        ]
+    );
+
+    // Reopening the file gives back the same tree.
+    let reopened = TransparencyLog::open(path).unwrap();
+    assert_eq!(reopened.size(), 8);
+    assert_eq!(reopened.entry(3), Some(LEAVES[3]));
+    assert_eq!(reopened.root_hash(8).unwrap(), log.root_hash(8).unwrap());
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_proofs() {
+    let path = "/tmp/qimem_test_log_proofs";
+    let log = reference_log(path);
+    for size in 1..=8u64 {
+        let root = log.root_hash(size).unwrap();
+        for index in 0..size {
+            let proof = log.inclusion_proof(index, size).unwrap();
+            let leaf = leaf_hash(LEAVES[index as usize]);
+            assert!(verify_inclusion(&leaf, index, size, &proof, &root), "{index} in {size}");
+            assert!(!verify_inclusion(&leaf_hash(b"swapped"), index, size, &proof, &root));
+            assert!(!verify_inclusion(&leaf, index ^ 1, size, &proof, &root) || size == 1);
+        }
+        for old_size in 0..=size {
+            let old_root = log.root_hash(old_size).unwrap();
+            let proof = log.consistency_proof(old_size, size).unwrap();
+            assert!(verify_consistency(old_size, size, &old_root, &root, &proof), "{old_size} -> {size}");
+            if old_size > 0 && old_size < size {
+                assert!(!verify_consistency(old_size, size, &leaf_hash(b"forged"), &root, &proof));
+                assert!(!verify_consistency(old_size, size, &old_root, &leaf_hash(b"forged"), &proof));
+            }
+        }
+    }
+    assert!(matches!(log.inclusion_proof(8, 8), Err(LogError::IndexOutOfRange { .. })));
+    assert!(matches!(log.consistency_proof(3, 9), Err(LogError::IndexOutOfRange { .. })));
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_signed_tree_heads() {
+    let path = "/tmp/qimem_test_log_heads";
+    let _ = std::fs::remove_file(path);
+    let (log_public, log_secret) = generate_keypair().unwrap();
+    let (other_public, other_secret) = generate_keypair().unwrap();
+    let mut log = TransparencyLog::open(path).unwrap();
+    log.append(b"alice: key-1").unwrap();
+    log.append(b"bob: key-1").unwrap();
+    let trusted = log.sign_tree_head(&log_secret).unwrap();
+    assert!(trusted.verify(&log_public).unwrap() && !trusted.verify(&other_public).unwrap());
+    assert_eq!(SignedTreeHead::from_bytes(&trusted.to_bytes()).unwrap(), trusted);
+
+    log.append(b"carol: key-1").unwrap();
+    let newer = log.sign_tree_head(&log_secret).unwrap();
+    let proof = log.consistency_proof(trusted.tree_size, newer.tree_size).unwrap();
+    check_tree_head_update(&log_public, &trusted, &newer, &proof).unwrap();
+    let forged = TransparencyLog::open(path).unwrap().sign_tree_head(&other_secret).unwrap();
+    assert!(matches!(check_tree_head_update(&log_public, &trusted, &forged, &proof), Err(LogError::InvalidSignature)));
+
+    // Rewriting Alice's key in the file is detected by a client holding the old head.
+    let swapped_path = "/tmp/qimem_test_log_heads_swapped";
+    let _ = std::fs::remove_file(swapped_path);
+    let mut swapped = TransparencyLog::open(swapped_path).unwrap();
+    for entry in [b"alice: key-2".as_slice(), b"bob: key-1", b"carol: key-1"] {
+        swapped.append(entry).unwrap();
+    }
+    let swapped_head = swapped.sign_tree_head(&log_secret).unwrap();
+    let swapped_proof = swapped.consistency_proof(2, 3).unwrap();
+    assert!(matches!(check_tree_head_update(&log_public, &trusted, &swapped_head, &swapped_proof), Err(LogError::Inconsistent)));
+
+    std::fs::write(path, b"not a log").unwrap();
+    assert!(matches!(TransparencyLog::open(path), Err(LogError::MalformedLog)));
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(swapped_path);
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use chrono::Utc;
+use rand::RngCore;
+use serde_json::{json, Map, Value};
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::signing::{sign_message, verify_signature, SigningError};
+use crate::utils::to_hex;
+
+/// Tokens are `qt1.<base64url claims JSON>.<base64url Ed25519 signature>`,
+/// the signature covering everything before the second dot.
+const TOKEN_PREFIX: &str = "qt1";
+/// Allowed difference between the issuer's and verifier's clocks.
+pub const DEFAULT_LEEWAY_SECS: i64 = 60;
+const TOKEN_ID_LEN: usize = 16;
+const RESERVED_CLAIMS: [&str; 5] = ["sub", "role", "iat", "exp", "jti"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum TokenError {
+    #[error("Malformed token")]
+    Malformed,
+    #[error("Unsupported token version")]
+    UnsupportedVersion,
+    #[error("Invalid claims: {0}")]
+    InvalidClaims(String),
+    #[error("Signature does not match the public key")]
+    InvalidSignature,
+    #[error("Token expired at {expired_at} (now {now})")]
+    Expired { expired_at: i64, now: i64 },
+    #[error("Token issued at {issued_at}, ahead of the local clock ({now}) by more than the allowed skew")]
+    IssuedInFuture { issued_at: i64, now: i64 },
+    #[error("Signing error: {0}")]
+    Signing(#[from] SigningError),
+}
+
+impl From<TokenError> for PyErr {
+    fn from(err: TokenError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// The claims carried by a token. Times are Unix seconds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenClaims {
+    pub subject: String,
+    pub role: String,
+    pub issued_at: i64,
+    pub expires_at: i64,
+    pub token_id: String,
+    pub custom: BTreeMap<String, String>,
+}
+
+impl TokenClaims {
+    fn to_json(&self) -> Value {
+        let mut object = Map::new();
+        for (name, value) in &self.custom {
+            object.insert(name.clone(), Value::String(value.clone()));
+        }
+        object.insert("sub".to_string(), json!(self.subject));
+        object.insert("role".to_string(), json!(self.role));
+        object.insert("iat".to_string(), json!(self.issued_at));
+        object.insert("exp".to_string(), json!(self.expires_at));
+        object.insert("jti".to_string(), json!(self.token_id));
+        Value::Object(object)
+    }
+
+    fn from_json(value: Value) -> Result<Self, TokenError> {
+        let Value::Object(mut object) = value else {
+            return Err(TokenError::InvalidClaims("Claims must be a JSON object".to_string()));
+        };
+        let mut string = |name: &str| match object.remove(name) {
+            Some(Value::String(s)) => Ok(s),
+            _ => Err(TokenError::InvalidClaims(format!("Missing or non-string \"{}\"", name))),
+        };
+        let (subject, role, token_id) = (string("sub")?, string("role")?, string("jti")?);
+        let mut integer = |name: &str| {
+            object
+                .remove(name)
+                .and_then(|v| v.as_i64())
+                .ok_or_else(|| TokenError::InvalidClaims(format!("Missing or non-integer \"{}\"", name)))
+        };
+        let (issued_at, expires_at) = (integer("iat")?, integer("exp")?);
+        let custom = object
+            .into_iter()
+            .map(|(name, value)| match value {
+                Value::String(s) => Ok((name, s)),
+                _ => Err(TokenError::InvalidClaims(format!("Custom claim \"{}\" must be a string", name))),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(TokenClaims { subject, role, issued_at, expires_at, token_id, custom })
+    }
+}
+
+/// Issues a token for `subject` with `role`, valid for `ttl_hours`, signed with
+/// the Ed25519 `secret_key`. Custom claims may not reuse the reserved names
+/// `sub`, `role`, `iat`, `exp` and `jti`.
+pub fn issue_token(
+    subject: &str,
+    role: &str,
+    ttl_hours: u32,
+    secret_key: &[u8],
+    custom: BTreeMap<String, String>,
+) -> Result<String, TokenError> {
+    if let Some(name) = custom.keys().find(|name| RESERVED_CLAIMS.contains(&name.as_str())) {
+        return Err(TokenError::InvalidClaims(format!("\"{}\" is a reserved claim", name)));
+    }
+    let mut token_id = [0u8; TOKEN_ID_LEN];
+    rand::thread_rng().fill_bytes(&mut token_id);
+    let issued_at = Utc::now().timestamp();
+    let claims = TokenClaims {
+        subject: subject.to_string(),
+        role: role.to_string(),
+        issued_at,
+        expires_at: issued_at + i64::from(ttl_hours) * 3600,
+        token_id: to_hex(&token_id),
+        custom,
+    };
+    let signing_input = format!("{}.{}", TOKEN_PREFIX, BASE64_URL.encode(claims.to_json().to_string()));
+    let signature = sign_message(secret_key, signing_input.as_bytes())?;
+    Ok(format!("{}.{}", signing_input, BASE64_URL.encode(signature)))
+}
+
+/// Verifies `token` against `public_key` at the current time with
+/// [`DEFAULT_LEEWAY_SECS`] of clock skew, returning its claims.
+pub fn verify_token(token: &str, public_key: &[u8]) -> Result<TokenClaims, TokenError> {
+    verify_token_at(token, public_key, Utc::now().timestamp(), DEFAULT_LEEWAY_SECS)
+}
+
+/// Like [`verify_token`] with an explicit current time and leeway (both in seconds).
+/// The signature is checked before any claim is trusted.
+pub fn verify_token_at(token: &str, public_key: &[u8], now: i64, leeway: i64) -> Result<TokenClaims, TokenError> {
+    let (signing_input, signature) = token.trim().rsplit_once('.').ok_or(TokenError::Malformed)?;
+    let (version, payload) = signing_input.split_once('.').ok_or(TokenError::Malformed)?;
+    if version != TOKEN_PREFIX {
+        return Err(TokenError::UnsupportedVersion);
+    }
+    let signature = BASE64_URL.decode(signature).map_err(|_| TokenError::Malformed)?;
+    if !verify_signature(public_key, signing_input.as_bytes(), &signature)? {
+        return Err(TokenError::InvalidSignature);
+    }
+    let payload = BASE64_URL.decode(payload).map_err(|_| TokenError::Malformed)?;
+    let value: Value = serde_json::from_slice(&payload).map_err(|e| TokenError::InvalidClaims(e.to_string()))?;
+    let claims = TokenClaims::from_json(value)?;
+    if claims.issued_at > now + leeway {
+        return Err(TokenError::IssuedInFuture { issued_at: claims.issued_at, now });
+    }
+    if claims.expires_at + leeway <= now {
+        return Err(TokenError::Expired { expired_at: claims.expires_at, now });
+    }
+    Ok(claims)
+}
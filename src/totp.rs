@@ -1,39 +1,231 @@
-use pyo3::prelude::*;
-use pyo3::types::PyBytes;
-use totp_rs::{Algorithm, TOTP};
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret, TOTP};
 use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
 use rand::RngCore;
+use subtle::{Choice, ConstantTimeEq};
+use pyo3::PyErr;
 use pyo3::exceptions::PyValueError;
 
-#[pyfunction]
-pub fn generate_totp_secret<'py>(py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
-    let mut bytes = [0u8; 20];
+const URI_PREFIX: &str = "otpauth://totp/";
+
+#[derive(thiserror::Error, Debug)]
+pub enum TotpError {
+    #[error("Invalid secret: expected base64 of at least 16 bytes")]
+    InvalidSecret,
+    #[error("Unknown TOTP algorithm {0:?}")]
+    UnknownAlgorithm(String),
+    #[error("TOTP codes must have 6 to 8 digits, not {0}")]
+    InvalidDigits(usize),
+    #[error("TOTP period must be at least one second")]
+    InvalidPeriod,
+    #[error("Invalid otpauth URI: {0}")]
+    InvalidUri(String),
+}
+
+impl From<TotpError> for PyErr {
+    fn from(err: TotpError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TotpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl TotpAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            TotpAlgorithm::Sha1 => "SHA1",
+            TotpAlgorithm::Sha256 => "SHA256",
+            TotpAlgorithm::Sha512 => "SHA512",
+        }
+    }
+
+    /// Case-insensitive, so `sha256` works too.
+    pub fn from_name(name: &str) -> Result<Self, TotpError> {
+        [TotpAlgorithm::Sha1, TotpAlgorithm::Sha256, TotpAlgorithm::Sha512]
+            .into_iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| TotpError::UnknownAlgorithm(name.to_string()))
+    }
+
+    /// Generated secrets match the HMAC output size, as in RFC 6238's test vectors.
+    fn secret_len(self) -> usize {
+        match self {
+            TotpAlgorithm::Sha1 => 20,
+            TotpAlgorithm::Sha256 => 32,
+            TotpAlgorithm::Sha512 => 64,
+        }
+    }
+
+    fn to_totp_rs(self) -> Algorithm {
+        match self {
+            TotpAlgorithm::Sha1 => Algorithm::SHA1,
+            TotpAlgorithm::Sha256 => Algorithm::SHA256,
+            TotpAlgorithm::Sha512 => Algorithm::SHA512,
+        }
+    }
+}
+
+/// How codes are generated and checked for one token. The default (SHA1,
+/// 6 digits, 30-second steps, one step of skew) is what authenticator apps assume.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TotpConfig {
+    pub algorithm: TotpAlgorithm,
+    pub digits: usize,
+    /// Seconds per step.
+    pub period: u64,
+    /// Steps either side of the current one that [`verify_totp_code`] accepts.
+    pub skew: u8,
+    pub issuer: Option<String>,
+    pub account: String,
+}
+
+impl Default for TotpConfig {
+    fn default() -> Self {
+        TotpConfig { algorithm: TotpAlgorithm::Sha1, digits: 6, period: 30, skew: 1, issuer: None, account: String::new() }
+    }
+}
+
+impl TotpConfig {
+    pub fn validate(&self) -> Result<(), TotpError> {
+        if !(6..=8).contains(&self.digits) {
+            return Err(TotpError::InvalidDigits(self.digits));
+        }
+        if self.period == 0 {
+            return Err(TotpError::InvalidPeriod);
+        }
+        Ok(())
+    }
+
+    fn totp(&self, secret: &str) -> Result<TOTP, TotpError> {
+        self.validate()?;
+        let secret = BASE64_STANDARD.decode(secret.trim()).map_err(|_| TotpError::InvalidSecret)?;
+        TOTP::new(self.algorithm.to_totp_rs(), self.digits, self.skew, self.period, secret).map_err(|_| TotpError::InvalidSecret)
+    }
+
+    /// Serializes the config together with its base64 `secret` as a Key URI
+    /// (`otpauth://totp/Issuer:account?secret=BASE32&...`), the format
+    /// authenticator apps scan from QR codes. `skew` is an extension they ignore.
+    pub fn to_uri(&self, secret: &str) -> Result<String, TotpError> {
+        let secret = BASE64_STANDARD.decode(secret.trim()).map_err(|_| TotpError::InvalidSecret)?;
+        let Secret::Encoded(secret) = Secret::Raw(secret).to_encoded() else { unreachable!("to_encoded returns base32") };
+        let mut uri = URI_PREFIX.to_string();
+        if let Some(issuer) = &self.issuer {
+            uri += &format!("{}:", percent_encode(issuer));
+        }
+        uri += &format!("{}?secret={}", percent_encode(&self.account), secret);
+        if let Some(issuer) = &self.issuer {
+            uri += &format!("&issuer={}", percent_encode(issuer));
+        }
+        uri += &format!("&algorithm={}&digits={}&period={}&skew={}", self.algorithm.name(), self.digits, self.period, self.skew);
+        Ok(uri)
+    }
+
+    /// Parses a Key URI into its config and base64 secret. Missing parameters
+    /// take their defaults; the `issuer` parameter wins over the label prefix.
+    pub fn from_uri(uri: &str) -> Result<(TotpConfig, String), TotpError> {
+        let invalid = |reason: &str| TotpError::InvalidUri(reason.to_string());
+        let rest = uri.trim().strip_prefix(URI_PREFIX).ok_or_else(|| invalid("expected otpauth://totp/"))?;
+        let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+        // Split before decoding, so an encoded ':' (%3A) stays part of the issuer or account.
+        let mut config = match label.split_once(':') {
+            Some((issuer, account)) => TotpConfig {
+                issuer: Some(percent_decode(issuer, false)?.trim().to_string()),
+                account: percent_decode(account, false)?.trim().to_string(),
+                ..Default::default()
+            },
+            None => TotpConfig { account: percent_decode(label, false)?.trim().to_string(), ..Default::default() },
+        };
+        let mut secret = None;
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').ok_or_else(|| invalid("malformed query"))?;
+            let value = percent_decode(value, true)?;
+            match key {
+                "secret" => {
+                    let encoded = value.trim_end_matches('=').to_ascii_uppercase();
+                    secret = Some(Secret::Encoded(encoded).to_bytes().map_err(|_| TotpError::InvalidSecret)?);
+                }
+                "issuer" => config.issuer = Some(value),
+                "algorithm" => config.algorithm = TotpAlgorithm::from_name(&value)?,
+                "digits" => config.digits = value.parse().map_err(|_| invalid("digits"))?,
+                "period" => config.period = value.parse().map_err(|_| invalid("period"))?,
+                "skew" => config.skew = value.parse().map_err(|_| invalid("skew"))?,
+                // Such as `image`, or `counter` meant for HOTP.
+                _ => {}
+            }
+        }
+        config.validate()?;
+        let secret = secret.ok_or_else(|| invalid("missing secret"))?;
+        Ok((config, BASE64_STANDARD.encode(secret)))
+    }
+}
+
+/// A random base64 secret sized for `config.algorithm`.
+pub fn generate_totp_secret(config: &TotpConfig) -> String {
+    let mut bytes = vec![0u8; config.algorithm.secret_len()];
     rand::thread_rng().fill_bytes(&mut bytes);
-    let secret = BASE64_STANDARD.encode(bytes);
-    Ok(PyBytes::new_bound(py, secret.as_bytes()))
-}
-
-#[pyfunction]
-pub fn get_totp_code<'py>(py: Python<'py>, secret: &str) -> PyResult<Bound<'py, PyBytes>> {
-    let totp = TOTP::new(
-        Algorithm::SHA1,
-        6,
-        1,
-        30,
-        BASE64_STANDARD.decode(secret).map_err(|_| PyValueError::new_err("Invalid secret"))?,
-    ).map_err(|_| PyValueError::new_err("Failed to create TOTP"))?;
-    let code = totp.generate_current().map_err(|_| PyValueError::new_err("Failed to generate TOTP code"))?;
-    Ok(PyBytes::new_bound(py, code.as_bytes()))
-}
-
-#[pyfunction]
-pub fn verify_totp_code(secret: &str, code: &str) -> PyResult<bool> {
-    let totp = TOTP::new(
-        Algorithm::SHA1,
-        6,
-        1,
-        30,
-        BASE64_STANDARD.decode(secret).map_err(|_| PyValueError::new_err("Invalid secret"))?,
-    ).map_err(|_| PyValueError::new_err("Failed to create TOTP"))?;
-    Ok(totp.check_current(code).map_err(|_| PyValueError::new_err("Failed to verify TOTP code"))?)
-}
\ No newline at end of file
+    BASE64_STANDARD.encode(bytes)
+}
+
+pub fn get_totp_code(secret: &str, config: &TotpConfig) -> Result<String, TotpError> {
+    get_totp_code_at(secret, config, unix_time())
+}
+
+pub fn get_totp_code_at(secret: &str, config: &TotpConfig, time: u64) -> Result<String, TotpError> {
+    Ok(config.totp(secret)?.generate(time))
+}
+
+pub fn verify_totp_code(secret: &str, code: &str, config: &TotpConfig) -> Result<bool, TotpError> {
+    verify_totp_code_at(secret, code, config, unix_time())
+}
+
+/// Accepts codes from `config.skew` steps either side of `time`, comparing
+/// each in constant time. Steps whose start time doesn't fit in a u64 are skipped.
+pub fn verify_totp_code_at(secret: &str, code: &str, config: &TotpConfig, time: u64) -> Result<bool, TotpError> {
+    let totp = config.totp(secret)?;
+    let step = time / config.period;
+    let skew = u64::from(config.skew);
+    let matched = (step.saturating_sub(skew)..=step.saturating_add(skew))
+        .filter_map(|step| step.checked_mul(config.period))
+        .fold(Choice::from(0), |matched, start| matched | totp.generate(start).as_bytes().ct_eq(code.trim().as_bytes()));
+    Ok(matched.into())
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Percent-encodes everything but RFC 3986 unreserved characters.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// `+` means a space only in the query (form encoding); in the label it is literal.
+fn percent_decode(value: &str, plus_is_space: bool) -> Result<String, TotpError> {
+    let invalid = || TotpError::InvalidUri("bad percent-encoding".to_string());
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        match b {
+            b'%' => {
+                let hex = rest.get(..2).filter(|hex| hex.iter().all(u8::is_ascii_hexdigit)).ok_or_else(invalid)?;
+                bytes.push(u8::from_str_radix(std::str::from_utf8(hex).unwrap(), 16).unwrap());
+                rest = &rest[2..];
+            }
+            b'+' if plus_is_space => bytes.push(b' '),
+            _ => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
@@ -0,0 +1,329 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use pyo3::PyErr;
+use pyo3::exceptions::PyValueError;
+use crate::signing::{sign_message, verify_signature, SigningError};
+
+pub const HASH_LEN: usize = 32;
+const FILE_MAGIC: &[u8; 8] = b"QIMEMLOG";
+const TREE_HEAD_CONTEXT: &[u8] = b"qimem-tree-head-v1";
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+// tree size u64 | timestamp i64 | root hash | signature
+pub const TREE_HEAD_LEN: usize = 8 + 8 + HASH_LEN + 64;
+
+pub type Hash = [u8; HASH_LEN];
+
+#[derive(thiserror::Error, Debug)]
+pub enum LogError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Malformed log file")]
+    MalformedLog,
+    #[error("Malformed tree head")]
+    MalformedTreeHead,
+    #[error("Entry is too large")]
+    EntryTooLarge,
+    #[error("Index {index} is out of range for a tree of size {size}")]
+    IndexOutOfRange { index: u64, size: u64 },
+    #[error("Invalid tree head signature")]
+    InvalidSignature,
+    #[error("New tree head is not consistent with the trusted one")]
+    Inconsistent,
+    #[error("Signing error: {0}")]
+    Signing(#[from] SigningError),
+}
+
+impl From<LogError> for PyErr {
+    fn from(err: LogError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// A signed commitment to the log's contents at a given size.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedTreeHead {
+    pub tree_size: u64,
+    pub timestamp: i64,
+    pub root_hash: Hash,
+    pub signature: [u8; 64],
+}
+
+impl SignedTreeHead {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.signed_bytes();
+        out.extend_from_slice(&self.signature);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SignedTreeHead, LogError> {
+        if bytes.len() != TREE_HEAD_LEN {
+            return Err(LogError::MalformedTreeHead);
+        }
+        Ok(SignedTreeHead {
+            tree_size: u64::from_be_bytes(bytes[..8].try_into().unwrap()),
+            timestamp: i64::from_be_bytes(bytes[8..16].try_into().unwrap()),
+            root_hash: bytes[16..16 + HASH_LEN].try_into().unwrap(),
+            signature: bytes[16 + HASH_LEN..].try_into().unwrap(),
+        })
+    }
+
+    pub fn verify(&self, public_key: &[u8]) -> Result<bool, SigningError> {
+        verify_signature(public_key, &[TREE_HEAD_CONTEXT, &self.signed_bytes()].concat(), &self.signature)
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut out = self.tree_size.to_be_bytes().to_vec();
+        out.extend_from_slice(&self.timestamp.to_be_bytes());
+        out.extend_from_slice(&self.root_hash);
+        out
+    }
+}
+
+/// An append-only log of entries kept in a local file, hashed into an
+/// RFC 6962 Merkle tree.
+///
+/// The file is `QIMEMLOG` followed by each entry as length (u32 BE) | bytes.
+/// Entries are only ever appended, so anyone holding an older tree head can
+/// ask for a consistency proof and detect an entry that was changed or removed.
+pub struct TransparencyLog {
+    path: PathBuf,
+    entries: Vec<Vec<u8>>,
+    leaves: Vec<Hash>,
+}
+
+impl TransparencyLog {
+    /// Opens the log at `path`, creating an empty one if it doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<TransparencyLog, LogError> {
+        let path = path.as_ref().to_path_buf();
+        if !path.exists() {
+            fs::write(&path, FILE_MAGIC)?;
+        }
+        let data = fs::read(&path)?;
+        let mut rest = data.strip_prefix(FILE_MAGIC.as_slice()).ok_or(LogError::MalformedLog)?;
+        let mut entries = Vec::new();
+        while !rest.is_empty() {
+            if rest.len() < 4 {
+                return Err(LogError::MalformedLog);
+            }
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let entry = rest.get(4..4 + len).ok_or(LogError::MalformedLog)?;
+            entries.push(entry.to_vec());
+            rest = &rest[4 + len..];
+        }
+        let leaves = entries.iter().map(|entry| leaf_hash(entry)).collect();
+        Ok(TransparencyLog { path, entries, leaves })
+    }
+
+    /// Appends `entry` to the file and returns its index.
+    pub fn append(&mut self, entry: &[u8]) -> Result<u64, LogError> {
+        let len = u32::try_from(entry.len()).map_err(|_| LogError::EntryTooLarge)?;
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(&[&len.to_be_bytes(), entry].concat())?;
+        file.sync_data()?;
+        self.entries.push(entry.to_vec());
+        self.leaves.push(leaf_hash(entry));
+        Ok(self.leaves.len() as u64 - 1)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.leaves.len() as u64
+    }
+
+    pub fn entry(&self, index: u64) -> Option<&[u8]> {
+        self.entries.get(usize::try_from(index).ok()?).map(Vec::as_slice)
+    }
+
+    /// Root hash of the first `tree_size` entries.
+    pub fn root_hash(&self, tree_size: u64) -> Result<Hash, LogError> {
+        Ok(root_hash(self.prefix(tree_size)?))
+    }
+
+    /// Audit path for the entry at `index` in the tree of the first `tree_size` entries.
+    pub fn inclusion_proof(&self, index: u64, tree_size: u64) -> Result<Vec<Hash>, LogError> {
+        if index >= tree_size {
+            return Err(LogError::IndexOutOfRange { index, size: tree_size });
+        }
+        Ok(inclusion_path(index as usize, self.prefix(tree_size)?))
+    }
+
+    /// Proof that the tree of size `old_size` is a prefix of the tree of size `new_size`.
+    pub fn consistency_proof(&self, old_size: u64, new_size: u64) -> Result<Vec<Hash>, LogError> {
+        if old_size > new_size {
+            return Err(LogError::IndexOutOfRange { index: old_size, size: new_size });
+        }
+        let leaves = self.prefix(new_size)?;
+        if old_size == 0 || old_size == new_size {
+            return Ok(Vec::new());
+        }
+        Ok(subproof(old_size as usize, leaves, true))
+    }
+
+    /// Signs the current size and root hash.
+    pub fn sign_tree_head(&self, secret_key: &[u8]) -> Result<SignedTreeHead, LogError> {
+        let mut head = SignedTreeHead {
+            tree_size: self.size(),
+            timestamp: Utc::now().timestamp(),
+            root_hash: root_hash(&self.leaves),
+            signature: [0; 64],
+        };
+        let signature = sign_message(secret_key, &[TREE_HEAD_CONTEXT, &head.signed_bytes()].concat())?;
+        head.signature = signature.try_into().unwrap();
+        Ok(head)
+    }
+
+    fn prefix(&self, tree_size: u64) -> Result<&[Hash], LogError> {
+        if tree_size > self.size() {
+            return Err(LogError::IndexOutOfRange { index: tree_size, size: self.size() });
+        }
+        Ok(&self.leaves[..tree_size as usize])
+    }
+}
+
+pub fn leaf_hash(entry: &[u8]) -> Hash {
+    Sha256::new().chain_update([LEAF_PREFIX]).chain_update(entry).finalize().into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    Sha256::new().chain_update([NODE_PREFIX]).chain_update(left).chain_update(right).finalize().into()
+}
+
+/// The Merkle tree hash of a list of leaf hashes (RFC 6962 section 2.1).
+pub fn root_hash(leaves: &[Hash]) -> Hash {
+    match leaves.len() {
+        0 => Sha256::digest([]).into(),
+        1 => leaves[0],
+        n => {
+            let k = split_point(n);
+            node_hash(&root_hash(&leaves[..k]), &root_hash(&leaves[k..]))
+        }
+    }
+}
+
+/// Checks an audit path from [`TransparencyLog::inclusion_proof`] (RFC 9162 section 2.1.3.2).
+pub fn verify_inclusion(leaf_hash: &Hash, index: u64, tree_size: u64, proof: &[Hash], root_hash: &Hash) -> bool {
+    if index >= tree_size {
+        return false;
+    }
+    let (mut fn_, mut sn) = (index, tree_size - 1);
+    let mut r = *leaf_hash;
+    for p in proof {
+        if sn == 0 {
+            return false;
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            r = node_hash(p, &r);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            r = node_hash(&r, p);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    sn == 0 && r == *root_hash
+}
+
+/// Checks a proof from [`TransparencyLog::consistency_proof`] (RFC 9162 section 2.1.4.2).
+pub fn verify_consistency(old_size: u64, new_size: u64, old_root: &Hash, new_root: &Hash, proof: &[Hash]) -> bool {
+    if old_size > new_size {
+        return false;
+    }
+    if old_size == new_size {
+        return proof.is_empty() && old_root == new_root;
+    }
+    if old_size == 0 {
+        return proof.is_empty();
+    }
+    let mut path = proof.to_vec();
+    if old_size.is_power_of_two() {
+        path.insert(0, *old_root);
+    }
+    let Some((first, rest)) = path.split_first() else {
+        return false;
+    };
+    let (mut fn_, mut sn) = (old_size - 1, new_size - 1);
+    while fn_ & 1 == 1 {
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    let (mut fr, mut sr) = (*first, *first);
+    for c in rest {
+        if sn == 0 {
+            return false;
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            fr = node_hash(c, &fr);
+            sr = node_hash(c, &sr);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            sr = node_hash(&sr, c);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    sn == 0 && fr == *old_root && sr == *new_root
+}
+
+/// What a client runs when the log publishes a new tree head: both heads must
+/// be signed by the log's key, and the new tree must extend the one the client
+/// already trusts. On success the client should keep `new` as its trusted head.
+pub fn check_tree_head_update(
+    log_public_key: &[u8],
+    trusted: &SignedTreeHead,
+    new: &SignedTreeHead,
+    consistency_proof: &[Hash],
+) -> Result<(), LogError> {
+    if !trusted.verify(log_public_key)? || !new.verify(log_public_key)? {
+        return Err(LogError::InvalidSignature);
+    }
+    if !verify_consistency(trusted.tree_size, new.tree_size, &trusted.root_hash, &new.root_hash, consistency_proof) {
+        return Err(LogError::Inconsistent);
+    }
+    Ok(())
+}
+
+/// The largest power of two smaller than `n` (for `n > 1`).
+fn split_point(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+fn inclusion_path(index: usize, leaves: &[Hash]) -> Vec<Hash> {
+    if leaves.len() <= 1 {
+        return Vec::new();
+    }
+    let k = split_point(leaves.len());
+    if index < k {
+        let mut path = inclusion_path(index, &leaves[..k]);
+        path.push(root_hash(&leaves[k..]));
+        path
+    } else {
+        let mut path = inclusion_path(index - k, &leaves[k..]);
+        path.push(root_hash(&leaves[..k]));
+        path
+    }
+}
+
+fn subproof(old_size: usize, leaves: &[Hash], complete: bool) -> Vec<Hash> {
+    if old_size == leaves.len() {
+        return if complete { Vec::new() } else { vec![root_hash(leaves)] };
+    }
+    let k = split_point(leaves.len());
+    if old_size <= k {
+        let mut proof = subproof(old_size, &leaves[..k], complete);
+        proof.push(root_hash(&leaves[k..]));
+        proof
+    } else {
+        let mut proof = subproof(old_size - k, &leaves[k..], false);
+        proof.push(root_hash(&leaves[..k]));
+        proof
+    }
+}
@@ -0,0 +1,25 @@
+use std::sync::OnceLock;
+
+/// Number of words in the embedded list (the BIP39 English list, 11 bits per word).
+pub const WORD_COUNT: usize = 2048;
+
+const ENGLISH: &str = include_str!("wordlists/english.txt");
+
+/// The embedded English word list, sorted alphabetically.
+pub fn words() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    WORDS.get_or_init(|| ENGLISH.lines().collect())
+}
+
+/// Position of `word` in the list, if present.
+pub fn index_of(word: &str) -> Option<usize> {
+    words().binary_search(&word).ok()
+}
+
+const COMMON_PASSWORDS: &str = include_str!("wordlists/common_passwords.txt");
+
+/// Frequently leaked passwords, most common first.
+pub fn common_passwords() -> &'static [&'static str] {
+    static PASSWORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    PASSWORDS.get_or_init(|| COMMON_PASSWORDS.lines().collect())
+}